@@ -0,0 +1,54 @@
+//! Solver benchmarks on generated instances.
+//!
+//! Run with `cargo bench`. Instances come from the seeded generator, so
+//! numbers are comparable across commits.
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use vrp_planner::generator::{InstanceConfig, generate};
+use vrp_planner::haversine::HaversineMatrix;
+use vrp_planner::solver::{SolveOptions, solve};
+
+fn bench_scale(c: &mut Criterion) {
+    let mut group = c.benchmark_group("solve_scale");
+    group.sample_size(10);
+    for (visits, visitors) in [(50, 5), (100, 10), (200, 20)] {
+        let instance = generate(&InstanceConfig::new(visits, visitors).with_clusters(visitors));
+        group.bench_with_input(BenchmarkId::from_parameter(visits), &instance, |b, instance| {
+            b.iter(|| {
+                solve(
+                    instance.service_date,
+                    &instance.visits,
+                    &instance.visitors,
+                    &instance.availability,
+                    &HaversineMatrix::default(),
+                    SolveOptions::default(),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_window_tightness(c: &mut Criterion) {
+    let mut group = c.benchmark_group("solve_window_tightness");
+    group.sample_size(10);
+    for tightness in [0.0, 0.5, 0.9] {
+        let instance = generate(&InstanceConfig::new(100, 10).with_window_tightness(tightness));
+        group.bench_with_input(BenchmarkId::from_parameter(tightness), &instance, |b, instance| {
+            b.iter(|| {
+                solve(
+                    instance.service_date,
+                    &instance.visits,
+                    &instance.visitors,
+                    &instance.availability,
+                    &HaversineMatrix::default(),
+                    SolveOptions::default(),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_scale, bench_window_tightness);
+criterion_main!(benches);
@@ -0,0 +1,362 @@
+//! Standard benchmark instances.
+//!
+//! Loads Solomon VRPTW and CVRPLIB (TSPLIB-style CVRP) instances into the
+//! plain-data model so plans can be compared against published best-known
+//! solutions. Instance coordinates are planar, not lat/lng: solve with the
+//! instance's [`EuclideanMatrix`], which reads one distance unit as one
+//! minute of travel and 1000 meters of distance.
+//!
+//! The solver leaves routes open at their last visit, while benchmark
+//! objectives count the drive back to the depot, so compare
+//! [`BenchmarkInstance::plan_distance`] against the best-known value rather
+//! than the plan's own totals.
+
+use std::collections::HashMap;
+
+use crate::constraint::{Feasibility, RouteCandidate, RouteConstraint};
+use crate::model::{AvailabilityData, VisitData, VisitorData};
+use crate::solver::PlannerResult;
+use crate::traits::Visit;
+
+pub use crate::matrix::synthetic::EuclideanMatrix;
+
+/// Horizon used when an instance has no time windows (CVRP), in seconds.
+const UNBOUNDED_HORIZON: i32 = 1 << 30;
+
+/// A parsed benchmark instance.
+///
+/// Every visitor starts and ends at the depot and is available over the
+/// depot's time window. Solomon times are read as minutes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkInstance {
+    pub name: String,
+    pub depot: (f64, f64),
+    pub visits: Vec<VisitData>,
+    pub visitors: Vec<VisitorData>,
+    pub availability: AvailabilityData,
+    pub matrix: EuclideanMatrix,
+    /// Vehicle capacity, in the same units as the demands.
+    pub capacity: u32,
+    /// Demand of each visit, by visit ID.
+    pub demands: HashMap<String, u32>,
+}
+
+impl BenchmarkInstance {
+    /// Constraint keeping each route's total demand within the vehicle capacity.
+    pub fn capacity_constraint(&self) -> CapacityConstraint {
+        CapacityConstraint {
+            capacity: self.capacity,
+            demands: self.demands.clone(),
+        }
+    }
+
+    /// Total distance of a plan in instance units, depot to depot.
+    pub fn plan_distance(&self, plan: &PlannerResult<String, String>) -> f64 {
+        let locations: HashMap<&str, (f64, f64)> =
+            self.visits.iter().map(|visit| (visit.id.as_str(), visit.location)).collect();
+        plan.routes
+            .iter()
+            .filter(|route| !route.visit_ids.is_empty())
+            .map(|route| {
+                let stops = route.visit_ids.iter().map(|id| locations[id.as_str()]);
+                let path: Vec<(f64, f64)> =
+                    std::iter::once(self.depot).chain(stops).chain(std::iter::once(self.depot)).collect();
+                path.windows(2).map(|leg| self.matrix.distance(leg[0], leg[1])).sum::<f64>()
+            })
+            .sum()
+    }
+}
+
+/// Percentage by which `cost` exceeds the best-known cost.
+pub fn gap_pct(cost: f64, best_known: f64) -> f64 {
+    (cost - best_known) / best_known * 100.0
+}
+
+/// Route constraint on total demand, for instances with vehicle capacities.
+#[derive(Debug, Clone)]
+pub struct CapacityConstraint {
+    capacity: u32,
+    demands: HashMap<String, u32>,
+}
+
+impl RouteConstraint<VisitData, VisitorData> for CapacityConstraint {
+    fn check(&self, route: &RouteCandidate<'_, VisitData, VisitorData>) -> Feasibility {
+        let load: u32 = route.visits.iter().map(|visit| self.demands.get(visit.id()).copied().unwrap_or(0)).sum();
+        (load <= self.capacity).into()
+    }
+}
+
+/// Error from parsing a benchmark instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstanceParseError {
+    /// A required section or field is absent.
+    Missing(&'static str),
+    /// A line couldn't be read (1-based line number).
+    InvalidLine(usize),
+    /// The instance uses distances other than `EUC_2D`.
+    UnsupportedEdgeWeight(String),
+}
+
+impl std::fmt::Display for InstanceParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InstanceParseError::Missing(what) => write!(f, "Instance is missing {}", what),
+            InstanceParseError::InvalidLine(line) => write!(f, "Invalid instance data on line {}", line),
+            InstanceParseError::UnsupportedEdgeWeight(kind) => write!(f, "Unsupported edge weight type {}", kind),
+        }
+    }
+}
+
+impl std::error::Error for InstanceParseError {}
+
+/// Parses a Solomon VRPTW instance (e.g., `C101.txt`).
+///
+/// Customer 0 is the depot. Ready and due times bound when service may
+/// start, so each visit's committed window runs from ready time to due time
+/// plus service time.
+pub fn parse_solomon(text: &str) -> Result<BenchmarkInstance, InstanceParseError> {
+    let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    let name = lines.next().ok_or(InstanceParseError::Missing("name"))?.1.trim().to_string();
+
+    let mut fleet: Option<(usize, u32)> = None;
+    let mut rows: Vec<[f64; 7]> = Vec::new();
+    for (idx, line) in lines {
+        let Some(first) = line.split_whitespace().next() else {
+            continue;
+        };
+        if first.parse::<f64>().is_err() {
+            continue; // section header
+        }
+        let numbers = parse_numbers(line).ok_or(InstanceParseError::InvalidLine(idx + 1))?;
+        match (fleet, numbers.as_slice()) {
+            (None, &[vehicles, capacity]) => fleet = Some((vehicles as usize, capacity as u32)),
+            (Some(_), &[id, x, y, demand, ready, due, service]) => rows.push([id, x, y, demand, ready, due, service]),
+            _ => return Err(InstanceParseError::InvalidLine(idx + 1)),
+        }
+    }
+
+    let (vehicles, capacity) = fleet.ok_or(InstanceParseError::Missing("VEHICLE section"))?;
+    let (depot_row, customers) = rows.split_first().ok_or(InstanceParseError::Missing("depot"))?;
+    let depot = (depot_row[1], depot_row[2]);
+    let minutes = |time: f64| (time * 60.0).round() as i32;
+
+    let mut demands = HashMap::new();
+    let visits = customers
+        .iter()
+        .map(|&[id, x, y, demand, ready, due, service]| {
+            let id = (id as u32).to_string();
+            demands.insert(id.clone(), demand as u32);
+            VisitData::new(id, (x, y), service as i32).with_committed_window(minutes(ready), minutes(due + service))
+        })
+        .collect();
+
+    Ok(BenchmarkInstance {
+        name,
+        depot,
+        visits,
+        visitors: depot_visitors(depot, vehicles),
+        availability: AvailabilityData::new(vec![(minutes(depot_row[4]), minutes(depot_row[5]))]),
+        matrix: EuclideanMatrix::default(),
+        capacity,
+        demands,
+    })
+}
+
+/// Parses a CVRPLIB instance in TSPLIB format (e.g., `A-n32-k5.vrp`).
+///
+/// Only `EUC_2D` instances with a single depot are supported. The fleet size
+/// comes from the `-k` suffix of the name, or one vehicle per customer if
+/// the name has none. Visits have no duration or time windows.
+pub fn parse_cvrplib(text: &str) -> Result<BenchmarkInstance, InstanceParseError> {
+    #[derive(PartialEq)]
+    enum Section {
+        Header,
+        Coords,
+        Demands,
+        Depots,
+    }
+
+    let mut name = None;
+    let mut capacity = None;
+    let mut section = Section::Header;
+    let mut coords: Vec<(u32, (f64, f64))> = Vec::new();
+    let mut node_demands: HashMap<u32, u32> = HashMap::new();
+    let mut depot_id = None;
+
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line == "EOF" {
+            continue;
+        }
+        match line {
+            "NODE_COORD_SECTION" => section = Section::Coords,
+            "DEMAND_SECTION" => section = Section::Demands,
+            "DEPOT_SECTION" => section = Section::Depots,
+            _ if section == Section::Header => {
+                let Some((key, value)) = line.split_once(':') else {
+                    return Err(InstanceParseError::InvalidLine(idx + 1));
+                };
+                let value = value.trim();
+                match key.trim() {
+                    "NAME" => name = Some(value.to_string()),
+                    "CAPACITY" => capacity = value.parse().ok(),
+                    "EDGE_WEIGHT_TYPE" if value != "EUC_2D" => {
+                        return Err(InstanceParseError::UnsupportedEdgeWeight(value.to_string()));
+                    }
+                    _ => {}
+                }
+            }
+            _ => {
+                let numbers = parse_numbers(line).ok_or(InstanceParseError::InvalidLine(idx + 1))?;
+                match (&section, numbers.as_slice()) {
+                    (Section::Coords, &[id, x, y]) => coords.push((id as u32, (x, y))),
+                    (Section::Demands, &[id, demand]) => {
+                        node_demands.insert(id as u32, demand as u32);
+                    }
+                    (Section::Depots, &[id]) if id >= 0.0 => {
+                        depot_id.get_or_insert(id as u32);
+                    }
+                    (Section::Depots, &[_]) => {}
+                    _ => return Err(InstanceParseError::InvalidLine(idx + 1)),
+                }
+            }
+        }
+    }
+
+    let name = name.ok_or(InstanceParseError::Missing("NAME"))?;
+    let capacity = capacity.ok_or(InstanceParseError::Missing("CAPACITY"))?;
+    let depot_id = depot_id.ok_or(InstanceParseError::Missing("DEPOT_SECTION"))?;
+    let depot = coords
+        .iter()
+        .find(|(id, _)| *id == depot_id)
+        .map(|&(_, location)| location)
+        .ok_or(InstanceParseError::Missing("depot coordinates"))?;
+
+    let mut demands = HashMap::new();
+    let visits: Vec<VisitData> = coords
+        .iter()
+        .filter(|(id, _)| *id != depot_id)
+        .map(|&(id, location)| {
+            demands.insert(id.to_string(), node_demands.get(&id).copied().unwrap_or(0));
+            VisitData::new(id.to_string(), location, 0)
+        })
+        .collect();
+    let vehicles = name
+        .rsplit_once("-k")
+        .and_then(|(_, k)| k.parse().ok())
+        .unwrap_or(visits.len());
+
+    Ok(BenchmarkInstance {
+        name,
+        depot,
+        visitors: depot_visitors(depot, vehicles),
+        visits,
+        availability: AvailabilityData::new(vec![(0, UNBOUNDED_HORIZON)]),
+        matrix: EuclideanMatrix::default().with_rounding(),
+        capacity,
+        demands,
+    })
+}
+
+fn parse_numbers(line: &str) -> Option<Vec<f64>> {
+    line.split_whitespace().map(|token| token.parse().ok()).collect()
+}
+
+fn depot_visitors(depot: (f64, f64), vehicles: usize) -> Vec<VisitorData> {
+    (1..=vehicles)
+        .map(|idx| {
+            VisitorData::new(format!("vehicle{idx}"))
+                .with_start_location(depot.0, depot.1)
+                .with_end_location(depot.0, depot.1)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::{SolveOptions, solve_with_constraints};
+
+    const SOLOMON: &str = "C101
+
+VEHICLE
+NUMBER     CAPACITY
+  2         200
+
+CUSTOMER
+CUST NO.  XCOORD.   YCOORD.    DEMAND   READY TIME  DUE DATE   SERVICE   TIME
+
+    0      40         50          0          0       1236          0
+    1      45         68         10        912        967         90
+    2      45         70         30        825        870         90
+    3      42         66         10         65        146         90
+";
+
+    const CVRPLIB: &str = "NAME : tiny-n4-k2
+COMMENT : hand-made
+TYPE : CVRP
+DIMENSION : 4
+EDGE_WEIGHT_TYPE : EUC_2D
+CAPACITY : 10
+NODE_COORD_SECTION
+ 1 0 0
+ 2 3 4
+ 3 6 8
+ 4 -3 -4
+DEMAND_SECTION
+1 0
+2 4
+3 4
+4 5
+DEPOT_SECTION
+ 1
+ -1
+EOF
+";
+
+    #[test]
+    fn test_parse_solomon() {
+        let instance = parse_solomon(SOLOMON).unwrap();
+
+        assert_eq!(instance.name, "C101");
+        assert_eq!(instance.depot, (40.0, 50.0));
+        assert_eq!(instance.visitors.len(), 2);
+        assert_eq!(instance.capacity, 200);
+        assert_eq!(instance.visits.len(), 3);
+        let first = &instance.visits[0];
+        assert_eq!(first.id, "1");
+        assert_eq!(first.committed_window(), Some((912 * 60, (967 + 90) * 60)));
+        assert_eq!(instance.demands["2"], 30);
+        assert_eq!(instance.availability.default_windows, Some(vec![(0, 1236 * 60)]));
+    }
+
+    #[test]
+    fn test_parse_cvrplib_and_score_plan() {
+        let instance = parse_cvrplib(CVRPLIB).unwrap();
+        assert_eq!(instance.visitors.len(), 2);
+        assert_eq!(instance.visits.len(), 3);
+
+        let constraints: Vec<Box<dyn RouteConstraint<VisitData, VisitorData>>> =
+            vec![Box::new(instance.capacity_constraint())];
+        let plan = solve_with_constraints(
+            0,
+            &instance.visits,
+            &instance.visitors,
+            &instance.availability,
+            &instance.matrix,
+            SolveOptions::default(),
+            &constraints,
+        ).unwrap();
+
+        // Capacity 10 splits 2 and 3 (load 8) from 4 (load 5): 20 + 10 depot to depot
+        assert!(plan.unassigned.is_empty());
+        assert_eq!(instance.plan_distance(&plan), 30.0);
+        assert_eq!(gap_pct(33.0, 30.0), 10.0);
+    }
+
+    #[test]
+    fn test_rejects_explicit_edge_weights() {
+        let text = CVRPLIB.replace("EUC_2D", "EXPLICIT");
+        assert_eq!(parse_cvrplib(&text), Err(InstanceParseError::UnsupportedEdgeWeight("EXPLICIT".to_string())));
+    }
+}
@@ -0,0 +1,213 @@
+//! Command-line solver.
+//!
+//! Reads a [`Problem`] from a JSON file (or stdin) and writes the plan as
+//! JSON or GeoJSON, so a customer's day can be reproduced without writing
+//! Rust. Built with the `cli` feature.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
+
+use vrp_planner::geojson::plan_to_geojson;
+use vrp_planner::haversine::HaversineMatrix;
+use vrp_planner::model::Problem;
+use vrp_planner::osrm::{OsrmClient, OsrmConfig};
+use vrp_planner::polyline::Polyline;
+use vrp_planner::solver::PlannerResult;
+
+const USAGE: &str = "\
+Usage: vrp-planner [OPTIONS] <PROBLEM.json>
+
+Solves the problem in PROBLEM.json (or stdin, given as -) and prints the plan.
+
+Options:
+  --matrix <haversine|osrm>  Travel time source [default: haversine]
+  --osrm-url <URL>           OSRM server for --matrix osrm [default: http://localhost:5000]
+  --format <json|geojson>    Output format [default: json]
+  -o, --output <FILE>        Write the plan to FILE instead of stdout
+  -h, --help                 Print this help
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MatrixSource {
+    Haversine,
+    Osrm,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Json,
+    GeoJson,
+}
+
+#[derive(Debug, PartialEq)]
+struct Args {
+    input: String,
+    matrix: MatrixSource,
+    osrm_url: Option<String>,
+    format: Format,
+    output: Option<String>,
+}
+
+/// Parses arguments (without the program name). `Ok(None)` means help was requested.
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut input = None;
+    let mut matrix = MatrixSource::Haversine;
+    let mut osrm_url = None;
+    let mut format = Format::Json;
+    let mut output = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |flag: &str| args.next().ok_or_else(|| format!("{} needs a value", flag));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--matrix" => {
+                matrix = match value("--matrix")?.as_str() {
+                    "haversine" => MatrixSource::Haversine,
+                    "osrm" => MatrixSource::Osrm,
+                    other => return Err(format!("unknown matrix source: {}", other)),
+                }
+            }
+            "--osrm-url" => osrm_url = Some(value("--osrm-url")?),
+            "--format" => {
+                format = match value("--format")?.as_str() {
+                    "json" => Format::Json,
+                    "geojson" => Format::GeoJson,
+                    other => return Err(format!("unknown format: {}", other)),
+                }
+            }
+            "-o" | "--output" => output = Some(value("--output")?),
+            flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option: {}", flag)),
+            _ if input.is_some() => return Err(format!("unexpected argument: {}", arg)),
+            _ => input = Some(arg),
+        }
+    }
+
+    let input = input.ok_or("missing problem file")?;
+    Ok(Some(Args { input, matrix, osrm_url, format, output }))
+}
+
+fn main() -> ExitCode {
+    match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => match run(&args) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                ExitCode::FAILURE
+            }
+        },
+        Ok(None) => {
+            print!("{}", USAGE);
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    let text = if args.input == "-" {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        text
+    } else {
+        fs::read_to_string(&args.input)?
+    };
+    let problem: Problem = serde_json::from_str(&text)?;
+
+    let osrm = match args.matrix {
+        MatrixSource::Osrm => {
+            let mut config = OsrmConfig::default();
+            if let Some(url) = &args.osrm_url {
+                config.base_url = url.clone();
+            }
+            Some(OsrmClient::new(config)?)
+        }
+        MatrixSource::Haversine => None,
+    };
+    let plan = match &osrm {
+        Some(client) => problem.solve(client)?,
+        None => problem.solve(&HaversineMatrix::default())?,
+    };
+
+    let rendered = match args.format {
+        Format::Json => plan.to_json()?,
+        Format::GeoJson => {
+            let geometries = route_geometries(&problem, &plan, osrm.as_ref());
+            serde_json::to_string(&plan_to_geojson(&plan, &geometries)?)?
+        }
+    };
+
+    match &args.output {
+        Some(path) => fs::write(path, rendered)?,
+        None => writeln!(io::stdout(), "{}", rendered)?,
+    }
+    Ok(())
+}
+
+/// Line for each route from the visitor's start through its visits: road
+/// geometry from OSRM when available, straight lines otherwise.
+fn route_geometries(problem: &Problem, plan: &PlannerResult<String, String>, osrm: Option<&OsrmClient>) -> Vec<Polyline> {
+    plan.routes
+        .iter()
+        .map(|route| {
+            let visitor = problem.visitors.iter().find(|visitor| visitor.id == route.visitor_id);
+            let start = visitor.and_then(|visitor| visitor.start_location);
+            let profile = visitor.and_then(|visitor| visitor.profile.as_deref());
+            let stops = route
+                .visit_ids
+                .iter()
+                .filter_map(|id| problem.visits.iter().find(|visit| &visit.id == id))
+                .map(|visit| visit.location);
+            let waypoints: Vec<(f64, f64)> = start.into_iter().chain(stops).collect();
+
+            osrm.filter(|_| waypoints.len() >= 2)
+                .and_then(|client| match profile {
+                    Some(profile) => client.with_profile(profile).get_route_geometry(&waypoints).ok(),
+                    None => client.get_route_geometry(&waypoints).ok(),
+                })
+                .map(|geometry| geometry.polyline())
+                .unwrap_or_else(|| Polyline::new(waypoints))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Result<Option<Args>, String> {
+        parse_args(list.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_defaults_to_haversine_json() {
+        let parsed = args(&["day.json"]).unwrap().unwrap();
+        assert_eq!(parsed.input, "day.json");
+        assert_eq!((parsed.matrix, parsed.format, parsed.output), (MatrixSource::Haversine, Format::Json, None));
+    }
+
+    #[test]
+    fn test_parses_flags() {
+        let parsed = args(&["--matrix", "osrm", "--osrm-url", "http://osrm:5000", "--format", "geojson", "-o", "plan.geojson", "-"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(parsed.matrix, MatrixSource::Osrm);
+        assert_eq!(parsed.osrm_url.as_deref(), Some("http://osrm:5000"));
+        assert_eq!(parsed.format, Format::GeoJson);
+        assert_eq!(parsed.output.as_deref(), Some("plan.geojson"));
+        assert_eq!(parsed.input, "-");
+    }
+
+    #[test]
+    fn test_rejects_bad_arguments() {
+        assert_eq!(args(&["--help"]), Ok(None));
+        assert!(args(&[]).is_err());
+        assert!(args(&["--matrix", "valhalla", "day.json"]).is_err());
+        assert!(args(&["--format"]).is_err());
+        assert!(args(&["a.json", "b.json"]).is_err());
+    }
+}
@@ -0,0 +1,366 @@
+//! Matrix caching.
+//!
+//! Wraps a matrix provider and memoizes its results by location set, so
+//! repeated solves over the same locations (e.g., interactive re-solves of a
+//! day) don't refetch the matrix. Entries can optionally be persisted to disk
+//! to survive restarts.
+
+use std::collections::HashMap;
+use std::fs;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::traits::{AsyncDistanceMatrixProvider, DistanceMatrixProvider, LocationRef, TravelMatrices};
+
+/// Cache key: the routing profile, if not the provider's default, and the
+/// requested locations, quantized, in request order.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    profile: Option<String>,
+    locations: Vec<LocationRef>,
+}
+
+/// Memoizing wrapper around a matrix provider.
+///
+/// Matrices are keyed by the ordered location list (quantized to 1e-6
+/// degrees) and routing profile. Empty matrices are treated as failed fetches and not cached.
+/// The in-memory cache is unbounded; call [`clear`](Self::clear) to drop it.
+#[derive(Debug)]
+pub struct CachedMatrixProvider<P> {
+    inner: P,
+    entries: Mutex<HashMap<CacheKey, TravelMatrices>>,
+    disk_dir: Option<PathBuf>,
+}
+
+/// On-disk cache entry; the key is stored to detect hash collisions.
+#[derive(Serialize, Deserialize)]
+struct DiskEntry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    profile: Option<String>,
+    locations: Vec<LocationRef>,
+    matrices: TravelMatrices,
+}
+
+impl<P> CachedMatrixProvider<P> {
+    /// Wraps `inner` with an in-memory cache.
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            entries: Mutex::new(HashMap::new()),
+            disk_dir: None,
+        }
+    }
+
+    /// Also persists entries as JSON files in `dir`, created if missing.
+    pub fn with_disk_cache(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.disk_dir = Some(dir.into());
+        self
+    }
+
+    /// The wrapped provider.
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    /// Number of matrices held in memory.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Returns true if nothing is cached in memory.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops all in-memory entries. Files on disk are left in place.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    /// Look up a cached entry in memory, then on disk.
+    fn lookup(&self, key: &CacheKey) -> Option<TravelMatrices> {
+        if let Some(matrices) = self.entries.lock().unwrap().get(key) {
+            return Some(matrices.clone());
+        }
+
+        let path = self.disk_path(key)?;
+        let contents = fs::read_to_string(&path).ok()?;
+        let entry: DiskEntry = match serde_json::from_str(&contents) {
+            Ok(entry) => entry,
+            Err(err) => {
+                warn!(path = %path.display(), error = %err, "Ignoring unreadable matrix cache file");
+                return None;
+            }
+        };
+        if entry.profile != key.profile || entry.locations != key.locations {
+            return None;
+        }
+
+        debug!(path = %path.display(), "Matrix loaded from disk cache");
+        self.entries.lock().unwrap().insert(key.clone(), entry.matrices.clone());
+        Some(entry.matrices)
+    }
+
+    /// Cache a freshly fetched entry, skipping failed (empty) fetches.
+    fn store(&self, key: CacheKey, matrices: &TravelMatrices) {
+        if matrices.durations.is_empty() && !key.locations.is_empty() {
+            return;
+        }
+
+        if let Some(path) = self.disk_path(&key) {
+            let entry = DiskEntry {
+                profile: key.profile.clone(),
+                locations: key.locations.clone(),
+                matrices: matrices.clone(),
+            };
+            let written = path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::write(&path, serde_json::to_vec(&entry).unwrap_or_default()));
+            if let Err(err) = written {
+                warn!(path = %path.display(), error = %err, "Failed to write matrix cache file");
+            }
+        }
+
+        self.entries.lock().unwrap().insert(key, matrices.clone());
+    }
+
+    fn disk_path(&self, key: &CacheKey) -> Option<PathBuf> {
+        let dir = self.disk_dir.as_ref()?;
+        Some(dir.join(format!("{:016x}.json", key_hash(key))))
+    }
+}
+
+impl<P: DistanceMatrixProvider> DistanceMatrixProvider for CachedMatrixProvider<P> {
+    fn matrix_for(&self, locations: &[(f64, f64)]) -> Vec<Vec<i32>> {
+        let key = cache_key(None, locations);
+        if let Some(matrices) = self.lookup(&key) {
+            return matrices.durations;
+        }
+
+        let durations = self.inner.matrix_for(locations);
+        self.store(key, &TravelMatrices::from_durations(durations.clone()));
+        durations
+    }
+
+    fn matrices_for(&self, locations: &[(f64, f64)]) -> TravelMatrices {
+        let key = cache_key(None, locations);
+        // An entry cached by `matrix_for` has no distances; refetch to fill them in.
+        if let Some(matrices) = self.lookup(&key).filter(|matrices| matrices.distances.is_some()) {
+            return matrices;
+        }
+
+        let matrices = self.inner.matrices_for(locations);
+        self.store(key, &matrices);
+        matrices
+    }
+
+    fn matrices_for_profile(&self, profile: &str, locations: &[(f64, f64)]) -> TravelMatrices {
+        let key = cache_key(Some(profile), locations);
+        if let Some(matrices) = self.lookup(&key) {
+            return matrices;
+        }
+
+        let matrices = self.inner.matrices_for_profile(profile, locations);
+        self.store(key, &matrices);
+        matrices
+    }
+
+    fn bytes_transferred(&self) -> Option<u64> {
+        self.inner.bytes_transferred()
+    }
+}
+
+impl<P: AsyncDistanceMatrixProvider + Sync> AsyncDistanceMatrixProvider for CachedMatrixProvider<P> {
+    fn fetch_matrix(&self, locations: &[(f64, f64)]) -> impl Future<Output = Vec<Vec<i32>>> + Send {
+        let key = cache_key(None, locations);
+        let cached = self.lookup(&key);
+        let fetch = cached.is_none().then(|| self.inner.fetch_matrix(locations));
+
+        async move {
+            let Some(fetch) = fetch else {
+                return cached.map(|matrices| matrices.durations).unwrap_or_default();
+            };
+            let durations = fetch.await;
+            self.store(key, &TravelMatrices::from_durations(durations.clone()));
+            durations
+        }
+    }
+
+    fn fetch_matrices(&self, locations: &[(f64, f64)]) -> impl Future<Output = TravelMatrices> + Send {
+        let key = cache_key(None, locations);
+        let cached = self.lookup(&key).filter(|matrices| matrices.distances.is_some());
+        let fetch = cached.is_none().then(|| self.inner.fetch_matrices(locations));
+
+        async move {
+            let Some(fetch) = fetch else {
+                return cached.unwrap_or_else(|| TravelMatrices::from_durations(Vec::new()));
+            };
+            let matrices = fetch.await;
+            self.store(key, &matrices);
+            matrices
+        }
+    }
+
+    fn fetch_matrices_for_profile(
+        &self,
+        profile: &str,
+        locations: &[(f64, f64)],
+    ) -> impl Future<Output = TravelMatrices> + Send {
+        let key = cache_key(Some(profile), locations);
+        let cached = self.lookup(&key);
+        let fetch = cached.is_none().then(|| self.inner.fetch_matrices_for_profile(profile, locations));
+
+        async move {
+            let Some(fetch) = fetch else {
+                return cached.unwrap_or_else(|| TravelMatrices::from_durations(Vec::new()));
+            };
+            let matrices = fetch.await;
+            self.store(key, &matrices);
+            matrices
+        }
+    }
+
+    fn bytes_transferred(&self) -> Option<u64> {
+        self.inner.bytes_transferred()
+    }
+}
+
+fn cache_key(profile: Option<&str>, locations: &[(f64, f64)]) -> CacheKey {
+    CacheKey {
+        profile: profile.map(str::to_string),
+        locations: locations.iter().copied().map(LocationRef::from_coordinates).collect(),
+    }
+}
+
+/// FNV-1a over the key, stable across runs and Rust versions (unlike `DefaultHasher`).
+fn key_hash(key: &CacheKey) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut feed = |value: i64| {
+        for byte in value.to_le_bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    };
+    for location in &key.locations {
+        match *location {
+            LocationRef::Id(id) => {
+                feed(-1);
+                feed(id as i64);
+            }
+            LocationRef::Quantized(lat, lng) => {
+                feed(lat);
+                feed(lng);
+            }
+        }
+    }
+    // Default-profile keys hash as they did before profiles existed
+    if let Some(profile) = &key.profile {
+        feed(-2);
+        profile.bytes().for_each(|byte| feed(i64::from(byte)));
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::haversine::HaversineMatrix;
+
+    /// Haversine provider that counts calls.
+    #[derive(Default)]
+    struct CountingProvider {
+        calls: AtomicUsize,
+    }
+
+    impl DistanceMatrixProvider for CountingProvider {
+        fn matrix_for(&self, locations: &[(f64, f64)]) -> Vec<Vec<i32>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            HaversineMatrix::default().matrix_for(locations)
+        }
+
+        fn matrices_for(&self, locations: &[(f64, f64)]) -> TravelMatrices {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            HaversineMatrix::default().matrices_for(locations)
+        }
+    }
+
+    /// Provider that always fails, as OSRM does when unreachable.
+    struct FailingProvider;
+
+    impl DistanceMatrixProvider for FailingProvider {
+        fn matrix_for(&self, _locations: &[(f64, f64)]) -> Vec<Vec<i32>> {
+            Vec::new()
+        }
+    }
+
+    const LOCATIONS: [(f64, f64); 3] = [(36.1, -115.1), (36.2, -115.2), (36.3, -115.3)];
+
+    #[test]
+    fn test_repeated_requests_hit_cache() {
+        let cached = CachedMatrixProvider::new(CountingProvider::default());
+
+        let first = cached.matrix_for(&LOCATIONS);
+        let second = cached.matrix_for(&LOCATIONS);
+
+        assert_eq!(first, second);
+        assert_eq!(cached.inner().calls.load(Ordering::SeqCst), 1);
+
+        cached.matrix_for(&LOCATIONS[..2]);
+        assert_eq!(cached.inner().calls.load(Ordering::SeqCst), 2, "different location set");
+    }
+
+    #[test]
+    fn test_durations_only_entry_upgraded_for_distances() {
+        let cached = CachedMatrixProvider::new(CountingProvider::default());
+
+        cached.matrix_for(&LOCATIONS);
+        let matrices = cached.matrices_for(&LOCATIONS);
+        assert!(matrices.distances.is_some());
+        cached.matrix_for(&LOCATIONS);
+        cached.matrices_for(&LOCATIONS);
+
+        assert_eq!(cached.inner().calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_profiles_cached_separately() {
+        let cached = CachedMatrixProvider::new(CountingProvider::default());
+
+        cached.matrices_for(&LOCATIONS);
+        cached.matrices_for_profile("bicycle", &LOCATIONS);
+        cached.matrices_for_profile("bicycle", &LOCATIONS);
+        cached.matrices_for_profile("foot", &LOCATIONS);
+
+        assert_eq!(cached.inner().calls.load(Ordering::SeqCst), 3);
+        assert_eq!(cached.len(), 3);
+    }
+
+    #[test]
+    fn test_failed_fetch_not_cached() {
+        let cached = CachedMatrixProvider::new(FailingProvider);
+        assert!(cached.matrix_for(&LOCATIONS).is_empty());
+        assert!(cached.is_empty());
+    }
+
+    #[test]
+    fn test_disk_cache_survives_new_provider() {
+        let dir = std::env::temp_dir().join(format!("vrp-planner-cache-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let first = CachedMatrixProvider::new(CountingProvider::default()).with_disk_cache(&dir);
+        let expected = first.matrices_for(&LOCATIONS);
+
+        let second = CachedMatrixProvider::new(CountingProvider::default()).with_disk_cache(&dir);
+        assert_eq!(second.matrices_for(&LOCATIONS), expected);
+        assert_eq!(second.inner().calls.load(Ordering::SeqCst), 0);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,218 @@
+//! Partitioning visits among visitors before routing.
+//!
+//! Construction tries every position on every route for every visit, which
+//! stops scaling once a day runs to many hundreds of visits. With
+//! [`SolveOptions::clustering`](crate::solver::SolveOptions::clustering) set,
+//! the solver first splits the unpinned visits into one geographic cluster per
+//! available visitor and only tries a visit's own route, falling back to the
+//! others when it doesn't fit there. Local search still moves visits between
+//! routes afterwards.
+
+use std::f64::consts::TAU;
+
+use serde::{Deserialize, Serialize};
+
+/// Lloyd iterations before k-means gives up on converging.
+const K_MEANS_ITERATIONS: usize = 25;
+
+/// How visits are split into clusters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClusterMethod {
+    /// Sort visits by angle around the visitors' mean start and cut the
+    /// circle into arcs of roughly equal service time. Suits one shared depot.
+    Sweep,
+    /// Lloyd's k-means seeded at each visitor's start. Suits visitors who
+    /// start from home.
+    KMeans,
+}
+
+/// When and how to cluster before routing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Clustering {
+    pub method: ClusterMethod,
+    /// Fewest unpinned visits worth clustering; smaller days are routed as usual.
+    pub min_visits: usize,
+}
+
+impl Clustering {
+    /// Sweep clustering from 500 visits.
+    pub fn sweep() -> Self {
+        Self { method: ClusterMethod::Sweep, min_visits: 500 }
+    }
+
+    /// K-means clustering from 500 visits.
+    pub fn k_means() -> Self {
+        Self { method: ClusterMethod::KMeans, min_visits: 500 }
+    }
+
+    pub fn with_min_visits(mut self, min_visits: usize) -> Self {
+        self.min_visits = min_visits;
+        self
+    }
+}
+
+/// Cluster index for each point, in `0..anchors.len()`.
+///
+/// `anchors` are the visitors' start locations; cluster `i` is seeded or
+/// oriented toward `anchors[i]`. `weights` (e.g., service minutes) balance
+/// sweep arcs and are ignored by k-means. Returns nothing if `anchors` is empty.
+pub fn partition(
+    method: ClusterMethod,
+    points: &[(f64, f64)],
+    weights: &[f64],
+    anchors: &[Option<(f64, f64)>],
+) -> Vec<usize> {
+    if anchors.is_empty() {
+        return Vec::new();
+    }
+    match method {
+        ClusterMethod::Sweep => sweep(points, weights, anchors),
+        ClusterMethod::KMeans => k_means(points, anchors),
+    }
+}
+
+fn sweep(points: &[(f64, f64)], weights: &[f64], anchors: &[Option<(f64, f64)>]) -> Vec<usize> {
+    let k = anchors.len();
+    let starts: Vec<(f64, f64)> = anchors.iter().flatten().copied().collect();
+    let depot = centroid(if starts.is_empty() { points } else { &starts }).unwrap_or((0.0, 0.0));
+    let angle = |(lat, lng): (f64, f64)| (lat - depot.0).atan2(lng - depot.1);
+
+    let mut order: Vec<usize> = (0..points.len()).collect();
+    order.sort_by(|&a, &b| angle(points[a]).total_cmp(&angle(points[b])));
+
+    // Start the sweep after the widest empty arc so no cluster straddles a gap
+    let first = (0..order.len())
+        .max_by(|&a, &b| {
+            let gap = |i: usize| {
+                let previous = angle(points[order[(i + order.len() - 1) % order.len()]]);
+                (angle(points[order[i]]) - previous).rem_euclid(TAU)
+            };
+            gap(a).total_cmp(&gap(b))
+        })
+        .unwrap_or(0);
+    order.rotate_left(first);
+    let origin = order.first().map_or(0.0, |&i| angle(points[i]));
+
+    // Arcs go to anchors in the same angular order, unlocated anchors last
+    let mut arc_anchors: Vec<usize> = (0..k).collect();
+    arc_anchors.sort_by(|&a, &b| {
+        let relative = |i: usize| anchors[i].map_or(f64::INFINITY, |start| (angle(start) - origin).rem_euclid(TAU));
+        relative(a).total_cmp(&relative(b))
+    });
+
+    let weight = |i: usize| weights.get(i).copied().filter(|w| *w > 0.0).unwrap_or(1.0);
+    let total: f64 = order.iter().map(|&i| weight(i)).sum();
+    let mut clusters = vec![0; points.len()];
+    let mut swept = 0.0;
+    for &i in &order {
+        let arc = (((swept + weight(i) / 2.0) / total) * k as f64) as usize;
+        clusters[i] = arc_anchors[arc.min(k - 1)];
+        swept += weight(i);
+    }
+    clusters
+}
+
+fn k_means(points: &[(f64, f64)], anchors: &[Option<(f64, f64)>]) -> Vec<usize> {
+    // Seed at distinct anchors, then at the points farthest from every seed
+    let mut seeds: Vec<Option<(f64, f64)>> = vec![None; anchors.len()];
+    for (i, anchor) in anchors.iter().enumerate() {
+        if anchor.is_some() && !seeds[..i].contains(anchor) {
+            seeds[i] = *anchor;
+        }
+    }
+    for i in 0..seeds.len() {
+        if seeds[i].is_none() {
+            seeds[i] = points
+                .iter()
+                .copied()
+                .max_by(|a, b| nearest_distance(*a, &seeds).total_cmp(&nearest_distance(*b, &seeds)));
+        }
+    }
+    let mut centers: Vec<(f64, f64)> = seeds.into_iter().map(|seed| seed.unwrap_or((0.0, 0.0))).collect();
+
+    let mut clusters = vec![usize::MAX; points.len()];
+    for _ in 0..K_MEANS_ITERATIONS {
+        let mut changed = false;
+        for (cluster, &point) in clusters.iter_mut().zip(points) {
+            let nearest = (0..centers.len())
+                .min_by(|&a, &b| distance_sq(point, centers[a]).total_cmp(&distance_sq(point, centers[b])))
+                .unwrap_or(0);
+            changed |= *cluster != nearest;
+            *cluster = nearest;
+        }
+        if !changed {
+            break;
+        }
+        for (index, center) in centers.iter_mut().enumerate() {
+            let members: Vec<(f64, f64)> =
+                points.iter().zip(&clusters).filter(|(_, c)| **c == index).map(|(p, _)| *p).collect();
+            if let Some(mean) = centroid(&members) {
+                *center = mean;
+            }
+        }
+    }
+    clusters
+}
+
+fn nearest_distance(point: (f64, f64), seeds: &[Option<(f64, f64)>]) -> f64 {
+    seeds
+        .iter()
+        .flatten()
+        .map(|seed| distance_sq(point, *seed))
+        .fold(f64::INFINITY, f64::min)
+}
+
+pub(crate) fn distance_sq(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)
+}
+
+pub(crate) fn centroid(points: &[(f64, f64)]) -> Option<(f64, f64)> {
+    if points.is_empty() {
+        return None;
+    }
+    let n = points.len() as f64;
+    let (lat, lng) = points.iter().fold((0.0, 0.0), |(lat, lng), p| (lat + p.0, lng + p.1));
+    Some((lat / n, lng / n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two tight groups east and west of the origin.
+    fn two_groups() -> Vec<(f64, f64)> {
+        vec![(0.1, 5.0), (-0.1, 5.0), (0.0, 5.2), (0.1, -5.0), (-0.1, -5.0), (0.0, -5.2)]
+    }
+
+    #[test]
+    fn test_k_means_seeds_at_anchors() {
+        let anchors = [Some((0.0, -4.0)), Some((0.0, 4.0))];
+        let clusters = partition(ClusterMethod::KMeans, &two_groups(), &[], &anchors);
+        assert_eq!(clusters, vec![1, 1, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_k_means_spreads_seeds_for_a_shared_depot() {
+        let anchors = [Some((0.0, 0.0)); 2];
+        let clusters = partition(ClusterMethod::KMeans, &two_groups(), &[], &anchors);
+        assert_eq!(clusters[0], clusters[1]);
+        assert_eq!(clusters[0], clusters[2]);
+        assert_eq!(clusters[3], clusters[4]);
+        assert_ne!(clusters[0], clusters[3]);
+    }
+
+    #[test]
+    fn test_sweep_balances_arcs_by_weight() {
+        // Half a circle around a shared depot, swept from the first point
+        let points: Vec<(f64, f64)> = (0..8).map(|i| (f64::from(i) * TAU / 16.0).sin_cos()).collect();
+        let mut weights = vec![1.0; 8];
+        weights[0] = 7.0;
+        let clusters = partition(ClusterMethod::Sweep, &points, &weights, &[Some((0.0, 0.0)); 2]);
+        assert_eq!(clusters, vec![0, 1, 1, 1, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_no_anchors_gives_no_clusters() {
+        assert!(partition(ClusterMethod::Sweep, &two_groups(), &[], &[]).is_empty());
+    }
+}
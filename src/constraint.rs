@@ -0,0 +1,135 @@
+//! Custom route constraints.
+//!
+//! Lets callers add hard constraints (e.g., "at most 2 pool-drain jobs per
+//! route") without touching solver internals. Every candidate route the
+//! solver considers, during insertion and local search, is checked against
+//! each constraint after its schedule has been computed.
+//!
+//! The solver's own rules on single routes are built-in constraints of the
+//! same kind: [`Capabilities`], [`Pinning`], and [`CommittedWindows`]. The
+//! solver applies them through the same checks, only earlier: before a visit
+//! is tried on a route, or while its schedule is built.
+
+use crate::traits::{TimeWindow, Visit, VisitPinType, Visitor, WindowSemantics, service_bounds};
+
+/// Whether a candidate route is allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feasibility {
+    Feasible,
+    Infeasible,
+}
+
+impl Feasibility {
+    pub fn is_feasible(self) -> bool {
+        self == Feasibility::Feasible
+    }
+}
+
+impl From<bool> for Feasibility {
+    fn from(feasible: bool) -> Self {
+        if feasible { Feasibility::Feasible } else { Feasibility::Infeasible }
+    }
+}
+
+/// A route the solver is considering, with its computed schedule.
+#[derive(Debug)]
+pub struct RouteCandidate<'a, V, R> {
+    pub service_date: i64,
+    pub visitor: &'a R,
+    /// Visits in route order.
+    pub visits: &'a [&'a V],
+    /// Estimated (start, end) of each visit, parallel to `visits`.
+    pub windows: &'a [(i32, i32)],
+}
+
+/// A hard constraint on routes.
+///
+/// Constraints are checked from the solver's worker threads, so they must be
+/// `Send + Sync`. Closures taking a [`RouteCandidate`] implement this trait.
+/// Visits that can't be placed on any route because of a constraint are
+/// reported as [`RouteFull`](crate::traits::UnassignedReason::RouteFull).
+pub trait RouteConstraint<V: Visit, R: Visitor<Id = V::VisitorId>>: Send + Sync {
+    fn check(&self, route: &RouteCandidate<'_, V, R>) -> Feasibility;
+}
+
+impl<V, R, F> RouteConstraint<V, R> for F
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+    F: Fn(&RouteCandidate<'_, V, R>) -> Feasibility + Send + Sync,
+{
+    fn check(&self, route: &RouteCandidate<'_, V, R>) -> Feasibility {
+        self(route)
+    }
+}
+
+/// Visits go only to visitors with every capability they require.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Capabilities;
+
+impl Capabilities {
+    /// Whether `visitor` has all of `visit`'s required capabilities.
+    pub fn allows<V, R>(visit: &V, visitor: &R) -> bool
+    where
+        V: Visit,
+        R: Visitor<Id = V::VisitorId>,
+    {
+        let required = visit.required_capabilities();
+        required.is_empty() || required.iter().all(|cap| visitor.capabilities().contains(cap))
+    }
+}
+
+impl<V: Visit, R: Visitor<Id = V::VisitorId>> RouteConstraint<V, R> for Capabilities {
+    fn check(&self, route: &RouteCandidate<'_, V, R>) -> Feasibility {
+        route.visits.iter().all(|visit| Self::allows(*visit, route.visitor)).into()
+    }
+}
+
+/// Visits pinned to a visitor stay with that visitor, and visits pinned to a
+/// date are served on it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Pinning;
+
+impl Pinning {
+    /// Whether `visit`'s pin lets `visitor` serve it.
+    pub fn allows<V, R>(visit: &V, visitor: &R) -> bool
+    where
+        V: Visit,
+        R: Visitor<Id = V::VisitorId>,
+    {
+        !matches!(visit.pin_type(), VisitPinType::Visitor | VisitPinType::VisitorAndDate)
+            || visit.pinned_visitor() == Some(visitor.id())
+    }
+}
+
+impl<V: Visit, R: Visitor<Id = V::VisitorId>> RouteConstraint<V, R> for Pinning {
+    fn check(&self, route: &RouteCandidate<'_, V, R>) -> Feasibility {
+        route
+            .visits
+            .iter()
+            .all(|visit| {
+                Self::allows(*visit, route.visitor) && visit.pinned_date().is_none_or(|date| date == route.service_date)
+            })
+            .into()
+    }
+}
+
+/// Visits run within their committed windows, release times, and deadlines.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CommittedWindows {
+    /// How windows without their own semantics are read.
+    pub semantics: WindowSemantics,
+}
+
+impl CommittedWindows {
+    /// Whether `visit` scheduled at `window` keeps its commitments.
+    pub fn allows<V: Visit>(&self, visit: &V, (start, end): TimeWindow) -> bool {
+        service_bounds(visit, self.semantics).is_none_or(|(earliest, latest)| start >= earliest && end <= latest)
+    }
+}
+
+impl<V: Visit, R: Visitor<Id = V::VisitorId>> RouteConstraint<V, R> for CommittedWindows {
+    fn check(&self, route: &RouteCandidate<'_, V, R>) -> Feasibility {
+        route.visits.iter().zip(route.windows).all(|(visit, window)| self.allows(*visit, *window)).into()
+    }
+}
@@ -0,0 +1,257 @@
+//! Plan diffing.
+//!
+//! Compares two planner results for the same day and reports what changed
+//! for each visitor, so only affected visitors need to be notified.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::solver::PlannerResult;
+use crate::traits::Id;
+
+/// Differences between two plans.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanDiff<VisitorId, VisitId> {
+    /// Visits whose visitor changed (including newly assigned and dropped visits).
+    pub moved: Vec<MovedVisit<VisitorId, VisitId>>,
+    /// Per-visitor changes, only for visitors whose day changed.
+    pub visitors: Vec<VisitorChanges<VisitorId, VisitId>>,
+}
+
+/// A visit that changed visitor between plans.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MovedVisit<VisitorId, VisitId> {
+    pub visit_id: VisitId,
+    /// Visitor in the old plan, or `None` if it was unassigned.
+    pub from: Option<VisitorId>,
+    /// Visitor in the new plan, or `None` if it is now unassigned.
+    pub to: Option<VisitorId>,
+}
+
+/// Changes to a single visitor's route.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VisitorChanges<VisitorId, VisitId> {
+    pub visitor_id: VisitorId,
+    /// Visits that joined this route.
+    pub added: Vec<VisitId>,
+    /// Visits that left this route.
+    pub removed: Vec<VisitId>,
+    /// Visits kept on this route whose order relative to other kept visits changed.
+    pub resequenced: Vec<VisitId>,
+    /// Visits kept on this route whose estimated window changed.
+    pub window_changes: Vec<WindowChange<VisitId>>,
+}
+
+/// An estimated window that moved between plans.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WindowChange<VisitId> {
+    pub visit_id: VisitId,
+    pub old: (i32, i32),
+    pub new: (i32, i32),
+}
+
+impl<VisitorId, VisitId> PlanDiff<VisitorId, VisitId>
+where
+    VisitorId: Id,
+{
+    /// Returns true if the plans are identical.
+    pub fn is_empty(&self) -> bool {
+        self.moved.is_empty() && self.visitors.is_empty()
+    }
+
+    /// Changes for a single visitor, if their day changed.
+    pub fn for_visitor(&self, visitor_id: &VisitorId) -> Option<&VisitorChanges<VisitorId, VisitId>> {
+        self.visitors.iter().find(|changes| &changes.visitor_id == visitor_id)
+    }
+}
+
+/// Compare two plans and list moved visits, resequencings, and window changes.
+///
+/// Visitors are reported in the order they first appear in `new`, then `old`.
+pub fn diff_plans<VisitorId, VisitId>(
+    old: &PlannerResult<VisitorId, VisitId>,
+    new: &PlannerResult<VisitorId, VisitId>,
+) -> PlanDiff<VisitorId, VisitId>
+where
+    VisitorId: Id,
+    VisitId: Id,
+{
+    let old_assignments = assignments(old);
+    let new_assignments = assignments(new);
+
+    let mut moved = Vec::new();
+    for route in &new.routes {
+        for visit_id in &route.visit_ids {
+            let from = old_assignments.get(visit_id).map(|(visitor, _)| visitor);
+            if from != Some(&&route.visitor_id) {
+                moved.push(MovedVisit {
+                    visit_id: visit_id.clone(),
+                    from: from.map(|visitor| (*visitor).clone()),
+                    to: Some(route.visitor_id.clone()),
+                });
+            }
+        }
+    }
+    for route in &old.routes {
+        for visit_id in &route.visit_ids {
+            if !new_assignments.contains_key(visit_id) {
+                moved.push(MovedVisit {
+                    visit_id: visit_id.clone(),
+                    from: Some(route.visitor_id.clone()),
+                    to: None,
+                });
+            }
+        }
+    }
+
+    let mut visitor_ids: Vec<&VisitorId> = Vec::new();
+    for route in new.routes.iter().chain(old.routes.iter()) {
+        if !visitor_ids.contains(&&route.visitor_id) {
+            visitor_ids.push(&route.visitor_id);
+        }
+    }
+
+    let empty = Vec::new();
+    let mut visitors = Vec::new();
+    for visitor_id in visitor_ids {
+        let old_route = old.routes.iter().find(|route| &route.visitor_id == visitor_id);
+        let new_route = new.routes.iter().find(|route| &route.visitor_id == visitor_id);
+        let old_ids = old_route.map_or(&empty, |route| &route.visit_ids);
+        let new_ids = new_route.map_or(&empty, |route| &route.visit_ids);
+
+        let added: Vec<VisitId> = new_ids.iter().filter(|id| !old_ids.contains(id)).cloned().collect();
+        let removed: Vec<VisitId> = old_ids.iter().filter(|id| !new_ids.contains(id)).cloned().collect();
+
+        let old_kept: Vec<&VisitId> = old_ids.iter().filter(|id| new_ids.contains(id)).collect();
+        let new_kept: Vec<&VisitId> = new_ids.iter().filter(|id| old_ids.contains(id)).collect();
+        let resequenced: Vec<VisitId> = new_kept
+            .iter()
+            .zip(old_kept.iter())
+            .filter(|(new_id, old_id)| new_id != old_id)
+            .map(|(new_id, _)| (*new_id).clone())
+            .collect();
+
+        let window_changes: Vec<WindowChange<VisitId>> = new_kept
+            .iter()
+            .filter_map(|visit_id| {
+                let (_, old_window) = old_assignments.get(*visit_id)?;
+                let (_, new_window) = new_assignments.get(*visit_id)?;
+                (old_window != new_window).then(|| WindowChange {
+                    visit_id: (*visit_id).clone(),
+                    old: *old_window,
+                    new: *new_window,
+                })
+            })
+            .collect();
+
+        if added.is_empty() && removed.is_empty() && resequenced.is_empty() && window_changes.is_empty() {
+            continue;
+        }
+        visitors.push(VisitorChanges {
+            visitor_id: visitor_id.clone(),
+            added,
+            removed,
+            resequenced,
+            window_changes,
+        });
+    }
+
+    PlanDiff { moved, visitors }
+}
+
+/// Map each assigned visit to its visitor and estimated window.
+fn assignments<VisitorId, VisitId>(
+    plan: &PlannerResult<VisitorId, VisitId>,
+) -> HashMap<&VisitId, (&VisitorId, (i32, i32))>
+where
+    VisitId: Id,
+{
+    plan.routes
+        .iter()
+        .flat_map(|route| {
+            route.visit_ids.iter().enumerate().map(move |(idx, visit_id)| {
+                let window = route.estimated_windows.get(idx).copied().unwrap_or_default();
+                (visit_id, (&route.visitor_id, window))
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::RouteResult;
+
+    fn route(visitor: &'static str, visits: &[(&'static str, i32)]) -> RouteResult<&'static str, &'static str> {
+        RouteResult {
+            visitor_id: visitor,
+            visit_ids: visits.iter().map(|(id, _)| *id).collect(),
+            estimated_windows: visits.iter().map(|(_, start)| (*start, start + 1800)).collect(),
+            total_travel_time: 0,
+            total_distance_km: None,
+            legs: Vec::new(),
+            window_miss_risk: 0.0,
+            robustness_slack_secs: None,
+            return_leg: None,
+        }
+    }
+
+    fn plan(routes: Vec<RouteResult<&'static str, &'static str>>) -> PlannerResult<&'static str, &'static str> {
+        PlannerResult { routes, unassigned: Vec::new() }
+    }
+
+    #[test]
+    fn test_identical_plans_have_empty_diff() {
+        let old = plan(vec![route("alice", &[("v1", 100), ("v2", 200)])]);
+        let diff = diff_plans(&old, &old.clone());
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn test_moved_visit() {
+        let old = plan(vec![route("alice", &[("v1", 100), ("v2", 200)]), route("bob", &[])]);
+        let new = plan(vec![route("alice", &[("v1", 100)]), route("bob", &[("v2", 300)])]);
+        let diff = diff_plans(&old, &new);
+
+        assert_eq!(
+            diff.moved,
+            vec![MovedVisit { visit_id: "v2", from: Some("alice"), to: Some("bob") }]
+        );
+        assert_eq!(diff.for_visitor(&"alice").unwrap().removed, vec!["v2"]);
+        assert_eq!(diff.for_visitor(&"bob").unwrap().added, vec!["v2"]);
+    }
+
+    #[test]
+    fn test_resequenced_and_window_changes() {
+        let old = plan(vec![route("alice", &[("v1", 100), ("v2", 200), ("v3", 300)])]);
+        let new = plan(vec![route("alice", &[("v2", 100), ("v1", 200), ("v3", 300)])]);
+        let diff = diff_plans(&old, &new);
+
+        assert!(diff.moved.is_empty());
+        let alice = diff.for_visitor(&"alice").unwrap();
+        assert_eq!(alice.resequenced, vec!["v2", "v1"]);
+        assert_eq!(alice.window_changes.len(), 2);
+        assert_eq!(alice.window_changes[0], WindowChange { visit_id: "v2", old: (200, 2000), new: (100, 1900) });
+    }
+
+    #[test]
+    fn test_dropped_and_new_visits() {
+        let old = plan(vec![route("alice", &[("v1", 100)])]);
+        let new = plan(vec![route("alice", &[("v2", 100)])]);
+        let diff = diff_plans(&old, &new);
+
+        assert!(diff.moved.contains(&MovedVisit { visit_id: "v2", from: None, to: Some("alice") }));
+        assert!(diff.moved.contains(&MovedVisit { visit_id: "v1", from: Some("alice"), to: None }));
+    }
+
+    #[test]
+    fn test_unchanged_visitor_not_reported() {
+        let old = plan(vec![route("alice", &[("v1", 100)]), route("bob", &[("v2", 100)])]);
+        let new = plan(vec![route("alice", &[("v1", 100)]), route("bob", &[("v2", 400)])]);
+        let diff = diff_plans(&old, &new);
+
+        assert!(diff.for_visitor(&"alice").is_none());
+        assert!(diff.for_visitor(&"bob").is_some());
+    }
+}
@@ -0,0 +1,240 @@
+//! Fallback matrix provider.
+//!
+//! Tries a primary provider (typically OSRM) and falls back to a secondary
+//! one (typically haversine) when the primary fails, so an OSRM outage
+//! degrades route quality instead of failing the solve.
+
+use std::future::Future;
+
+use tracing::warn;
+
+use crate::haversine::{HaversineMatrix, TYPICAL_CIRCUITY};
+use crate::matrix::metered::sum_bytes;
+use crate::traits::{AsyncDistanceMatrixProvider, DistanceMatrixProvider, TravelMatrices};
+
+/// Matrix provider that falls back to a secondary provider on failure.
+///
+/// The primary is considered to have failed when it returns a matrix whose
+/// dimensions don't match the requested locations (the OSRM clients return an
+/// empty matrix on errors and timeouts). Fallback durations and distances are
+/// multiplied by the fallback scale. Providers can be nested to form a chain.
+#[derive(Debug, Clone)]
+pub struct FallbackMatrixProvider<P, F = HaversineMatrix> {
+    primary: P,
+    fallback: F,
+    fallback_scale: f64,
+}
+
+impl<P> FallbackMatrixProvider<P, HaversineMatrix> {
+    /// Falls back to [`HaversineMatrix`] with [`TYPICAL_CIRCUITY`] applied.
+    pub fn with_haversine(primary: P) -> Self {
+        Self::new(primary, HaversineMatrix::default().with_circuity(TYPICAL_CIRCUITY))
+    }
+}
+
+impl<P, F> FallbackMatrixProvider<P, F> {
+    /// Falls back to `fallback` unscaled.
+    pub fn new(primary: P, fallback: F) -> Self {
+        Self {
+            primary,
+            fallback,
+            fallback_scale: 1.0,
+        }
+    }
+
+    /// Multiplies fallback durations and distances by `scale`.
+    pub fn with_fallback_scale(mut self, scale: f64) -> Self {
+        self.fallback_scale = scale;
+        self
+    }
+
+    pub fn primary(&self) -> &P {
+        &self.primary
+    }
+
+    pub fn fallback(&self) -> &F {
+        &self.fallback
+    }
+}
+
+impl<P, F> DistanceMatrixProvider for FallbackMatrixProvider<P, F>
+where
+    P: DistanceMatrixProvider,
+    F: DistanceMatrixProvider,
+{
+    fn matrix_for(&self, locations: &[(f64, f64)]) -> Vec<Vec<i32>> {
+        let durations = self.primary.matrix_for(locations);
+        if is_complete(&durations, locations.len()) {
+            return durations;
+        }
+
+        warn!(locations = locations.len(), "Primary matrix provider failed, using fallback");
+        scale(self.fallback.matrix_for(locations), self.fallback_scale)
+    }
+
+    fn matrices_for(&self, locations: &[(f64, f64)]) -> TravelMatrices {
+        let matrices = self.primary.matrices_for(locations);
+        if is_complete(&matrices.durations, locations.len()) {
+            return matrices;
+        }
+
+        warn!(locations = locations.len(), "Primary matrix provider failed, using fallback");
+        scale_matrices(self.fallback.matrices_for(locations), self.fallback_scale)
+    }
+
+    fn matrices_for_profile(&self, profile: &str, locations: &[(f64, f64)]) -> TravelMatrices {
+        let matrices = self.primary.matrices_for_profile(profile, locations);
+        if is_complete(&matrices.durations, locations.len()) {
+            return matrices;
+        }
+
+        warn!(locations = locations.len(), profile, "Primary matrix provider failed, using fallback");
+        scale_matrices(self.fallback.matrices_for_profile(profile, locations), self.fallback_scale)
+    }
+
+    fn bytes_transferred(&self) -> Option<u64> {
+        sum_bytes(self.primary.bytes_transferred(), self.fallback.bytes_transferred())
+    }
+}
+
+impl<P, F> AsyncDistanceMatrixProvider for FallbackMatrixProvider<P, F>
+where
+    P: AsyncDistanceMatrixProvider + Sync,
+    F: AsyncDistanceMatrixProvider + Sync,
+{
+    fn fetch_matrix(&self, locations: &[(f64, f64)]) -> impl Future<Output = Vec<Vec<i32>>> + Send {
+        let primary = self.primary.fetch_matrix(locations);
+        // Owned so the fallback is only queried if the primary fails.
+        let locations = locations.to_vec();
+
+        async move {
+            let durations = primary.await;
+            if is_complete(&durations, locations.len()) {
+                return durations;
+            }
+
+            warn!(locations = locations.len(), "Primary matrix provider failed, using fallback");
+            scale(self.fallback.fetch_matrix(&locations).await, self.fallback_scale)
+        }
+    }
+
+    fn fetch_matrices(&self, locations: &[(f64, f64)]) -> impl Future<Output = TravelMatrices> + Send {
+        let primary = self.primary.fetch_matrices(locations);
+        // Owned so the fallback is only queried if the primary fails.
+        let locations = locations.to_vec();
+
+        async move {
+            let matrices = primary.await;
+            if is_complete(&matrices.durations, locations.len()) {
+                return matrices;
+            }
+
+            warn!(locations = locations.len(), "Primary matrix provider failed, using fallback");
+            scale_matrices(self.fallback.fetch_matrices(&locations).await, self.fallback_scale)
+        }
+    }
+
+    fn fetch_matrices_for_profile(
+        &self,
+        profile: &str,
+        locations: &[(f64, f64)],
+    ) -> impl Future<Output = TravelMatrices> + Send {
+        let primary = self.primary.fetch_matrices_for_profile(profile, locations);
+        // Owned so the fallback is only queried if the primary fails.
+        let (profile, locations) = (profile.to_string(), locations.to_vec());
+
+        async move {
+            let matrices = primary.await;
+            if is_complete(&matrices.durations, locations.len()) {
+                return matrices;
+            }
+
+            warn!(locations = locations.len(), profile, "Primary matrix provider failed, using fallback");
+            scale_matrices(self.fallback.fetch_matrices_for_profile(&profile, &locations).await, self.fallback_scale)
+        }
+    }
+
+    fn bytes_transferred(&self) -> Option<u64> {
+        sum_bytes(self.primary.bytes_transferred(), self.fallback.bytes_transferred())
+    }
+}
+
+/// Whether a matrix has a full row and column for each location.
+fn is_complete(matrix: &[Vec<i32>], locations: usize) -> bool {
+    matrix.len() == locations && matrix.iter().all(|row| row.len() == locations)
+}
+
+fn scale(matrix: Vec<Vec<i32>>, factor: f64) -> Vec<Vec<i32>> {
+    if factor == 1.0 {
+        return matrix;
+    }
+    matrix
+        .into_iter()
+        .map(|row| row.into_iter().map(|value| (value as f64 * factor).round() as i32).collect())
+        .collect()
+}
+
+fn scale_matrices(matrices: TravelMatrices, factor: f64) -> TravelMatrices {
+    TravelMatrices {
+        durations: scale(matrices.durations, factor),
+        distances: matrices.distances.map(|distances| scale(distances, factor)),
+        snap_distances: matrices.snap_distances,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Provider that always fails, as OSRM does when unreachable.
+    struct FailingProvider;
+
+    impl DistanceMatrixProvider for FailingProvider {
+        fn matrix_for(&self, _locations: &[(f64, f64)]) -> Vec<Vec<i32>> {
+            Vec::new()
+        }
+    }
+
+    /// Provider that returns a fixed travel time between distinct locations.
+    struct ConstantProvider(i32);
+
+    impl DistanceMatrixProvider for ConstantProvider {
+        fn matrix_for(&self, locations: &[(f64, f64)]) -> Vec<Vec<i32>> {
+            let n = locations.len();
+            (0..n).map(|i| (0..n).map(|j| if i == j { 0 } else { self.0 }).collect()).collect()
+        }
+    }
+
+    const LOCATIONS: [(f64, f64); 2] = [(36.1, -115.1), (36.2, -115.2)];
+
+    #[test]
+    fn test_primary_used_when_it_succeeds() {
+        let provider = FallbackMatrixProvider::new(ConstantProvider(100), ConstantProvider(999));
+        assert_eq!(provider.matrix_for(&LOCATIONS)[0][1], 100);
+    }
+
+    #[test]
+    fn test_fallback_scaled_when_primary_fails() {
+        let provider = FallbackMatrixProvider::new(FailingProvider, ConstantProvider(100)).with_fallback_scale(1.5);
+        assert_eq!(provider.matrix_for(&LOCATIONS), vec![vec![0, 150], vec![150, 0]]);
+    }
+
+    #[test]
+    fn test_haversine_fallback_includes_distances() {
+        let provider = FallbackMatrixProvider::with_haversine(FailingProvider);
+        let matrices = provider.matrices_for(&LOCATIONS);
+
+        let straight = HaversineMatrix::default().matrices_for(&LOCATIONS);
+        assert!(matrices.durations[0][1] > straight.durations[0][1]);
+        assert!(matrices.distances.is_some());
+    }
+
+    #[test]
+    fn test_chained_fallbacks() {
+        let provider = FallbackMatrixProvider::new(
+            FailingProvider,
+            FallbackMatrixProvider::new(FailingProvider, ConstantProvider(60)),
+        );
+        assert_eq!(provider.matrix_for(&LOCATIONS)[1][0], 60);
+    }
+}
@@ -0,0 +1,114 @@
+//! C ABI for embedding.
+//!
+//! Exposes the JSON model over plain C strings so Go (cgo), .NET (P/Invoke),
+//! and other non-Rust services can link the planner directly. Build a shared
+//! library with:
+//!
+//! ```text
+//! cargo rustc --release --features ffi --crate-type cdylib
+//! ```
+//!
+//! and declare:
+//!
+//! ```c
+//! char *vrp_solve(const char *problem_json);
+//! void vrp_string_free(char *s);
+//! ```
+//!
+//! Travel times use [`HaversineMatrix`]; callers needing road times should
+//! use the HTTP server or the Rust API.
+
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+
+use serde_json::json;
+
+use crate::haversine::HaversineMatrix;
+use crate::model::Problem;
+
+/// Solves a [`Problem`] given as a NUL-terminated JSON string.
+///
+/// Returns the plan as JSON (see [`PlannerResult::to_json`](crate::solver::PlannerResult::to_json)),
+/// or `{"error": "..."}` if the input can't be read or the solve fails.
+/// The result is owned by the caller and must be released with
+/// [`vrp_string_free`]. Returns null only if `problem_json` is null.
+///
+/// # Safety
+///
+/// `problem_json` must be null or point to a NUL-terminated string that
+/// stays valid for the duration of the call.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vrp_solve(problem_json: *const c_char) -> *mut c_char {
+    if problem_json.is_null() {
+        return std::ptr::null_mut();
+    }
+    // SAFETY: non-null, and the caller guarantees NUL termination and lifetime.
+    let input = unsafe { CStr::from_ptr(problem_json) };
+
+    let output = panic::catch_unwind(AssertUnwindSafe(|| solve_json(input)))
+        .unwrap_or_else(|_| Err("solver panicked".to_string()))
+        .unwrap_or_else(|err| json!({ "error": err }).to_string());
+
+    // JSON escapes control characters, so the output never contains NUL.
+    CString::new(output).map_or(std::ptr::null_mut(), CString::into_raw)
+}
+
+/// Releases a string returned by [`vrp_solve`]. Null is ignored.
+///
+/// # Safety
+///
+/// `s` must be null or a pointer returned by [`vrp_solve`] that has not
+/// already been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn vrp_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        // SAFETY: the caller guarantees `s` came from `CString::into_raw` in `vrp_solve`.
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+fn solve_json(input: &CStr) -> Result<String, String> {
+    let text = input.to_str().map_err(|err| format!("input is not UTF-8: {}", err))?;
+    let problem: Problem = serde_json::from_str(text).map_err(|err| format!("invalid problem: {}", err))?;
+    let plan = problem.solve(&HaversineMatrix::default()).map_err(|err| err.to_string())?;
+    plan.to_json().map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+
+    fn call(input: &str) -> Value {
+        let input = CString::new(input).unwrap();
+        unsafe {
+            let output = vrp_solve(input.as_ptr());
+            let value = serde_json::from_str(CStr::from_ptr(output).to_str().unwrap()).unwrap();
+            vrp_string_free(output);
+            value
+        }
+    }
+
+    #[test]
+    fn test_solve_round_trips_json() {
+        let plan = call(
+            r#"{
+                "service_date": 86400,
+                "visits": [{ "id": "v1", "location": [36.11, -115.17], "duration_minutes": 30 }],
+                "visitors": [{ "id": "alice", "start_location": [36.10, -115.15] }],
+                "availability": { "default_windows": [[28800, 61200]] }
+            }"#,
+        );
+
+        assert_eq!(plan["schema_version"], 1);
+        assert_eq!(plan["routes"][0]["visit_ids"][0], "v1");
+    }
+
+    #[test]
+    fn test_reports_errors_as_json() {
+        assert!(call("{ not json").get("error").is_some());
+        assert!(unsafe { vrp_solve(std::ptr::null()) }.is_null());
+        unsafe { vrp_string_free(std::ptr::null_mut()) };
+    }
+}
@@ -0,0 +1,185 @@
+//! Synthetic problem instances.
+//!
+//! Generates reproducible random days for benchmarks and scale tests: the
+//! same [`InstanceConfig`] (including its seed) always produces the same
+//! visits, visitors, and availability, so timings and plan quality can be
+//! compared across solver changes.
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::{AvailabilityData, VisitData, VisitorData};
+
+/// Center of the generated service area (Las Vegas).
+const CENTER: (f64, f64) = (36.15, -115.15);
+/// Half-width of the service area, in degrees (~20 km).
+const AREA_RADIUS_DEG: f64 = 0.18;
+/// Spread of visits around their cluster center, in degrees (~2 km).
+const CLUSTER_RADIUS_DEG: f64 = 0.02;
+/// Working day every visitor is available for: 8:00 to 17:00.
+const DAY_WINDOW: (i32, i32) = (8 * 3600, 17 * 3600);
+
+/// Shape of a generated instance.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InstanceConfig {
+    pub visits: usize,
+    pub visitors: usize,
+    /// How narrow committed windows are, from 0.0 (no committed windows) to
+    /// 1.0 (each window just fits its visit).
+    pub window_tightness: f64,
+    /// Number of geographic clusters visits are drawn around; 0 spreads them
+    /// uniformly over the service area.
+    pub clusters: usize,
+    pub seed: u64,
+}
+
+impl Default for InstanceConfig {
+    fn default() -> Self {
+        Self {
+            visits: 100,
+            visitors: 10,
+            window_tightness: 0.0,
+            clusters: 0,
+            seed: 42,
+        }
+    }
+}
+
+impl InstanceConfig {
+    pub fn new(visits: usize, visitors: usize) -> Self {
+        Self {
+            visits,
+            visitors,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_window_tightness(mut self, tightness: f64) -> Self {
+        self.window_tightness = tightness.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_clusters(mut self, clusters: usize) -> Self {
+        self.clusters = clusters;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+/// A generated day, ready to pass to [`solve`](crate::solver::solve).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Instance {
+    pub service_date: i64,
+    pub visits: Vec<VisitData>,
+    pub visitors: Vec<VisitorData>,
+    pub availability: AvailabilityData,
+}
+
+/// Generates an instance from `config`.
+///
+/// Visits last 15 to 60 minutes. Visitors start spread over the service area
+/// and are available 8:00 to 17:00. Committed windows, when enabled, are
+/// placed uniformly within the working day.
+pub fn generate(config: &InstanceConfig) -> Instance {
+    let mut rng = SplitMix64(config.seed);
+
+    let centers: Vec<(f64, f64)> = (0..config.clusters).map(|_| rng.point(CENTER, AREA_RADIUS_DEG)).collect();
+
+    let visits = (0..config.visits)
+        .map(|idx| {
+            let location = if centers.is_empty() {
+                rng.point(CENTER, AREA_RADIUS_DEG)
+            } else {
+                let center = centers[rng.below(centers.len())];
+                rng.point(center, CLUSTER_RADIUS_DEG)
+            };
+            let duration_minutes = 15 + 15 * rng.below(4) as i32;
+            let visit = VisitData::new(format!("v{idx}"), location, duration_minutes);
+
+            if config.window_tightness <= 0.0 {
+                return visit;
+            }
+            let duration_secs = duration_minutes * 60;
+            let slack = ((DAY_WINDOW.1 - DAY_WINDOW.0 - duration_secs) as f64 * (1.0 - config.window_tightness)) as i32;
+            let width = duration_secs + slack;
+            let start = DAY_WINDOW.0 + rng.below((DAY_WINDOW.1 - DAY_WINDOW.0 - width + 1) as usize) as i32;
+            visit.with_committed_window(start, start + width)
+        })
+        .collect();
+
+    let visitors = (0..config.visitors)
+        .map(|idx| {
+            let (lat, lng) = rng.point(CENTER, AREA_RADIUS_DEG);
+            VisitorData::new(format!("r{idx}")).with_start_location(lat, lng)
+        })
+        .collect();
+
+    Instance {
+        service_date: 0,
+        visits,
+        visitors,
+        availability: AvailabilityData::new(vec![DAY_WINDOW]),
+    }
+}
+
+/// Small deterministic PRNG, so instances don't depend on an RNG crate's
+/// stream staying stable across versions.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1).
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform in [0, n).
+    fn below(&mut self, n: usize) -> usize {
+        (self.unit() * n as f64) as usize
+    }
+
+    /// Uniform point in the square of half-width `radius` around `center`.
+    fn point(&mut self, center: (f64, f64), radius: f64) -> (f64, f64) {
+        (
+            center.0 + (self.unit() * 2.0 - 1.0) * radius,
+            center.1 + (self.unit() * 2.0 - 1.0) * radius,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::Visit;
+
+    #[test]
+    fn test_same_seed_same_instance() {
+        let config = InstanceConfig::new(50, 5).with_clusters(3).with_window_tightness(0.5);
+
+        assert_eq!(generate(&config), generate(&config));
+        assert_ne!(generate(&config), generate(&config.clone().with_seed(7)));
+    }
+
+    #[test]
+    fn test_committed_windows_fit_visits_and_day() {
+        let instance = generate(&InstanceConfig::new(200, 10).with_window_tightness(1.0));
+
+        for visit in &instance.visits {
+            let (start, end) = visit.committed_window().unwrap();
+            assert_eq!(end - start, visit.estimated_duration_minutes() * 60);
+            assert!(start >= DAY_WINDOW.0 && end <= DAY_WINDOW.1);
+        }
+        assert!(generate(&InstanceConfig::new(20, 2)).visits.iter().all(|visit| visit.committed_window().is_none()));
+    }
+}
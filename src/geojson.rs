@@ -0,0 +1,115 @@
+//! GeoJSON export for route geometries and plans.
+//!
+//! Coordinates are written in GeoJSON's (lng, lat) order. A `&Polyline`
+//! converts into a LineString [`Value`], and from there into a [`Geometry`].
+
+use ::geojson::{Feature, FeatureCollection, Geometry, JsonObject, Value};
+use serde::Serialize;
+
+use crate::polyline::Polyline;
+use crate::solver::{PlannerResult, RouteResult};
+
+impl From<&Polyline> for Value {
+    fn from(polyline: &Polyline) -> Self {
+        Value::LineString(polyline.points().iter().map(|&(lat, lng)| vec![lng, lat]).collect())
+    }
+}
+
+/// Converts a plan into a FeatureCollection with one feature per route.
+///
+/// `geometries` is parallel to `plan.routes`; routes without an entry get a
+/// null geometry. Each feature carries the visitor id, stop order, estimated
+/// windows, and travel totals as properties.
+pub fn plan_to_geojson<VisitorId, VisitId>(
+    plan: &PlannerResult<VisitorId, VisitId>,
+    geometries: &[Polyline],
+) -> Result<FeatureCollection, serde_json::Error>
+where
+    VisitorId: Serialize,
+    VisitId: Serialize,
+{
+    let features = plan
+        .routes
+        .iter()
+        .enumerate()
+        .map(|(idx, route)| {
+            Ok(Feature {
+                bbox: None,
+                geometry: geometries.get(idx).map(Geometry::from),
+                id: None,
+                properties: Some(route_properties(idx, route)?),
+                foreign_members: None,
+            })
+        })
+        .collect::<Result<_, serde_json::Error>>()?;
+
+    Ok(FeatureCollection { bbox: None, features, foreign_members: None })
+}
+
+fn route_properties<VisitorId, VisitId>(
+    idx: usize,
+    route: &RouteResult<VisitorId, VisitId>,
+) -> Result<JsonObject, serde_json::Error>
+where
+    VisitorId: Serialize,
+    VisitId: Serialize,
+{
+    let mut properties = JsonObject::new();
+    properties.insert("route_index".to_string(), idx.into());
+    properties.insert("visitor_id".to_string(), serde_json::to_value(&route.visitor_id)?);
+    properties.insert("stop_order".to_string(), serde_json::to_value(&route.visit_ids)?);
+    properties.insert("estimated_windows".to_string(), serde_json::to_value(&route.estimated_windows)?);
+    properties.insert("total_travel_time".to_string(), route.total_travel_time.into());
+    if let Some(km) = route.total_distance_km {
+        properties.insert("total_distance_km".to_string(), km.into());
+    }
+    Ok(properties)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(visitor: &'static str, visits: &[&'static str]) -> RouteResult<&'static str, &'static str> {
+        RouteResult {
+            visitor_id: visitor,
+            visit_ids: visits.to_vec(),
+            estimated_windows: visits.iter().map(|_| (32_400, 34_200)).collect(),
+            total_travel_time: 600,
+            total_distance_km: Some(8.5),
+            legs: Vec::new(),
+            window_miss_risk: 0.0,
+            robustness_slack_secs: None,
+            return_leg: None,
+        }
+    }
+
+    #[test]
+    fn test_polyline_to_line_string_swaps_to_lng_lat() {
+        let polyline = Polyline::new(vec![(36.1, -115.1), (36.2, -115.2)]);
+        let value = Value::from(&polyline);
+        assert_eq!(value, Value::LineString(vec![vec![-115.1, 36.1], vec![-115.2, 36.2]]));
+    }
+
+    #[test]
+    fn test_plan_to_feature_collection() {
+        let plan = PlannerResult {
+            routes: vec![route("alice", &["v2", "v1"]), route("bob", &["v3"])],
+            unassigned: Vec::new(),
+        };
+        let geometries = vec![Polyline::new(vec![(36.1, -115.1), (36.2, -115.2)])];
+
+        let collection = plan_to_geojson(&plan, &geometries).expect("serialize plan");
+
+        assert_eq!(collection.features.len(), 2);
+        let alice = &collection.features[0];
+        assert!(alice.geometry.is_some());
+        assert_eq!(alice.property("visitor_id"), Some(&serde_json::json!("alice")));
+        assert_eq!(alice.property("stop_order"), Some(&serde_json::json!(["v2", "v1"])));
+        assert_eq!(alice.property("estimated_windows"), Some(&serde_json::json!([[32_400, 34_200], [32_400, 34_200]])));
+        assert!(collection.features[1].geometry.is_none(), "no geometry supplied for bob");
+
+        let json = collection.to_string();
+        assert!(json.contains(r#""type":"FeatureCollection""#), "{json}");
+    }
+}
@@ -3,7 +3,9 @@
 //! Uses great-circle distance to estimate travel time.
 //! Less accurate than OSRM (ignores roads) but always available.
 
-use crate::traits::DistanceMatrixProvider;
+use std::future::Future;
+
+use crate::traits::{AsyncDistanceMatrixProvider, DistanceMatrixProvider, TravelMatrices};
 
 /// Average driving speed assumption for time estimation.
 const DEFAULT_SPEED_KMH: f64 = 40.0;
@@ -11,31 +13,74 @@ const DEFAULT_SPEED_KMH: f64 = 40.0;
 /// Earth radius in kilometers.
 const EARTH_RADIUS_KM: f64 = 6371.0;
 
+/// Typical ratio of road distance to straight-line distance in a street grid.
+pub const TYPICAL_CIRCUITY: f64 = 1.3;
+
+/// Speed for legs up to a given road distance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpeedBand {
+    /// Upper bound of road distance (km) this band applies to.
+    pub max_km: f64,
+    /// Assumed average driving speed in km/h for legs in this band.
+    pub speed_kmh: f64,
+}
+
 /// Haversine-based distance matrix provider.
 ///
 /// Estimates travel time using straight-line distance and an assumed speed.
 /// Useful as a fallback when OSRM is unavailable.
 #[derive(Debug, Clone)]
 pub struct HaversineMatrix {
-    /// Assumed average driving speed in km/h.
+    /// Assumed average driving speed in km/h (for legs beyond every speed band).
     pub speed_kmh: f64,
+    /// Multiplier from straight-line to estimated road distance.
+    pub circuity: f64,
+    /// Speeds by leg length, sorted by `max_km`. The first band whose
+    /// `max_km` covers the leg's road distance sets its speed.
+    pub speed_bands: Vec<SpeedBand>,
 }
 
 impl Default for HaversineMatrix {
     fn default() -> Self {
         Self {
             speed_kmh: DEFAULT_SPEED_KMH,
+            circuity: 1.0,
+            speed_bands: Vec::new(),
         }
     }
 }
 
 impl HaversineMatrix {
     pub fn new(speed_kmh: f64) -> Self {
-        Self { speed_kmh }
+        Self { speed_kmh, ..Self::default() }
+    }
+
+    /// Scale straight-line distances by `circuity` (e.g., [`TYPICAL_CIRCUITY`]).
+    pub fn with_circuity(mut self, circuity: f64) -> Self {
+        self.circuity = circuity;
+        self
+    }
+
+    /// Use different speeds for short and long legs.
+    ///
+    /// E.g., `[(2.0, 20.0), (15.0, 45.0)]` for slow urban hops, arterial
+    /// trips, and `speed_kmh` for anything longer (freeway legs).
+    pub fn with_speed_bands(mut self, bands: impl IntoIterator<Item = (f64, f64)>) -> Self {
+        self.speed_bands = bands
+            .into_iter()
+            .map(|(max_km, speed_kmh)| SpeedBand { max_km, speed_kmh })
+            .collect();
+        self.speed_bands.sort_by(|a, b| a.max_km.total_cmp(&b.max_km));
+        self
+    }
+
+    /// Estimated road distance in kilometers between two points.
+    fn road_km(&self, from: (f64, f64), to: (f64, f64)) -> f64 {
+        Self::haversine_km(from, to) * self.circuity
     }
 
     /// Calculate haversine distance between two points in kilometers.
-    fn haversine_km(from: (f64, f64), to: (f64, f64)) -> f64 {
+    pub(crate) fn haversine_km(from: (f64, f64), to: (f64, f64)) -> f64 {
         let (lat1, lng1) = from;
         let (lat2, lng2) = to;
 
@@ -51,9 +96,14 @@ impl HaversineMatrix {
         EARTH_RADIUS_KM * c
     }
 
-    /// Convert distance in km to travel time in seconds.
+    /// Convert road distance in km to travel time in seconds.
     fn km_to_seconds(&self, km: f64) -> i32 {
-        let hours = km / self.speed_kmh;
+        let speed_kmh = self
+            .speed_bands
+            .iter()
+            .find(|band| km <= band.max_km)
+            .map_or(self.speed_kmh, |band| band.speed_kmh);
+        let hours = km / speed_kmh;
         (hours * 3600.0).round() as i32
     }
 }
@@ -66,7 +116,7 @@ impl DistanceMatrixProvider for HaversineMatrix {
         for (i, from) in locations.iter().enumerate() {
             for (j, to) in locations.iter().enumerate() {
                 if i != j {
-                    let km = Self::haversine_km(*from, *to);
+                    let km = self.road_km(*from, *to);
                     matrix[i][j] = self.km_to_seconds(km);
                 }
             }
@@ -74,6 +124,38 @@ impl DistanceMatrixProvider for HaversineMatrix {
 
         matrix
     }
+
+    fn matrices_for(&self, locations: &[(f64, f64)]) -> TravelMatrices {
+        let n = locations.len();
+        let mut durations = vec![vec![0; n]; n];
+        let mut distances = vec![vec![0; n]; n];
+
+        for (i, from) in locations.iter().enumerate() {
+            for (j, to) in locations.iter().enumerate() {
+                if i != j {
+                    let km = self.road_km(*from, *to);
+                    durations[i][j] = self.km_to_seconds(km);
+                    distances[i][j] = (km * 1000.0).round() as i32;
+                }
+            }
+        }
+
+        TravelMatrices {
+            durations,
+            distances: Some(distances),
+            snap_distances: None,
+        }
+    }
+}
+
+impl AsyncDistanceMatrixProvider for HaversineMatrix {
+    fn fetch_matrix(&self, locations: &[(f64, f64)]) -> impl Future<Output = Vec<Vec<i32>>> + Send {
+        std::future::ready(self.matrix_for(locations))
+    }
+
+    fn fetch_matrices(&self, locations: &[(f64, f64)]) -> impl Future<Output = TravelMatrices> + Send {
+        std::future::ready(self.matrices_for(locations))
+    }
 }
 
 #[cfg(test)]
@@ -100,8 +182,8 @@ mod tests {
         let locations = vec![(36.1, -115.1), (36.2, -115.2), (36.3, -115.3)];
         let matrix = provider.matrix_for(&locations);
 
-        for i in 0..locations.len() {
-            assert_eq!(matrix[i][i], 0, "Diagonal should be zero");
+        for (i, row) in matrix.iter().enumerate() {
+            assert_eq!(row[i], 0, "Diagonal should be zero");
         }
     }
 
@@ -115,6 +197,40 @@ mod tests {
         assert_eq!(matrix[0][1], matrix[1][0], "Matrix should be symmetric");
     }
 
+    #[test]
+    fn test_matrices_include_distances() {
+        let provider = HaversineMatrix::default();
+        let locations = vec![(36.17, -115.14), (34.05, -118.24)];
+        let matrices = provider.matrices_for(&locations);
+
+        assert_eq!(matrices.durations, provider.matrix_for(&locations));
+        let distances = matrices.distances.expect("haversine provides distances");
+        assert!(distances[0][1] > 350_000 && distances[0][1] < 400_000);
+        assert_eq!(distances[1][1], 0);
+    }
+
+    #[test]
+    fn test_circuity_scales_time_and_distance() {
+        let locations = vec![(36.1, -115.1), (36.2, -115.2)];
+        let straight = HaversineMatrix::default().matrices_for(&locations);
+        let road = HaversineMatrix::default().with_circuity(2.0).matrices_for(&locations);
+
+        assert!((road.durations[0][1] - 2 * straight.durations[0][1]).abs() <= 1);
+        let (straight_m, road_m) = (straight.distances.unwrap()[0][1], road.distances.unwrap()[0][1]);
+        assert!((road_m - 2 * straight_m).abs() <= 1);
+    }
+
+    #[test]
+    fn test_speed_bands() {
+        let provider = HaversineMatrix::new(90.0).with_speed_bands([(20.0, 50.0), (2.0, 20.0)]);
+        // 1 km at 20 km/h = 180 seconds
+        assert_eq!(provider.km_to_seconds(1.0), 180);
+        // 10 km at 50 km/h = 720 seconds
+        assert_eq!(provider.km_to_seconds(10.0), 720);
+        // 90 km at 90 km/h = 3600 seconds
+        assert_eq!(provider.km_to_seconds(90.0), 3600);
+    }
+
     #[test]
     fn test_reasonable_travel_time() {
         let provider = HaversineMatrix::new(40.0); // 40 km/h
@@ -0,0 +1,151 @@
+//! Stop-by-stop itineraries.
+//!
+//! Expands a route's estimated windows and per-leg travel into explicit
+//! arrival, wait, start, end, and departure times, so clients don't have to
+//! re-derive them from the raw `(start, end)` tuples.
+
+use serde::{Deserialize, Serialize};
+
+use crate::solver::{PlannerResult, RouteResult};
+
+/// A visitor's day as an ordered list of stops.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Itinerary<VisitorId, VisitId> {
+    pub visitor_id: VisitorId,
+    pub stops: Vec<ItineraryStop<VisitId>>,
+    pub total_travel_secs: i32,
+    pub total_wait_secs: i32,
+}
+
+/// One stop, with times in seconds since midnight like the estimated windows.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItineraryStop<VisitId> {
+    pub visit_id: VisitId,
+    /// Travel from the previous stop (or the visitor's start location).
+    pub travel_secs: i32,
+    /// Meters driven from the previous stop, if the matrix provided distances.
+    pub distance_m: Option<i32>,
+    pub arrival: i32,
+    /// Idle time between arrival and start.
+    pub wait_secs: i32,
+    pub start: i32,
+    pub end: i32,
+    pub departure: i32,
+}
+
+impl<VisitorId: Clone, VisitId: Clone> Itinerary<VisitorId, VisitId> {
+    /// Builds the itinerary for one route.
+    ///
+    /// Travel and waiting come from the route's legs. Routes without legs
+    /// (e.g., deserialized from before legs were reported) get zero travel
+    /// and waiting, so arrival equals start.
+    pub fn from_route(route: &RouteResult<VisitorId, VisitId>) -> Self {
+        let stops: Vec<ItineraryStop<VisitId>> = route
+            .visit_ids
+            .iter()
+            .zip(&route.estimated_windows)
+            .enumerate()
+            .map(|(idx, (visit_id, &(start, end)))| {
+                let leg = route.legs.get(idx);
+                let travel_secs = leg.map_or(0, |leg| leg.travel_secs);
+                let wait_secs = leg.map_or(0, |leg| leg.wait_secs);
+                ItineraryStop {
+                    visit_id: visit_id.clone(),
+                    travel_secs,
+                    distance_m: leg.and_then(|leg| leg.distance_m),
+                    arrival: start - wait_secs,
+                    wait_secs,
+                    start,
+                    end,
+                    departure: end,
+                }
+            })
+            .collect();
+
+        Self {
+            visitor_id: route.visitor_id.clone(),
+            total_travel_secs: stops.iter().map(|stop| stop.travel_secs).sum(),
+            total_wait_secs: stops.iter().map(|stop| stop.wait_secs).sum(),
+            stops,
+        }
+    }
+}
+
+/// Itineraries for every route in a plan, in route order.
+pub fn itineraries<VisitorId: Clone, VisitId: Clone>(
+    plan: &PlannerResult<VisitorId, VisitId>,
+) -> Vec<Itinerary<VisitorId, VisitId>> {
+    plan.routes.iter().map(Itinerary::from_route).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::LegDetail;
+
+    #[test]
+    fn test_stop_times_from_legs() {
+        let route = RouteResult {
+            visitor_id: "alice",
+            visit_ids: vec!["v1", "v2"],
+            estimated_windows: vec![(28_860, 30_660), (36_000, 37_800)],
+            total_travel_time: 120,
+            total_distance_km: Some(2.0),
+            legs: vec![
+                LegDetail { from: None, to: "v1", travel_secs: 60, wait_secs: 0, distance_m: Some(1000) },
+                LegDetail { from: Some("v1"), to: "v2", travel_secs: 60, wait_secs: 5280, distance_m: Some(1000) },
+            ],
+            window_miss_risk: 0.0,
+            robustness_slack_secs: None,
+            return_leg: None,
+        };
+
+        let itinerary = Itinerary::from_route(&route);
+
+        assert_eq!(itinerary.stops.len(), 2);
+        let second = &itinerary.stops[1];
+        assert_eq!(second.arrival, 30_720);
+        assert_eq!((second.start, second.end, second.departure), (36_000, 37_800, 37_800));
+        assert_eq!(itinerary.total_travel_secs, 120);
+        assert_eq!(itinerary.total_wait_secs, 5280);
+    }
+
+    #[test]
+    fn test_route_without_legs_arrives_at_start() {
+        let route = RouteResult {
+            visitor_id: "alice",
+            visit_ids: vec!["v1"],
+            estimated_windows: vec![(32_400, 34_200)],
+            total_travel_time: 0,
+            total_distance_km: None,
+            legs: Vec::new(),
+            window_miss_risk: 0.0,
+            robustness_slack_secs: None,
+            return_leg: None,
+        };
+
+        let stop = &Itinerary::from_route(&route).stops[0];
+        assert_eq!((stop.arrival, stop.wait_secs, stop.travel_secs), (32_400, 0, 0));
+    }
+
+    #[test]
+    fn test_itinerary_serializes_to_json() {
+        let plan = PlannerResult {
+            routes: vec![RouteResult {
+                visitor_id: "alice",
+                visit_ids: vec!["v1"],
+                estimated_windows: vec![(32_400, 34_200)],
+                total_travel_time: 0,
+                total_distance_km: None,
+                legs: Vec::new(),
+                window_miss_risk: 0.0,
+                robustness_slack_secs: None,
+                return_leg: None,
+            }],
+            unassigned: Vec::new(),
+        };
+
+        let json = serde_json::to_value(itineraries(&plan)).expect("serialize");
+        assert_eq!(json[0]["stops"][0]["departure"], 34_200);
+    }
+}
@@ -4,7 +4,40 @@
 
 pub mod traits;
 pub mod solver;
+pub mod cluster;
+pub mod territory;
+pub mod constraint;
+#[cfg(feature = "http")]
 pub mod osrm;
+#[cfg(feature = "http")]
 pub mod osrm_data;
 pub mod haversine;
+pub mod matrix;
+pub mod cache;
+pub mod fallback;
+#[cfg(feature = "http")]
+pub mod valhalla;
+#[cfg(feature = "http")]
+pub mod mapbox;
+#[cfg(feature = "local-routing")]
+pub mod local_routing;
 pub mod polyline;
+pub mod diff;
+pub mod itinerary;
+pub mod metrics;
+pub mod split;
+pub mod midday;
+pub mod recurrence;
+pub mod shift;
+pub mod timezone;
+pub mod generator;
+pub mod benchmark;
+pub mod pragmatic;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "geojson")]
+pub mod geojson;
+pub mod model;
+mod telemetry;
@@ -0,0 +1,394 @@
+//! In-process routing over a road graph built from an OpenStreetMap extract.
+//!
+//! Computes matrices without an OSRM server, for small regions and
+//! deployments that can't run Docker. [`RoadGraph::from_pbf`] builds the car
+//! network from an `.osm.pbf` extract, such as the
+//! [`OsrmDataset::pbf_path`](crate::osrm_data::OsrmDataset::pbf_path) that
+//! [`osrm_data`](crate::osrm_data) downloads. Roads are timed at their
+//! `maxspeed` or a default for their `highway` class; turn restrictions and
+//! traffic are not modeled, so times run somewhat below OSRM's.
+//!
+//! Importing a region takes a while, so save the preprocessed graph with
+//! [`RoadGraph::write_json_file`] and load it at startup with
+//! [`RoadGraph::from_json_file`]:
+//!
+//! ```json
+//! {
+//!   "nodes": [[36.1147, -115.1728], [36.1150, -115.1731]],
+//!   "edges": [{"from": 0, "to": 1, "duration_secs": 6.5, "distance_m": 48.0}]
+//! }
+//! ```
+//!
+//! Edges are directed; two-way roads have an edge in each direction.
+//! Locations snap to the nearest node through a grid index, and each matrix
+//! row is one Dijkstra search that stops once every location is settled, so
+//! cost grows with the area the locations span rather than the whole graph.
+
+mod pbf;
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::haversine::HaversineMatrix;
+use crate::traits::{DistanceMatrixProvider, TravelMatrices, UNREACHABLE_METERS, UNREACHABLE_SECS};
+
+/// A directed road segment.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RoadEdge {
+    pub from: usize,
+    pub to: usize,
+    pub duration_secs: f64,
+    pub distance_m: f64,
+}
+
+/// Serialized graph format.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct GraphFile {
+    nodes: Vec<(f64, f64)>,
+    edges: Vec<RoadEdge>,
+}
+
+/// Side of a snapping grid cell, in degrees (about 1 km of latitude).
+const CELL_DEGREES: f64 = 0.01;
+
+/// Meters per degree of latitude.
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// Road graph held in memory as an adjacency list.
+#[derive(Debug, Clone)]
+pub struct RoadGraph {
+    /// Node coordinates (lat, lng).
+    nodes: Vec<(f64, f64)>,
+    /// Outgoing edges per node.
+    adjacency: Vec<Vec<RoadEdge>>,
+    /// Nodes by [`CELL_DEGREES`] grid cell, for snapping.
+    cells: HashMap<(i64, i64), Vec<usize>>,
+}
+
+fn cell_of((lat, lng): (f64, f64)) -> (i64, i64) {
+    ((lat / CELL_DEGREES).floor() as i64, (lng / CELL_DEGREES).floor() as i64)
+}
+
+impl RoadGraph {
+    /// Builds a graph, ignoring edges that reference missing nodes.
+    pub fn new(nodes: Vec<(f64, f64)>, edges: impl IntoIterator<Item = RoadEdge>) -> Self {
+        let mut adjacency = vec![Vec::new(); nodes.len()];
+        for edge in edges {
+            if edge.from < nodes.len() && edge.to < nodes.len() {
+                adjacency[edge.from].push(edge);
+            }
+        }
+        let mut cells: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+        for (idx, &node) in nodes.iter().enumerate() {
+            cells.entry(cell_of(node)).or_default().push(idx);
+        }
+        Self { nodes, adjacency, cells }
+    }
+
+    /// Builds the car network from an OpenStreetMap `.osm.pbf` extract.
+    pub fn from_pbf(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = pbf::read_road_network(path.as_ref())?;
+        Ok(Self::new(file.nodes, file.edges))
+    }
+
+    /// Loads a graph from the JSON format described in the module docs.
+    pub fn from_json_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let file: GraphFile =
+            serde_json::from_str(&contents).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(Self::new(file.nodes, file.edges))
+    }
+
+    /// Saves the graph in the JSON format [`from_json_file`](Self::from_json_file) reads.
+    pub fn write_json_file(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let file = GraphFile { nodes: self.nodes.clone(), edges: self.adjacency.iter().flatten().copied().collect() };
+        let contents = serde_json::to_vec(&file).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(path, contents)
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Nearest node to a location and the straight-line distance to it in meters.
+    ///
+    /// Searches rings of grid cells outward until no unsearched cell can
+    /// hold a closer node.
+    fn nearest_node(&self, location: (f64, f64)) -> Option<(usize, f64)> {
+        if location.0.is_nan() || location.1.is_nan() {
+            return None;
+        }
+        let (row, col) = cell_of(location);
+        let max_ring = self
+            .cells
+            .keys()
+            .map(|&(cell_row, cell_col)| (cell_row - row).abs().max((cell_col - col).abs()))
+            .max()?;
+        // A cell r rings out is at least (r - 1) cells away in one axis
+        let meters_per_cell = CELL_DEGREES * METERS_PER_DEGREE * location.0.to_radians().cos().abs();
+
+        let mut best: Option<(usize, f64)> = None;
+        for ring in 0..=max_ring {
+            if best.is_some_and(|(_, meters)| meters <= (ring - 1) as f64 * meters_per_cell) {
+                break;
+            }
+            for cell_row in row - ring..=row + ring {
+                for cell_col in col - ring..=col + ring {
+                    if (cell_row - row).abs() != ring && (cell_col - col).abs() != ring {
+                        continue;
+                    }
+                    for &idx in self.cells.get(&(cell_row, cell_col)).into_iter().flatten() {
+                        let meters = HaversineMatrix::haversine_km(location, self.nodes[idx]) * 1000.0;
+                        if best.is_none_or(|(_, best_meters)| meters < best_meters) {
+                            best = Some((idx, meters));
+                        }
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    /// Shortest travel times from `source` to each of `targets`, with the
+    /// distance along each fastest path, stopping once every target is
+    /// settled. Unreached targets are missing from the result.
+    fn shortest_paths(&self, source: usize, targets: &HashSet<usize>) -> HashMap<usize, (f64, f64)> {
+        let mut best: HashMap<usize, (f64, f64)> = HashMap::new();
+        let mut settled: HashSet<usize> = HashSet::new();
+        let mut heap = BinaryHeap::new();
+        best.insert(source, (0.0, 0.0));
+        heap.push(QueueEntry { duration: 0.0, distance: 0.0, node: source });
+
+        let mut remaining = targets.len();
+        while let Some(QueueEntry { duration, distance, node }) = heap.pop() {
+            if !settled.insert(node) {
+                continue;
+            }
+            if targets.contains(&node) {
+                remaining -= 1;
+                if remaining == 0 {
+                    break;
+                }
+            }
+            for edge in &self.adjacency[node] {
+                let next_duration = duration + edge.duration_secs;
+                if best.get(&edge.to).is_none_or(|&(known, _)| next_duration < known) {
+                    let next_distance = distance + edge.distance_m;
+                    best.insert(edge.to, (next_duration, next_distance));
+                    heap.push(QueueEntry { duration: next_duration, distance: next_distance, node: edge.to });
+                }
+            }
+        }
+
+        best.retain(|node, _| targets.contains(node));
+        best
+    }
+}
+
+/// Min-heap entry ordered by duration.
+struct QueueEntry {
+    duration: f64,
+    distance: f64,
+    node: usize,
+}
+
+impl PartialEq for QueueEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.duration == other.duration
+    }
+}
+
+impl Eq for QueueEntry {}
+
+impl PartialOrd for QueueEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueueEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.duration.total_cmp(&self.duration)
+    }
+}
+
+/// Matrix provider that routes over a [`RoadGraph`] in-process.
+#[derive(Debug, Clone)]
+pub struct LocalRouter {
+    graph: RoadGraph,
+}
+
+impl LocalRouter {
+    pub fn new(graph: RoadGraph) -> Self {
+        Self { graph }
+    }
+
+    pub fn graph(&self) -> &RoadGraph {
+        &self.graph
+    }
+}
+
+impl DistanceMatrixProvider for LocalRouter {
+    fn matrix_for(&self, locations: &[(f64, f64)]) -> Vec<Vec<i32>> {
+        self.matrices_for(locations).durations
+    }
+
+    /// Durations, distances, and snap distances (location to nearest node).
+    fn matrices_for(&self, locations: &[(f64, f64)]) -> TravelMatrices {
+        let Some(snapped) = locations
+            .iter()
+            .map(|&location| self.graph.nearest_node(location))
+            .collect::<Option<Vec<_>>>()
+        else {
+            // Empty graph: nothing to route on.
+            return TravelMatrices::from_durations(Vec::new());
+        };
+
+        let targets: HashSet<usize> = snapped.iter().map(|&(node, _)| node).collect();
+        let rows: Vec<(Vec<i32>, Vec<i32>)> = snapped
+            .par_iter()
+            .map(|&(source, _)| {
+                let paths = self.graph.shortest_paths(source, &targets);
+                snapped
+                    .iter()
+                    .map(|&(target, _)| match paths.get(&target).copied() {
+                        Some((duration, distance)) => (duration.round() as i32, distance.round() as i32),
+                        None => (UNREACHABLE_SECS, UNREACHABLE_METERS),
+                    })
+                    .unzip()
+            })
+            .collect();
+        let (durations, distances) = rows.into_iter().unzip();
+
+        TravelMatrices {
+            durations,
+            distances: Some(distances),
+            snap_distances: Some(snapped.iter().map(|&(_, meters)| meters.round() as i32).collect()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(from: usize, to: usize, duration_secs: f64, distance_m: f64) -> RoadEdge {
+        RoadEdge { from, to, duration_secs, distance_m }
+    }
+
+    /// A - B - C in a line, plus a slow direct A -> C shortcut and a one-way C -> D.
+    fn line_graph() -> RoadGraph {
+        RoadGraph::new(
+            vec![(36.100, -115.100), (36.101, -115.100), (36.102, -115.100), (36.103, -115.100)],
+            vec![
+                edge(0, 1, 10.0, 100.0),
+                edge(1, 0, 10.0, 100.0),
+                edge(1, 2, 10.0, 100.0),
+                edge(2, 1, 10.0, 100.0),
+                edge(0, 2, 60.0, 150.0),
+                edge(2, 3, 5.0, 50.0),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_fastest_path_and_its_distance() {
+        let router = LocalRouter::new(line_graph());
+        let matrices = router.matrices_for(&[(36.100, -115.100), (36.102, -115.100)]);
+
+        // Via B (20 s, 200 m) beats the direct shortcut (60 s, 150 m)
+        assert_eq!(matrices.durations, vec![vec![0, 20], vec![20, 0]]);
+        assert_eq!(matrices.distances.unwrap()[0][1], 200);
+    }
+
+    #[test]
+    fn test_one_way_edge_unreachable_in_reverse() {
+        let router = LocalRouter::new(line_graph());
+        let matrix = router.matrix_for(&[(36.102, -115.100), (36.103, -115.100)]);
+
+        assert_eq!(matrix[0][1], 5);
+        assert_eq!(matrix[1][0], UNREACHABLE_SECS);
+    }
+
+    #[test]
+    fn test_locations_snap_to_nearest_node() {
+        let router = LocalRouter::new(line_graph());
+        // ~9 m east of node B
+        let matrices = router.matrices_for(&[(36.101, -115.0999)]);
+        let snap = matrices.snap_distances.unwrap()[0];
+        assert!((8..=10).contains(&snap), "unexpected snap distance {snap}");
+    }
+
+    #[test]
+    fn test_snapping_matches_a_full_scan() {
+        // Nodes scattered over ~20 km, some cells empty
+        let nodes: Vec<(f64, f64)> = (0..200_u32)
+            .map(|i| (36.0 + f64::from(i * 37 % 199) / 1000.0, -115.0 + f64::from(i * 53 % 197) / 1000.0))
+            .collect();
+        let graph = RoadGraph::new(nodes.clone(), []);
+
+        for location in [(36.05, -114.95), (35.9, -115.3), (36.1234, -114.9876), (36.0, -115.0)] {
+            let (_, scanned) = nodes
+                .iter()
+                .map(|&node| HaversineMatrix::haversine_km(location, node) * 1000.0)
+                .enumerate()
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap();
+            let (_, snapped) = graph.nearest_node(location).unwrap();
+            assert!((snapped - scanned).abs() < 1e-6, "{location:?}: {snapped} vs {scanned}");
+        }
+        assert!(RoadGraph::new(Vec::new(), []).nearest_node((36.0, -115.0)).is_none());
+    }
+
+    #[test]
+    fn test_graph_from_json() {
+        let path = std::env::temp_dir().join(format!("vrp-planner-graph-{}.json", std::process::id()));
+        fs::write(
+            &path,
+            r#"{"nodes": [[36.1, -115.1], [36.2, -115.1]],
+                "edges": [{"from": 0, "to": 1, "duration_secs": 600, "distance_m": 11000}]}"#,
+        )
+        .unwrap();
+
+        let graph = RoadGraph::from_json_file(&path).expect("load graph");
+        fs::remove_file(&path).ok();
+
+        assert_eq!(graph.node_count(), 2);
+        assert_eq!(LocalRouter::new(graph).matrix_for(&[(36.1, -115.1), (36.2, -115.1)])[0][1], 600);
+    }
+
+    #[test]
+    fn test_routes_over_an_extract_and_its_saved_graph() {
+        let dir = std::env::temp_dir();
+        let pbf_path = dir.join(format!("vrp-planner-router-{}.osm.pbf", std::process::id()));
+        let json_path = pbf_path.with_extension("json");
+        // A one-way primary road at 36 km/h, then a residential street
+        pbf::tests::write_pbf(
+            &pbf_path,
+            &[(1, 36.100, -115.100), (2, 36.101, -115.100), (3, 36.102, -115.100)],
+            &[
+                (&[1, 2], &[("highway", "primary"), ("oneway", "yes"), ("maxspeed", "36")]),
+                (&[2, 3], &[("highway", "residential")]),
+            ],
+        );
+
+        let graph = RoadGraph::from_pbf(&pbf_path).expect("import extract");
+        graph.write_json_file(&json_path).expect("save graph");
+        let saved = RoadGraph::from_json_file(&json_path).expect("load graph");
+        fs::remove_file(&pbf_path).ok();
+        fs::remove_file(&json_path).ok();
+
+        let locations = [(36.100, -115.100), (36.102, -115.100)];
+        let matrix = LocalRouter::new(graph).matrix_for(&locations);
+        // ~111 m at 10 m/s, then ~111 m at 25 km/h
+        assert_eq!(matrix[0][1], 27);
+        assert_eq!(matrix[1][0], UNREACHABLE_SECS);
+        assert_eq!(LocalRouter::new(saved).matrix_for(&locations), matrix);
+    }
+}
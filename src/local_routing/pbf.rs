@@ -0,0 +1,548 @@
+//! Road network import from OpenStreetMap `.osm.pbf` extracts.
+//!
+//! Reads the file format Geofabrik serves (and
+//! [`osrm_data`](crate::osrm_data) downloads): a sequence of zlib-compressed
+//! blocks of protobuf-encoded nodes and ways. Ways tagged as roads a car may
+//! use become directed edges between consecutive nodes, timed at the way's
+//! `maxspeed` or a default speed for its `highway` class. Relations (and so
+//! turn restrictions) are not read.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+use flate2::read::ZlibDecoder;
+
+use super::{GraphFile, RoadEdge};
+use crate::haversine::HaversineMatrix;
+
+/// Largest blob header the format allows.
+const MAX_HEADER_BYTES: usize = 64 * 1024;
+
+/// Largest blob the format allows.
+const MAX_BLOB_BYTES: usize = 32 * 1024 * 1024;
+
+/// Default speed in km/h by `highway` class, roughly OSRM's car profile.
+fn highway_speed_kmh(highway: &str) -> Option<f64> {
+    let speed = match highway {
+        "motorway" => 90.0,
+        "trunk" => 85.0,
+        "primary" => 65.0,
+        "secondary" => 55.0,
+        "tertiary" => 40.0,
+        "motorway_link" => 45.0,
+        "trunk_link" => 40.0,
+        "primary_link" => 30.0,
+        "secondary_link" => 25.0,
+        "tertiary_link" => 20.0,
+        "unclassified" | "residential" => 25.0,
+        "living_street" => 10.0,
+        "service" => 15.0,
+        _ => return None,
+    };
+    Some(speed)
+}
+
+/// A `maxspeed` tag in km/h; plain numbers are km/h, `mph` is converted.
+fn parse_maxspeed(value: &str) -> Option<f64> {
+    let (number, factor) = match value.trim().strip_suffix("mph") {
+        Some(number) => (number, 1.609_344),
+        None => (value.trim().strip_suffix("km/h").unwrap_or(value.trim()), 1.0),
+    };
+    number.trim().parse::<f64>().ok().filter(|speed| *speed > 0.0).map(|speed| speed * factor)
+}
+
+/// Which ways along a road a car may travel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Both,
+    Forward,
+    Backward,
+}
+
+/// A routable way: its node IDs in order, speed, and direction.
+struct Way {
+    refs: Vec<i64>,
+    speed_kmh: f64,
+    direction: Direction,
+}
+
+impl Way {
+    /// The way, if its tags make it a road a car may use.
+    fn from_tags(refs: Vec<i64>, tags: &HashMap<&str, &str>) -> Option<Self> {
+        let highway = tags.get("highway")?;
+        let default_kmh = highway_speed_kmh(highway)?;
+        let speed_kmh = tags.get("maxspeed").and_then(|value| parse_maxspeed(value)).unwrap_or(default_kmh);
+        let closed = |key| tags.get(key).is_some_and(|value| matches!(*value, "no" | "private"));
+        if closed("access") || closed("motor_vehicle") || closed("motorcar") || tags.get("area") == Some(&"yes") {
+            return None;
+        }
+        let direction = match tags.get("oneway").copied() {
+            Some("yes" | "true" | "1") => Direction::Forward,
+            Some("-1" | "reverse") => Direction::Backward,
+            Some("no" | "false" | "0") => Direction::Both,
+            _ if *highway == "motorway" => Direction::Forward,
+            _ if matches!(tags.get("junction").copied(), Some("roundabout" | "circular")) => Direction::Forward,
+            _ => Direction::Both,
+        };
+        Some(Self { refs, speed_kmh, direction })
+    }
+}
+
+/// Nodes (lat, lng) and directed edges of the car network in an extract.
+///
+/// Only nodes on routable ways are kept. The file is read twice: once for
+/// the ways, then for the coordinates of the nodes they use.
+pub(super) fn read_road_network(path: &Path) -> io::Result<GraphFile> {
+    let mut ways = Vec::new();
+    for_each_block(path, |block| {
+        block.ways(|refs, tags| ways.extend(Way::from_tags(refs, tags)));
+        Ok(())
+    })?;
+
+    let mut index: HashMap<i64, usize> = HashMap::new();
+    let needed: HashSet<i64> = ways.iter().flat_map(|way| way.refs.iter().copied()).collect();
+    let mut nodes = Vec::new();
+    for_each_block(path, |block| {
+        block.nodes(|id, location| {
+            if needed.contains(&id) && !index.contains_key(&id) {
+                index.insert(id, nodes.len());
+                nodes.push(location);
+            }
+        })
+    })?;
+
+    let mut edges = Vec::new();
+    for way in &ways {
+        let speed_mps = way.speed_kmh / 3.6;
+        for pair in way.refs.windows(2) {
+            let (Some(&from), Some(&to)) = (index.get(&pair[0]), index.get(&pair[1])) else {
+                continue;
+            };
+            let distance_m = HaversineMatrix::haversine_km(nodes[from], nodes[to]) * 1000.0;
+            let duration_secs = distance_m / speed_mps;
+            if way.direction != Direction::Backward {
+                edges.push(RoadEdge { from, to, duration_secs, distance_m });
+            }
+            if way.direction != Direction::Forward {
+                edges.push(RoadEdge { from: to, to: from, duration_secs, distance_m });
+            }
+        }
+    }
+    Ok(GraphFile { nodes, edges })
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Calls `visit` with each data block in the file, in order.
+fn for_each_block(path: &Path, mut visit: impl FnMut(&PrimitiveBlock<'_>) -> io::Result<()>) -> io::Result<()> {
+    let mut file = BufReader::new(File::open(path)?);
+    loop {
+        let mut len = [0; 4];
+        match file.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(err),
+        }
+        let header = read_bytes(&mut file, u32::from_be_bytes(len) as usize, MAX_HEADER_BYTES)?;
+        let (kind, data_size) = blob_header(&header)?;
+        let blob = read_bytes(&mut file, data_size, MAX_BLOB_BYTES)?;
+        if kind == b"OSMData" {
+            let data = blob_data(&blob)?;
+            visit(&PrimitiveBlock::parse(&data)?)?;
+        }
+    }
+}
+
+fn read_bytes(reader: &mut impl Read, len: usize, max: usize) -> io::Result<Vec<u8>> {
+    if len > max {
+        return Err(invalid(format!("PBF block of {} bytes is over the {} byte limit", len, max)));
+    }
+    let mut bytes = vec![0; len];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// A blob header's type and the size of the blob that follows.
+fn blob_header(header: &[u8]) -> io::Result<(&[u8], usize)> {
+    let (mut kind, mut data_size) = (None, None);
+    for field in Fields(header) {
+        match field? {
+            (1, Value::Bytes(bytes)) => kind = Some(bytes),
+            (3, Value::Varint(size)) => data_size = Some(size as usize),
+            _ => {}
+        }
+    }
+    kind.zip(data_size).ok_or_else(|| invalid("PBF blob header without a type or size"))
+}
+
+/// A blob's contents, decompressed.
+fn blob_data(blob: &[u8]) -> io::Result<Vec<u8>> {
+    for field in Fields(blob) {
+        match field? {
+            (1, Value::Bytes(raw)) => return Ok(raw.to_vec()),
+            (3, Value::Bytes(compressed)) => {
+                let mut data = Vec::new();
+                ZlibDecoder::new(compressed).take(MAX_BLOB_BYTES as u64).read_to_end(&mut data)?;
+                return Ok(data);
+            }
+            (4..=7, Value::Bytes(_)) => return Err(invalid("PBF blob compression other than zlib")),
+            _ => {}
+        }
+    }
+    Err(invalid("PBF blob without data"))
+}
+
+/// One decoded `PrimitiveBlock`: its string table, coordinate scaling, and
+/// primitive groups.
+struct PrimitiveBlock<'a> {
+    strings: Vec<&'a str>,
+    granularity: i64,
+    lat_offset: i64,
+    lon_offset: i64,
+    groups: Vec<&'a [u8]>,
+}
+
+impl<'a> PrimitiveBlock<'a> {
+    fn parse(data: &'a [u8]) -> io::Result<Self> {
+        let mut block = Self { strings: Vec::new(), granularity: 100, lat_offset: 0, lon_offset: 0, groups: Vec::new() };
+        for field in Fields(data) {
+            match field? {
+                (1, Value::Bytes(table)) => {
+                    for field in Fields(table) {
+                        if let (1, Value::Bytes(s)) = field? {
+                            block.strings.push(std::str::from_utf8(s).unwrap_or(""));
+                        }
+                    }
+                }
+                (2, Value::Bytes(group)) => block.groups.push(group),
+                (17, Value::Varint(granularity)) => block.granularity = granularity as i64,
+                (19, Value::Varint(offset)) => block.lat_offset = offset as i64,
+                (20, Value::Varint(offset)) => block.lon_offset = offset as i64,
+                _ => {}
+            }
+        }
+        Ok(block)
+    }
+
+    fn string(&self, idx: u64) -> &'a str {
+        self.strings.get(idx as usize).copied().unwrap_or("")
+    }
+
+    fn location(&self, lat: i64, lon: i64) -> (f64, f64) {
+        (
+            1e-9 * (self.lat_offset + self.granularity * lat) as f64,
+            1e-9 * (self.lon_offset + self.granularity * lon) as f64,
+        )
+    }
+
+    /// Calls `visit` with each node's ID and (lat, lng).
+    fn nodes(&self, mut visit: impl FnMut(i64, (f64, f64))) -> io::Result<()> {
+        for &group in &self.groups {
+            for field in Fields(group) {
+                match field? {
+                    (1, Value::Bytes(node)) => {
+                        let (mut id, mut lat, mut lon) = (0, 0, 0);
+                        for field in Fields(node) {
+                            match field? {
+                                (1, Value::Varint(value)) => id = zigzag(value),
+                                (8, Value::Varint(value)) => lat = zigzag(value),
+                                (9, Value::Varint(value)) => lon = zigzag(value),
+                                _ => {}
+                            }
+                        }
+                        visit(id, self.location(lat, lon));
+                    }
+                    (2, Value::Bytes(dense)) => {
+                        let (mut ids, mut lats, mut lons) = (Vec::new(), Vec::new(), Vec::new());
+                        for field in Fields(dense) {
+                            match field? {
+                                (1, Value::Bytes(packed)) => ids = deltas(packed)?,
+                                (8, Value::Bytes(packed)) => lats = deltas(packed)?,
+                                (9, Value::Bytes(packed)) => lons = deltas(packed)?,
+                                _ => {}
+                            }
+                        }
+                        if ids.len() != lats.len() || ids.len() != lons.len() {
+                            return Err(invalid("PBF dense nodes with mismatched columns"));
+                        }
+                        for ((id, lat), lon) in ids.into_iter().zip(lats).zip(lons) {
+                            visit(id, self.location(lat, lon));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Calls `visit` with each way's node IDs and tags. Malformed ways are
+    /// skipped.
+    fn ways(&self, mut visit: impl FnMut(Vec<i64>, &HashMap<&str, &str>)) {
+        for &group in &self.groups {
+            for field in Fields(group) {
+                let Ok((3, Value::Bytes(way))) = field else {
+                    continue;
+                };
+                let (mut keys, mut values, mut refs) = (Vec::new(), Vec::new(), Vec::new());
+                for field in Fields(way) {
+                    match field {
+                        Ok((2, Value::Bytes(packed))) => keys = varints(packed).collect(),
+                        Ok((3, Value::Bytes(packed))) => values = varints(packed).collect(),
+                        Ok((8, Value::Bytes(packed))) => refs = deltas(packed).unwrap_or_default(),
+                        _ => {}
+                    }
+                }
+                let tags: HashMap<&str, &str> =
+                    keys.into_iter().zip(values).map(|(key, value)| (self.string(key), self.string(value))).collect();
+                visit(refs, &tags);
+            }
+        }
+    }
+}
+
+/// A protobuf field value; fixed-width values are skipped.
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// Iterates over the (field number, value) pairs of a protobuf message.
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = io::Result<(u64, Value<'a>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let field = self.read_field();
+        if field.is_err() {
+            self.0 = &[];
+        }
+        Some(field)
+    }
+}
+
+impl<'a> Fields<'a> {
+    fn read_field(&mut self) -> io::Result<(u64, Value<'a>)> {
+        let key = read_varint(&mut self.0)?;
+        let value = match key & 7 {
+            0 => Value::Varint(read_varint(&mut self.0)?),
+            1 => self.skip(8)?,
+            2 => {
+                let len = read_varint(&mut self.0)? as usize;
+                let bytes = self.0.get(..len).ok_or_else(|| invalid("truncated PBF field"))?;
+                self.0 = &self.0[len..];
+                Value::Bytes(bytes)
+            }
+            5 => self.skip(4)?,
+            _ => return Err(invalid("unsupported protobuf wire type")),
+        };
+        Ok((key >> 3, value))
+    }
+
+    fn skip(&mut self, len: usize) -> io::Result<Value<'a>> {
+        self.0 = self.0.get(len..).ok_or_else(|| invalid("truncated PBF field"))?;
+        Ok(Value::Fixed)
+    }
+}
+
+fn read_varint(buf: &mut &[u8]) -> io::Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = buf.split_first().ok_or_else(|| invalid("truncated varint"))?;
+        *buf = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte < 0x80 {
+            return Ok(value);
+        }
+    }
+    Err(invalid("varint longer than 64 bits"))
+}
+
+fn zigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+/// Packed varints, stopping at the first malformed one.
+fn varints(mut packed: &[u8]) -> impl Iterator<Item = u64> + '_ {
+    std::iter::from_fn(move || (!packed.is_empty()).then(|| read_varint(&mut packed).ok()).flatten())
+}
+
+/// Packed delta-coded sint64 values, summed back into absolute values.
+fn deltas(mut packed: &[u8]) -> io::Result<Vec<i64>> {
+    let mut values = Vec::new();
+    let mut current = 0_i64;
+    while !packed.is_empty() {
+        current = current.wrapping_add(zigzag(read_varint(&mut packed)?));
+        values.push(current);
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use std::io::Write;
+
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+
+    use super::*;
+
+    fn varint(mut value: u64, out: &mut Vec<u8>) {
+        while value >= 0x80 {
+            out.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    fn bytes_field(field: u64, bytes: &[u8], out: &mut Vec<u8>) {
+        varint(field << 3 | 2, out);
+        varint(bytes.len() as u64, out);
+        out.extend_from_slice(bytes);
+    }
+
+    fn packed(values: impl IntoIterator<Item = u64>) -> Vec<u8> {
+        let mut out = Vec::new();
+        for value in values {
+            varint(value, &mut out);
+        }
+        out
+    }
+
+    fn packed_deltas(values: &[i64]) -> Vec<u8> {
+        let mut previous = 0;
+        packed(values.iter().map(|&value| {
+            let delta = value - previous;
+            previous = value;
+            ((delta << 1) ^ (delta >> 63)) as u64
+        }))
+    }
+
+    fn blob(kind: &str, data: &[u8], out: &mut Vec<u8>) {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        let mut blob = Vec::new();
+        varint(2 << 3, &mut blob);
+        varint(data.len() as u64, &mut blob);
+        bytes_field(3, &encoder.finish().unwrap(), &mut blob);
+
+        let mut header = Vec::new();
+        bytes_field(1, kind.as_bytes(), &mut header);
+        varint(3 << 3, &mut header);
+        varint(blob.len() as u64, &mut header);
+
+        out.extend_from_slice(&(header.len() as u32).to_be_bytes());
+        out.extend_from_slice(&header);
+        out.extend_from_slice(&blob);
+    }
+
+    /// A test way: its node IDs and tags.
+    pub(in crate::local_routing) type TestWay<'a> = (&'a [i64], &'a [(&'a str, &'a str)]);
+
+    /// A `.osm.pbf` with dense `nodes` (id, lat, lng) in one block and
+    /// `ways` in the next, as extracts order them.
+    pub(in crate::local_routing) fn write_pbf(path: &Path, nodes: &[(i64, f64, f64)], ways: &[TestWay<'_>]) {
+        let mut strings: Vec<&str> = vec![""];
+        for (_, tags) in ways {
+            for &(key, value) in *tags {
+                for s in [key, value] {
+                    if !strings.contains(&s) {
+                        strings.push(s);
+                    }
+                }
+            }
+        }
+        let mut table = Vec::new();
+        for s in &strings {
+            bytes_field(1, s.as_bytes(), &mut table);
+        }
+        let position = |s: &str| strings.iter().position(|known| *known == s).unwrap() as u64;
+
+        let mut dense = Vec::new();
+        let ids: Vec<i64> = nodes.iter().map(|node| node.0).collect();
+        let lats: Vec<i64> = nodes.iter().map(|node| (node.1 * 1e7).round() as i64).collect();
+        let lons: Vec<i64> = nodes.iter().map(|node| (node.2 * 1e7).round() as i64).collect();
+        bytes_field(1, &packed_deltas(&ids), &mut dense);
+        bytes_field(8, &packed_deltas(&lats), &mut dense);
+        bytes_field(9, &packed_deltas(&lons), &mut dense);
+        let mut node_group = Vec::new();
+        bytes_field(2, &dense, &mut node_group);
+
+        let mut way_group = Vec::new();
+        for (idx, (refs, tags)) in ways.iter().enumerate() {
+            let mut way = Vec::new();
+            varint(1 << 3, &mut way);
+            varint(idx as u64 + 1, &mut way);
+            bytes_field(2, &packed(tags.iter().map(|(key, _)| position(key))), &mut way);
+            bytes_field(3, &packed(tags.iter().map(|(_, value)| position(value))), &mut way);
+            bytes_field(8, &packed_deltas(refs), &mut way);
+            bytes_field(3, &way, &mut way_group);
+        }
+
+        let mut file = Vec::new();
+        blob("OSMHeader", b"", &mut file);
+        for group in [node_group, way_group] {
+            let mut block = Vec::new();
+            bytes_field(1, &table, &mut block);
+            bytes_field(2, &group, &mut block);
+            blob("OSMData", &block, &mut file);
+        }
+        std::fs::write(path, file).unwrap();
+    }
+
+    #[test]
+    fn test_reads_car_roads_from_an_extract() {
+        let path = std::env::temp_dir().join(format!("vrp-planner-extract-{}.osm.pbf", std::process::id()));
+        write_pbf(
+            &path,
+            &[(10, 36.100, -115.100), (11, 36.101, -115.100), (12, 36.102, -115.100), (13, 36.103, -115.100)],
+            &[
+                (&[10, 11], &[("highway", "residential")]),
+                (&[11, 12], &[("highway", "primary"), ("oneway", "yes"), ("maxspeed", "36")]),
+                (&[12, 13], &[("highway", "footway")]),
+            ],
+        );
+
+        let GraphFile { nodes, edges } = read_road_network(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        // The footway and its far node are left out
+        assert_eq!(nodes.len(), 3);
+        assert!((nodes[1].0 - 36.101).abs() < 1e-9);
+        let pairs: Vec<(usize, usize)> = edges.iter().map(|edge| (edge.from, edge.to)).collect();
+        assert_eq!(pairs, vec![(0, 1), (1, 0), (1, 2)]);
+        // About 111 m at 36 km/h
+        assert!((edges[2].duration_secs - edges[2].distance_m / 10.0).abs() < 1e-9);
+        assert!((110.0..112.0).contains(&edges[2].distance_m));
+    }
+
+    #[test]
+    fn test_tags_decide_access_and_direction() {
+        let tags = |pairs: &[(&'static str, &'static str)]| pairs.iter().copied().collect::<HashMap<_, _>>();
+        let way = |pairs| Way::from_tags(vec![1, 2], &tags(pairs));
+
+        assert!(way(&[("highway", "path")]).is_none());
+        assert!(way(&[("highway", "service"), ("access", "private")]).is_none());
+        assert_eq!(way(&[("highway", "motorway")]).unwrap().direction, Direction::Forward);
+        assert_eq!(way(&[("highway", "primary"), ("junction", "roundabout")]).unwrap().direction, Direction::Forward);
+        assert_eq!(way(&[("highway", "primary"), ("oneway", "-1")]).unwrap().direction, Direction::Backward);
+        assert_eq!(way(&[("highway", "residential")]).unwrap().speed_kmh, 25.0);
+        assert!((way(&[("highway", "primary"), ("maxspeed", "30 mph")]).unwrap().speed_kmh - 48.28).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_malformed_files_are_errors() {
+        assert!(read_varint(&mut &[0xff_u8; 11][..]).is_err());
+        assert!(Fields(&[0x0a, 0x05, 0x01]).next().unwrap().is_err());
+        assert!(blob_data(&[]).is_err());
+    }
+}
@@ -0,0 +1,257 @@
+//! Mapbox Matrix API adapter for distance matrices.
+//!
+//! The Matrix API accepts at most 25 coordinates per request (10 for the
+//! `driving-traffic` profile), so larger location sets are split into blocks
+//! of sources and destinations and stitched back together.
+
+use std::time::Duration;
+
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::matrix::metered::{decode_body, is_gzipped, ByteCounter};
+use crate::traits::{DistanceMatrixProvider, TravelMatrices, UNREACHABLE_METERS, UNREACHABLE_SECS};
+
+#[derive(Debug, Clone)]
+pub struct MapboxConfig {
+    pub base_url: String,
+    /// Routing profile, e.g. "mapbox/driving" or "mapbox/driving-traffic".
+    pub profile: String,
+    pub access_token: String,
+    pub timeout_secs: u64,
+    /// Coordinates allowed per request (25, or 10 for `driving-traffic`).
+    pub max_coordinates: usize,
+}
+
+impl MapboxConfig {
+    /// Driving profile with the standard 25-coordinate limit.
+    pub fn new(access_token: impl Into<String>) -> Self {
+        Self {
+            base_url: "https://api.mapbox.com".to_string(),
+            profile: "mapbox/driving".to_string(),
+            access_token: access_token.into(),
+            timeout_secs: 10,
+            max_coordinates: 25,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MapboxClient {
+    config: MapboxConfig,
+    client: reqwest::blocking::Client,
+    /// Matrix request URLs and response bodies.
+    wire: ByteCounter,
+}
+
+impl MapboxClient {
+    pub fn new(config: MapboxConfig) -> Result<Self, reqwest::Error> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()?;
+
+        Ok(Self { config, client, wire: ByteCounter::default() })
+    }
+
+    /// Request one block of the matrix, or `None` on any failure.
+    fn fetch_block(&self, locations: &[(f64, f64)], block: &Block) -> Option<MatrixResponse> {
+        let coordinates = block.coordinates();
+        let coords = coordinates
+            .iter()
+            .map(|&idx| {
+                let (lat, lng) = locations[idx];
+                format!("{:.6},{:.6}", lng, lat)
+            })
+            .collect::<Vec<_>>()
+            .join(";");
+        let positions = |indices: &[usize]| {
+            indices
+                .iter()
+                .map(|idx| coordinates.iter().position(|c| c == idx).unwrap_or_default().to_string())
+                .collect::<Vec<_>>()
+                .join(";")
+        };
+        let url = format!(
+            "{}/directions-matrix/v1/{}/{}?annotations=duration,distance&sources={}&destinations={}&access_token={}",
+            self.config.base_url,
+            self.config.profile,
+            coords,
+            positions(&block.sources),
+            positions(&block.destinations),
+            self.config.access_token
+        );
+
+        let result = self
+            .client
+            .get(&url)
+            .header(reqwest::header::ACCEPT_ENCODING, "gzip")
+            .send()
+            .and_then(|resp: reqwest::blocking::Response| resp.error_for_status())
+            .and_then(|resp: reqwest::blocking::Response| {
+                let gzipped = is_gzipped(resp.headers());
+                resp.bytes().map(|body| (body, gzipped))
+            });
+        let result = result.map(|(body, gzipped)| {
+            self.wire.add(url.len() + body.len());
+            let body = decode_body(&body, gzipped).map_err(serde_json::Error::io)?;
+            serde_json::from_slice::<MatrixResponse>(&body)
+        });
+        match result {
+            Ok(Ok(body)) if body.code == "Ok" => Some(body),
+            Ok(Ok(body)) => {
+                warn!(code = %body.code, "Mapbox matrix request rejected");
+                None
+            }
+            Ok(Err(err)) => {
+                warn!(error = %err, "Mapbox matrix response unreadable");
+                None
+            }
+            Err(err) => {
+                // Don't log the URL; it carries the access token.
+                warn!(error = %err.without_url(), "Mapbox matrix request failed");
+                None
+            }
+        }
+    }
+}
+
+impl DistanceMatrixProvider for MapboxClient {
+    fn matrix_for(&self, locations: &[(f64, f64)]) -> Vec<Vec<i32>> {
+        self.matrices_for(locations).durations
+    }
+
+    /// Durations and distances, fetched in as many blocks as the coordinate
+    /// limit requires. Any failed block fails the whole matrix.
+    fn matrices_for(&self, locations: &[(f64, f64)]) -> TravelMatrices {
+        let n = locations.len();
+        let mut durations = vec![vec![0; n]; n];
+        let mut distances = vec![vec![0; n]; n];
+
+        for block in plan_blocks(n, self.config.max_coordinates) {
+            let Some(body) = self.fetch_block(locations, &block) else {
+                return TravelMatrices::from_durations(Vec::new());
+            };
+            for (row, &from) in block.sources.iter().enumerate() {
+                for (col, &to) in block.destinations.iter().enumerate() {
+                    durations[from][to] = cell(&body.durations, row, col, UNREACHABLE_SECS);
+                    distances[from][to] = cell(&body.distances, row, col, UNREACHABLE_METERS);
+                }
+            }
+        }
+
+        TravelMatrices {
+            durations,
+            distances: Some(distances),
+            snap_distances: None,
+        }
+    }
+
+    fn bytes_transferred(&self) -> Option<u64> {
+        Some(self.wire.total())
+    }
+}
+
+/// One request: the location indices to use as sources and destinations.
+#[derive(Debug, Clone, PartialEq)]
+struct Block {
+    sources: Vec<usize>,
+    destinations: Vec<usize>,
+}
+
+impl Block {
+    /// Distinct locations sent in the request, sources first.
+    fn coordinates(&self) -> Vec<usize> {
+        let mut coordinates = self.sources.clone();
+        for idx in &self.destinations {
+            if !coordinates.contains(idx) {
+                coordinates.push(*idx);
+            }
+        }
+        coordinates
+    }
+}
+
+/// Split an `n`-location matrix into requests of at most `max_coordinates`.
+///
+/// Small matrices are a single request. Larger ones are chunked into halves
+/// of the limit and every (source chunk, destination chunk) pair becomes a
+/// request, so no request exceeds the limit.
+fn plan_blocks(n: usize, max_coordinates: usize) -> Vec<Block> {
+    if n == 0 {
+        return Vec::new();
+    }
+    if n <= max_coordinates {
+        let all: Vec<usize> = (0..n).collect();
+        return vec![Block { sources: all.clone(), destinations: all }];
+    }
+
+    let chunk_size = (max_coordinates / 2).max(1);
+    let indices: Vec<usize> = (0..n).collect();
+    let chunks: Vec<&[usize]> = indices.chunks(chunk_size).collect();
+    let mut blocks = Vec::with_capacity(chunks.len() * chunks.len());
+    for sources in &chunks {
+        for destinations in &chunks {
+            blocks.push(Block { sources: sources.to_vec(), destinations: destinations.to_vec() });
+        }
+    }
+    blocks
+}
+
+/// Read a response cell, substituting `missing` for unreachable pairs.
+fn cell(table: &Option<Vec<Vec<Option<f64>>>>, row: usize, col: usize, missing: i32) -> i32 {
+    table
+        .as_ref()
+        .and_then(|table| table.get(row)?.get(col).copied().flatten())
+        .map_or(missing, |value| value.round() as i32)
+}
+
+#[derive(Debug, Deserialize)]
+struct MatrixResponse {
+    code: String,
+    /// Durations in seconds, null if unreachable
+    #[serde(default)]
+    durations: Option<Vec<Vec<Option<f64>>>>,
+    /// Distances in meters, null if unreachable
+    #[serde(default)]
+    distances: Option<Vec<Vec<Option<f64>>>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_matrix_is_one_block() {
+        let blocks = plan_blocks(25, 25);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].coordinates().len(), 25);
+    }
+
+    #[test]
+    fn test_large_matrix_blocks_cover_every_pair_within_limit() {
+        let n = 40;
+        let blocks = plan_blocks(n, 25);
+
+        let mut covered = vec![vec![false; n]; n];
+        for block in &blocks {
+            assert!(block.coordinates().len() <= 25);
+            for &from in &block.sources {
+                for &to in &block.destinations {
+                    covered[from][to] = true;
+                }
+            }
+        }
+        assert!(covered.iter().flatten().all(|&pair| pair));
+    }
+
+    #[test]
+    fn test_unreachable_cells() {
+        let body: MatrixResponse = serde_json::from_str(
+            r#"{"code": "Ok", "durations": [[0, null]], "distances": [[0, 1500.4]]}"#,
+        )
+        .expect("parse matrix response");
+
+        assert_eq!(cell(&body.durations, 0, 1, UNREACHABLE_SECS), UNREACHABLE_SECS);
+        assert_eq!(cell(&body.distances, 0, 1, UNREACHABLE_METERS), 1500);
+    }
+}
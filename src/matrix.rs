@@ -0,0 +1,5 @@
+//! Ready-made distance matrix providers.
+
+pub mod metered;
+pub mod sparse;
+pub mod synthetic;
@@ -0,0 +1,388 @@
+//! Matrix request accounting and budgets.
+//!
+//! Cloud matrix APIs bill per element and cap request sizes.
+//! [`MeteredMatrixProvider`] wraps any provider to count the cells, time, and
+//! bytes its requests cost, and refuses requests that would break a budget
+//! before they reach the provider.
+
+#[cfg(feature = "http")]
+use std::borrow::Cow;
+use std::future::Future;
+#[cfg(feature = "http")]
+use std::io::Read;
+#[cfg(any(feature = "http", test))]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(any(feature = "http", test))]
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::time::Instant;
+
+use tracing::warn;
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+use crate::solver::wasm_clock::Instant;
+use crate::traits::{AsyncDistanceMatrixProvider, DistanceMatrixProvider, TravelMatrices};
+
+/// What a provider's requests have cost so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatrixUsage {
+    /// Requests passed to the provider.
+    pub requests: u64,
+    /// Matrix elements requested, as billed: n² for a full matrix.
+    pub cells: u64,
+    /// Requests refused for exceeding the budget.
+    pub rejected: u64,
+    /// Time spent waiting on the provider.
+    pub elapsed: Duration,
+    /// Bytes sent and received, if the provider reports them.
+    pub bytes: Option<u64>,
+}
+
+/// Matrix provider wrapper that meters requests and enforces a budget.
+///
+/// A request over budget is refused with an empty matrix, the same signal as
+/// a failed fetch, so a [`FallbackMatrixProvider`](crate::fallback::FallbackMatrixProvider)
+/// around this one can take over.
+#[derive(Debug)]
+pub struct MeteredMatrixProvider<P> {
+    inner: P,
+    meter: Mutex<Meter>,
+    max_cells_per_request: Option<u64>,
+    max_total_cells: Option<u64>,
+}
+
+/// Usage so far, and the provider's byte count when metering began.
+///
+/// Bytes are read as the growth of the provider's running total rather than
+/// per request, so requests in flight at the same time aren't counted twice.
+#[derive(Debug, Default)]
+struct Meter {
+    usage: MatrixUsage,
+    /// Set by the first request after creation or a reset.
+    bytes_from: Option<u64>,
+}
+
+impl<P> MeteredMatrixProvider<P> {
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            meter: Mutex::new(Meter::default()),
+            max_cells_per_request: None,
+            max_total_cells: None,
+        }
+    }
+
+    /// Refuses single requests larger than `cells` (e.g., 625 for an API that
+    /// takes at most 25 × 25).
+    pub fn with_max_cells_per_request(mut self, cells: u64) -> Self {
+        self.max_cells_per_request = Some(cells);
+        self
+    }
+
+    /// Refuses requests once `cells` have been requested in total.
+    pub fn with_max_total_cells(mut self, cells: u64) -> Self {
+        self.max_total_cells = Some(cells);
+        self
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    pub fn usage(&self) -> MatrixUsage {
+        self.meter.lock().unwrap().usage
+    }
+
+    /// Zeroes the usage, e.g. at the start of a billing period.
+    pub fn reset(&self) {
+        *self.meter.lock().unwrap() = Meter::default();
+    }
+
+    /// Counts a request of `cells` against the budget, or refuses it.
+    ///
+    /// `bytes` is the provider's byte count before the request.
+    fn admit(&self, cells: u64, bytes: Option<u64>) -> bool {
+        let mut meter = self.meter.lock().unwrap();
+        meter.bytes_from.get_or_insert(bytes.unwrap_or(0));
+        let usage = &mut meter.usage;
+        let too_large = self.max_cells_per_request.is_some_and(|max| cells > max);
+        let over_total = self.max_total_cells.is_some_and(|max| usage.cells + cells > max);
+        if too_large || over_total {
+            usage.rejected += 1;
+            warn!(cells, used = usage.cells, too_large, over_total, "Matrix request refused over budget");
+            return false;
+        }
+        usage.requests += 1;
+        usage.cells += cells;
+        true
+    }
+
+    /// `bytes` is the provider's byte count after the request.
+    fn record(&self, started: Instant, bytes: Option<u64>) {
+        let mut meter = self.meter.lock().unwrap();
+        let transferred = transferred(meter.bytes_from, bytes);
+        meter.usage.elapsed += started.elapsed();
+        meter.usage.bytes = meter.usage.bytes.max(transferred);
+    }
+}
+
+/// Bytes transferred between two readings of a provider's running total.
+fn transferred(before: Option<u64>, after: Option<u64>) -> Option<u64> {
+    Some(after?.saturating_sub(before.unwrap_or(0)))
+}
+
+impl<P: DistanceMatrixProvider> MeteredMatrixProvider<P> {
+    fn metered<T>(&self, cells: u64, refused: T, fetch: impl FnOnce() -> T) -> T {
+        if !self.admit(cells, self.inner.bytes_transferred()) {
+            return refused;
+        }
+        let started = Instant::now();
+        let result = fetch();
+        self.record(started, self.inner.bytes_transferred());
+        result
+    }
+}
+
+fn square(locations: &[(f64, f64)]) -> u64 {
+    (locations.len() as u64).pow(2)
+}
+
+impl<P: DistanceMatrixProvider> DistanceMatrixProvider for MeteredMatrixProvider<P> {
+    fn matrix_for(&self, locations: &[(f64, f64)]) -> Vec<Vec<i32>> {
+        self.metered(square(locations), Vec::new(), || self.inner.matrix_for(locations))
+    }
+
+    fn matrices_for(&self, locations: &[(f64, f64)]) -> TravelMatrices {
+        self.metered(square(locations), TravelMatrices::from_durations(Vec::new()), || {
+            self.inner.matrices_for(locations)
+        })
+    }
+
+    fn table_for(&self, locations: &[(f64, f64)], sources: &[usize], destinations: &[usize]) -> TravelMatrices {
+        let cells = (sources.len() * destinations.len()) as u64;
+        self.metered(cells, TravelMatrices::from_durations(Vec::new()), || {
+            self.inner.table_for(locations, sources, destinations)
+        })
+    }
+
+    fn matrices_for_profile(&self, profile: &str, locations: &[(f64, f64)]) -> TravelMatrices {
+        self.metered(square(locations), TravelMatrices::from_durations(Vec::new()), || {
+            self.inner.matrices_for_profile(profile, locations)
+        })
+    }
+
+    fn bytes_transferred(&self) -> Option<u64> {
+        self.inner.bytes_transferred()
+    }
+}
+
+impl<P: AsyncDistanceMatrixProvider + Sync> MeteredMatrixProvider<P> {
+    async fn fetch_metered<T>(&self, cells: u64, refused: T, fetch: impl Future<Output = T>) -> T {
+        if !self.admit(cells, self.inner.bytes_transferred()) {
+            return refused;
+        }
+        let started = Instant::now();
+        let result = fetch.await;
+        self.record(started, self.inner.bytes_transferred());
+        result
+    }
+}
+
+impl<P: AsyncDistanceMatrixProvider + Sync> AsyncDistanceMatrixProvider for MeteredMatrixProvider<P> {
+    fn fetch_matrix(&self, locations: &[(f64, f64)]) -> impl Future<Output = Vec<Vec<i32>>> + Send {
+        self.fetch_metered(square(locations), Vec::new(), self.inner.fetch_matrix(locations))
+    }
+
+    fn fetch_matrices(&self, locations: &[(f64, f64)]) -> impl Future<Output = TravelMatrices> + Send {
+        let refused = TravelMatrices::from_durations(Vec::new());
+        self.fetch_metered(square(locations), refused, self.inner.fetch_matrices(locations))
+    }
+
+    fn fetch_table(
+        &self,
+        locations: &[(f64, f64)],
+        sources: &[usize],
+        destinations: &[usize],
+    ) -> impl Future<Output = TravelMatrices> + Send {
+        let cells = (sources.len() * destinations.len()) as u64;
+        let refused = TravelMatrices::from_durations(Vec::new());
+        self.fetch_metered(cells, refused, self.inner.fetch_table(locations, sources, destinations))
+    }
+
+    fn fetch_matrices_for_profile(
+        &self,
+        profile: &str,
+        locations: &[(f64, f64)],
+    ) -> impl Future<Output = TravelMatrices> + Send {
+        let refused = TravelMatrices::from_durations(Vec::new());
+        self.fetch_metered(square(locations), refused, self.inner.fetch_matrices_for_profile(profile, locations))
+    }
+
+    fn bytes_transferred(&self) -> Option<u64> {
+        self.inner.bytes_transferred()
+    }
+}
+
+/// Combined bytes of two providers, if either reports them.
+pub(crate) fn sum_bytes(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+    match (a, b) {
+        (None, None) => None,
+        _ => Some(a.unwrap_or(0) + b.unwrap_or(0)),
+    }
+}
+
+/// Running total of bytes a network provider has sent and received, shared
+/// by its clones.
+#[cfg(any(feature = "http", test))]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ByteCounter(Arc<AtomicU64>);
+
+#[cfg(any(feature = "http", test))]
+impl ByteCounter {
+    pub fn add(&self, bytes: usize) {
+        self.0.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn total(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Whether a response body arrived gzip-compressed.
+#[cfg(feature = "http")]
+pub(crate) fn is_gzipped(headers: &reqwest::header::HeaderMap) -> bool {
+    headers.get(reqwest::header::CONTENT_ENCODING).is_some_and(|encoding| encoding == "gzip")
+}
+
+/// A response body as sent, decompressed if `gzipped`.
+///
+/// Network providers ask for gzip themselves instead of letting reqwest
+/// decompress, which would hide the size on the wire from [`ByteCounter`].
+#[cfg(feature = "http")]
+pub(crate) fn decode_body(body: &[u8], gzipped: bool) -> std::io::Result<Cow<'_, [u8]>> {
+    if !gzipped {
+        return Ok(Cow::Borrowed(body));
+    }
+    let mut decoded = Vec::new();
+    flate2::read::GzDecoder::new(body).read_to_end(&mut decoded)?;
+    Ok(Cow::Owned(decoded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::synthetic::GridMatrix;
+
+    /// Grid travel that reports 10 bytes per cell.
+    #[derive(Default)]
+    struct WiredGrid {
+        wire: ByteCounter,
+    }
+
+    impl DistanceMatrixProvider for WiredGrid {
+        fn matrix_for(&self, locations: &[(f64, f64)]) -> Vec<Vec<i32>> {
+            self.wire.add(10 * locations.len() * locations.len());
+            GridMatrix::default().matrix_for(locations)
+        }
+
+        fn bytes_transferred(&self) -> Option<u64> {
+            Some(self.wire.total())
+        }
+    }
+
+    fn line(n: usize) -> Vec<(f64, f64)> {
+        (0..n).map(|i| (i as f64, 0.0)).collect()
+    }
+
+    #[test]
+    fn test_counts_cells_requests_and_bytes() {
+        let metered = MeteredMatrixProvider::new(WiredGrid::default());
+
+        metered.matrix_for(&line(3));
+        metered.table_for(&line(10), &[0], &[1, 2, 3, 4]);
+
+        let usage = metered.usage();
+        assert_eq!((usage.requests, usage.cells, usage.rejected), (2, 9 + 4, 0));
+        // The default table_for asks for a 5 × 5 matrix over the locations involved
+        assert_eq!(usage.bytes, Some(90 + 250));
+
+        metered.reset();
+        assert_eq!(metered.usage(), MatrixUsage::default());
+    }
+
+    /// Grid travel that holds each request open until `requests` are in flight.
+    struct OverlappingGrid {
+        wire: ByteCounter,
+        in_flight: std::sync::Barrier,
+    }
+
+    impl DistanceMatrixProvider for OverlappingGrid {
+        fn matrix_for(&self, locations: &[(f64, f64)]) -> Vec<Vec<i32>> {
+            self.wire.add(10 * locations.len() * locations.len());
+            self.in_flight.wait();
+            GridMatrix::default().matrix_for(locations)
+        }
+
+        fn bytes_transferred(&self) -> Option<u64> {
+            Some(self.wire.total())
+        }
+    }
+
+    #[test]
+    fn test_concurrent_requests_count_bytes_once() {
+        let inner = OverlappingGrid { wire: ByteCounter::default(), in_flight: std::sync::Barrier::new(2) };
+        let metered = MeteredMatrixProvider::new(inner);
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| metered.matrix_for(&line(3)));
+            scope.spawn(|| metered.matrix_for(&line(3)));
+        });
+
+        assert_eq!(metered.usage().requests, 2);
+        assert_eq!(metered.usage().bytes, Some(180));
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_decodes_gzipped_bodies() {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(br#"{"code": "Ok"}"#).unwrap();
+        let body = encoder.finish().unwrap();
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        assert!(!is_gzipped(&headers));
+        headers.insert(reqwest::header::CONTENT_ENCODING, "gzip".parse().unwrap());
+        assert!(is_gzipped(&headers));
+
+        assert_eq!(&*decode_body(&body, true).unwrap(), br#"{"code": "Ok"}"#);
+        assert_eq!(&*decode_body(b"plain", false).unwrap(), b"plain");
+        assert!(decode_body(b"plain", true).is_err());
+    }
+
+    #[test]
+    fn test_bytes_unknown_without_provider_support() {
+        let metered = MeteredMatrixProvider::new(GridMatrix::default());
+        metered.matrix_for(&line(3));
+        assert_eq!(metered.usage().bytes, None);
+    }
+
+    #[test]
+    fn test_budget_refuses_before_fetching() {
+        let metered = MeteredMatrixProvider::new(WiredGrid::default())
+            .with_max_cells_per_request(25)
+            .with_max_total_cells(40);
+
+        assert!(metered.matrix_for(&line(6)).is_empty(), "36 cells is over the per-request limit");
+        assert_eq!(metered.matrix_for(&line(5)).len(), 5);
+        assert_eq!(metered.matrix_for(&line(4)).len(), 0, "25 + 16 is over the total");
+        assert_eq!(metered.matrix_for(&line(3)).len(), 3);
+
+        let usage = metered.usage();
+        assert_eq!((usage.requests, usage.cells, usage.rejected), (2, 34, 2));
+        assert_eq!(usage.bytes, Some(340));
+    }
+}
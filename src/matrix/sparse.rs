@@ -0,0 +1,507 @@
+//! Nearest-neighbor matrices for very large location sets.
+//!
+//! A dense table over 2,000 locations is 4M cells, most of which the solver
+//! never weighs seriously: routes are built from short hops between nearby
+//! stops. [`SparseMatrixProvider`] asks the routing backend only for each
+//! location's nearest neighbors, plus full rows and columns for hubs such as
+//! depots, and fills the remaining cells from a cheap estimate scaled to
+//! match the routed cells. The solver still gets a full matrix; what's saved
+//! is routing work, not memory.
+
+use std::collections::{HashSet, VecDeque};
+use std::future::Future;
+
+use tracing::{debug, warn};
+
+use crate::haversine::{HaversineMatrix, TYPICAL_CIRCUITY};
+use crate::matrix::metered::sum_bytes;
+use crate::traits::{
+    AsyncDistanceMatrixProvider, DistanceMatrixProvider, LocationRef, TravelMatrices, UNREACHABLE_METERS,
+    UNREACHABLE_SECS,
+};
+
+/// Locations per table request unless set otherwise: OSRM's default
+/// `--max-table-size`.
+pub const DEFAULT_MAX_TABLE_LOCATIONS: usize = 100;
+
+/// Matrix provider that routes only the K nearest neighbors of each location.
+///
+/// Neighbors are ranked by the estimate provider. Nearby sources share one
+/// [`table_for`](DistanceMatrixProvider::table_for) request to the inner
+/// provider, up to a limit on the locations each request involves. Every
+/// other cell is the estimate times the ratio of routed to estimated travel
+/// over the routed cells, as are the cells of any request that fails.
+/// Distances are kept if the estimate and every routed table have them.
+/// Location sets of at most `neighbors + 1`, and matrices for other routing
+/// profiles, are fetched dense.
+#[derive(Debug, Clone)]
+pub struct SparseMatrixProvider<P, F = HaversineMatrix> {
+    inner: P,
+    estimate: F,
+    neighbors: usize,
+    hubs: HashSet<LocationRef>,
+    max_table_locations: usize,
+}
+
+impl<P> SparseMatrixProvider<P, HaversineMatrix> {
+    /// Routes `neighbors` per location, estimating the rest with
+    /// [`HaversineMatrix`] at [`TYPICAL_CIRCUITY`].
+    pub fn new(inner: P, neighbors: usize) -> Self {
+        Self::with_estimate(inner, HaversineMatrix::default().with_circuity(TYPICAL_CIRCUITY), neighbors)
+    }
+}
+
+impl<P, F> SparseMatrixProvider<P, F> {
+    pub fn with_estimate(inner: P, estimate: F, neighbors: usize) -> Self {
+        Self { inner, estimate, neighbors, hubs: HashSet::new(), max_table_locations: DEFAULT_MAX_TABLE_LOCATIONS }
+    }
+
+    /// Routes every cell to and from `location` (e.g., a depot, which starts
+    /// routes toward stops far from it).
+    pub fn with_hub(mut self, location: (f64, f64)) -> Self {
+        self.hubs.insert(LocationRef::from_coordinates(location));
+        self
+    }
+
+    /// Caps the sources plus destinations of one table request, to stay
+    /// within the backend's limit (at least 2).
+    pub fn with_max_table_locations(mut self, max: usize) -> Self {
+        self.max_table_locations = max.max(2);
+        self
+    }
+
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+}
+
+/// One table request: sources routed together to every destination any of
+/// them needs.
+struct Batch {
+    sources: Vec<usize>,
+    destinations: Vec<usize>,
+}
+
+impl Batch {
+    /// Adds `source` and the destinations it needs, tracking every location
+    /// the request involves.
+    fn add(&mut self, source: usize, needed: &[usize], involved: &mut HashSet<usize>) {
+        self.sources.push(source);
+        involved.insert(source);
+        for &destination in needed {
+            involved.insert(destination);
+            if !self.destinations.contains(&destination) {
+                self.destinations.push(destination);
+            }
+        }
+    }
+}
+
+/// What to route for one location set.
+struct SparsePlan {
+    estimate: TravelMatrices,
+    batches: Vec<Batch>,
+}
+
+impl<P, F: DistanceMatrixProvider> SparseMatrixProvider<P, F> {
+    /// `None` if the set is small enough to fetch dense or the estimate failed.
+    fn plan(&self, locations: &[(f64, f64)]) -> Option<SparsePlan> {
+        if locations.len() <= self.neighbors + 1 {
+            return None;
+        }
+        let estimate = self.estimate.matrices_for(locations);
+        if !is_square(&estimate.durations, locations.len()) {
+            warn!(locations = locations.len(), "Sparse matrix estimate failed, fetching dense");
+            return None;
+        }
+        let hubs: Vec<usize> = (0..locations.len())
+            .filter(|&index| self.hubs.contains(&LocationRef::from_coordinates(locations[index])))
+            .collect();
+
+        let destinations: Vec<Vec<usize>> = (0..locations.len())
+            .map(|source| {
+                if hubs.contains(&source) {
+                    return (0..locations.len()).collect();
+                }
+                let mut nearest: Vec<usize> = (0..locations.len()).filter(|&index| index != source).collect();
+                let row = &estimate.durations[source];
+                if nearest.len() > self.neighbors {
+                    nearest.select_nth_unstable_by_key(self.neighbors, |&index| row[index]);
+                    nearest.truncate(self.neighbors);
+                }
+                let unlisted: Vec<usize> =
+                    hubs.iter().copied().filter(|&hub| hub != source && !nearest.contains(&hub)).collect();
+                nearest.extend(unlisted);
+                nearest
+            })
+            .collect();
+        let batches = batches(&destinations, self.max_table_locations);
+        Some(SparsePlan { estimate, batches })
+    }
+}
+
+fn is_square(matrix: &[Vec<i32>], n: usize) -> bool {
+    matrix.len() == n && matrix.iter().all(|row| row.len() == n)
+}
+
+/// Groups sources into table requests of at most `max_locations` locations
+/// each, sources and destinations together.
+///
+/// A batch starts from the first source not yet batched and grows through
+/// its neighbors, and theirs, which mostly need the same destinations. A
+/// source with more destinations than fit in one request gets requests of
+/// its own.
+fn batches(destinations: &[Vec<usize>], max_locations: usize) -> Vec<Batch> {
+    let mut batched = vec![false; destinations.len()];
+    let mut batches = Vec::new();
+    for seed in 0..destinations.len() {
+        if batched[seed] {
+            continue;
+        }
+        batched[seed] = true;
+        if destinations[seed].len() + 1 > max_locations {
+            batches.extend(
+                destinations[seed]
+                    .chunks(max_locations - 1)
+                    .map(|chunk| Batch { sources: vec![seed], destinations: chunk.to_vec() }),
+            );
+            continue;
+        }
+
+        let mut batch = Batch { sources: Vec::new(), destinations: Vec::new() };
+        let mut involved = HashSet::new();
+        batch.add(seed, &destinations[seed], &mut involved);
+        let mut candidates: VecDeque<usize> = destinations[seed].iter().copied().collect();
+        while let Some(source) = candidates.pop_front() {
+            let added = usize::from(!involved.contains(&source))
+                + destinations[source].iter().filter(|destination| !involved.contains(destination)).count();
+            if batched[source] || involved.len() + added > max_locations {
+                continue;
+            }
+            batched[source] = true;
+            batch.add(source, &destinations[source], &mut involved);
+            candidates.extend(&destinations[source]);
+        }
+        batches.push(batch);
+    }
+    batches
+}
+
+/// Whether a routed table has a row per source and a cell per destination.
+fn covers(batch: &Batch, cells: &[Vec<i32>]) -> bool {
+    cells.len() == batch.sources.len() && cells.iter().all(|row| row.len() == batch.destinations.len())
+}
+
+/// Overlays routed tables on the estimate, scaling the unrouted cells.
+/// Tables that failed leave their cells to the estimate.
+fn assemble(plan: SparsePlan, tables: Vec<TravelMatrices>) -> TravelMatrices {
+    let SparsePlan { estimate, batches } = plan;
+    let routed: Vec<(&Batch, TravelMatrices)> = batches
+        .iter()
+        .zip(tables)
+        .filter(|(batch, table)| {
+            let ok = covers(batch, &table.durations);
+            if !ok {
+                warn!(sources = batch.sources.len(), "Sparse matrix table request failed, keeping estimates");
+            }
+            ok
+        })
+        .collect();
+
+    let durations = overlay(estimate.durations, &routed, |table| Some(&table.durations), UNREACHABLE_SECS);
+    let distances = estimate
+        .distances
+        .filter(|distances| is_square(distances, durations.len()))
+        .filter(|_| {
+            routed.iter().all(|(batch, table)| table.distances.as_ref().is_some_and(|cells| covers(batch, cells)))
+        })
+        .map(|distances| overlay(distances, &routed, |table| table.distances.as_ref(), UNREACHABLE_METERS));
+    debug!(
+        locations = durations.len(),
+        requests = batches.len(),
+        failed = batches.len() - routed.len(),
+        routed = routed.iter().map(|(batch, _)| batch.sources.len() * batch.destinations.len()).sum::<usize>(),
+        "Assembled sparse matrix"
+    );
+    TravelMatrices { durations, distances, snap_distances: None }
+}
+
+/// The estimate scaled by the ratio of routed to estimated values over the
+/// routed cells, with the routed cells written over it.
+fn overlay(
+    mut estimate: Vec<Vec<i32>>,
+    routed: &[(&Batch, TravelMatrices)],
+    cells: impl Fn(&TravelMatrices) -> Option<&Vec<Vec<i32>>>,
+    unreachable: i32,
+) -> Vec<Vec<i32>> {
+    let routed_cells = || {
+        routed.iter().filter_map(|(batch, table)| Some((*batch, cells(table)?))).flat_map(|(batch, table)| {
+            batch.sources.iter().zip(table).flat_map(move |(&source, row)| {
+                batch.destinations.iter().zip(row).map(move |(&destination, &value)| (source, destination, value))
+            })
+        })
+    };
+
+    let (mut routed_sum, mut estimated_sum) = (0.0, 0.0);
+    for (source, destination, value) in routed_cells() {
+        // Unreachable pairs say nothing about the scale of the estimate
+        if value < unreachable {
+            routed_sum += f64::from(value);
+            estimated_sum += f64::from(estimate[source][destination]);
+        }
+    }
+    let ratio = if routed_sum > 0.0 && estimated_sum > 0.0 { routed_sum / estimated_sum } else { 1.0 };
+
+    for row in &mut estimate {
+        for value in row.iter_mut() {
+            *value = (f64::from(*value) * ratio).round() as i32;
+        }
+    }
+    for (source, destination, value) in routed_cells() {
+        estimate[source][destination] = value;
+    }
+    estimate
+}
+
+impl<P, F> SparseMatrixProvider<P, F>
+where
+    P: DistanceMatrixProvider,
+    F: DistanceMatrixProvider,
+{
+    fn fetch_sparse(&self, locations: &[(f64, f64)], plan: SparsePlan) -> TravelMatrices {
+        let tables = plan
+            .batches
+            .iter()
+            .map(|batch| self.inner.table_for(locations, &batch.sources, &batch.destinations))
+            .collect();
+        assemble(plan, tables)
+    }
+}
+
+impl<P, F> DistanceMatrixProvider for SparseMatrixProvider<P, F>
+where
+    P: DistanceMatrixProvider,
+    F: DistanceMatrixProvider,
+{
+    fn matrix_for(&self, locations: &[(f64, f64)]) -> Vec<Vec<i32>> {
+        match self.plan(locations) {
+            Some(plan) => self.fetch_sparse(locations, plan).durations,
+            None => self.inner.matrix_for(locations),
+        }
+    }
+
+    fn matrices_for(&self, locations: &[(f64, f64)]) -> TravelMatrices {
+        match self.plan(locations) {
+            Some(plan) => self.fetch_sparse(locations, plan),
+            None => self.inner.matrices_for(locations),
+        }
+    }
+
+    fn matrices_for_profile(&self, profile: &str, locations: &[(f64, f64)]) -> TravelMatrices {
+        self.inner.matrices_for_profile(profile, locations)
+    }
+
+    fn bytes_transferred(&self) -> Option<u64> {
+        sum_bytes(self.inner.bytes_transferred(), self.estimate.bytes_transferred())
+    }
+}
+
+impl<P, F> SparseMatrixProvider<P, F>
+where
+    P: AsyncDistanceMatrixProvider + Sync,
+    F: DistanceMatrixProvider + Sync,
+{
+    async fn fetch_sparse_async(&self, locations: &[(f64, f64)], plan: SparsePlan) -> TravelMatrices {
+        let mut tables = Vec::with_capacity(plan.batches.len());
+        for batch in &plan.batches {
+            tables.push(self.inner.fetch_table(locations, &batch.sources, &batch.destinations).await);
+        }
+        assemble(plan, tables)
+    }
+}
+
+impl<P, F> AsyncDistanceMatrixProvider for SparseMatrixProvider<P, F>
+where
+    P: AsyncDistanceMatrixProvider + Sync,
+    F: DistanceMatrixProvider + Sync,
+{
+    fn fetch_matrix(&self, locations: &[(f64, f64)]) -> impl Future<Output = Vec<Vec<i32>>> + Send {
+        // Owned so the tables can be requested one after another.
+        let locations = locations.to_vec();
+
+        async move {
+            match self.plan(&locations) {
+                Some(plan) => self.fetch_sparse_async(&locations, plan).await.durations,
+                None => self.inner.fetch_matrix(&locations).await,
+            }
+        }
+    }
+
+    fn fetch_matrices(&self, locations: &[(f64, f64)]) -> impl Future<Output = TravelMatrices> + Send {
+        let locations = locations.to_vec();
+
+        async move {
+            match self.plan(&locations) {
+                Some(plan) => self.fetch_sparse_async(&locations, plan).await,
+                None => self.inner.fetch_matrices(&locations).await,
+            }
+        }
+    }
+
+    fn fetch_matrices_for_profile(
+        &self,
+        profile: &str,
+        locations: &[(f64, f64)],
+    ) -> impl Future<Output = TravelMatrices> + Send {
+        self.inner.fetch_matrices_for_profile(profile, locations)
+    }
+
+    fn bytes_transferred(&self) -> Option<u64> {
+        sum_bytes(self.inner.bytes_transferred(), self.estimate.bytes_transferred())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::matrix::synthetic::{EuclideanMatrix, GridMatrix};
+
+    /// Grid travel that counts the requests and cells it's asked for, and
+    /// fails any table request from `failing`.
+    #[derive(Default)]
+    struct CountingGrid {
+        requests: Cell<usize>,
+        cells: Cell<usize>,
+        /// Most locations any one table request involved.
+        widest: Cell<usize>,
+        failing: Option<usize>,
+    }
+
+    impl DistanceMatrixProvider for CountingGrid {
+        fn matrix_for(&self, locations: &[(f64, f64)]) -> Vec<Vec<i32>> {
+            self.matrices_for(locations).durations
+        }
+
+        fn matrices_for(&self, locations: &[(f64, f64)]) -> TravelMatrices {
+            self.requests.set(self.requests.get() + 1);
+            self.cells.set(self.cells.get() + locations.len() * locations.len());
+            GridMatrix::default().matrices_for(locations)
+        }
+
+        fn table_for(&self, locations: &[(f64, f64)], sources: &[usize], destinations: &[usize]) -> TravelMatrices {
+            self.requests.set(self.requests.get() + 1);
+            self.cells.set(self.cells.get() + sources.len() * destinations.len());
+            let involved: HashSet<&usize> = sources.iter().chain(destinations).collect();
+            self.widest.set(self.widest.get().max(involved.len()));
+            if self.failing.is_some_and(|failing| sources.contains(&failing)) {
+                return TravelMatrices::from_durations(Vec::new());
+            }
+            let matrices = GridMatrix::default().matrices_for(locations);
+            let table = |matrix: &Vec<Vec<i32>>| -> Vec<Vec<i32>> {
+                sources.iter().map(|&from| destinations.iter().map(|&to| matrix[from][to]).collect()).collect()
+            };
+            TravelMatrices {
+                durations: table(&matrices.durations),
+                distances: matrices.distances.as_ref().map(table),
+                snap_distances: None,
+            }
+        }
+    }
+
+    fn line(n: usize) -> Vec<(f64, f64)> {
+        (0..n).map(|i| (i as f64, 0.0)).collect()
+    }
+
+    #[test]
+    fn test_routes_nearest_neighbors_in_batches() {
+        let locations = line(50);
+        let sparse = SparseMatrixProvider::with_estimate(CountingGrid::default(), EuclideanMatrix::default(), 4)
+            .with_max_table_locations(12);
+
+        let matrix = sparse.matrix_for(&locations);
+
+        // Six requests instead of one per location, still far from all 2,500 cells
+        assert_eq!(sparse.inner().requests.get(), 6);
+        assert!(sparse.inner().widest.get() <= 12);
+        assert!(sparse.inner().cells.get() < 600);
+        // On a line, grid and straight-line travel agree everywhere
+        assert_eq!(matrix, GridMatrix::default().matrix_for(&locations));
+    }
+
+    #[test]
+    fn test_requests_stay_within_the_table_limit() {
+        let mut locations = line(39);
+        locations.push((1000.0, 0.0));
+        let sparse = SparseMatrixProvider::with_estimate(CountingGrid::default(), EuclideanMatrix::default(), 6)
+            .with_hub((1000.0, 0.0))
+            .with_max_table_locations(10);
+
+        let matrix = sparse.matrix_for(&locations);
+
+        assert!(sparse.inner().widest.get() <= 10);
+        assert_eq!(matrix[39][0], 1000 * 60);
+        assert_eq!(matrix, GridMatrix::default().matrix_for(&locations));
+    }
+
+    #[test]
+    fn test_unrouted_cells_scale_to_routed_ones() {
+        // Routed travel is twice the estimate
+        let inner = GridMatrix::new(30.0);
+        let sparse = SparseMatrixProvider::with_estimate(inner, GridMatrix::default(), 2);
+
+        let matrix = sparse.matrix_for(&line(10));
+
+        assert_eq!(matrix[0][1], 120);
+        assert_eq!(matrix[0][9], 9 * 120);
+    }
+
+    #[test]
+    fn test_failed_request_keeps_estimates_for_its_rows() {
+        let inner = CountingGrid { failing: Some(20), ..CountingGrid::default() };
+        // The estimate is half the routed travel, so its cells show through
+        let sparse = SparseMatrixProvider::with_estimate(inner, GridMatrix::new(120.0), 2).with_max_table_locations(6);
+
+        let matrix = sparse.matrix_for(&line(40));
+
+        assert_eq!(matrix.len(), 40);
+        assert_eq!(matrix[0][1], 60);
+        // Row 20's request failed: its cells are the estimate scaled up
+        assert_eq!(matrix[20][21], 60);
+        assert_eq!(matrix[20][39], 19 * 60);
+    }
+
+    #[test]
+    fn test_keeps_distances() {
+        let locations = line(30);
+        let sparse = SparseMatrixProvider::with_estimate(CountingGrid::default(), EuclideanMatrix::default(), 3);
+
+        let matrices = sparse.matrices_for(&locations);
+
+        assert_eq!(matrices.distances, GridMatrix::default().matrices_for(&locations).distances);
+    }
+
+    #[test]
+    fn test_hubs_route_full_rows_and_columns() {
+        // A depot far from 19 stops
+        let mut locations = line(19);
+        locations.push((1000.0, 0.0));
+        let sparse = SparseMatrixProvider::with_estimate(CountingGrid::default(), EuclideanMatrix::default(), 3)
+            .with_hub((1000.0, 0.0));
+
+        let matrix = sparse.matrix_for(&locations);
+
+        assert_eq!(matrix[19][0], 1000 * 60);
+        assert_eq!(matrix[0][19], 1000 * 60);
+    }
+
+    #[test]
+    fn test_small_sets_are_fetched_dense() {
+        let sparse = SparseMatrixProvider::with_estimate(CountingGrid::default(), EuclideanMatrix::default(), 4);
+
+        sparse.matrix_for(&line(5));
+
+        assert_eq!(sparse.inner().cells.get(), 25);
+    }
+}
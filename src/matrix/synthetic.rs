@@ -0,0 +1,170 @@
+//! Matrix providers over planar coordinates, for tests and benchmarks.
+//!
+//! Locations are read as (x, y) in abstract units rather than lat/lng, and
+//! travel is distance over a fixed speed. By default one unit takes one
+//! minute and covers 1000 meters.
+
+use std::future::Future;
+
+use crate::traits::{AsyncDistanceMatrixProvider, DistanceMatrixProvider, TravelMatrices};
+
+/// Default speed: one unit a minute.
+const DEFAULT_UNITS_PER_HOUR: f64 = 60.0;
+
+/// Default length of a unit, in meters.
+const DEFAULT_UNIT_METERS: f64 = 1000.0;
+
+/// Straight-line travel between points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EuclideanMatrix {
+    /// Round each leg to the nearest whole unit, as TSPLIB's `EUC_2D` does.
+    pub rounded: bool,
+    /// Travel speed in units per hour.
+    pub units_per_hour: f64,
+    /// Meters reported per unit of distance.
+    pub unit_meters: f64,
+}
+
+impl Default for EuclideanMatrix {
+    fn default() -> Self {
+        Self::new(DEFAULT_UNITS_PER_HOUR)
+    }
+}
+
+impl EuclideanMatrix {
+    pub const fn new(units_per_hour: f64) -> Self {
+        Self { rounded: false, units_per_hour, unit_meters: DEFAULT_UNIT_METERS }
+    }
+
+    pub fn with_rounding(mut self) -> Self {
+        self.rounded = true;
+        self
+    }
+
+    pub fn with_unit_meters(mut self, unit_meters: f64) -> Self {
+        self.unit_meters = unit_meters;
+        self
+    }
+
+    /// Distance between two points in units.
+    pub fn distance(&self, from: (f64, f64), to: (f64, f64)) -> f64 {
+        let distance = (from.0 - to.0).hypot(from.1 - to.1);
+        if self.rounded { distance.round() } else { distance }
+    }
+}
+
+/// Travel along a street grid: the Manhattan distance between points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridMatrix {
+    /// Travel speed in units per hour.
+    pub units_per_hour: f64,
+    /// Meters reported per unit of distance.
+    pub unit_meters: f64,
+}
+
+impl Default for GridMatrix {
+    fn default() -> Self {
+        Self::new(DEFAULT_UNITS_PER_HOUR)
+    }
+}
+
+impl GridMatrix {
+    pub const fn new(units_per_hour: f64) -> Self {
+        Self { units_per_hour, unit_meters: DEFAULT_UNIT_METERS }
+    }
+
+    pub fn with_unit_meters(mut self, unit_meters: f64) -> Self {
+        self.unit_meters = unit_meters;
+        self
+    }
+
+    /// Distance between two points in units.
+    pub fn distance(&self, from: (f64, f64), to: (f64, f64)) -> f64 {
+        (from.0 - to.0).abs() + (from.1 - to.1).abs()
+    }
+}
+
+/// Every pairwise `distance` times `scale`, rounded.
+fn scaled(locations: &[(f64, f64)], scale: f64, distance: impl Fn((f64, f64), (f64, f64)) -> f64) -> Vec<Vec<i32>> {
+    locations
+        .iter()
+        .map(|&from| locations.iter().map(|&to| (distance(from, to) * scale).round() as i32).collect())
+        .collect()
+}
+
+impl DistanceMatrixProvider for EuclideanMatrix {
+    fn matrix_for(&self, locations: &[(f64, f64)]) -> Vec<Vec<i32>> {
+        scaled(locations, 3600.0 / self.units_per_hour, |from, to| self.distance(from, to))
+    }
+
+    fn matrices_for(&self, locations: &[(f64, f64)]) -> TravelMatrices {
+        TravelMatrices {
+            durations: self.matrix_for(locations),
+            distances: Some(scaled(locations, self.unit_meters, |from, to| self.distance(from, to))),
+            snap_distances: None,
+        }
+    }
+}
+
+impl AsyncDistanceMatrixProvider for EuclideanMatrix {
+    fn fetch_matrix(&self, locations: &[(f64, f64)]) -> impl Future<Output = Vec<Vec<i32>>> + Send {
+        std::future::ready(self.matrix_for(locations))
+    }
+
+    fn fetch_matrices(&self, locations: &[(f64, f64)]) -> impl Future<Output = TravelMatrices> + Send {
+        std::future::ready(self.matrices_for(locations))
+    }
+}
+
+impl DistanceMatrixProvider for GridMatrix {
+    fn matrix_for(&self, locations: &[(f64, f64)]) -> Vec<Vec<i32>> {
+        scaled(locations, 3600.0 / self.units_per_hour, |from, to| self.distance(from, to))
+    }
+
+    fn matrices_for(&self, locations: &[(f64, f64)]) -> TravelMatrices {
+        TravelMatrices {
+            durations: self.matrix_for(locations),
+            distances: Some(scaled(locations, self.unit_meters, |from, to| self.distance(from, to))),
+            snap_distances: None,
+        }
+    }
+}
+
+impl AsyncDistanceMatrixProvider for GridMatrix {
+    fn fetch_matrix(&self, locations: &[(f64, f64)]) -> impl Future<Output = Vec<Vec<i32>>> + Send {
+        std::future::ready(self.matrix_for(locations))
+    }
+
+    fn fetch_matrices(&self, locations: &[(f64, f64)]) -> impl Future<Output = TravelMatrices> + Send {
+        std::future::ready(self.matrices_for(locations))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_speed_is_a_unit_a_minute() {
+        let locations = vec![(0.0, 0.0), (3.0, 4.0)];
+
+        assert_eq!(EuclideanMatrix::default().matrix_for(&locations), vec![vec![0, 300], vec![300, 0]]);
+        assert_eq!(GridMatrix::default().matrix_for(&locations), vec![vec![0, 420], vec![420, 0]]);
+    }
+
+    #[test]
+    fn test_speed_and_unit_length_scale_legs() {
+        let locations = vec![(0.0, 0.0), (3.0, 4.0)];
+        let matrices = GridMatrix::new(30.0).with_unit_meters(500.0).matrices_for(&locations);
+
+        assert_eq!(matrices.durations[0][1], 840);
+        assert_eq!(matrices.distances.unwrap()[0][1], 3500);
+    }
+
+    #[test]
+    fn test_rounding_snaps_legs_to_whole_units() {
+        let matrix = EuclideanMatrix::default().with_rounding();
+        assert_eq!(matrix.distance((0.0, 0.0), (1.0, 1.0)), 1.0);
+        assert_eq!(matrix.matrix_for(&[(0.0, 0.0), (1.0, 1.0)])[0][1], 60);
+    }
+}
@@ -0,0 +1,133 @@
+//! Plan KPIs.
+//!
+//! Summarizes a planner result into the handful of numbers ops teams use to
+//! judge a plan, computed one way for every consumer.
+
+use serde::{Deserialize, Serialize};
+
+use crate::solver::{PlannerResult, RouteResult};
+
+/// KPI summary of a plan.
+///
+/// A route's working span runs from leaving the start location to the end of
+/// its last visit. Travel and waiting come from each route's legs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanMetrics {
+    /// Routes with at least one visit.
+    pub routes: usize,
+    pub assigned_visits: usize,
+    pub unassigned_visits: usize,
+    /// Share of working span spent on visits, 0 to 100.
+    pub utilization_pct: f64,
+    pub total_travel_secs: i32,
+    pub avg_travel_secs_per_route: f64,
+    /// Visits completed per hour of working span.
+    pub visits_per_hour: f64,
+    /// Time spent waiting for windows to open.
+    pub idle_secs: i32,
+    /// Share of visits left unassigned, 0 to 100.
+    pub unassigned_rate_pct: f64,
+    /// Kilometers driven, if every route reported distances.
+    pub total_distance_km: Option<f64>,
+}
+
+/// Computes KPIs for a plan.
+pub fn summarize<VisitorId, VisitId>(plan: &PlannerResult<VisitorId, VisitId>) -> PlanMetrics {
+    let routes: Vec<&RouteResult<VisitorId, VisitId>> =
+        plan.routes.iter().filter(|route| !route.visit_ids.is_empty()).collect();
+
+    let assigned_visits: usize = routes.iter().map(|route| route.visit_ids.len()).sum();
+    let unassigned_visits = plan.unassigned.len();
+    let total_travel_secs: i32 = routes.iter().map(|route| route.total_travel_time).sum();
+    let idle_secs: i32 = routes.iter().flat_map(|route| &route.legs).map(|leg| leg.wait_secs).sum();
+    let service_secs: i32 = routes
+        .iter()
+        .flat_map(|route| &route.estimated_windows)
+        .map(|(start, end)| end - start)
+        .sum();
+    let span_secs: i32 = routes.iter().map(|route| working_span(route)).sum();
+
+    PlanMetrics {
+        routes: routes.len(),
+        assigned_visits,
+        unassigned_visits,
+        utilization_pct: percent(service_secs as f64, span_secs as f64),
+        total_travel_secs,
+        avg_travel_secs_per_route: if routes.is_empty() { 0.0 } else { total_travel_secs as f64 / routes.len() as f64 },
+        visits_per_hour: if span_secs == 0 { 0.0 } else { assigned_visits as f64 / (span_secs as f64 / 3600.0) },
+        idle_secs,
+        unassigned_rate_pct: percent(unassigned_visits as f64, (assigned_visits + unassigned_visits) as f64),
+        total_distance_km: routes.iter().map(|route| route.total_distance_km).sum(),
+    }
+}
+
+/// Seconds from leaving the start location to the end of the last visit.
+fn working_span<VisitorId, VisitId>(route: &RouteResult<VisitorId, VisitId>) -> i32 {
+    let (Some(&(first_start, _)), Some(&(_, last_end))) =
+        (route.estimated_windows.first(), route.estimated_windows.last())
+    else {
+        return 0;
+    };
+    let lead_in = route.legs.first().map_or(0, |leg| leg.travel_secs + leg.wait_secs);
+    last_end - (first_start - lead_in)
+}
+
+fn percent(part: f64, whole: f64) -> f64 {
+    if whole == 0.0 { 0.0 } else { part / whole * 100.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::{LegDetail, UnassignedVisit};
+    use crate::traits::UnassignedReason;
+
+    fn leg(to: &'static str, travel_secs: i32, wait_secs: i32) -> LegDetail<&'static str> {
+        LegDetail { from: None, to, travel_secs, wait_secs, distance_m: Some(1000) }
+    }
+
+    #[test]
+    fn test_summary_of_one_route() {
+        // 8:00 depart, 30 min drive, 1 h visit, 30 min drive, 1 h wait, 1 h visit -> 4 h span
+        let plan = PlannerResult {
+            routes: vec![RouteResult {
+                visitor_id: "alice",
+                visit_ids: vec!["v1", "v2"],
+                estimated_windows: vec![(30_600, 34_200), (39_600, 43_200)],
+                total_travel_time: 3600,
+                total_distance_km: Some(2.0),
+                legs: vec![leg("v1", 1800, 0), leg("v2", 1800, 3600)],
+                window_miss_risk: 0.0,
+                robustness_slack_secs: None,
+                return_leg: None,
+            }],
+            unassigned: vec![UnassignedVisit {
+                visit_id: "v3",
+                reason: UnassignedReason::RouteFull,
+                unavailable_visitors: Vec::new(),
+                relaxation: None,
+                mandatory: false,
+            }],
+        };
+
+        let metrics = summarize(&plan);
+
+        assert_eq!(metrics.routes, 1);
+        assert_eq!(metrics.utilization_pct, 50.0);
+        assert_eq!(metrics.avg_travel_secs_per_route, 3600.0);
+        assert_eq!(metrics.visits_per_hour, 0.5);
+        assert_eq!(metrics.idle_secs, 3600);
+        assert!((metrics.unassigned_rate_pct - 100.0 / 3.0).abs() < 1e-9);
+        assert_eq!(metrics.total_distance_km, Some(2.0));
+    }
+
+    #[test]
+    fn test_empty_plan_has_zero_rates() {
+        let plan: PlannerResult<&str, &str> = PlannerResult { routes: Vec::new(), unassigned: Vec::new() };
+        let metrics = summarize(&plan);
+
+        assert_eq!(metrics.utilization_pct, 0.0);
+        assert_eq!(metrics.visits_per_hour, 0.0);
+        assert_eq!(metrics.unassigned_rate_pct, 0.0);
+    }
+}
@@ -0,0 +1,424 @@
+//! Re-planning the rest of a day already under way.
+//!
+//! [`solve_midday`] takes where each visitor is and what they've done, drops
+//! completed visits, holds each visitor until their in-progress visit
+//! finishes, and solves only the remaining visits from the current time.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::recurrence::Recurrence;
+use crate::solver::{solve, LegDetail, RouteResult, SolveOptions, SolveResult};
+use crate::traits::{
+    AvailabilityProvider, DistanceMatrixProvider, LocationRef, RoutePosition, TimeWindow, Visit, VisitGap, VisitPinType,
+    Visitor, WindowSemantics,
+};
+
+/// Where a visitor is and what they've done so far today.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VisitorProgress<VisitorId, VisitId> {
+    pub visitor_id: VisitorId,
+    /// Current position (lat, lng), used as the start of the re-planned route.
+    pub position: (f64, f64),
+    #[serde(default = "Vec::new")]
+    pub completed: Vec<VisitId>,
+    /// Visit being served now, with its expected finish (seconds from midnight).
+    #[serde(default)]
+    pub in_progress: Option<(VisitId, i32)>,
+}
+
+impl<VisitorId, VisitId> VisitorProgress<VisitorId, VisitId> {
+    pub fn new(visitor_id: VisitorId, position: (f64, f64)) -> Self {
+        Self { visitor_id, position, completed: Vec::new(), in_progress: None }
+    }
+
+    pub fn with_completed(mut self, visit_ids: impl IntoIterator<Item = VisitId>) -> Self {
+        self.completed.extend(visit_ids);
+        self
+    }
+
+    /// Marks `visit_id` as being served until `finish`.
+    pub fn with_in_progress(mut self, visit_id: VisitId, finish: i32) -> Self {
+        self.in_progress = Some((visit_id, finish));
+        self
+    }
+}
+
+/// Re-plans the visits not yet done, starting at `now` (seconds from midnight).
+///
+/// Completed visits are left out of the result. An in-progress visit stays
+/// first on its visitor's route, and that visitor is free from its finish
+/// time; the rest are free from `now`. Visitors with progress start from
+/// their reported position, others from their usual start location.
+/// A visitor's [`max_visits`](Visitor::max_visits) counts visits already done.
+#[allow(clippy::too_many_arguments)]
+pub fn solve_midday<V, R, A, M>(
+    service_date: i64,
+    now: i32,
+    visits: &[V],
+    visitors: &[R],
+    progress: &[VisitorProgress<V::VisitorId, V::Id>],
+    availability: &A,
+    matrix_provider: &M,
+    options: SolveOptions,
+) -> SolveResult<V::VisitorId, V::Id>
+where
+    V: Visit<Id: Sync> + Sync,
+    V::VisitorId: Sync,
+    R: Visitor<Id = V::VisitorId> + Sync,
+    A: AvailabilityProvider<VisitorId = V::VisitorId> + Sync,
+    M: DistanceMatrixProvider,
+{
+    let progress: HashMap<&V::VisitorId, &VisitorProgress<V::VisitorId, V::Id>> =
+        progress.iter().map(|progress| (&progress.visitor_id, progress)).collect();
+    let done: HashSet<&V::Id> = progress
+        .values()
+        .flat_map(|progress| progress.completed.iter().chain(progress.in_progress.as_ref().map(|(id, _)| id)))
+        .collect();
+
+    let remaining: Vec<RemainingVisit<'_, V>> =
+        visits.iter().filter(|visit| !done.contains(visit.id())).map(RemainingVisit).collect();
+    let midday_visitors: Vec<MiddayVisitor<'_, R>> = visitors
+        .iter()
+        .map(|visitor| {
+            let progress = progress.get(visitor.id());
+            MiddayVisitor {
+                visitor,
+                position: progress.map(|progress| progress.position),
+                done: progress.map_or(0, |progress| progress.completed.len() + progress.in_progress.iter().len()),
+            }
+        })
+        .collect();
+    let free_from = progress
+        .values()
+        .map(|progress| {
+            let finish = progress.in_progress.as_ref().map_or(now, |(_, finish)| now.max(*finish));
+            (progress.visitor_id.clone(), finish)
+        })
+        .collect();
+    let availability = MiddayAvailability { availability, now, free_from };
+
+    let mut result = solve(service_date, &remaining, &midday_visitors, &availability, matrix_provider, options)?;
+
+    let durations: HashMap<&V::Id, i32> =
+        visits.iter().map(|visit| (visit.id(), visit.estimated_duration_minutes() * 60)).collect();
+    for progress in progress.values() {
+        let Some((visit_id, finish)) = &progress.in_progress else {
+            continue;
+        };
+        let Some(&duration) = durations.get(visit_id) else {
+            continue;
+        };
+        let route = match result.routes.iter().position(|route| route.visitor_id == progress.visitor_id) {
+            Some(idx) => &mut result.routes[idx],
+            None => {
+                result.routes.push(empty_route(progress.visitor_id.clone()));
+                result.routes.last_mut().expect("route was just pushed")
+            }
+        };
+        prepend_in_progress(route, visit_id.clone(), (finish - duration, *finish));
+    }
+
+    Ok(result)
+}
+
+fn empty_route<VisitorId, VisitId>(visitor_id: VisitorId) -> RouteResult<VisitorId, VisitId> {
+    RouteResult {
+        visitor_id,
+        visit_ids: Vec::new(),
+        estimated_windows: Vec::new(),
+        total_travel_time: 0,
+        total_distance_km: None,
+        legs: Vec::new(),
+        window_miss_risk: 0.0,
+        robustness_slack_secs: None,
+        return_leg: None,
+    }
+}
+
+/// Puts the in-progress visit ahead of the re-planned stops, with no travel in.
+fn prepend_in_progress<VisitorId, VisitId: Clone>(
+    route: &mut RouteResult<VisitorId, VisitId>,
+    visit_id: VisitId,
+    window: TimeWindow,
+) {
+    if let Some(first) = route.legs.first_mut() {
+        first.from = Some(visit_id.clone());
+    }
+    let distance_m = route.total_distance_km.map(|_| 0);
+    route.legs.insert(0, LegDetail { from: None, to: visit_id.clone(), travel_secs: 0, wait_secs: 0, distance_m });
+    route.visit_ids.insert(0, visit_id);
+    route.estimated_windows.insert(0, window);
+}
+
+/// Visitor starting from their reported position.
+struct MiddayVisitor<'a, R> {
+    visitor: &'a R,
+    position: Option<(f64, f64)>,
+    done: usize,
+}
+
+impl<R: Visitor> Visitor for MiddayVisitor<'_, R> {
+    type Id = R::Id;
+
+    fn id(&self) -> &Self::Id {
+        self.visitor.id()
+    }
+
+    fn start_location(&self) -> Option<(f64, f64)> {
+        self.position.or_else(|| self.visitor.start_location())
+    }
+
+    fn end_location(&self) -> Option<(f64, f64)> {
+        self.visitor.end_location()
+    }
+
+    fn start_location_ref(&self) -> Option<LocationRef> {
+        match self.position {
+            Some(position) => Some(LocationRef::from_coordinates(position)),
+            None => self.visitor.start_location_ref(),
+        }
+    }
+
+    fn end_location_ref(&self) -> Option<LocationRef> {
+        self.visitor.end_location_ref()
+    }
+
+    fn capabilities(&self) -> &[String] {
+        self.visitor.capabilities()
+    }
+
+    fn hourly_cost(&self) -> f64 {
+        self.visitor.hourly_cost()
+    }
+
+    fn per_km_cost(&self) -> f64 {
+        self.visitor.per_km_cost()
+    }
+
+    fn max_visits(&self) -> Option<usize> {
+        self.visitor.max_visits().map(|max| max.saturating_sub(self.done))
+    }
+
+    fn return_window(&self) -> Option<TimeWindow> {
+        self.visitor.return_window()
+    }
+
+    fn routing_profile(&self) -> Option<&str> {
+        self.visitor.routing_profile()
+    }
+}
+
+/// Availability clipped to when each visitor is free again.
+struct MiddayAvailability<'a, A: AvailabilityProvider> {
+    availability: &'a A,
+    now: i32,
+    free_from: HashMap<A::VisitorId, i32>,
+}
+
+impl<A: AvailabilityProvider> AvailabilityProvider for MiddayAvailability<'_, A> {
+    type VisitorId = A::VisitorId;
+
+    fn availability_for(&self, visitor_id: &Self::VisitorId, date: i64) -> Option<Vec<TimeWindow>> {
+        let free_from = self.free_from.get(visitor_id).copied().unwrap_or(self.now);
+        let windows: Vec<TimeWindow> = self
+            .availability
+            .availability_for(visitor_id, date)?
+            .into_iter()
+            .map(|(start, end)| (start.max(free_from), end))
+            .filter(|(start, end)| start < end)
+            .collect();
+        (!windows.is_empty()).then_some(windows)
+    }
+}
+
+/// A visit still to be done.
+struct RemainingVisit<'a, V>(&'a V);
+
+impl<V: Visit> Visit for RemainingVisit<'_, V> {
+    type Id = V::Id;
+    type VisitorId = V::VisitorId;
+
+    fn id(&self) -> &Self::Id {
+        self.0.id()
+    }
+
+    fn scheduled_date(&self) -> Option<i64> {
+        self.0.scheduled_date()
+    }
+
+    fn estimated_duration_minutes(&self) -> i32 {
+        self.0.estimated_duration_minutes()
+    }
+
+    fn duration_buffer_minutes(&self) -> i32 {
+        self.0.duration_buffer_minutes()
+    }
+
+    fn duration_std_dev_minutes(&self) -> f64 {
+        self.0.duration_std_dev_minutes()
+    }
+
+    fn committed_window(&self) -> Option<(i32, i32)> {
+        self.0.committed_window()
+    }
+
+    fn committed_window_semantics(&self) -> Option<WindowSemantics> {
+        self.0.committed_window_semantics()
+    }
+
+    fn not_before(&self) -> Option<i32> {
+        self.0.not_before()
+    }
+
+    fn same_visitor_group(&self) -> Option<&str> {
+        self.0.same_visitor_group()
+    }
+
+    fn different_visitor_group(&self) -> Option<&str> {
+        self.0.different_visitor_group()
+    }
+
+    fn start_gap(&self) -> Option<&VisitGap<Self::Id>> {
+        self.0.start_gap()
+    }
+
+    fn latest_completion(&self) -> Option<i32> {
+        self.0.latest_completion()
+    }
+
+    fn target_time(&self) -> Option<i32> {
+        self.0.target_time()
+    }
+
+    fn target_time_weight(&self) -> Option<i32> {
+        self.0.target_time_weight()
+    }
+
+    fn target_time_tolerance(&self) -> i32 {
+        self.0.target_time_tolerance()
+    }
+
+    fn pin_type(&self) -> VisitPinType {
+        self.0.pin_type()
+    }
+
+    fn pinned_visitor(&self) -> Option<&Self::VisitorId> {
+        self.0.pinned_visitor()
+    }
+
+    fn pinned_date(&self) -> Option<i64> {
+        self.0.pinned_date()
+    }
+
+    fn required_capabilities(&self) -> &[String] {
+        self.0.required_capabilities()
+    }
+
+    fn location(&self) -> (f64, f64) {
+        self.0.location()
+    }
+
+    fn location_ref(&self) -> LocationRef {
+        self.0.location_ref()
+    }
+
+    fn current_visitor_id(&self) -> Option<&Self::VisitorId> {
+        self.0.current_visitor_id()
+    }
+
+    fn reassignment_penalty(&self) -> Option<i32> {
+        self.0.reassignment_penalty()
+    }
+
+    fn locked_sequence(&self) -> Option<u32> {
+        self.0.locked_sequence()
+    }
+
+    fn route_position(&self) -> Option<RoutePosition> {
+        self.0.route_position()
+    }
+
+    fn must_assign(&self) -> bool {
+        self.0.must_assign()
+    }
+
+    fn recurrence(&self) -> Option<&Recurrence> {
+        self.0.recurrence()
+    }
+
+    fn date_window(&self) -> Option<(i64, i64)> {
+        self.0.date_window()
+    }
+
+    fn allowed_dates(&self) -> Option<&[i64]> {
+        self.0.allowed_dates()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::haversine::HaversineMatrix;
+    use crate::model::{AvailabilityData, VisitData, VisitorData};
+    use crate::solver::PlannerResult;
+
+    const DAY: i64 = 86_400;
+
+    fn visit_ids(result: &PlannerResult<String, String>, visitor_id: &str) -> Vec<String> {
+        result
+            .routes
+            .iter()
+            .find(|route| route.visitor_id == visitor_id)
+            .map_or_else(Vec::new, |route| route.visit_ids.clone())
+    }
+
+    #[test]
+    fn test_midday_replans_only_remaining_visits_after_now() {
+        let visits = vec![
+            VisitData::new("done", (36.10, -115.10), 30),
+            VisitData::new("current", (36.11, -115.11), 60),
+            VisitData::new("next", (36.12, -115.12), 30),
+            VisitData::new("later", (36.13, -115.13), 30),
+        ];
+        let visitors = vec![VisitorData::new("alice").with_start_location(36.0, -115.0)];
+        let availability = AvailabilityData::new(vec![(8 * 3600, 17 * 3600)]);
+        let progress = vec![
+            VisitorProgress::new("alice".to_string(), (36.11, -115.11))
+                .with_completed(["done".to_string()])
+                .with_in_progress("current".to_string(), 11 * 3600),
+        ];
+
+        let result = solve_midday(
+            DAY,
+            10 * 3600,
+            &visits,
+            &visitors,
+            &progress,
+            &availability,
+            &HaversineMatrix::default(),
+            SolveOptions::default(),
+        ).unwrap();
+
+        assert!(result.unassigned.is_empty());
+        let route = &result.routes[0];
+        assert_eq!(route.visit_ids, vec!["current", "next", "later"]);
+        assert_eq!(route.estimated_windows[0], (10 * 3600, 11 * 3600));
+        assert!(route.estimated_windows[1].0 > 11 * 3600, "next starts after the current visit");
+        assert_eq!(route.legs[1].from.as_deref(), Some("current"));
+    }
+
+    #[test]
+    fn test_midday_visitors_without_progress_start_now() {
+        let visits = vec![VisitData::new("v1", (36.12, -115.12), 30).with_committed_window(9 * 3600, 10 * 3600)];
+        let visitors = vec![VisitorData::new("alice").with_start_location(36.1, -115.1)];
+        let availability = AvailabilityData::new(vec![(8 * 3600, 17 * 3600)]);
+        let matrix = HaversineMatrix::default();
+
+        let at = |now: i32| solve_midday(DAY, now, &visits, &visitors, &[], &availability, &matrix, SolveOptions::default()).unwrap();
+
+        assert_eq!(visit_ids(&at(8 * 3600), "alice"), vec!["v1"]);
+        assert!(visit_ids(&at(12 * 3600), "alice").is_empty(), "the committed window has passed");
+    }
+}
@@ -0,0 +1,1017 @@
+//! Plain-data input model.
+//!
+//! Ready-made implementations of the planner traits for callers without an
+//! existing domain model (or loading problems from JSON). IDs are strings.
+
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+
+use serde::{Deserialize, Serialize};
+
+use crate::recurrence::Recurrence;
+use crate::solver::{is_valid_location, solve, SolveError, SolveOptions, SolveResult};
+use crate::timezone::{PlanningDay, TimeZone};
+
+use crate::traits::{
+    AsyncAvailabilityProvider, AvailabilityProvider, DistanceMatrixProvider, LocationRef, RoutePosition, TimeWindow,
+    Visit, VisitGap, VisitPinType, Visitor, WindowSemantics, service_bounds,
+};
+
+/// A visit as plain data.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VisitData {
+    pub id: String,
+    /// Location (lat, lng).
+    pub location: (f64, f64),
+    /// Estimated service duration in minutes.
+    pub duration_minutes: i32,
+    /// Minutes held free after the visit for overruns.
+    #[serde(default)]
+    pub duration_buffer_minutes: i32,
+    /// Standard deviation of the actual duration, in minutes.
+    #[serde(default)]
+    pub duration_std_dev_minutes: f64,
+    #[serde(default)]
+    pub scheduled_date: Option<i64>,
+    #[serde(default)]
+    pub committed_window: Option<TimeWindow>,
+    /// How the committed window is read, overriding the solve-wide setting.
+    #[serde(default)]
+    pub committed_window_semantics: Option<WindowSemantics>,
+    /// Earliest start, e.g. once a part arrives.
+    #[serde(default)]
+    pub not_before: Option<i32>,
+    /// Hard deadline for finishing the visit, e.g. an SLA.
+    #[serde(default)]
+    pub latest_completion: Option<i32>,
+    #[serde(default)]
+    pub target_time: Option<i32>,
+    /// Per-second deviation penalty, overriding the solve-wide weight.
+    #[serde(default)]
+    pub target_time_weight: Option<i32>,
+    /// Seconds either side of the target time with no penalty.
+    #[serde(default)]
+    pub target_time_tolerance: i32,
+    #[serde(default = "default_pin_type")]
+    pub pin_type: VisitPinType,
+    #[serde(default)]
+    pub pinned_visitor: Option<String>,
+    #[serde(default)]
+    pub pinned_date: Option<i64>,
+    /// Position in the pinned visitor's locked route prefix.
+    #[serde(default)]
+    pub locked_sequence: Option<u32>,
+    /// First or last stop on whichever route serves the visit.
+    #[serde(default)]
+    pub route_position: Option<RoutePosition>,
+    #[serde(default)]
+    pub required_capabilities: Vec<String>,
+    #[serde(default)]
+    pub current_visitor_id: Option<String>,
+    /// Reassignment penalty, overriding the solve-wide one.
+    #[serde(default)]
+    pub reassignment_penalty: Option<i32>,
+    /// Caller-assigned site ID; visits sharing one share a matrix row.
+    #[serde(default)]
+    pub location_id: Option<u64>,
+    /// Visits sharing a group are served by one visitor.
+    #[serde(default)]
+    pub same_visitor_group: Option<String>,
+    /// Visits sharing a group are each served by a different visitor.
+    #[serde(default)]
+    pub different_visitor_group: Option<String>,
+    /// Another visit this one must start a bounded time after.
+    #[serde(default)]
+    pub start_gap: Option<VisitGap<String>>,
+    /// The plan is infeasible if this visit is left unassigned.
+    #[serde(default)]
+    pub must_assign: bool,
+    /// Series this visit is an occurrence of.
+    #[serde(default)]
+    pub recurrence: Option<Recurrence>,
+    /// Earliest and latest dates (inclusive) the visit may be routed on.
+    #[serde(default)]
+    pub date_window: Option<(i64, i64)>,
+    #[serde(default)]
+    pub allowed_dates: Option<Vec<i64>>,
+}
+
+fn default_pin_type() -> VisitPinType {
+    VisitPinType::None
+}
+
+impl VisitData {
+    /// Creates an unpinned visit with no windows or capability requirements.
+    pub fn new(id: impl Into<String>, location: (f64, f64), duration_minutes: i32) -> Self {
+        Self {
+            id: id.into(),
+            location,
+            duration_minutes,
+            duration_buffer_minutes: 0,
+            duration_std_dev_minutes: 0.0,
+            scheduled_date: None,
+            committed_window: None,
+            committed_window_semantics: None,
+            not_before: None,
+            latest_completion: None,
+            target_time: None,
+            target_time_weight: None,
+            target_time_tolerance: 0,
+            pin_type: VisitPinType::None,
+            pinned_visitor: None,
+            pinned_date: None,
+            locked_sequence: None,
+            route_position: None,
+            required_capabilities: Vec::new(),
+            current_visitor_id: None,
+            reassignment_penalty: None,
+            location_id: None,
+            same_visitor_group: None,
+            different_visitor_group: None,
+            start_gap: None,
+            must_assign: false,
+            recurrence: None,
+            date_window: None,
+            allowed_dates: None,
+        }
+    }
+
+    pub fn with_duration_buffer(mut self, minutes: i32) -> Self {
+        self.duration_buffer_minutes = minutes;
+        self
+    }
+
+    pub fn with_duration_std_dev(mut self, minutes: f64) -> Self {
+        self.duration_std_dev_minutes = minutes;
+        self
+    }
+
+    pub fn with_scheduled_date(mut self, date: i64) -> Self {
+        self.scheduled_date = Some(date);
+        self
+    }
+
+    pub fn with_committed_window(mut self, start: i32, end: i32) -> Self {
+        self.committed_window = Some((start, end));
+        self
+    }
+
+    /// Reads the committed window as an arrival or completion window.
+    pub fn with_committed_window_semantics(mut self, semantics: WindowSemantics) -> Self {
+        self.committed_window_semantics = Some(semantics);
+        self
+    }
+
+    pub fn with_not_before(mut self, release: i32) -> Self {
+        self.not_before = Some(release);
+        self
+    }
+
+    pub fn with_latest_completion(mut self, deadline: i32) -> Self {
+        self.latest_completion = Some(deadline);
+        self
+    }
+
+    pub fn with_target_time(mut self, time: i32) -> Self {
+        self.target_time = Some(time);
+        self
+    }
+
+    pub fn with_target_time_weight(mut self, weight: i32) -> Self {
+        self.target_time_weight = Some(weight);
+        self
+    }
+
+    /// Accepts any start within `secs` of the target time without penalty.
+    pub fn with_target_time_tolerance(mut self, secs: i32) -> Self {
+        self.target_time_tolerance = secs;
+        self
+    }
+
+    /// Pins the visit to a visitor (keeping any date pin).
+    pub fn with_pinned_visitor(mut self, visitor_id: impl Into<String>) -> Self {
+        self.pinned_visitor = Some(visitor_id.into());
+        self.pin_type = match self.pin_type {
+            VisitPinType::Date | VisitPinType::VisitorAndDate => VisitPinType::VisitorAndDate,
+            VisitPinType::None | VisitPinType::Visitor => VisitPinType::Visitor,
+        };
+        self
+    }
+
+    /// Pins the visit to a visitor at `sequence` in their locked route prefix.
+    pub fn with_locked_sequence(mut self, visitor_id: impl Into<String>, sequence: u32) -> Self {
+        self.locked_sequence = Some(sequence);
+        self.with_pinned_visitor(visitor_id)
+    }
+
+    pub fn with_route_position(mut self, position: RoutePosition) -> Self {
+        self.route_position = Some(position);
+        self
+    }
+
+    /// Pins the visit to a date (keeping any visitor pin).
+    pub fn with_pinned_date(mut self, date: i64) -> Self {
+        self.pinned_date = Some(date);
+        self.pin_type = match self.pin_type {
+            VisitPinType::Visitor | VisitPinType::VisitorAndDate => VisitPinType::VisitorAndDate,
+            VisitPinType::None | VisitPinType::Date => VisitPinType::Date,
+        };
+        self
+    }
+
+    pub fn with_required_capability(mut self, capability: impl Into<String>) -> Self {
+        self.required_capabilities.push(capability.into());
+        self
+    }
+
+    pub fn with_current_visitor(mut self, visitor_id: impl Into<String>) -> Self {
+        self.current_visitor_id = Some(visitor_id.into());
+        self
+    }
+
+    pub fn with_reassignment_penalty(mut self, penalty: i32) -> Self {
+        self.reassignment_penalty = Some(penalty);
+        self
+    }
+
+    pub fn with_location_id(mut self, location_id: u64) -> Self {
+        self.location_id = Some(location_id);
+        self
+    }
+
+    pub fn with_same_visitor_group(mut self, group: impl Into<String>) -> Self {
+        self.same_visitor_group = Some(group.into());
+        self
+    }
+
+    pub fn with_different_visitor_group(mut self, group: impl Into<String>) -> Self {
+        self.different_visitor_group = Some(group.into());
+        self
+    }
+
+    /// Starts this visit between `min_secs` and `max_secs` after visit `after` ends.
+    pub fn with_start_gap(mut self, after: impl Into<String>, min_secs: i32, max_secs: i32) -> Self {
+        self.start_gap = Some(VisitGap { after: after.into(), min_secs, max_secs });
+        self
+    }
+
+    pub fn with_must_assign(mut self) -> Self {
+        self.must_assign = true;
+        self
+    }
+
+    pub fn with_recurrence(mut self, recurrence: Recurrence) -> Self {
+        self.recurrence = Some(recurrence);
+        self
+    }
+
+    pub fn with_date_window(mut self, earliest: i64, latest: i64) -> Self {
+        self.date_window = Some((earliest, latest));
+        self
+    }
+
+    pub fn with_allowed_dates(mut self, dates: impl IntoIterator<Item = i64>) -> Self {
+        self.allowed_dates = Some(dates.into_iter().collect());
+        self
+    }
+}
+
+impl Visit for VisitData {
+    type Id = String;
+    type VisitorId = String;
+
+    fn id(&self) -> &Self::Id {
+        &self.id
+    }
+
+    fn scheduled_date(&self) -> Option<i64> {
+        self.scheduled_date
+    }
+
+    fn estimated_duration_minutes(&self) -> i32 {
+        self.duration_minutes
+    }
+
+    fn duration_buffer_minutes(&self) -> i32 {
+        self.duration_buffer_minutes
+    }
+
+    fn duration_std_dev_minutes(&self) -> f64 {
+        self.duration_std_dev_minutes
+    }
+
+    fn committed_window(&self) -> Option<(i32, i32)> {
+        self.committed_window
+    }
+
+    fn committed_window_semantics(&self) -> Option<WindowSemantics> {
+        self.committed_window_semantics
+    }
+
+    fn not_before(&self) -> Option<i32> {
+        self.not_before
+    }
+
+    fn latest_completion(&self) -> Option<i32> {
+        self.latest_completion
+    }
+
+    fn target_time(&self) -> Option<i32> {
+        self.target_time
+    }
+
+    fn target_time_weight(&self) -> Option<i32> {
+        self.target_time_weight
+    }
+
+    fn target_time_tolerance(&self) -> i32 {
+        self.target_time_tolerance
+    }
+
+    fn pin_type(&self) -> VisitPinType {
+        self.pin_type
+    }
+
+    fn pinned_visitor(&self) -> Option<&Self::VisitorId> {
+        self.pinned_visitor.as_ref()
+    }
+
+    fn pinned_date(&self) -> Option<i64> {
+        self.pinned_date
+    }
+
+    fn locked_sequence(&self) -> Option<u32> {
+        self.locked_sequence
+    }
+
+    fn route_position(&self) -> Option<RoutePosition> {
+        self.route_position
+    }
+
+    fn required_capabilities(&self) -> &[String] {
+        &self.required_capabilities
+    }
+
+    fn location(&self) -> (f64, f64) {
+        self.location
+    }
+
+    fn current_visitor_id(&self) -> Option<&Self::VisitorId> {
+        self.current_visitor_id.as_ref()
+    }
+
+    fn reassignment_penalty(&self) -> Option<i32> {
+        self.reassignment_penalty
+    }
+
+    fn location_ref(&self) -> LocationRef {
+        self.location_id
+            .map(LocationRef::Id)
+            .unwrap_or_else(|| LocationRef::from_coordinates(self.location))
+    }
+
+    fn same_visitor_group(&self) -> Option<&str> {
+        self.same_visitor_group.as_deref()
+    }
+
+    fn different_visitor_group(&self) -> Option<&str> {
+        self.different_visitor_group.as_deref()
+    }
+
+    fn start_gap(&self) -> Option<&VisitGap<String>> {
+        self.start_gap.as_ref()
+    }
+
+    fn must_assign(&self) -> bool {
+        self.must_assign
+    }
+
+    fn recurrence(&self) -> Option<&Recurrence> {
+        self.recurrence.as_ref()
+    }
+
+    fn date_window(&self) -> Option<(i64, i64)> {
+        self.date_window
+    }
+
+    fn allowed_dates(&self) -> Option<&[i64]> {
+        self.allowed_dates.as_deref()
+    }
+}
+
+/// A visitor as plain data.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VisitorData {
+    pub id: String,
+    #[serde(default)]
+    pub start_location: Option<(f64, f64)>,
+    #[serde(default)]
+    pub end_location: Option<(f64, f64)>,
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    /// Cost per hour of working time.
+    #[serde(default)]
+    pub hourly_cost: f64,
+    /// Cost per kilometer driven.
+    #[serde(default)]
+    pub per_km_cost: f64,
+    /// Daily visit cap, overriding the solve-wide one.
+    #[serde(default)]
+    pub max_visits: Option<usize>,
+    /// Earliest and latest arrival at the end location.
+    #[serde(default)]
+    pub return_window: Option<TimeWindow>,
+    /// Routing profile, e.g. `bicycle`; the matrix provider's default if unset.
+    #[serde(default)]
+    pub profile: Option<String>,
+}
+
+impl VisitorData {
+    /// Creates a visitor with no start/end location or capabilities.
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            start_location: None,
+            end_location: None,
+            capabilities: Vec::new(),
+            hourly_cost: 0.0,
+            per_km_cost: 0.0,
+            max_visits: None,
+            return_window: None,
+            profile: None,
+        }
+    }
+
+    pub fn with_start_location(mut self, lat: f64, lng: f64) -> Self {
+        self.start_location = Some((lat, lng));
+        self
+    }
+
+    pub fn with_end_location(mut self, lat: f64, lng: f64) -> Self {
+        self.end_location = Some((lat, lng));
+        self
+    }
+
+    pub fn with_capability(mut self, capability: impl Into<String>) -> Self {
+        self.capabilities.push(capability.into());
+        self
+    }
+
+    pub fn with_costs(mut self, hourly_cost: f64, per_km_cost: f64) -> Self {
+        self.hourly_cost = hourly_cost;
+        self.per_km_cost = per_km_cost;
+        self
+    }
+
+    pub fn with_max_visits(mut self, max_visits: usize) -> Self {
+        self.max_visits = Some(max_visits);
+        self
+    }
+
+    pub fn with_return_window(mut self, earliest: i32, latest: i32) -> Self {
+        self.return_window = Some((earliest, latest));
+        self
+    }
+
+    pub fn with_profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = Some(profile.into());
+        self
+    }
+}
+
+impl Visitor for VisitorData {
+    type Id = String;
+
+    fn id(&self) -> &Self::Id {
+        &self.id
+    }
+
+    fn start_location(&self) -> Option<(f64, f64)> {
+        self.start_location
+    }
+
+    fn end_location(&self) -> Option<(f64, f64)> {
+        self.end_location
+    }
+
+    fn capabilities(&self) -> &[String] {
+        &self.capabilities
+    }
+
+    fn hourly_cost(&self) -> f64 {
+        self.hourly_cost
+    }
+
+    fn per_km_cost(&self) -> f64 {
+        self.per_km_cost
+    }
+
+    fn max_visits(&self) -> Option<usize> {
+        self.max_visits
+    }
+
+    fn return_window(&self) -> Option<TimeWindow> {
+        self.return_window
+    }
+
+    fn routing_profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+}
+
+/// Availability as plain data: a default set of windows plus per-visitor overrides.
+///
+/// An override of `None` marks the visitor unavailable. Dates are ignored;
+/// build one `AvailabilityData` per service date.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AvailabilityData {
+    #[serde(default)]
+    pub default_windows: Option<Vec<TimeWindow>>,
+    #[serde(default)]
+    pub visitors: HashMap<String, Option<Vec<TimeWindow>>>,
+}
+
+impl AvailabilityData {
+    /// Every visitor is available for the given windows unless overridden.
+    pub fn new(default_windows: Vec<TimeWindow>) -> Self {
+        Self {
+            default_windows: Some(default_windows),
+            visitors: HashMap::new(),
+        }
+    }
+
+    pub fn with_visitor_windows(mut self, visitor_id: impl Into<String>, windows: Vec<TimeWindow>) -> Self {
+        self.visitors.insert(visitor_id.into(), Some(windows));
+        self
+    }
+
+    pub fn with_visitor_unavailable(mut self, visitor_id: impl Into<String>) -> Self {
+        self.visitors.insert(visitor_id.into(), None);
+        self
+    }
+}
+
+impl AvailabilityProvider for AvailabilityData {
+    type VisitorId = String;
+
+    fn availability_for(&self, visitor_id: &Self::VisitorId, _date: i64) -> Option<Vec<TimeWindow>> {
+        let windows = match self.visitors.get(visitor_id) {
+            Some(windows) => windows.clone(),
+            None => self.default_windows.clone(),
+        };
+        windows.filter(|windows| !windows.is_empty())
+    }
+}
+
+impl AsyncAvailabilityProvider for AvailabilityData {
+    type VisitorId = String;
+
+    fn fetch_availability(
+        &self,
+        visitor_id: &Self::VisitorId,
+        date: i64,
+    ) -> impl Future<Output = Option<Vec<TimeWindow>>> + Send {
+        std::future::ready(self.availability_for(visitor_id, date))
+    }
+}
+
+/// A complete day to plan, as read from a JSON file or request body.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Problem {
+    /// Service date (unix timestamp, date only).
+    pub service_date: i64,
+    pub visits: Vec<VisitData>,
+    pub visitors: Vec<VisitorData>,
+    pub availability: AvailabilityData,
+    /// Solver options; omitted fields take their defaults.
+    #[serde(default)]
+    pub options: SolveOptions,
+    /// Zone the service date is in; UTC if omitted. Times stay seconds since
+    /// local midnight, which on a DST day is not the wall-clock time.
+    #[serde(default)]
+    pub time_zone: Option<TimeZone>,
+}
+
+impl Problem {
+    /// The service date in the problem's time zone.
+    pub fn planning_day(&self) -> PlanningDay {
+        PlanningDay::new(self.service_date, self.time_zone.unwrap_or_default())
+    }
+
+    /// Solves the problem with the given matrix provider and its own options,
+    /// failing with [`SolveError::InvalidOptions`] if those don't validate.
+    pub fn solve<M: DistanceMatrixProvider>(&self, matrix_provider: &M) -> SolveResult<String, String> {
+        self.options.validate().map_err(SolveError::InvalidOptions)?;
+        solve(
+            self.service_date,
+            &self.visits,
+            &self.visitors,
+            &self.availability,
+            matrix_provider,
+            self.options.clone(),
+        )
+    }
+
+    /// Input mistakes that would make the solve misbehave or drop visits for
+    /// reasons unrelated to routing. Empty if the problem looks well-formed.
+    pub fn validate(&self) -> Vec<ProblemIssue> {
+        let mut issues = Vec::new();
+
+        let mut visitor_ids = HashSet::new();
+        for visitor in &self.visitors {
+            if !visitor_ids.insert(visitor.id.as_str()) {
+                issues.push(ProblemIssue::DuplicateVisitorId { visitor_id: visitor.id.clone() });
+            }
+            if visitor.return_window.is_some_and(|(earliest, latest)| earliest > latest) {
+                issues.push(ProblemIssue::InvertedReturnWindow { visitor_id: visitor.id.clone() });
+            }
+            if !visitor.start_location.into_iter().chain(visitor.end_location).all(is_valid_location) {
+                issues.push(ProblemIssue::InvalidVisitorLocation { visitor_id: visitor.id.clone() });
+            }
+        }
+
+        let mut visit_ids = HashSet::new();
+        let mut locked = HashSet::new();
+        for visit in &self.visits {
+            if !visit_ids.insert(visit.id.as_str()) {
+                issues.push(ProblemIssue::DuplicateVisitId { visit_id: visit.id.clone() });
+            }
+            if !is_valid_location(visit.location) {
+                issues.push(ProblemIssue::InvalidVisitLocation { visit_id: visit.id.clone() });
+            }
+            if visit.duration_minutes <= 0 {
+                issues.push(ProblemIssue::NonPositiveDuration { visit_id: visit.id.clone() });
+            }
+            let std_dev = visit.duration_std_dev_minutes;
+            if visit.duration_buffer_minutes < 0 || !std_dev.is_finite() || std_dev < 0.0 {
+                issues.push(ProblemIssue::InvalidDurationUncertainty { visit_id: visit.id.clone() });
+            }
+            if visit.committed_window.is_some_and(|(start, end)| start > end) {
+                issues.push(ProblemIssue::InvertedCommittedWindow { visit_id: visit.id.clone() });
+            } else if let Some((start, end)) = service_bounds(visit, self.options.committed_window_semantics)
+                && end - start < visit.duration_minutes * 60
+            {
+                issues.push(ProblemIssue::CommittedWindowTooShort { visit_id: visit.id.clone() });
+            }
+            if visit.target_time_weight.is_some_and(|weight| weight < 0) || visit.target_time_tolerance < 0 {
+                issues.push(ProblemIssue::NegativeTargetTimePreference { visit_id: visit.id.clone() });
+            }
+            if visit.reassignment_penalty.is_some_and(|penalty| penalty < 0) {
+                issues.push(ProblemIssue::NegativeReassignmentPenalty { visit_id: visit.id.clone() });
+            }
+            if let (Some(visitor_id), Some(sequence)) = (&visit.pinned_visitor, visit.locked_sequence)
+                && !locked.insert((visitor_id.as_str(), sequence))
+            {
+                issues.push(ProblemIssue::DuplicateLockedSequence { visitor_id: visitor_id.clone(), sequence });
+            }
+            if let Some(visitor_id) = &visit.pinned_visitor
+                && !visitor_ids.contains(visitor_id.as_str())
+            {
+                issues.push(ProblemIssue::UnknownPinnedVisitor {
+                    visit_id: visit.id.clone(),
+                    visitor_id: visitor_id.clone(),
+                });
+            }
+        }
+        for visit in &self.visits {
+            if let Some(gap) = &visit.start_gap
+                && (gap.min_secs > gap.max_secs || gap.after == visit.id || !visit_ids.contains(gap.after.as_str()))
+            {
+                issues.push(ProblemIssue::InvalidStartGap { visit_id: visit.id.clone() });
+            }
+        }
+
+        let windows = self
+            .availability
+            .default_windows
+            .iter()
+            .map(|windows| (None, windows))
+            .chain(self.availability.visitors.iter().filter_map(|(id, windows)| Some((Some(id), windows.as_ref()?))));
+        for (visitor_id, windows) in windows {
+            if windows.iter().any(|(start, end)| start >= end) {
+                issues.push(ProblemIssue::EmptyAvailabilityWindow { visitor_id: visitor_id.cloned() });
+            }
+        }
+
+        if let Err(err) = self.options.validate() {
+            issues.push(ProblemIssue::InvalidOptions { reason: err.to_string() });
+        }
+
+        issues
+    }
+}
+
+/// A problem found by [`Problem::validate`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProblemIssue {
+    DuplicateVisitId { visit_id: String },
+    DuplicateVisitorId { visitor_id: String },
+    /// The visit's coordinates aren't finite or are off the globe.
+    InvalidVisitLocation { visit_id: String },
+    /// The visitor's start or end coordinates aren't finite or are off the
+    /// globe.
+    InvalidVisitorLocation { visitor_id: String },
+    NonPositiveDuration { visit_id: String },
+    /// The visit's duration buffer or standard deviation is negative, or the
+    /// standard deviation isn't finite.
+    InvalidDurationUncertainty { visit_id: String },
+    /// The committed window ends before it starts.
+    InvertedCommittedWindow { visit_id: String },
+    /// The committed window, narrowed to the visit's release time and
+    /// deadline, can't hold the visit's duration.
+    CommittedWindowTooShort { visit_id: String },
+    /// The visit's target-time weight or tolerance is negative.
+    NegativeTargetTimePreference { visit_id: String },
+    /// The visit's reassignment penalty override is negative.
+    NegativeReassignmentPenalty { visit_id: String },
+    /// Two visits hold the same place in a visitor's locked route prefix.
+    DuplicateLockedSequence { visitor_id: String, sequence: u32 },
+    /// The visit is pinned to a visitor who isn't in the problem.
+    UnknownPinnedVisitor { visit_id: String, visitor_id: String },
+    /// An availability window ends at or before it starts; `None` for the
+    /// default windows.
+    EmptyAvailabilityWindow { visitor_id: Option<String> },
+    /// The visitor's return window ends before it starts.
+    InvertedReturnWindow { visitor_id: String },
+    /// The visit's start gap follows an unknown visit or itself, or its
+    /// minimum exceeds its maximum.
+    InvalidStartGap { visit_id: String },
+    /// The solver options are out of range (see [`SolveOptions::validate`]).
+    InvalidOptions { reason: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::haversine::HaversineMatrix;
+    use crate::solver::{solve_async, InvalidOptions};
+    use crate::traits::UnassignedReason;
+
+    #[test]
+    fn test_pin_builders_combine() {
+        let visit = VisitData::new("v1", (36.1, -115.1), 30)
+            .with_pinned_visitor("alice")
+            .with_pinned_date(1);
+        assert_eq!(visit.pin_type(), VisitPinType::VisitorAndDate);
+        assert_eq!(visit.pinned_visitor(), Some(&"alice".to_string()));
+        assert_eq!(visit.pinned_date(), Some(1));
+    }
+
+    #[test]
+    fn test_availability_overrides() {
+        let availability = AvailabilityData::new(vec![(8 * 3600, 17 * 3600)])
+            .with_visitor_unavailable("bob")
+            .with_visitor_windows("carol", vec![(8 * 3600, 12 * 3600)]);
+
+        assert_eq!(availability.availability_for(&"alice".to_string(), 1), Some(vec![(8 * 3600, 17 * 3600)]));
+        assert_eq!(availability.availability_for(&"bob".to_string(), 1), None);
+        assert_eq!(availability.availability_for(&"carol".to_string(), 1), Some(vec![(8 * 3600, 12 * 3600)]));
+    }
+
+    #[test]
+    fn test_solve_with_plain_data() {
+        let visits = vec![
+            VisitData::new("v1", (36.11, -115.17), 30),
+            VisitData::new("v2", (36.12, -115.16), 30).with_pinned_visitor("alice"),
+        ];
+        let visitors = vec![VisitorData::new("alice").with_start_location(36.10, -115.15)];
+        let availability = AvailabilityData::new(vec![(8 * 3600, 17 * 3600)]);
+
+        let result = solve(1, &visits, &visitors, &availability, &HaversineMatrix::default(), SolveOptions::default()).unwrap();
+
+        assert!(result.unassigned.is_empty());
+        assert_eq!(result.routes[0].visit_ids.len(), 2);
+    }
+
+    #[test]
+    fn test_solve_async_matches_solve() {
+        let visits = vec![
+            VisitData::new("v1", (36.11, -115.17), 30),
+            VisitData::new("v2", (36.12, -115.16), 30),
+        ];
+        let visitors = vec![VisitorData::new("alice").with_start_location(36.10, -115.15)];
+        let availability = AvailabilityData::new(vec![(8 * 3600, 17 * 3600)]);
+
+        let matrix = HaversineMatrix::default();
+        let future = solve_async(1, &visits, &visitors, &availability, &matrix, SolveOptions::default());
+        // Providers here resolve immediately, so a single poll completes the solve.
+        let mut future = std::pin::pin!(future);
+        let mut context = std::task::Context::from_waker(std::task::Waker::noop());
+        let std::task::Poll::Ready(async_result) = future.as_mut().poll(&mut context) else {
+            panic!("solve_async should complete with ready providers");
+        };
+        let async_result = async_result.unwrap();
+
+        let sync_result = solve(1, &visits, &visitors, &availability, &matrix, SolveOptions::default()).unwrap();
+        assert_eq!(async_result.routes[0].visit_ids, sync_result.routes[0].visit_ids);
+        assert_eq!(async_result.routes[0].estimated_windows, sync_result.routes[0].estimated_windows);
+    }
+
+    #[test]
+    fn test_recurring_visit_only_routed_on_allowed_dates() {
+        let tuesday = 1_704_153_600;
+        let visits = vec![
+            VisitData::new("weekly", (36.11, -115.17), 30)
+                .with_recurrence(Recurrence::weekly(tuesday).with_tolerance_days(1)),
+        ];
+        let visitors = vec![VisitorData::new("alice").with_start_location(36.10, -115.15)];
+        let availability = AvailabilityData::new(vec![(8 * 3600, 17 * 3600)]);
+        let matrix = HaversineMatrix::default();
+
+        let wednesday = solve(tuesday + 8 * 86_400, &visits, &visitors, &availability, &matrix, SolveOptions::default()).unwrap();
+        assert!(wednesday.unassigned.is_empty());
+
+        let friday = solve(tuesday + 10 * 86_400, &visits, &visitors, &availability, &matrix, SolveOptions::default()).unwrap();
+        assert_eq!(friday.unassigned[0].reason, UnassignedReason::WrongDate);
+    }
+
+    #[test]
+    fn test_recurring_visit_prefers_its_nominal_date() {
+        let tuesday = 1_704_153_600;
+        let monday = tuesday + 6 * 86_400;
+        // Alice only has time for one of them
+        let visits = vec![
+            VisitData::new("tuesdays", (36.11, -115.17), 45)
+                .with_recurrence(Recurrence::weekly(tuesday).with_tolerance_days(1)),
+            VisitData::new("mondays", (36.12, -115.16), 45).with_recurrence(Recurrence::weekly(monday)),
+        ];
+        let visitors = vec![VisitorData::new("alice").with_start_location(36.10, -115.15)];
+        let availability = AvailabilityData::new(vec![(8 * 3600, 9 * 3600)]);
+        let matrix = HaversineMatrix::default();
+        let routed_on = |date: i64, options: SolveOptions| {
+            let result = solve(date, &visits, &visitors, &availability, &matrix, options).unwrap();
+            result.routes[0].visit_ids.clone()
+        };
+
+        assert_eq!(routed_on(monday, SolveOptions::default()), ["mondays"]);
+        assert_eq!(routed_on(monday + 86_400, SolveOptions::default()), ["tuesdays"]);
+        // Without the drift cost, input order decides
+        let no_drift_cost = SolveOptions::builder().with_recurrence_drift_weight(0).build().unwrap();
+        assert_eq!(routed_on(monday, no_drift_cost), ["tuesdays"]);
+    }
+
+    #[test]
+    fn test_date_bounds_for_unpinned_visits() {
+        let visits = vec![
+            VisitData::new("window", (36.11, -115.17), 30).with_date_window(2 * 86_400, 4 * 86_400),
+            VisitData::new("mon-thu", (36.12, -115.16), 30).with_allowed_dates([86_400, 4 * 86_400]),
+        ];
+        let visitors = vec![VisitorData::new("alice").with_start_location(36.10, -115.15)];
+        let availability = AvailabilityData::new(vec![(8 * 3600, 17 * 3600)]);
+        let matrix = HaversineMatrix::default();
+
+        let unassigned_on = |date: i64| -> Vec<String> {
+            let result = solve(date, &visits, &visitors, &availability, &matrix, SolveOptions::default()).unwrap();
+            assert!(result.unassigned.iter().all(|visit| visit.reason == UnassignedReason::WrongDate));
+            result.unassigned.into_iter().map(|visit| visit.visit_id).collect()
+        };
+
+        assert_eq!(unassigned_on(86_400), vec!["window"]);
+        assert_eq!(unassigned_on(3 * 86_400), vec!["mon-thu"]);
+        assert!(unassigned_on(4 * 86_400).is_empty());
+        assert_eq!(unassigned_on(5 * 86_400).len(), 2);
+    }
+
+    #[test]
+    fn test_validate_reports_input_mistakes() {
+        let problem = Problem {
+            service_date: 86_400,
+            visits: vec![
+                VisitData::new("v1", (36.11, -115.17), 30).with_committed_window(9 * 3600, 9 * 3600 + 600),
+                VisitData::new("v1", (36.12, -115.16), 0).with_pinned_visitor("zoe"),
+            ],
+            visitors: vec![VisitorData::new("alice").with_return_window(17 * 3600, 16 * 3600)],
+            availability: AvailabilityData::new(vec![(8 * 3600, 17 * 3600)])
+                .with_visitor_windows("alice", vec![(12 * 3600, 12 * 3600)]),
+            options: SolveOptions::default(),
+            time_zone: None,
+        };
+
+        assert_eq!(
+            problem.validate(),
+            vec![
+                ProblemIssue::InvertedReturnWindow { visitor_id: "alice".to_string() },
+                ProblemIssue::CommittedWindowTooShort { visit_id: "v1".to_string() },
+                ProblemIssue::DuplicateVisitId { visit_id: "v1".to_string() },
+                ProblemIssue::NonPositiveDuration { visit_id: "v1".to_string() },
+                ProblemIssue::UnknownPinnedVisitor { visit_id: "v1".to_string(), visitor_id: "zoe".to_string() },
+                ProblemIssue::EmptyAvailabilityWindow { visitor_id: Some("alice".to_string()) },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_reports_bad_coordinates_and_inverted_windows() {
+        let problem = Problem {
+            service_date: 86_400,
+            visits: vec![
+                VisitData::new("nan", (f64::NAN, -115.17), 30),
+                VisitData::new("inverted", (36.11, -115.17), 30).with_committed_window(11 * 3600, 10 * 3600),
+            ],
+            visitors: vec![VisitorData::new("alice").with_start_location(36.10, 215.0)],
+            availability: AvailabilityData::new(vec![(8 * 3600, 17 * 3600)]),
+            options: SolveOptions::default(),
+            time_zone: None,
+        };
+
+        assert_eq!(
+            problem.validate(),
+            vec![
+                ProblemIssue::InvalidVisitorLocation { visitor_id: "alice".to_string() },
+                ProblemIssue::InvalidVisitLocation { visit_id: "nan".to_string() },
+                ProblemIssue::InvertedCommittedWindow { visit_id: "inverted".to_string() },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_reports_invalid_start_gap() {
+        let problem = Problem {
+            service_date: 86_400,
+            visits: vec![
+                VisitData::new("prime", (36.11, -115.17), 60),
+                VisitData::new("coat", (36.11, -115.17), 60).with_start_gap("prime", 3600, 10_800),
+                VisitData::new("backwards", (36.11, -115.17), 60).with_start_gap("prime", 7200, 3600),
+                VisitData::new("orphan", (36.11, -115.17), 60).with_start_gap("missing", 0, 3600),
+            ],
+            visitors: vec![VisitorData::new("alice")],
+            availability: AvailabilityData::new(vec![(8 * 3600, 17 * 3600)]),
+            options: SolveOptions::default(),
+            time_zone: None,
+        };
+
+        assert_eq!(
+            problem.validate(),
+            vec![
+                ProblemIssue::InvalidStartGap { visit_id: "backwards".to_string() },
+                ProblemIssue::InvalidStartGap { visit_id: "orphan".to_string() },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_reports_duplicate_locked_sequence() {
+        let problem = Problem {
+            service_date: 86_400,
+            visits: vec![
+                VisitData::new("v1", (36.11, -115.17), 30).with_locked_sequence("alice", 0),
+                VisitData::new("v2", (36.12, -115.16), 30).with_locked_sequence("alice", 0),
+                VisitData::new("v3", (36.13, -115.15), 30).with_locked_sequence("bob", 0),
+            ],
+            visitors: vec![VisitorData::new("alice"), VisitorData::new("bob")],
+            availability: AvailabilityData::new(vec![(8 * 3600, 17 * 3600)]),
+            options: SolveOptions::default(),
+            time_zone: None,
+        };
+
+        assert_eq!(
+            problem.validate(),
+            vec![ProblemIssue::DuplicateLockedSequence { visitor_id: "alice".to_string(), sequence: 0 }]
+        );
+    }
+
+    #[test]
+    fn test_invalid_options_are_reported_and_rejected() {
+        let problem: Problem = serde_json::from_value(serde_json::json!({
+            "service_date": 86_400,
+            "visits": [],
+            "visitors": [],
+            "availability": {},
+            "options": { "target_time_weight": -5 },
+        }))
+        .expect("parse problem");
+
+        assert_eq!(
+            problem.validate(),
+            vec![ProblemIssue::InvalidOptions { reason: "target_time_weight must not be negative".to_string() }]
+        );
+        assert_eq!(
+            problem.solve(&HaversineMatrix::default()).unwrap_err(),
+            SolveError::InvalidOptions(InvalidOptions::Negative("target_time_weight"))
+        );
+    }
+
+    #[test]
+    fn test_problem_from_json_uses_default_options() {
+        let problem: Problem = serde_json::from_value(serde_json::json!({
+            "service_date": 86_400,
+            "visits": [VisitData::new("v1", (36.11, -115.17), 30)],
+            "visitors": [VisitorData::new("alice").with_start_location(36.10, -115.15)],
+            "availability": { "default_windows": [[8 * 3600, 17 * 3600]] },
+        }))
+        .expect("parse problem");
+
+        assert_eq!(problem.options.local_search_iterations, SolveOptions::default().local_search_iterations);
+        let result = problem.solve(&HaversineMatrix::default()).unwrap();
+        assert_eq!(result.routes[0].visit_ids, vec!["v1"]);
+    }
+
+    #[test]
+    fn test_visit_from_minimal_json() {
+        let visit: VisitData = serde_json::from_str(
+            r#"{"id": "v1", "location": [36.1, -115.1], "duration_minutes": 45}"#,
+        )
+        .expect("parse visit");
+        assert_eq!(visit, VisitData::new("v1", (36.1, -115.1), 45));
+    }
+}
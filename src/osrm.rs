@@ -1,14 +1,143 @@
 //! OSRM HTTP adapter for distance matrices and route geometry.
 
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
+use tracing::field::Empty;
+use tracing::{debug, info_span, warn, Instrument, Span};
 
-use crate::traits::DistanceMatrixProvider;
+use crate::polyline::Polyline;
+use crate::telemetry;
+use crate::matrix::metered::{decode_body, is_gzipped, ByteCounter};
+use crate::traits::{
+    AsyncDistanceMatrixProvider, DistanceMatrixProvider, TableSubset, TravelMatrices, UNREACHABLE_METERS,
+    UNREACHABLE_SECS,
+};
 
 #[derive(Debug, Clone)]
 pub struct OsrmConfig {
     pub base_url: String,
     pub profile: String,
     pub timeout_secs: u64,
+    /// Retries after the first attempt for transient failures (timeouts,
+    /// connection errors, HTTP 5xx and 429).
+    pub max_retries: u32,
+    /// Delay before the first retry; doubles on each subsequent retry.
+    pub initial_backoff_ms: u64,
+    /// Upper bound on the delay between retries.
+    pub max_backoff_ms: u64,
+    /// Routing parameters sent with every table and route request.
+    pub request: OsrmRequestOptions,
+    /// Idle keep-alive connections kept open to the server.
+    pub pool_max_idle_per_host: usize,
+    /// How long an idle connection stays in the pool; `None` keeps it until
+    /// the server closes it.
+    pub pool_idle_timeout_secs: Option<u64>,
+    /// Interval of TCP keep-alive probes on open connections, so load
+    /// balancers don't silently drop idle ones.
+    pub tcp_keepalive_secs: Option<u64>,
+    /// Ask for gzip-compressed responses; tables shrink several-fold.
+    pub gzip: bool,
+    /// Credentials for an OSRM server behind an authenticating gateway.
+    pub auth: Option<OsrmAuth>,
+    /// Extra headers sent with every request, as (name, value).
+    pub headers: Vec<(String, String)>,
+    /// How route geometries are requested and returned.
+    pub geometry_format: GeometryFormat,
+}
+
+/// OSRM `geometries` value for route requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GeometryFormat {
+    /// Precision-5 polylines, about 1 m.
+    #[default]
+    Polyline,
+    /// Precision-6 polylines, about 10 cm, for street-level maps.
+    Polyline6,
+    /// GeoJSON coordinates, returned already decoded in
+    /// [`RouteGeometry::points`] and [`LegGeometry::points`], with the encoded
+    /// polylines left empty.
+    GeoJson,
+}
+
+impl GeometryFormat {
+    fn param(self) -> &'static str {
+        match self {
+            GeometryFormat::Polyline => "polyline",
+            GeometryFormat::Polyline6 => "polyline6",
+            GeometryFormat::GeoJson => "geojson",
+        }
+    }
+}
+
+/// How requests authenticate to the OSRM server. `Debug` hides secrets.
+#[derive(Clone, PartialEq, Eq)]
+pub enum OsrmAuth {
+    /// `Authorization: Bearer <token>`.
+    Bearer(String),
+    /// HTTP basic auth.
+    Basic { username: String, password: Option<String> },
+    /// An API key in a named header, e.g. `x-api-key`.
+    ApiKey { header: String, key: String },
+}
+
+impl std::fmt::Debug for OsrmAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OsrmAuth::Bearer(_) => f.write_str("Bearer(..)"),
+            OsrmAuth::Basic { username, .. } => write!(f, "Basic {{ username: {username:?}, .. }}"),
+            OsrmAuth::ApiKey { header, .. } => write!(f, "ApiKey {{ header: {header:?}, .. }}"),
+        }
+    }
+}
+
+/// Routing parameters passed through to OSRM table and route requests.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OsrmRequestOptions {
+    /// Road classes to avoid, e.g. `["toll", "ferry"]`. The classes must be
+    /// defined by the server's profile.
+    pub exclude: Vec<String>,
+    /// Side of the road to approach every location from.
+    pub approach: Option<Approach>,
+    /// Departure time (unix seconds), sent as `depart=` for servers with
+    /// time-dependent traffic data. Stock OSRM rejects unknown parameters,
+    /// so leave unset unless the server supports it.
+    pub departure_time: Option<i64>,
+}
+
+/// OSRM `approaches` value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Approach {
+    /// Arrive on either side of the road.
+    Unrestricted,
+    /// Arrive on the curb side (right side in right-hand traffic).
+    Curb,
+}
+
+impl OsrmRequestOptions {
+    /// Query string suffix (starting with `&`, or empty) for `n` coordinates.
+    fn query(&self, n: usize) -> String {
+        let mut query = String::new();
+        if !self.exclude.is_empty() {
+            query.push_str(&format!("&exclude={}", self.exclude.join(",")));
+        }
+        if let Some(approach) = self.approach {
+            let value = match approach {
+                Approach::Unrestricted => "unrestricted",
+                Approach::Curb => "curb",
+            };
+            query.push_str(&format!("&approaches={}", vec![value; n].join(";")));
+        }
+        if let Some(departure_time) = self.departure_time {
+            query.push_str(&format!("&depart={}", departure_time));
+        }
+        query
+    }
 }
 
 impl Default for OsrmConfig {
@@ -17,15 +146,58 @@ impl Default for OsrmConfig {
             base_url: "http://localhost:5000".to_string(),
             profile: "car".to_string(),
             timeout_secs: 10,
+            max_retries: 3,
+            initial_backoff_ms: 200,
+            max_backoff_ms: 2_000,
+            request: OsrmRequestOptions::default(),
+            pool_max_idle_per_host: 32,
+            pool_idle_timeout_secs: Some(90),
+            tcp_keepalive_secs: Some(60),
+            gzip: true,
+            auth: None,
+            headers: Vec::new(),
+            geometry_format: GeometryFormat::Polyline,
         }
     }
 }
 
+impl OsrmConfig {
+    /// Delay before retry number `retry` (0-based), with exponential backoff.
+    fn backoff(&self, retry: u32) -> Duration {
+        let delay_ms = self.initial_backoff_ms.saturating_mul(1u64 << retry.min(32));
+        Duration::from_millis(delay_ms.min(self.max_backoff_ms))
+    }
+}
+
+/// A location snapped to the nearest road by OSRM's `/nearest` service.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SnappedLocation {
+    /// Snapped (lat, lng) on the road network
+    pub location: (f64, f64),
+    /// Distance in meters from the input location to the snapped location
+    pub distance_meters: f64,
+}
+
+/// A round trip sequenced by OSRM's `/trip` service.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OsrmTrip {
+    /// Input waypoint indices in visiting order, starting with 0
+    pub order: Vec<usize>,
+    /// Total distance in meters, including the return to the first waypoint
+    pub distance_meters: i32,
+    /// Total duration in seconds, including the return to the first waypoint
+    pub duration_seconds: i32,
+}
+
 /// Route geometry response from OSRM
 #[derive(Debug, Clone)]
 pub struct RouteGeometry {
     /// Encoded polyline for the entire route (Google Polyline Algorithm format)
     pub encoded_polyline: String,
+    /// Decimal digits the route and leg polylines are encoded with.
+    pub precision: u32,
+    /// The route's points, for [`GeometryFormat::GeoJson`].
+    pub points: Option<Polyline>,
     /// Total distance in meters
     pub distance_meters: i32,
     /// Total duration in seconds
@@ -34,11 +206,23 @@ pub struct RouteGeometry {
     pub legs: Vec<LegGeometry>,
 }
 
+impl RouteGeometry {
+    /// The route's points, decoding the polyline unless they came decoded.
+    pub fn polyline(&self) -> Polyline {
+        match &self.points {
+            Some(points) => points.clone(),
+            None => Polyline::decode_with_precision(&self.encoded_polyline, self.precision),
+        }
+    }
+}
+
 /// Geometry for a single leg (segment between two consecutive waypoints)
 #[derive(Debug, Clone)]
 pub struct LegGeometry {
     /// Encoded polyline for this leg
     pub encoded_polyline: String,
+    /// The leg's points, for [`GeometryFormat::GeoJson`].
+    pub points: Option<Polyline>,
     /// Distance of this leg in meters
     pub distance_meters: i32,
     /// Duration of this leg in seconds
@@ -50,6 +234,12 @@ pub struct LegGeometry {
 pub enum OsrmRouteError {
     /// HTTP request failed
     RequestFailed(String),
+    /// Request timed out
+    Timeout,
+    /// Could not connect to the OSRM server
+    ConnectionFailed(String),
+    /// OSRM responded with a non-success HTTP status
+    HttpStatus(u16),
     /// OSRM returned an error status
     OsrmError(String),
     /// Failed to parse response
@@ -62,6 +252,9 @@ impl std::fmt::Display for OsrmRouteError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             OsrmRouteError::RequestFailed(msg) => write!(f, "OSRM request failed: {}", msg),
+            OsrmRouteError::Timeout => write!(f, "OSRM request timed out"),
+            OsrmRouteError::ConnectionFailed(msg) => write!(f, "Failed to connect to OSRM: {}", msg),
+            OsrmRouteError::HttpStatus(status) => write!(f, "OSRM returned HTTP {}", status),
             OsrmRouteError::OsrmError(msg) => write!(f, "OSRM error: {}", msg),
             OsrmRouteError::ParseError(msg) => write!(f, "Failed to parse OSRM response: {}", msg),
             OsrmRouteError::NoRoute => write!(f, "No route found between waypoints"),
@@ -71,19 +264,187 @@ impl std::fmt::Display for OsrmRouteError {
 
 impl std::error::Error for OsrmRouteError {}
 
+impl OsrmRouteError {
+    /// Whether the failure is likely transient and worth retrying.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            OsrmRouteError::Timeout | OsrmRouteError::ConnectionFailed(_) => true,
+            OsrmRouteError::HttpStatus(status) => *status >= 500 || *status == 429,
+            OsrmRouteError::RequestFailed(_)
+            | OsrmRouteError::OsrmError(_)
+            | OsrmRouteError::ParseError(_)
+            | OsrmRouteError::NoRoute => false,
+        }
+    }
+}
+
+impl From<reqwest::Error> for OsrmRouteError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            OsrmRouteError::Timeout
+        } else if err.is_connect() {
+            OsrmRouteError::ConnectionFailed(err.to_string())
+        } else if let Some(status) = err.status() {
+            OsrmRouteError::HttpStatus(status.as_u16())
+        } else if err.is_decode() {
+            OsrmRouteError::ParseError(err.to_string())
+        } else {
+            OsrmRouteError::RequestFailed(err.to_string())
+        }
+    }
+}
+
+/// Blocking OSRM client for matrices, route geometry, trips, and snapping.
+///
+/// Every request goes through one connection pool, which clones share: build
+/// one client per OSRM fleet and clone it into concurrent solves rather than
+/// constructing a client per solve.
 #[derive(Debug, Clone)]
 pub struct OsrmClient {
     config: OsrmConfig,
     client: reqwest::blocking::Client,
+    /// Table request URLs and response bodies.
+    wire: ByteCounter,
+    geometry_cache: Option<Arc<Mutex<GeometryCache>>>,
+}
+
+/// Route geometries by request URL, oldest evicted first.
+#[derive(Debug)]
+struct GeometryCache {
+    capacity: usize,
+    entries: HashMap<String, RouteGeometry>,
+    order: VecDeque<String>,
+}
+
+impl GeometryCache {
+    fn insert(&mut self, url: String, geometry: RouteGeometry) {
+        if self.capacity == 0 || self.entries.contains_key(&url) {
+            return;
+        }
+        if self.entries.len() == self.capacity
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.entries.remove(&oldest);
+        }
+        self.order.push_back(url.clone());
+        self.entries.insert(url, geometry);
+    }
 }
 
 impl OsrmClient {
     pub fn new(config: OsrmConfig) -> Result<Self, reqwest::Error> {
         let client = reqwest::blocking::Client::builder()
-            .timeout(std::time::Duration::from_secs(config.timeout_secs))
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .pool_max_idle_per_host(config.pool_max_idle_per_host)
+            .pool_idle_timeout(config.pool_idle_timeout_secs.map(Duration::from_secs))
+            .tcp_keepalive(config.tcp_keepalive_secs.map(Duration::from_secs))
             .build()?;
 
-        Ok(Self { config, client })
+        Ok(Self { config, client, wire: ByteCounter::default(), geometry_cache: None })
+    }
+
+    /// A client sharing this one's connection pool but sending different
+    /// routing parameters, e.g. a departure time for one solve.
+    pub fn with_request_options(&self, request: OsrmRequestOptions) -> Self {
+        let mut client = self.clone();
+        client.config.request = request;
+        client
+    }
+
+    /// A client that keeps up to `capacity` route geometries, so rendering
+    /// an unchanged route again doesn't refetch it.
+    ///
+    /// Entries are keyed by the request URL (waypoints, profile, and routing
+    /// options) and shared by clones, including those from
+    /// [`with_profile`](Self::with_profile). Failed requests aren't cached.
+    pub fn with_geometry_cache(&self, capacity: usize) -> Self {
+        let mut client = self.clone();
+        client.geometry_cache = Some(Arc::new(Mutex::new(GeometryCache {
+            capacity,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        })));
+        client
+    }
+
+    /// Drops every cached route geometry.
+    pub fn clear_geometry_cache(&self) {
+        if let Some(cache) = &self.geometry_cache {
+            let mut cache = cache.lock().unwrap();
+            cache.entries.clear();
+            cache.order.clear();
+        }
+    }
+
+    /// A client sharing this one's connection pool that also sends
+    /// `headers`, e.g. a tenant or trace ID for one solve.
+    pub fn with_headers(&self, headers: Vec<(String, String)>) -> Self {
+        let mut client = self.clone();
+        client.config.headers.extend(headers);
+        client
+    }
+
+    /// A client sharing this one's connection pool but routing with another
+    /// profile served at the same base URL, e.g. `bicycle`.
+    pub fn with_profile(&self, profile: &str) -> Self {
+        let mut client = self.clone();
+        client.config.profile = profile.to_string();
+        client
+    }
+
+    /// Snap each location to the nearest point on the road network.
+    ///
+    /// Issues one `/nearest` request per location. Large snap distances
+    /// usually indicate a bad geocode (e.g., a parcel centroid far from the
+    /// street, or coordinates with swapped lat/lng).
+    pub fn snap(&self, locations: &[(f64, f64)]) -> Result<Vec<SnappedLocation>, OsrmRouteError> {
+        locations
+            .iter()
+            .map(|(lat, lng)| {
+                let url = format!(
+                    "{}/nearest/v1/{}/{:.6},{:.6}?number=1",
+                    self.config.base_url, self.config.profile, lng, lat
+                );
+                let body: OsrmNearestResponse = read_json(self.get_with_retry(&url, "nearest", 1)?)?;
+                if body.code != "Ok" {
+                    return Err(OsrmRouteError::OsrmError(body.code));
+                }
+
+                let waypoint = body.waypoints.into_iter().next().ok_or(OsrmRouteError::NoRoute)?;
+                Ok(SnappedLocation {
+                    location: (waypoint.location[1], waypoint.location[0]),
+                    distance_meters: waypoint.distance,
+                })
+            })
+            .collect()
+    }
+
+    /// Sequence waypoints with OSRM's `/trip` (TSP heuristic) service.
+    ///
+    /// The trip is a round trip starting and ending at the first waypoint,
+    /// matching a route that leaves from and returns to the visitor's start.
+    /// Useful as a post-pass to re-sequence a single route, or as an oracle
+    /// to check how far our local search is from OSRM's ordering.
+    pub fn trip(&self, waypoints: &[(f64, f64)]) -> Result<OsrmTrip, OsrmRouteError> {
+        if waypoints.len() < 2 {
+            return Err(OsrmRouteError::NoRoute);
+        }
+
+        let coords = waypoints
+            .iter()
+            .map(|(lat, lng)| format!("{:.6},{:.6}", lng, lat))
+            .collect::<Vec<_>>()
+            .join(";");
+        let url = format!(
+            "{}/trip/v1/{}/{}?roundtrip=true&source=first&overview=false{}",
+            self.config.base_url,
+            self.config.profile,
+            coords,
+            self.config.request.query(waypoints.len())
+        );
+
+        let body: OsrmTripResponse = read_json(self.get_with_retry(&url, "trip", waypoints.len())?)?;
+        trip_from_response(body)
     }
 
     /// Fetch route geometry between ordered waypoints.
@@ -93,7 +454,8 @@ impl OsrmClient {
     ///
     /// # Returns
     /// Route geometry including encoded polyline for full route and per-leg breakdown.
-    /// Returns error if OSRM request fails or no route is found.
+    /// Returns error if OSRM request fails or no route is found. Served from the
+    /// [geometry cache](Self::with_geometry_cache) when there is one.
     pub fn get_route_geometry(
         &self,
         waypoints: &[(f64, f64)],
@@ -109,30 +471,24 @@ impl OsrmClient {
             .collect::<Vec<_>>()
             .join(";");
 
-        // Request route with full geometry and per-step annotations
-        // overview=full gives us the complete route polyline
-        // steps=true with geometries=polyline gives us per-leg polylines
+        // overview=full gives us the complete route geometry, which is split
+        // into legs by the segment counts of their annotations
+        let format = self.config.geometry_format;
         let url = format!(
-            "{}/route/v1/{}/{}?overview=full&geometries=polyline&steps=true",
-            self.config.base_url, self.config.profile, coords
+            "{}/route/v1/{}/{}?overview=full&geometries={}&annotations=distance{}",
+            self.config.base_url,
+            self.config.profile,
+            coords,
+            format.param(),
+            self.config.request.query(waypoints.len())
         );
-
-        let response = self
-            .client
-            .get(&url)
-            .send()
-            .map_err(|e: reqwest::Error| OsrmRouteError::RequestFailed(e.to_string()))?;
-
-        if !response.status().is_success() {
-            return Err(OsrmRouteError::RequestFailed(format!(
-                "HTTP {}",
-                response.status()
-            )));
+        if let Some(cache) = &self.geometry_cache
+            && let Some(geometry) = cache.lock().unwrap().entries.get(&url)
+        {
+            return Ok(geometry.clone());
         }
 
-        let body: OsrmRouteResponse = response
-            .json()
-            .map_err(|e: reqwest::Error| OsrmRouteError::ParseError(e.to_string()))?;
+        let body: OsrmRouteResponse = read_json(self.get_with_retry(&url, "route", waypoints.len())?)?;
 
         // Check OSRM status
         if body.code != "Ok" {
@@ -141,189 +497,534 @@ impl OsrmClient {
 
         // Get the first (best) route
         let route = body.routes.into_iter().next().ok_or(OsrmRouteError::NoRoute)?;
+        let geometry = route_geometry(route, format);
+        if let Some(cache) = &self.geometry_cache {
+            cache.lock().unwrap().insert(url, geometry.clone());
+        }
+        Ok(geometry)
+    }
+}
+
+/// Convert a `/route` response's route into geometries in `format`.
+///
+/// Leg geometries are the full route's geometry split where OSRM ends each
+/// leg, so they join end to end into it.
+fn route_geometry(route: OsrmRoute, format: GeometryFormat) -> RouteGeometry {
+    let precision = match format {
+        GeometryFormat::Polyline6 => 6,
+        GeometryFormat::Polyline | GeometryFormat::GeoJson => 5,
+    };
+    let (encoded_polyline, route_points) = match route.geometry {
+        OsrmGeometry::Encoded(polyline) => {
+            let points = Polyline::decode_with_precision(&polyline, precision);
+            (polyline, points)
+        }
+        OsrmGeometry::GeoJson { coordinates } => (String::new(), geojson_polyline(&coordinates)),
+    };
+    let splits = leg_splits(route_points.points().len(), &route.legs);
+    let legs = route
+        .legs
+        .into_iter()
+        .enumerate()
+        .map(|(index, leg)| {
+            let points = match (splits.get(index), splits.get(index + 1)) {
+                (Some(&start), Some(&end)) => route_points.points()[start..=end].to_vec(),
+                _ => Vec::new(),
+            };
+            let (encoded_polyline, points) = match format {
+                GeometryFormat::GeoJson => (String::new(), Some(Polyline::new(points))),
+                _ => (Polyline::new(points).encode_with_precision(precision), None),
+            };
+            LegGeometry {
+                encoded_polyline,
+                points,
+                distance_meters: leg.distance.round() as i32,
+                duration_seconds: leg.duration.round() as i32,
+            }
+        })
+        .collect();
+
+    let points = (format == GeometryFormat::GeoJson).then_some(route_points);
+    RouteGeometry {
+        encoded_polyline,
+        precision,
+        points,
+        distance_meters: route.distance.round() as i32,
+        duration_seconds: route.duration.round() as i32,
+        legs,
+    }
+}
+
+/// GeoJSON `[lng, lat]` positions as a (lat, lng) polyline.
+fn geojson_polyline(coordinates: &[[f64; 2]]) -> Polyline {
+    Polyline::new(coordinates.iter().map(|&[lng, lat]| (lat, lng)).collect())
+}
+
+impl OsrmClient {
+    /// Checks once that the server answers a small `/table` request.
+    ///
+    /// Connection failures, timeouts, and 5xx statuses mean the server isn't
+    /// up (or is still loading its dataset); an OSRM error code or other
+    /// HTTP status means it's up but misconfigured, e.g. a wrong base URL or rejected auth.
+    pub fn ping(&self) -> Result<(), OsrmRouteError> {
+        let resp = self.get(&ping_url(&self.config)).send()?;
+        let (status, gzipped) = (resp.status().as_u16(), is_gzipped(resp.headers()));
+        ping_outcome(status, &decoded(&resp.bytes()?, gzipped)?)
+    }
 
-        // Build leg geometries from the route legs
-        let legs = route
-            .legs
-            .into_iter()
-            .map(|leg| {
-                // Combine step polylines for this leg, or use a fallback
-                let leg_polyline = if leg.steps.is_empty() {
-                    // No steps available, we'll need to handle this case
-                    String::new()
-                } else {
-                    // Concatenate step geometries or decode/re-encode
-                    // For simplicity, we'll use the first step's geometry as approximation
-                    // A more accurate approach would decode all steps and merge
-                    combine_step_geometries(&leg.steps)
-                };
-
-                LegGeometry {
-                    encoded_polyline: leg_polyline,
-                    distance_meters: leg.distance.round() as i32,
-                    duration_seconds: leg.duration.round() as i32,
+    /// Pings until the server is ready, backing off as for retries.
+    ///
+    /// Returns the first error that isn't transient, or the last one once
+    /// `timeout` has passed.
+    pub fn wait_ready(&self, timeout: Duration) -> Result<(), OsrmRouteError> {
+        let started = Instant::now();
+        let mut retry = 0;
+        loop {
+            match self.ping() {
+                Err(err) if err.is_transient() && started.elapsed() < timeout => {
+                    let delay = self.config.backoff(retry).min(timeout.saturating_sub(started.elapsed()));
+                    debug!(error = %err, delay_ms = delay.as_millis(), "OSRM not ready");
+                    std::thread::sleep(delay);
+                    retry += 1;
                 }
-            })
-            .collect();
+                result => return result,
+            }
+        }
+    }
 
-        Ok(RouteGeometry {
-            encoded_polyline: route.geometry,
-            distance_meters: route.distance.round() as i32,
-            duration_seconds: route.duration.round() as i32,
-            legs,
-        })
+    /// A GET request carrying the configured auth and headers.
+    fn get(&self, url: &str) -> reqwest::blocking::RequestBuilder {
+        let mut request = self.client.get(url);
+        match &self.config.auth {
+            Some(OsrmAuth::Bearer(token)) => request = request.bearer_auth(token),
+            Some(OsrmAuth::Basic { username, password }) => request = request.basic_auth(username, password.as_ref()),
+            Some(OsrmAuth::ApiKey { header, key }) => request = request.header(header.as_str(), key.as_str()),
+            None => {}
+        }
+        if self.config.gzip {
+            request = request.header(reqwest::header::ACCEPT_ENCODING, "gzip");
+        }
+        for (name, value) in &self.config.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        request
+    }
+
+    /// GET a URL, retrying transient failures with exponential backoff.
+    ///
+    /// Runs in a span naming the OSRM `service` and how many `locations`
+    /// the request covers.
+    fn get_with_retry(
+        &self,
+        url: &str,
+        service: &'static str,
+        locations: usize,
+    ) -> Result<reqwest::blocking::Response, OsrmRouteError> {
+        let span = request_span(&self.config, service, locations);
+        let _guard = span.enter();
+        let mut retry = 0;
+        loop {
+            let result = self
+                .get(url)
+                .send()
+                .and_then(|resp: reqwest::blocking::Response| resp.error_for_status())
+                .map_err(OsrmRouteError::from);
+
+            match result {
+                Err(err) if err.is_transient() && retry < self.config.max_retries => {
+                    let delay = self.config.backoff(retry);
+                    warn!(error = %err, retry = retry + 1, delay_ms = delay.as_millis(), "Retrying OSRM request");
+                    std::thread::sleep(delay);
+                    retry += 1;
+                }
+                result => {
+                    span.record("retries", retry);
+                    record_outcome(&span, result.as_ref().map(|resp| resp.status().as_u16()));
+                    return result;
+                }
+            }
+        }
+    }
+
+    /// Request a `/table` with the given annotations, or `None` on any failure.
+    fn request_table(&self, locations: &[(f64, f64)], annotations: &str) -> Option<OsrmTableResponse> {
+        if locations.is_empty() {
+            return None;
+        }
+        self.get_table(&table_url(&self.config, locations, annotations), locations.len())
+    }
+
+    fn get_table(&self, url: &str, locations: usize) -> Option<OsrmTableResponse> {
+        let result = self
+            .get_with_retry(url, "table", locations)
+            .and_then(|resp| {
+                let gzipped = is_gzipped(resp.headers());
+                let body = resp.bytes()?;
+                self.wire.add(url.len() + body.len());
+                serde_json::from_slice::<OsrmTableResponse>(&decoded(&body, gzipped)?)
+                    .map_err(|err| OsrmRouteError::ParseError(err.to_string()))
+            });
+        match result {
+            Ok(body) => Some(body),
+            Err(err) => {
+                warn!(error = %err, locations, "OSRM table request failed");
+                None
+            }
+        }
     }
 }
 
 impl DistanceMatrixProvider for OsrmClient {
     fn matrix_for(&self, locations: &[(f64, f64)]) -> Vec<Vec<i32>> {
-        if locations.is_empty() {
-            return Vec::new();
+        match self.request_table(locations, "duration") {
+            Some(body) => round_table(body.durations, UNREACHABLE_SECS),
+            None => Vec::new(),
         }
+    }
 
-        let coords = locations
-            .iter()
-            .map(|(lat, lng)| format!("{:.6},{:.6}", lng, lat))
-            .collect::<Vec<_>>()
-            .join(";");
+    /// Durations and distances from a single `annotations=duration,distance` request.
+    fn matrices_for(&self, locations: &[(f64, f64)]) -> TravelMatrices {
+        table_matrices(self.request_table(locations, "duration,distance"))
+    }
 
-        let url = format!(
-            "{}/table/v1/{}/{}?annotations=duration",
-            self.config.base_url, self.config.profile, coords
-        );
+    /// One `/table` request with `sources` and `destinations`, over just the
+    /// locations involved.
+    fn table_for(&self, locations: &[(f64, f64)], sources: &[usize], destinations: &[usize]) -> TravelMatrices {
+        let subset = TableSubset::new(locations, sources, destinations);
+        if subset.locations.is_empty() {
+            return TravelMatrices::from_durations(Vec::new());
+        }
+        let body = self.get_table(&subset_table_url(&self.config, &subset), subset.locations.len());
+        TravelMatrices { snap_distances: None, ..table_matrices(body) }
+    }
 
-        let response = self
-            .client
-            .get(url)
-            .send()
-            .and_then(|resp: reqwest::blocking::Response| resp.error_for_status())
-            .and_then(|resp: reqwest::blocking::Response| resp.json::<OsrmTableResponse>());
+    /// A `/table` request against `/table/v1/{profile}` on the same server.
+    fn matrices_for_profile(&self, profile: &str, locations: &[(f64, f64)]) -> TravelMatrices {
+        self.with_profile(profile).matrices_for(locations)
+    }
 
-        match response {
-            Ok(body) => body
-                .durations
-                .unwrap_or_default()
-                .into_iter()
-                .map(|row: Vec<f64>| row.into_iter().map(|value: f64| value.round() as i32).collect())
-                .collect(),
-            Err(_) => Vec::new(),
-        }
+    fn bytes_transferred(&self) -> Option<u64> {
+        Some(self.wire.total())
     }
 }
 
-/// Combine step geometries into a single polyline for the leg.
+/// Non-blocking OSRM client for use inside async runtimes.
 ///
-/// This decodes each step's polyline, concatenates the points, and re-encodes.
-fn combine_step_geometries(steps: &[OsrmRouteStep]) -> String {
-    let mut all_points: Vec<(f64, f64)> = Vec::new();
-
-    for step in steps {
-        let points = decode_polyline(&step.geometry);
-        if all_points.is_empty() {
-            all_points.extend(points);
-        } else {
-            // Skip first point as it's the same as the last point of previous step
-            all_points.extend(points.into_iter().skip(1));
+/// Shares [`OsrmConfig`] with [`OsrmClient`] but uses reqwest's async client,
+/// so matrix requests don't tie up an executor thread.
+/// Clones share one connection pool, as with [`OsrmClient`].
+#[derive(Debug, Clone)]
+pub struct AsyncOsrmClient {
+    config: OsrmConfig,
+    client: reqwest::Client,
+    /// Table request URLs and response bodies.
+    wire: ByteCounter,
+}
+
+impl AsyncOsrmClient {
+    pub fn new(config: OsrmConfig) -> Result<Self, reqwest::Error> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .pool_max_idle_per_host(config.pool_max_idle_per_host)
+            .pool_idle_timeout(config.pool_idle_timeout_secs.map(Duration::from_secs))
+            .tcp_keepalive(config.tcp_keepalive_secs.map(Duration::from_secs))
+            .build()?;
+
+        Ok(Self { config, client, wire: ByteCounter::default() })
+    }
+
+    /// See [`OsrmClient::with_request_options`].
+    pub fn with_request_options(&self, request: OsrmRequestOptions) -> Self {
+        Self {
+            config: OsrmConfig { request, ..self.config.clone() },
+            client: self.client.clone(),
+            wire: self.wire.clone(),
         }
     }
 
-    encode_polyline(&all_points)
-}
+    /// See [`OsrmClient::with_headers`].
+    pub fn with_headers(&self, headers: Vec<(String, String)>) -> Self {
+        let mut client = self.clone();
+        client.config.headers.extend(headers);
+        client
+    }
 
-/// Decode a Google Polyline Algorithm encoded string into coordinates.
-fn decode_polyline(encoded: &str) -> Vec<(f64, f64)> {
-    let mut points = Vec::new();
-    let mut lat = 0i64;
-    let mut lng = 0i64;
-    let mut index = 0;
-    let chars: Vec<char> = encoded.chars().collect();
+    /// See [`OsrmClient::with_profile`].
+    pub fn with_profile(&self, profile: &str) -> Self {
+        let mut client = self.clone();
+        client.config.profile = profile.to_string();
+        client
+    }
 
-    while index < chars.len() {
-        // Decode latitude
-        let mut shift = 0;
-        let mut result = 0i64;
-        loop {
-            if index >= chars.len() {
-                break;
-            }
-            let b = (chars[index] as i64) - 63;
-            index += 1;
-            result |= (b & 0x1f) << shift;
-            shift += 5;
-            if b < 0x20 {
-                break;
-            }
+    /// A GET request carrying the configured auth and headers.
+    fn get(&self, url: &str) -> reqwest::RequestBuilder {
+        let mut request = self.client.get(url);
+        match &self.config.auth {
+            Some(OsrmAuth::Bearer(token)) => request = request.bearer_auth(token),
+            Some(OsrmAuth::Basic { username, password }) => request = request.basic_auth(username, password.as_ref()),
+            Some(OsrmAuth::ApiKey { header, key }) => request = request.header(header.as_str(), key.as_str()),
+            None => {}
         }
-        lat += if (result & 1) != 0 {
-            !(result >> 1)
-        } else {
-            result >> 1
+        if self.config.gzip {
+            request = request.header(reqwest::header::ACCEPT_ENCODING, "gzip");
+        }
+        for (name, value) in &self.config.headers {
+            request = request.header(name.as_str(), value.as_str());
+        }
+        request
+    }
+
+    /// See [`OsrmClient::ping`].
+    pub async fn ping(&self) -> Result<(), OsrmRouteError> {
+        let resp = self.get(&ping_url(&self.config)).send().await?;
+        let (status, gzipped) = (resp.status().as_u16(), is_gzipped(resp.headers()));
+        ping_outcome(status, &decoded(&resp.bytes().await?, gzipped)?)
+    }
+
+    /// Request a `/table` with the given annotations, or `None` on any failure.
+    async fn request_table(&self, url: Option<String>, locations: usize) -> Option<OsrmTableResponse> {
+        let url = url?;
+        let span = request_span(&self.config, "table", locations);
+        let request = async {
+            let result = self
+                .get(&url)
+                .send()
+                .await
+                .and_then(|resp| resp.error_for_status())
+                .map_err(OsrmRouteError::from);
+            record_outcome(&Span::current(), result.as_ref().map(|resp| resp.status().as_u16()));
+            let resp = result.ok()?;
+            let gzipped = is_gzipped(resp.headers());
+            let body = resp.bytes().await.ok()?;
+            self.wire.add(url.len() + body.len());
+            serde_json::from_slice::<OsrmTableResponse>(&decode_body(&body, gzipped).ok()?).ok()
         };
+        request.instrument(span).await
+    }
+}
 
-        // Decode longitude
-        shift = 0;
-        result = 0;
-        loop {
-            if index >= chars.len() {
-                break;
-            }
-            let b = (chars[index] as i64) - 63;
-            index += 1;
-            result |= (b & 0x1f) << shift;
-            shift += 5;
-            if b < 0x20 {
-                break;
+impl AsyncDistanceMatrixProvider for AsyncOsrmClient {
+    fn fetch_matrix(&self, locations: &[(f64, f64)]) -> impl Future<Output = Vec<Vec<i32>>> + Send {
+        let url = (!locations.is_empty()).then(|| table_url(&self.config, locations, "duration"));
+
+        async move {
+            match self.request_table(url, locations.len()).await {
+                Some(body) => round_table(body.durations, UNREACHABLE_SECS),
+                None => Vec::new(),
             }
         }
-        lng += if (result & 1) != 0 {
-            !(result >> 1)
-        } else {
-            result >> 1
-        };
+    }
+
+    fn fetch_matrices(&self, locations: &[(f64, f64)]) -> impl Future<Output = TravelMatrices> + Send {
+        let url = (!locations.is_empty()).then(|| table_url(&self.config, locations, "duration,distance"));
 
-        points.push((lat as f64 / 1e5, lng as f64 / 1e5));
+        async move { table_matrices(self.request_table(url, locations.len()).await) }
     }
 
-    points
+    /// See [`OsrmClient`]'s `table_for`.
+    fn fetch_table(
+        &self,
+        locations: &[(f64, f64)],
+        sources: &[usize],
+        destinations: &[usize],
+    ) -> impl Future<Output = TravelMatrices> + Send {
+        let subset = TableSubset::new(locations, sources, destinations);
+        let url = (!subset.locations.is_empty()).then(|| subset_table_url(&self.config, &subset));
+
+        async move {
+            let body = self.request_table(url, subset.locations.len()).await;
+            TravelMatrices { snap_distances: None, ..table_matrices(body) }
+        }
+    }
+
+    fn fetch_matrices_for_profile(
+        &self,
+        profile: &str,
+        locations: &[(f64, f64)],
+    ) -> impl Future<Output = TravelMatrices> + Send {
+        let config = OsrmConfig { profile: profile.to_string(), ..self.config.clone() };
+        let url = (!locations.is_empty()).then(|| table_url(&config, locations, "duration,distance"));
+
+        async move { table_matrices(self.request_table(url, locations.len()).await) }
+    }
+
+    fn bytes_transferred(&self) -> Option<u64> {
+        Some(self.wire.total())
+    }
 }
 
-/// Encode coordinates into a Google Polyline Algorithm string.
-fn encode_polyline(points: &[(f64, f64)]) -> String {
-    let mut encoded = String::new();
-    let mut prev_lat = 0i64;
-    let mut prev_lng = 0i64;
+/// Span for one OSRM HTTP request, retries included.
+///
+/// The span names the service and profile but not the coordinates.
+fn request_span(config: &OsrmConfig, service: &'static str, locations: usize) -> Span {
+    let span = info_span!(
+        "osrm.request",
+        service,
+        locations,
+        retries = Empty,
+        status = Empty,
+        otel.kind = Empty,
+        otel.status_code = Empty,
+        http.request.method = Empty,
+        http.response.status_code = Empty,
+        url.path = Empty,
+    );
+    telemetry::http_client(&span, &format!("/{}/v1/{}", service, config.profile));
+    span
+}
 
-    for &(lat, lng) in points {
-        let lat_e5 = (lat * 1e5).round() as i64;
-        let lng_e5 = (lng * 1e5).round() as i64;
+/// Records the HTTP status (or failure) of a finished request.
+fn record_outcome(span: &Span, result: Result<u16, &OsrmRouteError>) {
+    let status = match result {
+        Ok(status) | Err(&OsrmRouteError::HttpStatus(status)) => Some(status),
+        Err(_) => None,
+    };
+    if let Some(status) = status {
+        span.record("status", status);
+    }
+    telemetry::http_status(span, status);
+    telemetry::status(span, result.is_ok());
+}
 
-        encode_value(lat_e5 - prev_lat, &mut encoded);
-        encode_value(lng_e5 - prev_lng, &mut encoded);
+/// Build the `/table` request URL for a set of (lat, lng) locations.
+///
+/// `annotations` is passed through, e.g. `"duration"` or `"duration,distance"`.
+fn table_url(config: &OsrmConfig, locations: &[(f64, f64)], annotations: &str) -> String {
+    let coords = locations
+        .iter()
+        .map(|(lat, lng)| format!("{:.6},{:.6}", lng, lat))
+        .collect::<Vec<_>>()
+        .join(";");
+
+    format!(
+        "{}/table/v1/{}/{}?annotations={}{}",
+        config.base_url,
+        config.profile,
+        coords,
+        annotations,
+        config.request.query(locations.len())
+    )
+}
 
-        prev_lat = lat_e5;
-        prev_lng = lng_e5;
+/// Build a durations and distances `/table` URL over a subset's locations,
+/// limited to its sources and destinations.
+fn subset_table_url(config: &OsrmConfig, subset: &TableSubset) -> String {
+    let indices = |positions: &[usize]| positions.iter().map(usize::to_string).collect::<Vec<_>>().join(";");
+    format!(
+        "{}&sources={}&destinations={}",
+        table_url(config, &subset.locations, "duration,distance"),
+        indices(&subset.sources),
+        indices(&subset.destinations)
+    )
+}
+
+/// A 2 × 2 `/table` request over one location: about the cheapest request
+/// that proves the dataset is loaded. The table service rejects fewer than
+/// two coordinates.
+fn ping_url(config: &OsrmConfig) -> String {
+    format!("{}/table/v1/{}/0.000000,0.000000;0.000000,0.000000", config.base_url, config.profile)
+}
+
+/// A response body, decompressed if OSRM gzipped it.
+fn decoded(body: &[u8], gzipped: bool) -> Result<Cow<'_, [u8]>, OsrmRouteError> {
+    decode_body(body, gzipped).map_err(|err| OsrmRouteError::ParseError(err.to_string()))
+}
+
+/// Parse a JSON response body.
+fn read_json<T: DeserializeOwned>(resp: reqwest::blocking::Response) -> Result<T, OsrmRouteError> {
+    let gzipped = is_gzipped(resp.headers());
+    let body = resp.bytes()?;
+    serde_json::from_slice(&decoded(&body, gzipped)?).map_err(|err| OsrmRouteError::ParseError(err.to_string()))
+}
+
+/// Whether a ping response shows a ready server.
+///
+/// A server (or the gateway in front of it) still loading its dataset
+/// answers with a 5xx status, reported as a transient
+/// [`HttpStatus`](OsrmRouteError::HttpStatus) whatever the body says.
+fn ping_outcome(status: u16, body: &[u8]) -> Result<(), OsrmRouteError> {
+    if status >= 500 {
+        return Err(OsrmRouteError::HttpStatus(status));
+    }
+    match serde_json::from_slice::<OsrmStatusResponse>(body) {
+        Ok(body) if body.code == "Ok" => Ok(()),
+        Ok(body) => Err(OsrmRouteError::OsrmError(body.code)),
+        Err(_) if !(200..300).contains(&status) => Err(OsrmRouteError::HttpStatus(status)),
+        Err(err) => Err(OsrmRouteError::ParseError(err.to_string())),
     }
+}
 
-    encoded
+/// Round a `/table` annotation (seconds or meters) to whole units. OSRM
+/// answers `null` for pairs it can't route, which become `unreachable`.
+fn round_table(table: Option<Vec<Vec<Option<f64>>>>, unreachable: i32) -> Vec<Vec<i32>> {
+    table
+        .unwrap_or_default()
+        .into_iter()
+        .map(|row| row.into_iter().map(|value| value.map_or(unreachable, |value| value.round() as i32)).collect())
+        .collect()
 }
 
-fn encode_value(mut value: i64, output: &mut String) {
-    // Two's complement for negative values
-    if value < 0 {
-        value = !value;
-        value <<= 1;
-        value |= 1;
-    } else {
-        value <<= 1;
+/// Convert a `/table` response (or a failed request) into travel matrices.
+///
+/// Each source waypoint's `distance` is how far OSRM moved the location to
+/// snap it onto the road network.
+fn table_matrices(body: Option<OsrmTableResponse>) -> TravelMatrices {
+    match body {
+        Some(body) => TravelMatrices {
+            durations: round_table(body.durations, UNREACHABLE_SECS),
+            distances: body.distances.map(|distances| round_table(Some(distances), UNREACHABLE_METERS)),
+            snap_distances: body
+                .sources
+                .map(|sources| sources.iter().map(|source| source.distance.round() as i32).collect()),
+        },
+        None => TravelMatrices::from_durations(Vec::new()),
     }
+}
 
-    while value >= 0x20 {
-        let chunk = ((value & 0x1f) | 0x20) as u8 + 63;
-        output.push(chunk as char);
-        value >>= 5;
+/// Indices into a route geometry of `points` points where each leg starts
+/// and ends.
+///
+/// A leg's annotation has one entry per segment of its geometry, and the
+/// full geometry is the legs joined end to end, so the boundaries are the
+/// running segment counts. Without an annotation on every leg, or when the
+/// counts don't add up to the geometry, every leg is empty.
+fn leg_splits(points: usize, legs: &[OsrmRouteLeg]) -> Vec<usize> {
+    let segments: Option<Vec<usize>> =
+        legs.iter().map(|leg| leg.annotation.as_ref().map(|annotation| annotation.distance.len())).collect();
+    match segments {
+        Some(segments) if points > 0 && segments.iter().sum::<usize>() == points - 1 => std::iter::once(0)
+            .chain(segments.iter().scan(0, |end, count| {
+                *end += count;
+                Some(*end)
+            }))
+            .collect(),
+        _ => {
+            if !legs.is_empty() {
+                warn!(points, legs = legs.len(), "Cannot split route geometry into legs");
+            }
+            Vec::new()
+        }
     }
+}
+
+/// Convert a `/trip` response into visiting order and totals.
+///
+/// OSRM lists waypoints in input order, each tagged with its position in
+/// the trip; inverting that gives the visiting order.
+fn trip_from_response(body: OsrmTripResponse) -> Result<OsrmTrip, OsrmRouteError> {
+    if body.code != "Ok" {
+        return Err(OsrmRouteError::OsrmError(body.code));
+    }
+    let trip = body.trips.into_iter().next().ok_or(OsrmRouteError::NoRoute)?;
+
+    let mut order: Vec<usize> = (0..body.waypoints.len()).collect();
+    order.sort_by_key(|&idx| body.waypoints[idx].waypoint_index);
 
-    output.push((value as u8 + 63) as char);
+    Ok(OsrmTrip {
+        order,
+        distance_meters: trip.distance.round() as i32,
+        duration_seconds: trip.duration.round() as i32,
+    })
 }
 
 // -----------------------------------------------------------------------------
@@ -332,7 +1033,34 @@ fn encode_value(mut value: i64, output: &mut String) {
 
 #[derive(Debug, Deserialize)]
 struct OsrmTableResponse {
-    durations: Option<Vec<Vec<f64>>>,
+    /// `null` for pairs with no route.
+    durations: Option<Vec<Vec<Option<f64>>>>,
+    #[serde(default)]
+    distances: Option<Vec<Vec<Option<f64>>>>,
+    #[serde(default)]
+    sources: Option<Vec<OsrmWaypoint>>,
+}
+
+/// The `code` every OSRM response carries, errors included.
+#[derive(Debug, Deserialize)]
+struct OsrmStatusResponse {
+    code: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OsrmNearestResponse {
+    code: String,
+    #[serde(default)]
+    waypoints: Vec<OsrmWaypoint>,
+}
+
+/// A location snapped onto the road network.
+#[derive(Debug, Deserialize)]
+struct OsrmWaypoint {
+    /// Snapped location as [lng, lat]
+    location: [f64; 2],
+    /// Distance in meters from the input location to the snapped location
+    distance: f64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -344,8 +1072,8 @@ struct OsrmRouteResponse {
 
 #[derive(Debug, Deserialize)]
 struct OsrmRoute {
-    /// Encoded polyline for full route
-    geometry: String,
+    /// Geometry of the full route
+    geometry: OsrmGeometry,
     /// Total distance in meters
     distance: f64,
     /// Total duration in seconds
@@ -354,19 +1082,404 @@ struct OsrmRoute {
     legs: Vec<OsrmRouteLeg>,
 }
 
+#[derive(Debug, Deserialize)]
+struct OsrmTripResponse {
+    code: String,
+    #[serde(default)]
+    trips: Vec<OsrmTripSummary>,
+    /// Waypoints in input order
+    #[serde(default)]
+    waypoints: Vec<OsrmTripWaypoint>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OsrmTripSummary {
+    /// Total distance in meters
+    distance: f64,
+    /// Total duration in seconds
+    duration: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct OsrmTripWaypoint {
+    /// Position of this waypoint in the trip
+    waypoint_index: usize,
+}
+
 #[derive(Debug, Deserialize)]
 struct OsrmRouteLeg {
     /// Distance in meters
     distance: f64,
     /// Duration in seconds
     duration: f64,
-    /// Steps within this leg (for per-step geometry)
+    /// Per-segment values along the leg's geometry
     #[serde(default)]
-    steps: Vec<OsrmRouteStep>,
+    annotation: Option<OsrmLegAnnotation>,
 }
 
 #[derive(Debug, Deserialize)]
-struct OsrmRouteStep {
-    /// Encoded polyline for this step
-    geometry: String,
+struct OsrmLegAnnotation {
+    /// Distance in meters of each segment between consecutive points
+    distance: Vec<f64>,
+}
+
+/// A route geometry in the requested `geometries` format.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum OsrmGeometry {
+    /// `polyline` or `polyline6`
+    Encoded(String),
+    /// `geojson`: a LineString of [lng, lat] positions
+    GeoJson { coordinates: Vec<[f64; 2]> },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_cap() {
+        let config = OsrmConfig { initial_backoff_ms: 100, max_backoff_ms: 500, ..Default::default() };
+        assert_eq!(config.backoff(0), Duration::from_millis(100));
+        assert_eq!(config.backoff(1), Duration::from_millis(200));
+        assert_eq!(config.backoff(2), Duration::from_millis(400));
+        assert_eq!(config.backoff(3), Duration::from_millis(500));
+        assert_eq!(config.backoff(40), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_transient_errors() {
+        assert!(OsrmRouteError::Timeout.is_transient());
+        assert!(OsrmRouteError::ConnectionFailed("refused".to_string()).is_transient());
+        assert!(OsrmRouteError::HttpStatus(503).is_transient());
+        assert!(OsrmRouteError::HttpStatus(429).is_transient());
+        assert!(!OsrmRouteError::HttpStatus(400).is_transient());
+        assert!(!OsrmRouteError::NoRoute.is_transient());
+    }
+
+    #[test]
+    fn test_request_options_in_table_url() {
+        let config = OsrmConfig {
+            request: OsrmRequestOptions {
+                exclude: vec!["toll".to_string(), "ferry".to_string()],
+                approach: Some(Approach::Curb),
+                departure_time: Some(1_700_000_000),
+            },
+            ..Default::default()
+        };
+
+        let url = table_url(&config, &[(36.1, -115.1), (36.2, -115.2)], "duration");
+        assert!(url.ends_with(
+            "?annotations=duration&exclude=toll,ferry&approaches=curb;curb&depart=1700000000"
+        ), "{url}");
+    }
+
+    #[test]
+    fn test_subset_table_url_names_sources_and_destinations() {
+        let locations = [(36.1, -115.1), (36.2, -115.2), (36.3, -115.3)];
+        let subset = TableSubset::new(&locations, &[2], &[0, 2]);
+
+        let url = subset_table_url(&OsrmConfig::default(), &subset);
+        assert!(url.contains("/-115.300000,36.300000;-115.100000,36.100000?"), "{url}");
+        assert!(url.ends_with("?annotations=duration,distance&sources=0&destinations=1;0"), "{url}");
+    }
+
+    #[test]
+    fn test_default_request_options_add_nothing() {
+        let url = table_url(&OsrmConfig::default(), &[(36.1, -115.1)], "duration");
+        assert!(url.ends_with("?annotations=duration"), "{url}");
+    }
+
+    #[test]
+    fn test_table_sources_become_snap_distances() {
+        let body: OsrmTableResponse = serde_json::from_str(
+            r#"{
+                "code": "Ok",
+                "durations": [[0, 60.4], [59.6, 0]],
+                "sources": [
+                    {"location": [-115.1, 36.1], "distance": 4.2},
+                    {"location": [-115.2, 36.2], "distance": 812.7}
+                ]
+            }"#,
+        )
+        .expect("parse table response");
+
+        let matrices = table_matrices(Some(body));
+        assert_eq!(matrices.durations, vec![vec![0, 60], vec![60, 0]]);
+        assert_eq!(matrices.snap_distances, Some(vec![4, 813]));
+    }
+
+    #[test]
+    fn test_null_table_cells_become_unreachable() {
+        let body: OsrmTableResponse = serde_json::from_str(
+            r#"{
+                "code": "Ok",
+                "durations": [[0, null], [59.6, 0]],
+                "distances": [[0, null], [812.7, 0]]
+            }"#,
+        )
+        .expect("parse table response");
+
+        let matrices = table_matrices(Some(body));
+        assert_eq!(matrices.durations, vec![vec![0, UNREACHABLE_SECS], vec![60, 0]]);
+        assert_eq!(matrices.distances, Some(vec![vec![0, UNREACHABLE_METERS], vec![813, 0]]));
+    }
+
+    #[test]
+    fn test_trip_waypoint_indices_become_visiting_order() {
+        let body: OsrmTripResponse = serde_json::from_str(
+            r#"{
+                "code": "Ok",
+                "trips": [{"distance": 15234.6, "duration": 1210.2, "legs": []}],
+                "waypoints": [
+                    {"waypoint_index": 0, "trips_index": 0, "location": [-115.1, 36.1]},
+                    {"waypoint_index": 3, "trips_index": 0, "location": [-115.2, 36.2]},
+                    {"waypoint_index": 1, "trips_index": 0, "location": [-115.3, 36.3]},
+                    {"waypoint_index": 2, "trips_index": 0, "location": [-115.4, 36.4]}
+                ]
+            }"#,
+        )
+        .expect("parse trip response");
+
+        let trip = trip_from_response(body).expect("trip");
+        assert_eq!(trip.order, vec![0, 2, 3, 1]);
+        assert_eq!(trip.distance_meters, 15235);
+        assert_eq!(trip.duration_seconds, 1210);
+    }
+
+    #[test]
+    fn test_connection_refused_is_retried_then_reported() {
+        let config = OsrmConfig {
+            base_url: "http://127.0.0.1:1".to_string(),
+            max_retries: 2,
+            initial_backoff_ms: 1,
+            ..Default::default()
+        };
+        let client = OsrmClient::new(config).expect("build client");
+
+        let err = client.get_route_geometry(&[(36.1, -115.1), (36.2, -115.2)]).unwrap_err();
+        assert!(matches!(err, OsrmRouteError::ConnectionFailed(_)), "unexpected error {err:?}");
+    }
+    #[test]
+    fn test_pool_settings_build_clients() {
+        let config = OsrmConfig {
+            pool_max_idle_per_host: 0,
+            pool_idle_timeout_secs: None,
+            tcp_keepalive_secs: None,
+            gzip: false,
+            ..Default::default()
+        };
+        assert!(OsrmClient::new(config.clone()).is_ok());
+        assert!(AsyncOsrmClient::new(config).is_ok());
+    }
+
+    #[test]
+    fn test_profile_client_routes_by_profile_on_shared_pool() {
+        let client = OsrmClient::new(OsrmConfig::default()).unwrap();
+        let bicycle = client.with_profile("bicycle");
+
+        assert!(table_url(&bicycle.config, &[(36.1, -115.1)], "duration").contains("/table/v1/bicycle/"));
+        bicycle.wire.add(10);
+        assert_eq!(client.wire.total(), 10);
+    }
+
+    #[test]
+    fn test_auth_and_headers_sent_with_requests() {
+        let config = OsrmConfig {
+            auth: Some(OsrmAuth::Bearer("secret".to_string())),
+            headers: vec![("x-fleet".to_string(), "west".to_string())],
+            ..Default::default()
+        };
+        let client = OsrmClient::new(config.clone()).unwrap().with_headers(vec![("x-trace".to_string(), "42".to_string())]);
+
+        let request = client.get("http://osrm/table").build().unwrap();
+        let headers = request.headers();
+        assert_eq!(headers["authorization"], "Bearer secret");
+        assert_eq!(headers["x-fleet"], "west");
+        assert_eq!(headers["x-trace"], "42");
+
+        let config = OsrmConfig {
+            auth: Some(OsrmAuth::ApiKey { header: "x-api-key".to_string(), key: "k".to_string() }),
+            ..config
+        };
+        let request = AsyncOsrmClient::new(config.clone()).unwrap().get("http://osrm/table").build().unwrap();
+        assert_eq!(request.headers()["x-api-key"], "k");
+        assert!(!format!("{config:?}").contains("\"k\""), "Debug leaks the key");
+    }
+
+    #[test]
+    fn test_ping_outcome() {
+        assert!(ping_url(&OsrmConfig::default()).ends_with("/table/v1/car/0.000000,0.000000;0.000000,0.000000"));
+        assert!(ping_outcome(200, br#"{"code":"Ok","durations":[[0,0],[0,0]]}"#).is_ok());
+        // A single coordinate, as earlier pings sent
+        assert!(matches!(
+            ping_outcome(400, br#"{"code":"InvalidOptions","message":"Number of coordinates needs to be at least two."}"#),
+            Err(ref err @ OsrmRouteError::OsrmError(_)) if !err.is_transient()
+        ));
+        let loading = ping_outcome(503, br#"{"code":"InvalidService","message":"Dataset not loaded"}"#);
+        assert!(matches!(loading, Err(ref err @ OsrmRouteError::HttpStatus(503)) if err.is_transient()));
+        assert!(matches!(
+            ping_outcome(400, br#"{"code":"InvalidService","message":"Service table not found"}"#),
+            Err(OsrmRouteError::OsrmError(code)) if code == "InvalidService"
+        ));
+        // A gateway in front of OSRM
+        assert!(matches!(ping_outcome(401, b"Unauthorized"), Err(OsrmRouteError::HttpStatus(401))));
+        assert!(matches!(ping_outcome(200, b"<html>"), Err(OsrmRouteError::ParseError(_))));
+    }
+
+    #[test]
+    fn test_wait_ready_gives_up_after_timeout() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let client = OsrmClient::new(OsrmConfig {
+            base_url: format!("http://127.0.0.1:{port}"),
+            initial_backoff_ms: 10,
+            ..Default::default()
+        })
+        .unwrap();
+
+        let started = Instant::now();
+        let result = client.wait_ready(Duration::from_millis(100));
+
+        assert!(matches!(result, Err(OsrmRouteError::ConnectionFailed(_))), "{result:?}");
+        assert!(started.elapsed() >= Duration::from_millis(100));
+    }
+
+    fn geometry(duration_seconds: i32) -> RouteGeometry {
+        RouteGeometry {
+            encoded_polyline: String::new(),
+            precision: 5,
+            points: None,
+            distance_meters: 0,
+            duration_seconds,
+            legs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_cached_geometry_served_without_a_request() {
+        // Nothing listens here, so only a cache hit can succeed
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let client = OsrmClient::new(OsrmConfig {
+            base_url: format!("http://127.0.0.1:{port}"),
+            max_retries: 0,
+            ..Default::default()
+        })
+        .unwrap()
+        .with_geometry_cache(8);
+        let waypoints = [(36.1, -115.1), (36.2, -115.2)];
+        let url = format!(
+            "http://127.0.0.1:{port}/route/v1/car/-115.100000,36.100000;-115.200000,36.200000\
+             ?overview=full&geometries=polyline&annotations=distance"
+        );
+        client.geometry_cache.as_ref().unwrap().lock().unwrap().insert(url, geometry(600));
+
+        assert_eq!(client.get_route_geometry(&waypoints).unwrap().duration_seconds, 600);
+        // Another profile is another route
+        assert!(client.with_profile("bicycle").get_route_geometry(&waypoints).is_err());
+
+        client.clear_geometry_cache();
+        assert!(client.get_route_geometry(&waypoints).is_err());
+    }
+
+    #[test]
+    fn test_geometry_cache_evicts_oldest() {
+        let mut cache = GeometryCache { capacity: 2, entries: HashMap::new(), order: VecDeque::new() };
+        cache.insert("a".to_string(), geometry(1));
+        cache.insert("b".to_string(), geometry(2));
+        cache.insert("c".to_string(), geometry(3));
+
+        assert!(!cache.entries.contains_key("a"));
+        assert_eq!(cache.entries.len(), 2);
+        assert_eq!(cache.order, ["b", "c"]);
+    }
+
+    /// Parse a one-route `/route` response.
+    fn parse_route(json: &str) -> OsrmRoute {
+        let body: OsrmRouteResponse = serde_json::from_str(json).unwrap();
+        body.routes.into_iter().next().unwrap()
+    }
+
+    #[test]
+    fn test_legs_join_into_the_full_route() {
+        let route = Polyline::new(vec![
+            (36.1, -115.1),
+            (36.12, -115.11),
+            (36.15, -115.15),
+            (36.17, -115.12),
+            (36.2, -115.2),
+            (36.22, -115.21),
+        ]);
+        let osrm_route = parse_route(&format!(
+            r#"{{"code":"Ok","routes":[{{"geometry":"{}","distance":3000,"duration":900,
+                "legs":[{{"distance":1000,"duration":300,"annotation":{{"distance":[400,600]}}}},
+                        {{"distance":1500,"duration":450,"annotation":{{"distance":[700,800]}}}},
+                        {{"distance":500,"duration":150,"annotation":{{"distance":[500]}}}}]}}]}}"#,
+            route.encode()
+        ));
+
+        let geometry = route_geometry(osrm_route, GeometryFormat::Polyline);
+
+        let legs: Vec<Polyline> = geometry.legs.iter().map(|leg| Polyline::decode(&leg.encoded_polyline)).collect();
+        assert_eq!(legs.iter().map(|leg| leg.points().len()).collect::<Vec<_>>(), [3, 3, 2]);
+        let mut joined = legs[0].points().to_vec();
+        for leg in &legs[1..] {
+            assert_eq!(leg.points()[0], *joined.last().unwrap(), "legs meet at the waypoints");
+            joined.extend(&leg.points()[1..]);
+        }
+        for (point, expected) in joined.iter().zip(geometry.polyline().points()) {
+            assert!((point.0 - expected.0).abs() < 1e-9 && (point.1 - expected.1).abs() < 1e-9);
+        }
+        assert_eq!(joined.len(), route.points().len());
+        assert_eq!(geometry.legs[1].duration_seconds, 450);
+    }
+
+    #[test]
+    fn test_legs_split_where_osrm_ends_them() {
+        // The route passes the second stop before reaching it from the far side
+        let route = Polyline::new(vec![(36.1, -115.1), (36.2, -115.2), (36.3, -115.3), (36.2, -115.2), (36.4, -115.4)]);
+        let osrm_route = parse_route(&format!(
+            r#"{{"code":"Ok","routes":[{{"geometry":"{}","distance":40,"duration":4,
+                "legs":[{{"distance":30,"duration":3,"annotation":{{"distance":[10,10,10]}}}},
+                        {{"distance":10,"duration":1,"annotation":{{"distance":[10]}}}}]}}]}}"#,
+            route.encode()
+        ));
+
+        let geometry = route_geometry(osrm_route, GeometryFormat::Polyline);
+
+        assert_eq!(Polyline::decode(&geometry.legs[0].encoded_polyline).points(), &route.points()[..4]);
+        assert_eq!(Polyline::decode(&geometry.legs[1].encoded_polyline).points(), &route.points()[3..]);
+    }
+
+    #[test]
+    fn test_legs_without_annotations_are_empty() {
+        let osrm_route = parse_route(
+            r#"{"code":"Ok","routes":[{"geometry":"_p~iF~ps|U_ulLnnqC","distance":1,"duration":1,
+                "legs":[{"distance":1,"duration":1}]}]}"#,
+        );
+
+        let geometry = route_geometry(osrm_route, GeometryFormat::Polyline);
+
+        assert!(geometry.legs[0].encoded_polyline.is_empty());
+        assert_eq!(geometry.polyline().points().len(), 2);
+    }
+
+    #[test]
+    fn test_geojson_geometry_comes_decoded() {
+        let route = parse_route(
+            r#"{"code":"Ok","routes":[{
+                "geometry":{"type":"LineString","coordinates":[[-115.1,36.1],[-115.15,36.15],[-115.2,36.2]]},
+                "distance":1200.4,"duration":300.6,
+                "legs":[{"distance":1200.4,"duration":300.6,"annotation":{"distance":[600.2,600.2]}}]
+            }]}"#,
+        );
+
+        let geometry = route_geometry(route, GeometryFormat::GeoJson);
+
+        let points = [(36.1, -115.1), (36.15, -115.15), (36.2, -115.2)];
+        assert_eq!(geometry.points.as_ref().unwrap().points(), &points);
+        assert_eq!(geometry.polyline().points(), &points);
+        assert!(geometry.encoded_polyline.is_empty());
+        assert_eq!(geometry.legs[0].points.as_ref().unwrap().points(), &points);
+        assert_eq!((geometry.distance_meters, geometry.duration_seconds), (1200, 301));
+    }
 }
@@ -1,9 +1,15 @@
-//! OSRM dataset preparation helpers (download + preprocess).
+//! OSRM dataset preparation helpers (download + preprocess + serve).
 
-use std::fs::{self, File};
-use std::io::{self, BufWriter, Write};
+use std::ffi::OsStr;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::{Duration, SystemTime};
+
+use md5::{Digest, Md5};
+
+use crate::osrm::{OsrmClient, OsrmConfig};
 
 #[derive(Debug, Clone)]
 pub struct GeofabrikRegion {
@@ -19,7 +25,7 @@ impl GeofabrikRegion {
     pub fn name(&self) -> String {
         self.path
             .split('/')
-            .last()
+            .next_back()
             .unwrap_or("region")
             .to_string()
     }
@@ -37,11 +43,73 @@ pub enum OsrmPrepMode {
     Mld,
 }
 
+/// Image with the OSRM tools, for [`OsrmToolchain::Docker`].
+const OSRM_IMAGE: &str = "osrm/osrm-backend";
+
+/// Image with `osmium`, for clipping under [`OsrmToolchain::Docker`].
+pub const OSMIUM_IMAGE: &str = "iboates/osmium";
+
+/// How the OSRM preprocessing tools are run.
+#[derive(Debug, Clone, Default)]
+pub enum OsrmToolchain {
+    /// The `osrm/osrm-backend` image, through `docker run`.
+    #[default]
+    Docker,
+    /// Locally installed binaries, for hosts without a Docker daemon.
+    Local(LocalOsrmTools),
+}
+
+/// Paths to locally installed OSRM tools.
+#[derive(Debug, Clone)]
+pub struct LocalOsrmTools {
+    pub extract: PathBuf,
+    pub partition: PathBuf,
+    pub customize: PathBuf,
+    /// `osmium`, for clipping to a bounding box.
+    pub osmium: PathBuf,
+    /// Lua profile used when the config doesn't set one.
+    pub default_profile: PathBuf,
+}
+
+impl Default for LocalOsrmTools {
+    /// The tools on `PATH`, with the car profile where `make install` puts it.
+    fn default() -> Self {
+        Self {
+            extract: "osrm-extract".into(),
+            partition: "osrm-partition".into(),
+            customize: "osrm-customize".into(),
+            osmium: "osmium".into(),
+            default_profile: "/usr/local/share/osrm/profiles/car.lua".into(),
+        }
+    }
+}
+
+impl LocalOsrmTools {
+    /// The tools in `bin_dir`, with the car profile at `default_profile`.
+    pub fn in_dir(bin_dir: impl AsRef<Path>, default_profile: impl Into<PathBuf>) -> Self {
+        let bin_dir = bin_dir.as_ref();
+        Self {
+            extract: bin_dir.join("osrm-extract"),
+            partition: bin_dir.join("osrm-partition"),
+            customize: bin_dir.join("osrm-customize"),
+            osmium: bin_dir.join("osmium"),
+            default_profile: default_profile.into(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct OsrmDatasetConfig {
     pub region: GeofabrikRegion,
     pub data_root: PathBuf,
     pub mode: OsrmPrepMode,
+    /// Lua profile for `osrm-extract` (e.g., a truck profile with height and
+    /// weight restrictions). `None` uses the image's `/opt/car.lua`.
+    pub profile: Option<PathBuf>,
+    pub toolchain: OsrmToolchain,
+    /// Service area as ((min_lat, min_lng), (max_lat, max_lng)). The region
+    /// is clipped to it with `osmium extract` before preprocessing.
+    pub bbox: Option<((f64, f64), (f64, f64))>,
 }
 
 impl OsrmDatasetConfig {
@@ -50,74 +118,274 @@ impl OsrmDatasetConfig {
             region,
             data_root: data_root.into(),
             mode: OsrmPrepMode::Mld,
+            profile: None,
+            toolchain: OsrmToolchain::Docker,
+            bbox: None,
         }
     }
+
+    /// Extracts with the Lua profile at `path` instead of the car profile.
+    ///
+    /// The dataset goes in a subdirectory named after the profile file, so
+    /// car and truck datasets for a region live side by side. The profile is
+    /// mounted next to the image's stock profiles, so `require("lib/...")`
+    /// still resolves.
+    pub fn with_profile(mut self, path: impl Into<PathBuf>) -> Self {
+        self.profile = Some(path.into());
+        self
+    }
+
+    /// Runs installed OSRM binaries instead of Docker.
+    pub fn with_local_tools(mut self, tools: LocalOsrmTools) -> Self {
+        self.toolchain = OsrmToolchain::Local(tools);
+        self
+    }
+
+    /// Preprocesses only the area between the `south_west` and `north_east`
+    /// corners, as (lat, lng).
+    ///
+    /// Extracting a metro area instead of its whole state is much faster
+    /// and smaller. The clipped dataset goes in a subdirectory named after
+    /// the box; routes that leave it can't be found.
+    pub fn with_bbox(mut self, south_west: (f64, f64), north_east: (f64, f64)) -> Self {
+        self.bbox = Some((south_west, north_east));
+        self
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct OsrmDataset {
+    /// Directory holding the `.osrm` files, to mount at `/data` for
+    /// `osrm-routed`.
     pub data_dir: PathBuf,
     pub osrm_base: PathBuf,
     pub pbf_path: PathBuf,
 }
 
+/// How far a PBF download has got.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DownloadProgress {
+    /// Bytes on disk, including any resumed from an earlier attempt.
+    pub downloaded: u64,
+    /// Size of the file, if the server reported it.
+    pub total: Option<u64>,
+}
+
+/// Why a dataset couldn't be prepared or served.
 #[derive(Debug)]
 pub enum OsrmDataError {
-    Io(io::Error),
-    Http(reqwest::Error),
-    ProcessFailure(String),
+    /// A file or directory couldn't be read or written.
+    Io { path: PathBuf, source: io::Error },
+    /// A download failed.
+    Http { url: String, source: reqwest::Error },
+    /// The HTTP client for a started server couldn't be built.
+    Client { source: reqwest::Error },
+    /// The checksum published for a download isn't an MD5 digest.
+    InvalidChecksumFile { url: String },
+    /// The downloaded PBF doesn't match the published MD5. The partial file
+    /// is removed, so the next attempt starts over.
+    ChecksumMismatch { path: PathBuf, expected: String, actual: String },
+    /// A tool couldn't be started, usually because it isn't installed (e.g.,
+    /// no `docker` on `PATH`).
+    ToolMissing { program: PathBuf, source: io::Error },
+    /// A tool ran and failed. `code` is `None` if it was killed by a signal;
+    /// `stderr` is empty when the tool wrote to the terminal.
+    ProcessFailure { command: String, code: Option<i32>, stderr: String },
+    /// A server container started without publishing the OSRM port.
+    NoPublishedPort { container: String },
+    /// The server didn't answer in time, or answered with an error.
+    ServerNotReady { base_url: String, source: crate::osrm::OsrmRouteError },
 }
 
-impl From<io::Error> for OsrmDataError {
-    fn from(err: io::Error) -> Self {
-        OsrmDataError::Io(err)
+impl std::fmt::Display for OsrmDataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OsrmDataError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            OsrmDataError::Http { url, source } => write!(f, "download of {} failed: {}", url, source),
+            OsrmDataError::Client { source } => write!(f, "could not build HTTP client: {}", source),
+            OsrmDataError::InvalidChecksumFile { url } => write!(f, "{} is not an MD5 checksum file", url),
+            OsrmDataError::ChecksumMismatch { path, expected, actual } => write!(
+                f,
+                "{} has MD5 {}, expected {}; the file was removed",
+                path.display(),
+                actual,
+                expected
+            ),
+            OsrmDataError::ToolMissing { program, source } => {
+                write!(f, "could not run {}: {}; is it installed and on PATH?", program.display(), source)
+            }
+            OsrmDataError::ProcessFailure { command, code, stderr } => {
+                match code {
+                    // Docker's own failures (daemon down, image pull) exit 125
+                    Some(125) if command.starts_with("docker ") => write!(
+                        f,
+                        "`{}` exited with code 125; is the Docker daemon running and the image available?",
+                        command
+                    )?,
+                    Some(code) => write!(f, "`{}` exited with code {}", command, code)?,
+                    None => write!(f, "`{}` was killed by a signal", command)?,
+                }
+                if !stderr.is_empty() {
+                    write!(f, ": {}", stderr)?;
+                }
+                Ok(())
+            }
+            OsrmDataError::NoPublishedPort { container } => {
+                write!(f, "container {} publishes no port for 5000/tcp", container)
+            }
+            OsrmDataError::ServerNotReady { base_url, source } => {
+                write!(f, "OSRM server at {} not ready: {}", base_url, source)
+            }
+        }
     }
 }
 
-impl From<reqwest::Error> for OsrmDataError {
-    fn from(err: reqwest::Error) -> Self {
-        OsrmDataError::Http(err)
+impl std::error::Error for OsrmDataError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OsrmDataError::Io { source, .. } | OsrmDataError::ToolMissing { source, .. } => Some(source),
+            OsrmDataError::Http { source, .. } | OsrmDataError::Client { source } => Some(source),
+            OsrmDataError::ServerNotReady { source, .. } => Some(source),
+            OsrmDataError::InvalidChecksumFile { .. }
+            | OsrmDataError::ChecksumMismatch { .. }
+            | OsrmDataError::ProcessFailure { .. }
+            | OsrmDataError::NoPublishedPort { .. } => None,
+        }
     }
 }
 
+/// Tags an I/O error with the path involved.
+fn at(path: &Path) -> impl FnOnce(io::Error) -> OsrmDataError + '_ {
+    move |source| OsrmDataError::Io { path: path.to_path_buf(), source }
+}
+
+/// Tags an HTTP error with the URL requested.
+fn fetching(url: &str) -> impl FnOnce(reqwest::Error) -> OsrmDataError + '_ {
+    move |source| OsrmDataError::Http { url: url.to_string(), source }
+}
+
 impl OsrmDataset {
     pub fn ensure(config: &OsrmDatasetConfig) -> Result<Self, OsrmDataError> {
+        Self::ensure_with_progress(config, |_| {})
+    }
+
+    /// Like [`ensure`](Self::ensure), reporting PBF download progress to
+    /// `progress` as the file streams to disk.
+    pub fn ensure_with_progress(
+        config: &OsrmDatasetConfig,
+        progress: impl FnMut(DownloadProgress),
+    ) -> Result<Self, OsrmDataError> {
         let region_name = config.region.name();
         let data_root = if config.data_root.is_absolute() {
             config.data_root.clone()
         } else {
-            std::env::current_dir()?.join(&config.data_root)
+            std::env::current_dir().map_err(at(Path::new(".")))?.join(&config.data_root)
         };
-        let data_dir = data_root.join(region_name);
-        fs::create_dir_all(&data_dir)?;
+        let region_dir = data_root.join(region_name);
+        fs::create_dir_all(&region_dir).map_err(at(&region_dir))?;
 
         let pbf_name = format!("{}-latest.osm.pbf", config.region.name());
-        let pbf_path = data_dir.join(pbf_name);
+        let pbf_path = region_dir.join(pbf_name);
         if !pbf_path.exists() {
-            download_pbf(&config.region.url(), &pbf_path)?;
+            download_pbf(&config.region.url(), &pbf_path, progress)?;
+        }
+
+        // A custom profile extracts into its own directory.
+        let profile = match &config.profile {
+            Some(profile) => {
+                let profile = fs::canonicalize(profile).map_err(at(profile))?;
+                let stem = profile
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .unwrap_or("custom")
+                    .to_string();
+                Some((profile, stem))
+            }
+            None => None,
+        };
+        let data_dir = match &profile {
+            Some((_, stem)) => region_dir.join(stem),
+            None => region_dir,
+        };
+        let data_dir = match config.bbox {
+            Some(bbox) => data_dir.join(bbox_dir_name(bbox)),
+            None => data_dir,
+        };
+        fs::create_dir_all(&data_dir).map_err(at(&data_dir))?;
+
+        // Extraction reads the PBF in the dataset's directory: a clip of the
+        // region, a link to it, or the region's own file.
+        let input = data_dir.join(file_name(&pbf_path));
+        if let Some(bbox) = config.bbox
+            && !input.exists()
+        {
+            clip_pbf(&config.toolchain, &pbf_path, &input, bbox)?;
         }
 
         let osrm_base = data_dir.join(format!("{}-latest.osrm", config.region.name()));
         if !osrm_base.exists() {
-            run_docker(&[
-                "osrm-extract",
-                "-p",
-                "/opt/car.lua",
-                &format!("/data/{}", file_name(&pbf_path)),
-            ], &data_dir)?;
+            match &config.toolchain {
+                OsrmToolchain::Docker => {
+                    // The PBF and a custom profile are mounted in; the
+                    // profile sits beside the stock ones so their `lib/`
+                    // modules resolve.
+                    let mut mounts = Vec::new();
+                    if !input.exists() {
+                        mounts.push((pbf_path.clone(), format!("/data/{}", file_name(&pbf_path))));
+                    }
+                    let profile = match profile {
+                        Some((profile, stem)) => {
+                            mounts.push((profile, format!("/opt/{}.lua", stem)));
+                            format!("/opt/{}.lua", stem)
+                        }
+                        None => "/opt/car.lua".to_string(),
+                    };
+                    run_docker(OSRM_IMAGE, &[
+                        "osrm-extract",
+                        "-p",
+                        &profile,
+                        &format!("/data/{}", file_name(&pbf_path)),
+                    ], &data_dir, &mounts)?;
+                }
+                OsrmToolchain::Local(tools) => {
+                    // osrm-extract writes beside its input, so a dataset in
+                    // a subdirectory extracts from a link to the PBF.
+                    if !input.exists() && fs::hard_link(&pbf_path, &input).is_err() {
+                        fs::copy(&pbf_path, &input).map_err(at(&input))?;
+                    }
+                    let profile = profile.map_or_else(|| tools.default_profile.clone(), |(profile, _)| profile);
+                    run_local(&tools.extract, &[
+                        OsStr::new("-p"),
+                        profile.as_os_str(),
+                        input.as_os_str(),
+                    ])?;
+                }
+            }
         }
 
         match config.mode {
             OsrmPrepMode::Mld => {
                 if !mld_ready(&osrm_base) {
-                    run_docker(
-                        &["osrm-partition", &format!("/data/{}", file_name(&osrm_base))],
-                        &data_dir,
-                    )?;
-                    run_docker(
-                        &["osrm-customize", &format!("/data/{}", file_name(&osrm_base))],
-                        &data_dir,
-                    )?;
+                    match &config.toolchain {
+                        OsrmToolchain::Docker => {
+                            run_docker(
+                                OSRM_IMAGE,
+                                &["osrm-partition", &format!("/data/{}", file_name(&osrm_base))],
+                                &data_dir,
+                                &[],
+                            )?;
+                            run_docker(
+                                OSRM_IMAGE,
+                                &["osrm-customize", &format!("/data/{}", file_name(&osrm_base))],
+                                &data_dir,
+                                &[],
+                            )?;
+                        }
+                        OsrmToolchain::Local(tools) => {
+                            run_local(&tools.partition, &[osrm_base.as_os_str()])?;
+                            run_local(&tools.customize, &[osrm_base.as_os_str()])?;
+                        }
+                    }
                 }
             }
         }
@@ -130,17 +398,235 @@ impl OsrmDataset {
     }
 }
 
-fn download_pbf(url: &str, dest: &Path) -> Result<(), OsrmDataError> {
-    let response = reqwest::blocking::get(url)?.error_for_status()?;
+/// How long [`OsrmServer::start`] waits for the server to load its dataset.
+const SERVER_STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// An `osrm-routed` container serving a prepared dataset.
+///
+/// The container is named after the dataset and its preprocessing time and
+/// is left running when this is dropped, so later runs (e.g., the next test
+/// binary) reuse it instead of waiting for another load. [`stop`](Self::stop)
+/// removes it.
+#[derive(Debug)]
+pub struct OsrmServer {
+    container: String,
+    base_url: String,
+    client: OsrmClient,
+}
+
+impl OsrmServer {
+    /// Starts, or reuses, a server for `dataset` on a free local port and
+    /// waits until it answers requests. Concurrent calls share one container:
+    /// a caller that loses the race to create it starts the winner's.
+    pub fn start(dataset: &OsrmDataset) -> Result<Self, OsrmDataError> {
+        let container = server_container_name(dataset);
+        let existing = docker_output(&["ps", "-aq", "--filter", &format!("name=^{}$", container)])?;
+        let mut created = false;
+        if existing.trim().is_empty() {
+            let data_dir = format!("{}:/data", dataset.data_dir.display());
+            let osrm_path = format!("/data/{}", file_name(&dataset.osrm_base));
+            let run = docker_output(&[
+                "run",
+                "-d",
+                "--name",
+                &container,
+                "-p",
+                "127.0.0.1::5000",
+                "-v",
+                &data_dir,
+                "--entrypoint",
+                "osrm-routed",
+                OSRM_IMAGE,
+                "--algorithm",
+                "mld",
+                &osrm_path,
+            ]);
+            match run {
+                Ok(_) => created = true,
+                // Another caller created it since the check
+                Err(OsrmDataError::ProcessFailure { stderr, .. }) if stderr.contains("is already in use") => {}
+                Err(err) => return Err(err),
+            }
+        }
+        if !created {
+            docker_output(&["start", &container])?;
+        }
+
+        let ports = docker_output(&["port", &container, "5000/tcp"])?;
+        let port = ports
+            .lines()
+            .find_map(|line| line.rsplit(':').next()?.trim().parse::<u16>().ok())
+            .ok_or_else(|| OsrmDataError::NoPublishedPort { container: container.clone() })?;
+        let base_url = format!("http://127.0.0.1:{}", port);
+        let client = OsrmClient::new(OsrmConfig {
+            base_url: base_url.clone(),
+            ..Default::default()
+        })
+        .map_err(|source| OsrmDataError::Client { source })?;
+        client
+            .wait_ready(SERVER_STARTUP_TIMEOUT)
+            .map_err(|source| OsrmDataError::ServerNotReady { base_url: base_url.clone(), source })?;
+        Ok(Self {
+            container,
+            base_url,
+            client,
+        })
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// A client for the server's `car` routes; [`OsrmClient::with_profile`]
+    /// and friends derive others on the same pool.
+    pub fn client(&self) -> &OsrmClient {
+        &self.client
+    }
+
+    /// Removes the container.
+    pub fn stop(self) -> Result<(), OsrmDataError> {
+        docker_output(&["rm", "-f", &self.container])?;
+        Ok(())
+    }
+}
+
+/// `osrm-<dataset>-mld-<preprocessed at>`, so a re-prepared dataset gets a
+/// fresh container.
+fn server_container_name(dataset: &OsrmDataset) -> String {
+    let prepared = fs::metadata(dataset.osrm_base.with_extension("osrm.partition"))
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let stem = file_name(&dataset.osrm_base);
+    let stem = stem.trim_end_matches(".osrm");
+    let dir = file_name(&dataset.data_dir);
+    let name = if stem.starts_with(dir.as_str()) {
+        format!("osrm-{}-mld-{}", stem, prepared)
+    } else {
+        format!("osrm-{}-{}-mld-{}", stem, dir, prepared)
+    };
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-') { c } else { '-' })
+        .collect()
+}
+
+/// Runs a docker CLI command, returning its stdout.
+fn docker_output(args: &[&str]) -> Result<String, OsrmDataError> {
+    let mut command = Command::new("docker");
+    command.args(args);
+    let output = command.output().map_err(missing(&command))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(OsrmDataError::ProcessFailure {
+            command: command_line(&command),
+            code: output.status.code(),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        })
+    }
+}
+
+/// How long [`download_pbf`] waits to connect to the download server.
+const DOWNLOAD_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long [`download_pbf`] waits for a response or the next chunk of the
+/// body before giving up, leaving the partial file to resume.
+const DOWNLOAD_READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Streams `url` to `dest`, resuming a partial download left by an earlier
+/// attempt, and checks it against the MD5 published at `{url}.md5`.
+fn download_pbf(
+    url: &str,
+    dest: &Path,
+    mut progress: impl FnMut(DownloadProgress),
+) -> Result<(), OsrmDataError> {
+    // The blocking client's timeout bounds each send and each body read, not
+    // the whole transfer, so a stalled connection fails instead of hanging
+    let client = reqwest::blocking::Client::builder()
+        .connect_timeout(DOWNLOAD_CONNECT_TIMEOUT)
+        .timeout(DOWNLOAD_READ_TIMEOUT)
+        .build()
+        .map_err(fetching(url))?;
+    let md5_url = format!("{}.md5", url);
+    let expected = client
+        .get(&md5_url)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.text())
+        .map_err(fetching(&md5_url))?;
+    let expected = parse_md5_file(&expected).ok_or(OsrmDataError::InvalidChecksumFile { url: md5_url })?;
+
     let tmp_path = dest.with_extension("tmp");
-    let mut writer = BufWriter::new(File::create(&tmp_path)?);
-    let bytes = response.bytes()?;
-    writer.write_all(&bytes)?;
-    writer.flush()?;
-    fs::rename(tmp_path, dest)?;
+    let resume_from = fs::metadata(&tmp_path).map(|meta| meta.len()).unwrap_or(0);
+    let mut request = client.get(url);
+    if resume_from > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", resume_from));
+    }
+    let mut response = request.send().map_err(fetching(url))?;
+    if response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        // The partial file is no prefix of the current extract; start over
+        response = client.get(url).send().map_err(fetching(url))?;
+    }
+    let mut response = response.error_for_status().map_err(fetching(url))?;
+
+    let resumed = response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+    let mut downloaded = if resumed { resume_from } else { 0 };
+    let total = response.content_length().map(|length| downloaded + length);
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(&tmp_path)
+        .map_err(at(&tmp_path))?;
+    let mut writer = BufWriter::new(file);
+    let mut buffer = vec![0; 64 * 1024];
+    progress(DownloadProgress { downloaded, total });
+    loop {
+        // Reading the body fails on a dropped connection; the partial file
+        // stays for the next attempt to resume
+        let read = response.read(&mut buffer).map_err(at(&tmp_path))?;
+        if read == 0 {
+            break;
+        }
+        writer.write_all(&buffer[..read]).map_err(at(&tmp_path))?;
+        downloaded += read as u64;
+        progress(DownloadProgress { downloaded, total });
+    }
+    writer.flush().map_err(at(&tmp_path))?;
+    drop(writer);
+
+    let actual = md5_file(&tmp_path).map_err(at(&tmp_path))?;
+    if actual != expected {
+        fs::remove_file(&tmp_path).map_err(at(&tmp_path))?;
+        return Err(OsrmDataError::ChecksumMismatch { path: dest.to_path_buf(), expected, actual });
+    }
+    fs::rename(&tmp_path, dest).map_err(at(dest))?;
     Ok(())
 }
 
+/// The digest from an `md5sum`-style line: `<hex>  <file name>`.
+fn parse_md5_file(contents: &str) -> Option<String> {
+    let digest = contents.split_whitespace().next()?.to_ascii_lowercase();
+    (digest.len() == 32 && digest.bytes().all(|byte| byte.is_ascii_hexdigit())).then_some(digest)
+}
+
+fn md5_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut md5 = Md5::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        md5.update(&buffer[..read]);
+    }
+    Ok(md5.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
 fn mld_ready(osrm_base: &Path) -> bool {
     let partition = osrm_base.with_extension("osrm.partition");
     let mldgr = osrm_base.with_extension("osrm.mldgr");
@@ -148,30 +634,144 @@ fn mld_ready(osrm_base: &Path) -> bool {
     osrm_base.exists() && partition.exists() && mldgr.exists() && cells.exists()
 }
 
-fn run_docker(args: &[&str], data_dir: &Path) -> Result<(), OsrmDataError> {
-    let status = Command::new("docker")
+/// Directory for a clipped dataset, e.g. `bbox_36.0000_-115.4000_36.4000_-114.9000`.
+fn bbox_dir_name(((min_lat, min_lng), (max_lat, max_lng)): ((f64, f64), (f64, f64))) -> String {
+    format!("bbox_{:.4}_{:.4}_{:.4}_{:.4}", min_lat, min_lng, max_lat, max_lng)
+}
+
+/// Clips `source` to `bbox` with `osmium extract`, writing `dest`.
+fn clip_pbf(
+    toolchain: &OsrmToolchain,
+    source: &Path,
+    dest: &Path,
+    ((min_lat, min_lng), (max_lat, max_lng)): ((f64, f64), (f64, f64)),
+) -> Result<(), OsrmDataError> {
+    // osmium takes left,bottom,right,top
+    let bbox = format!("{},{},{},{}", min_lng, min_lat, max_lng, max_lat);
+    // Written under another name first, so a failed clip isn't mistaken
+    // for a finished one
+    let tmp_path = dest.with_extension("clip.pbf");
+    let dir = dest.parent().unwrap_or(Path::new("."));
+    match toolchain {
+        OsrmToolchain::Docker => {
+            let input = format!("/input/{}", file_name(source));
+            run_docker(OSMIUM_IMAGE, &[
+                "osmium",
+                "extract",
+                "--bbox",
+                &bbox,
+                "--overwrite",
+                "-o",
+                &format!("/data/{}", file_name(&tmp_path)),
+                &input,
+            ], dir, &[(source.to_path_buf(), input.clone())])?;
+        }
+        OsrmToolchain::Local(tools) => {
+            run_local(&tools.osmium, &[
+                OsStr::new("extract"),
+                OsStr::new("--bbox"),
+                OsStr::new(&bbox),
+                OsStr::new("--overwrite"),
+                OsStr::new("-o"),
+                tmp_path.as_os_str(),
+                source.as_os_str(),
+            ])?;
+        }
+    }
+    fs::rename(&tmp_path, dest).map_err(at(dest))?;
+    Ok(())
+}
+
+/// Runs a tool in `image`, with `args[0]` as the entrypoint, `data_dir` at
+/// `/data`, and read-only `mounts` of host files at container paths.
+fn run_docker(
+    image: &str,
+    args: &[&str],
+    data_dir: &Path,
+    mounts: &[(PathBuf, String)],
+) -> Result<(), OsrmDataError> {
+    let mut command = Command::new("docker");
+    command
         .arg("run")
         .arg("--rm")
         .arg("-t")
         .arg("-v")
-        .arg(format!("{}:/data", data_dir.display()))
-        .arg("osrm/osrm-backend")
-        .args(args)
-        .status()?;
+        .arg(format!("{}:/data", data_dir.display()));
+    for (host, container) in mounts {
+        command
+            .arg("-v")
+            .arg(format!("{}:{}:ro", host.display(), container));
+    }
+    let (entrypoint, args) = args.split_first().unwrap_or((&"", &[]));
+    command
+        .arg("--entrypoint")
+        .arg(entrypoint)
+        .arg(image)
+        .args(args);
+    run(&mut command)
+}
+
+fn run_local(program: &Path, args: &[&OsStr]) -> Result<(), OsrmDataError> {
+    run(Command::new(program).args(args))
+}
 
+/// Runs `command` with its output on the terminal.
+fn run(command: &mut Command) -> Result<(), OsrmDataError> {
+    let status = command.status().map_err(missing(command))?;
     if status.success() {
         Ok(())
     } else {
-        Err(OsrmDataError::ProcessFailure(format!(
-            "docker exited with status {}",
-            status
-        )))
+        Err(OsrmDataError::ProcessFailure {
+            command: command_line(command),
+            code: status.code(),
+            stderr: String::new(),
+        })
     }
 }
 
+/// Tags a failure to start `command`.
+fn missing(command: &Command) -> impl FnOnce(io::Error) -> OsrmDataError {
+    let program = PathBuf::from(command.get_program());
+    move |source| OsrmDataError::ToolMissing { program, source }
+}
+
+/// `command` as it would be typed, for error messages.
+fn command_line(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| arg.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 fn file_name(path: &Path) -> String {
     path.file_name()
         .and_then(|name| name.to_str())
         .unwrap_or_default()
         .to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_tool_is_told_apart_from_a_failing_one() {
+        let err = run_local(Path::new("/nonexistent/osrm-extract"), &[]).unwrap_err();
+        assert!(matches!(err, OsrmDataError::ToolMissing { .. }), "{err:?}");
+        assert!(err.to_string().starts_with("could not run /nonexistent/osrm-extract:"), "{err}");
+
+        let err = run_local(Path::new("false"), &[OsStr::new("--flag")]).unwrap_err();
+        assert!(matches!(err, OsrmDataError::ProcessFailure { code: Some(1), .. }), "{err:?}");
+        assert_eq!(err.to_string(), "`false --flag` exited with code 1");
+    }
+
+    #[test]
+    fn test_parse_md5_file() {
+        assert_eq!(
+            parse_md5_file("4E1E9C4C0C1D1B7A8A2C6F3B1C2D3E4F  nevada-latest.osm.pbf\n").as_deref(),
+            Some("4e1e9c4c0c1d1b7a8a2c6f3b1c2d3e4f")
+        );
+        assert_eq!(parse_md5_file("<html>Not Found</html>"), None);
+    }
+}
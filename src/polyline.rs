@@ -2,10 +2,13 @@
 //!
 //! This module provides a type for working with polylines as decoded
 //! coordinate sequences. Encoding/decoding happens at the boundary
-//! (when receiving from OSRM or sending to frontend).
+//! (when receiving from OSRM or sending to frontend), using the Google
+//! Polyline Algorithm at precision 5 (OSRM, Google) or 6 (Valhalla).
 
 use serde::{Deserialize, Serialize};
 
+use crate::haversine::HaversineMatrix;
+
 /// A polyline representing a route geometry as decoded coordinates.
 ///
 /// Stores latitude/longitude points directly for internal processing.
@@ -33,6 +36,239 @@ impl Polyline {
     pub fn into_points(self) -> Vec<(f64, f64)> {
         self.points
     }
+
+    /// Decodes a precision-5 encoded polyline (OSRM's default geometry format).
+    ///
+    /// Decoding stops at the first truncated coordinate pair.
+    pub fn decode(encoded: &str) -> Self {
+        Self::decode_with_precision(encoded, 5)
+    }
+
+    /// Decodes a precision-6 encoded polyline (Valhalla's shape format,
+    /// OSRM's `polyline6`).
+    pub fn decode6(encoded: &str) -> Self {
+        Self::decode_with_precision(encoded, 6)
+    }
+
+    /// Decodes a polyline encoded with `precision` decimal digits.
+    pub fn decode_with_precision(encoded: &str, precision: u32) -> Self {
+        Self::decode_with_factor(encoded, 10f64.powi(precision as i32))
+    }
+
+    /// Encodes the points as a precision-5 polyline.
+    pub fn encode(&self) -> String {
+        self.encode_with_precision(5)
+    }
+
+    /// Encodes the points as a precision-6 polyline.
+    pub fn encode6(&self) -> String {
+        self.encode_with_precision(6)
+    }
+
+    /// Encodes the points with `precision` decimal digits.
+    pub fn encode_with_precision(&self, precision: u32) -> String {
+        self.encode_with_factor(10f64.powi(precision as i32))
+    }
+
+    /// Great-circle length of the line in meters.
+    pub fn length_meters(&self) -> f64 {
+        self.segment_lengths().sum()
+    }
+
+    /// Smallest box containing every point, as ((min_lat, min_lng), (max_lat, max_lng)).
+    ///
+    /// Returns `None` for an empty polyline. Longitudes are not wrapped, so a
+    /// line crossing the antimeridian gets a box spanning most of the globe.
+    pub fn bounding_box(&self) -> Option<((f64, f64), (f64, f64))> {
+        let (&first, rest) = self.points.split_first()?;
+        Some(rest.iter().fold((first, first), |((min_lat, min_lng), (max_lat, max_lng)), &(lat, lng)| {
+            ((min_lat.min(lat), min_lng.min(lng)), (max_lat.max(lat), max_lng.max(lng)))
+        }))
+    }
+
+    /// The point a `fraction` (0.0 to 1.0) of the way along the line by length.
+    ///
+    /// Interpolates along the great circle within the containing segment.
+    /// Fractions outside the range are clamped. Returns `None` for an empty
+    /// polyline.
+    pub fn point_at_fraction(&self, fraction: f64) -> Option<(f64, f64)> {
+        let last = *self.points.last()?;
+        let mut remaining = self.length_meters() * fraction.clamp(0.0, 1.0);
+
+        for (segment, length) in self.points.windows(2).zip(self.segment_lengths()) {
+            if remaining <= length && length > 0.0 {
+                return Some(interpolate_great_circle(segment[0], segment[1], remaining / length));
+            }
+            remaining -= length;
+        }
+        Some(last)
+    }
+
+    fn segment_lengths(&self) -> impl Iterator<Item = f64> + '_ {
+        self.points
+            .windows(2)
+            .map(|segment| HaversineMatrix::haversine_km(segment[0], segment[1]) * 1000.0)
+    }
+
+    /// Simplifies the line with the Ramer–Douglas–Peucker algorithm.
+    ///
+    /// Drops points that lie within `tolerance_m` meters of the simplified
+    /// line. The first and last points are always kept. Distances use a local
+    /// flat-earth projection, which is accurate at route scales.
+    pub fn simplify(&self, tolerance_m: f64) -> Self {
+        if self.points.len() < 3 {
+            return self.clone();
+        }
+
+        let projected = project_meters(&self.points);
+        let mut keep = vec![false; self.points.len()];
+        keep[0] = true;
+        keep[self.points.len() - 1] = true;
+
+        // Explicit stack instead of recursion; long polylines can have tens of thousands of points.
+        let mut stack = vec![(0, self.points.len() - 1)];
+        while let Some((first, last)) = stack.pop() {
+            let farthest = (first + 1..last)
+                .map(|idx| (idx, segment_distance(projected[idx], projected[first], projected[last])))
+                .max_by(|a, b| a.1.total_cmp(&b.1));
+            if let Some((idx, distance)) = farthest
+                && distance > tolerance_m
+            {
+                keep[idx] = true;
+                stack.push((first, idx));
+                stack.push((idx, last));
+            }
+        }
+
+        let points = self
+            .points
+            .iter()
+            .zip(keep)
+            .filter_map(|(&point, kept)| kept.then_some(point))
+            .collect();
+        Self { points }
+    }
+
+    fn decode_with_factor(encoded: &str, factor: f64) -> Self {
+        let bytes = encoded.as_bytes();
+        let mut points = Vec::new();
+        let mut index = 0;
+        let mut lat = 0i64;
+        let mut lng = 0i64;
+
+        while index < bytes.len() {
+            let (Some(dlat), Some(dlng)) = (decode_value(bytes, &mut index), decode_value(bytes, &mut index))
+            else {
+                break;
+            };
+            lat += dlat;
+            lng += dlng;
+            points.push((lat as f64 / factor, lng as f64 / factor));
+        }
+
+        Self { points }
+    }
+
+    fn encode_with_factor(&self, factor: f64) -> String {
+        let mut encoded = String::new();
+        let mut prev_lat = 0i64;
+        let mut prev_lng = 0i64;
+
+        for &(lat, lng) in &self.points {
+            let lat = (lat * factor).round() as i64;
+            let lng = (lng * factor).round() as i64;
+
+            encode_value(lat - prev_lat, &mut encoded);
+            encode_value(lng - prev_lng, &mut encoded);
+
+            prev_lat = lat;
+            prev_lng = lng;
+        }
+
+        encoded
+    }
+}
+
+/// Point a fraction `t` of the way from `from` to `to` along the great circle.
+fn interpolate_great_circle(from: (f64, f64), to: (f64, f64), t: f64) -> (f64, f64) {
+    let to_vector = |(lat, lng): (f64, f64)| {
+        let (lat, lng) = (lat.to_radians(), lng.to_radians());
+        [lat.cos() * lng.cos(), lat.cos() * lng.sin(), lat.sin()]
+    };
+    let (a, b) = (to_vector(from), to_vector(to));
+    let angle = (a[0] * b[0] + a[1] * b[1] + a[2] * b[2]).clamp(-1.0, 1.0).acos();
+    if angle < 1e-12 {
+        return from;
+    }
+
+    // Spherical linear interpolation between the two unit vectors
+    let wa = ((1.0 - t) * angle).sin() / angle.sin();
+    let wb = (t * angle).sin() / angle.sin();
+    let [x, y, z] = [0, 1, 2].map(|i| wa * a[i] + wb * b[i]);
+    (z.atan2(x.hypot(y)).to_degrees(), y.atan2(x).to_degrees())
+}
+
+/// Project (lat, lng) points onto a plane in meters, centered on their mean latitude.
+fn project_meters(points: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    const METERS_PER_DEGREE: f64 = 111_320.0;
+    let mean_lat = points.iter().map(|p| p.0).sum::<f64>() / points.len() as f64;
+    let lng_scale = METERS_PER_DEGREE * mean_lat.to_radians().cos();
+    points
+        .iter()
+        .map(|&(lat, lng)| (lng * lng_scale, lat * METERS_PER_DEGREE))
+        .collect()
+}
+
+/// Distance from `point` to the segment `start`-`end` in the projected plane.
+fn segment_distance(point: (f64, f64), start: (f64, f64), end: (f64, f64)) -> f64 {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let length_sq = dx * dx + dy * dy;
+    let t = if length_sq == 0.0 {
+        0.0
+    } else {
+        (((point.0 - start.0) * dx + (point.1 - start.1) * dy) / length_sq).clamp(0.0, 1.0)
+    };
+    let (x, y) = (start.0 + t * dx, start.1 + t * dy);
+    ((point.0 - x).powi(2) + (point.1 - y).powi(2)).sqrt()
+}
+
+/// Read one zigzag-encoded delta, or `None` if the input ends mid-value or
+/// the value runs past 64 bits.
+fn decode_value(bytes: &[u8], index: &mut usize) -> Option<i64> {
+    let mut shift = 0;
+    let mut result = 0i64;
+    loop {
+        if shift > 60 {
+            return None;
+        }
+        let b = (*bytes.get(*index)? as i64) - 63;
+        *index += 1;
+        result |= (b & 0x1f) << shift;
+        shift += 5;
+        if b < 0x20 {
+            break;
+        }
+    }
+    Some(if (result & 1) != 0 { !(result >> 1) } else { result >> 1 })
+}
+
+fn encode_value(mut value: i64, output: &mut String) {
+    // Two's complement for negative values
+    if value < 0 {
+        value = !value;
+        value <<= 1;
+        value |= 1;
+    } else {
+        value <<= 1;
+    }
+
+    while value >= 0x20 {
+        let chunk = ((value & 0x1f) | 0x20) as u8 + 63;
+        output.push(chunk as char);
+        value >>= 5;
+    }
+
+    output.push((value as u8 + 63) as char);
 }
 
 #[cfg(test)]
@@ -84,4 +320,128 @@ mod tests {
         assert_eq!(p1, p2);
         assert_ne!(p1, p3);
     }
+
+    #[test]
+    fn test_decode_reference_polyline() {
+        // Example from the Google Polyline Algorithm documentation
+        let polyline = Polyline::decode("_p~iF~ps|U_ulLnnqC_mqNvxq`@");
+        assert_eq!(polyline.points(), &[(38.5, -120.2), (40.7, -120.95), (43.252, -126.453)]);
+    }
+
+    #[test]
+    fn test_decode6() {
+        // (38.5, -120.2), (40.7, -120.95), (43.252, -126.453) at precision 6
+        let points = Polyline::decode6("_izlhA~rlgdF_{geC~ywl@_kwzCn`{nI").into_points();
+        assert_eq!(points.len(), 3);
+        assert!((points[0].0 - 38.5).abs() < 1e-6 && (points[0].1 + 120.2).abs() < 1e-6);
+        assert!((points[2].0 - 43.252).abs() < 1e-6 && (points[2].1 + 126.453).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_encode_reference_polyline() {
+        let polyline = Polyline::new(vec![(38.5, -120.2), (40.7, -120.95), (43.252, -126.453)]);
+        assert_eq!(polyline.encode(), "_p~iF~ps|U_ulLnnqC_mqNvxq`@");
+    }
+
+    #[test]
+    fn test_encode_round_trips() {
+        let polyline = Polyline::new(vec![(36.11471, -115.17283), (36.1727, -115.158), (0.0, 0.0)]);
+        assert_eq!(Polyline::decode(&polyline.encode()), polyline);
+        assert_eq!(Polyline::decode6(&polyline.encode6()), polyline);
+    }
+
+    #[test]
+    fn test_higher_precision_keeps_more_digits() {
+        let polyline = Polyline::new(vec![(36.123456, -115.123456)]);
+
+        let five = Polyline::decode_with_precision(&polyline.encode_with_precision(5), 5);
+        let six = Polyline::decode_with_precision(&polyline.encode_with_precision(6), 6);
+        assert_eq!(five.points(), &[(36.12346, -115.12346)]);
+        assert_eq!(six, polyline);
+        assert_eq!(polyline.encode_with_precision(6), polyline.encode6());
+    }
+
+    #[test]
+    fn test_truncated_input_stops_decoding() {
+        let polyline = Polyline::decode("_p~iF~ps|U_ulL");
+        assert_eq!(polyline.points(), &[(38.5, -120.2)]);
+    }
+
+    #[test]
+    fn test_overlong_value_stops_decoding() {
+        // A valid point, then a run of continuation bytes no value fits in
+        let malformed = format!("_p~iF~ps|U{}", "~".repeat(20));
+        assert_eq!(Polyline::decode(&malformed).points(), &[(38.5, -120.2)]);
+    }
+
+    #[test]
+    fn test_simplify_drops_nearly_collinear_points() {
+        // 0.00001 degrees of latitude is ~1.1 m of wobble off a west-east line
+        let polyline = Polyline::new(vec![
+            (36.0, -115.000),
+            (36.00001, -115.001),
+            (36.0, -115.002),
+            (35.99999, -115.003),
+            (36.0, -115.004),
+        ]);
+
+        let simplified = polyline.simplify(5.0);
+        assert_eq!(simplified.points(), &[(36.0, -115.000), (36.0, -115.004)]);
+    }
+
+    #[test]
+    fn test_simplify_keeps_corners() {
+        // An L-shaped route with a ~1.1 km corner
+        let polyline = Polyline::new(vec![(36.0, -115.0), (36.005, -115.0), (36.01, -115.0), (36.01, -115.01)]);
+
+        let simplified = polyline.simplify(10.0);
+        assert_eq!(simplified.points(), &[(36.0, -115.0), (36.01, -115.0), (36.01, -115.01)]);
+    }
+
+    #[test]
+    fn test_simplify_short_polyline_unchanged() {
+        let polyline = Polyline::new(vec![(36.0, -115.0), (36.1, -115.1)]);
+        assert_eq!(polyline.simplify(1000.0), polyline);
+    }
+
+    #[test]
+    fn test_length_meters() {
+        // 0.01 degrees of latitude is ~1.11 km
+        let polyline = Polyline::new(vec![(36.0, -115.0), (36.01, -115.0), (36.02, -115.0)]);
+        assert!((polyline.length_meters() - 2224.0).abs() < 5.0, "{}", polyline.length_meters());
+        assert_eq!(Polyline::new(vec![(36.0, -115.0)]).length_meters(), 0.0);
+    }
+
+    #[test]
+    fn test_bounding_box() {
+        let polyline = Polyline::new(vec![(36.1, -115.2), (36.3, -115.1), (36.2, -115.3)]);
+        assert_eq!(polyline.bounding_box(), Some(((36.1, -115.3), (36.3, -115.1))));
+        assert_eq!(Polyline::new(vec![]).bounding_box(), None);
+    }
+
+    #[test]
+    fn test_point_at_fraction() {
+        // First segment is 1/3 of the length, second is 2/3
+        let polyline = Polyline::new(vec![(36.0, -115.0), (36.01, -115.0), (36.03, -115.0)]);
+
+        let start = polyline.point_at_fraction(0.0).unwrap();
+        let at_corner = polyline.point_at_fraction(1.0 / 3.0).unwrap();
+        let middle = polyline.point_at_fraction(0.5).unwrap();
+        let end = polyline.point_at_fraction(1.5).unwrap();
+
+        assert!((start.0 - 36.0).abs() < 1e-9);
+        assert!((at_corner.0 - 36.01).abs() < 1e-6, "{at_corner:?}");
+        assert!((middle.0 - 36.015).abs() < 1e-6 && (middle.1 + 115.0).abs() < 1e-6, "{middle:?}");
+        assert!((end.0 - 36.03).abs() < 1e-9);
+        assert_eq!(Polyline::new(vec![]).point_at_fraction(0.5), None);
+    }
+
+    #[test]
+    fn test_point_at_fraction_follows_great_circle() {
+        // Halfway between two points on the same parallel bulges poleward
+        let polyline = Polyline::new(vec![(60.0, -30.0), (60.0, 30.0)]);
+        let (lat, lng) = polyline.point_at_fraction(0.5).unwrap();
+        assert!(lat > 60.5, "{lat}");
+        assert!(lng.abs() < 1e-9);
+    }
 }
@@ -0,0 +1,1036 @@
+//! Interop with the `vrp-pragmatic` JSON format.
+//!
+//! Converts [`Problem`]s and plans to and from the problem and solution
+//! format read and written by `vrp-cli` (reinterpretcat/vrp), so both
+//! solvers can be run on identical inputs and their plans compared with the
+//! same tooling. Only the shared subset is converted:
+//!
+//! - Visits become single-delivery jobs with a demand of one. Committed
+//!   windows bound when service may start, and capabilities become
+//!   `allOf` skills. A pinned visitor becomes a `visitor:<id>` skill that
+//!   only that vehicle has.
+//! - Visitors become one vehicle type each, with a single shift spanning
+//!   their availability. Gaps between availability windows become breaks
+//!   fixed at the start of the gap, and a visit cap becomes the capacity.
+//! - Date bounds, recurrence, target times, and current visitors have no
+//!   pragmatic equivalent and are not exported.
+//!
+//! Timestamps are RFC 3339 in UTC. Shifts end at the visitor's end location,
+//! or back at the start, so `vrp` counts a drive home that this solver's
+//! open routes don't.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::{AvailabilityData, Problem, VisitData, VisitorData};
+use crate::solver::{LegDetail, Objective, PlannerResult, ReturnLeg, RouteResult, UnassignedVisit};
+use crate::timezone::{civil_from_days, days_from_civil};
+use crate::traits::{
+    AvailabilityProvider, DistanceMatrixProvider, LocationRef, TimeWindow, UnassignedReason, VisitPinType,
+    service_bounds,
+};
+
+/// Routing profile every exported vehicle uses.
+const PROFILE: &str = "car";
+
+/// Skill prefix marking a visit as pinned to the vehicle with the given ID.
+const PIN_SKILL_PREFIX: &str = "visitor:";
+
+/// Activity types that don't serve a job.
+const NON_JOB_ACTIVITIES: &[&str] = &["departure", "arrival", "break", "reload", "dispatch"];
+
+/// A `vrp-pragmatic` problem.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PragmaticProblem {
+    pub plan: PragmaticPlan,
+    pub fleet: PragmaticFleet,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PragmaticPlan {
+    pub jobs: Vec<PragmaticJob>,
+    /// Job-to-vehicle relations. Not supported on import.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub relations: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PragmaticJob {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pickups: Vec<PragmaticJobTask>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deliveries: Vec<PragmaticJobTask>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub services: Vec<PragmaticJobTask>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skills: Option<PragmaticJobSkills>,
+    /// Jobs sharing a group are served by one vehicle.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PragmaticJobTask {
+    pub places: Vec<PragmaticJobPlace>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub demand: Option<Vec<i32>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PragmaticJobPlace {
+    pub location: PragmaticLocation,
+    /// Service duration in seconds.
+    pub duration: f64,
+    /// Windows in which service may start.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub times: Option<Vec<(String, String)>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PragmaticJobSkills {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub all_of: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub one_of: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub none_of: Option<Vec<String>>,
+}
+
+/// A location by coordinates, or by index into a routing matrix.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PragmaticLocation {
+    Coordinate { lat: f64, lng: f64 },
+    Reference { index: usize },
+}
+
+impl From<(f64, f64)> for PragmaticLocation {
+    fn from((lat, lng): (f64, f64)) -> Self {
+        PragmaticLocation::Coordinate { lat, lng }
+    }
+}
+
+impl PragmaticLocation {
+    fn coordinates(&self) -> Result<(f64, f64), PragmaticError> {
+        match *self {
+            PragmaticLocation::Coordinate { lat, lng } => Ok((lat, lng)),
+            PragmaticLocation::Reference { .. } => Err(PragmaticError::Unsupported("indexed locations")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PragmaticFleet {
+    pub vehicles: Vec<PragmaticVehicleType>,
+    pub profiles: Vec<PragmaticProfile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PragmaticVehicleType {
+    pub type_id: String,
+    pub vehicle_ids: Vec<String>,
+    pub profile: PragmaticVehicleProfile,
+    pub costs: PragmaticCosts,
+    pub shifts: Vec<PragmaticShift>,
+    pub capacity: Vec<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skills: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PragmaticVehicleProfile {
+    pub matrix: String,
+}
+
+/// Vehicle costs: `fixed` per tour, `distance` per meter, `time` per second.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PragmaticCosts {
+    pub fixed: f64,
+    pub distance: f64,
+    pub time: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PragmaticShift {
+    pub start: PragmaticShiftStart,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<PragmaticShiftEnd>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub breaks: Vec<PragmaticBreak>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PragmaticShiftStart {
+    pub earliest: String,
+    pub location: PragmaticLocation,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PragmaticShiftEnd {
+    pub latest: String,
+    pub location: PragmaticLocation,
+}
+
+/// A break taken at some point within `time`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PragmaticBreak {
+    pub time: (String, String),
+    pub places: Vec<PragmaticBreakPlace>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PragmaticBreakPlace {
+    /// Break length in seconds.
+    pub duration: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PragmaticProfile {
+    pub name: String,
+}
+
+/// A routing matrix for a problem whose locations are indexed.
+///
+/// Both matrices are flattened row by row.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PragmaticMatrix {
+    pub profile: String,
+    pub travel_times: Vec<i64>,
+    pub distances: Vec<i64>,
+}
+
+/// A `vrp-pragmatic` solution.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PragmaticSolution {
+    pub statistic: PragmaticStatistic,
+    pub tours: Vec<PragmaticTour>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unassigned: Vec<PragmaticUnassignedJob>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PragmaticStatistic {
+    pub cost: f64,
+    /// Meters driven.
+    pub distance: i64,
+    /// Seconds from departure to the end of the last stop.
+    pub duration: i64,
+    pub times: PragmaticTimes,
+}
+
+/// Time spent, in seconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PragmaticTimes {
+    pub driving: i64,
+    pub serving: i64,
+    pub waiting: i64,
+    #[serde(rename = "break")]
+    pub break_time: i64,
+    pub commuting: i64,
+    pub parking: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PragmaticTour {
+    pub vehicle_id: String,
+    pub type_id: String,
+    #[serde(default)]
+    pub shift_index: usize,
+    pub stops: Vec<PragmaticStop>,
+    pub statistic: PragmaticStatistic,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PragmaticStop {
+    pub location: PragmaticLocation,
+    pub time: PragmaticStopTime,
+    /// Meters driven since the tour started.
+    pub distance: i64,
+    pub load: Vec<i32>,
+    pub activities: Vec<PragmaticActivity>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PragmaticStopTime {
+    pub arrival: String,
+    pub departure: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PragmaticActivity {
+    pub job_id: String,
+    #[serde(rename = "type")]
+    pub activity_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<PragmaticLocation>,
+    /// Set when a stop has several activities.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<PragmaticActivityTime>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PragmaticActivityTime {
+    pub start: String,
+    pub end: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PragmaticUnassignedJob {
+    pub job_id: String,
+    pub reasons: Vec<PragmaticUnassignedReason>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PragmaticUnassignedReason {
+    pub code: String,
+    pub description: String,
+}
+
+/// Error converting to or from the pragmatic format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PragmaticError {
+    /// The input uses a feature with no equivalent on the other side.
+    Unsupported(&'static str),
+    /// A timestamp isn't RFC 3339.
+    InvalidTimestamp(String),
+    /// The visitor has no start location, which pragmatic shifts require.
+    MissingStartLocation(String),
+    /// A plan or solution refers to a job the problem doesn't have.
+    UnknownJob(String),
+}
+
+impl std::fmt::Display for PragmaticError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PragmaticError::Unsupported(what) => write!(f, "Unsupported pragmatic feature: {}", what),
+            PragmaticError::InvalidTimestamp(value) => write!(f, "Invalid timestamp {}", value),
+            PragmaticError::MissingStartLocation(id) => write!(f, "Visitor {} has no start location", id),
+            PragmaticError::UnknownJob(id) => write!(f, "Unknown job {}", id),
+        }
+    }
+}
+
+impl std::error::Error for PragmaticError {}
+
+/// Converts a problem into a pragmatic problem with coordinate locations.
+///
+/// Visitors unavailable on the service date are left out of the fleet.
+pub fn problem_to_pragmatic(problem: &Problem) -> Result<PragmaticProblem, PragmaticError> {
+    let date = problem.planning_day().midnight();
+    let jobs = problem
+        .visits
+        .iter()
+        .map(|visit| {
+            let duration = visit.duration_minutes * 60;
+            let times = service_bounds(visit, problem.options.committed_window_semantics).map(|(start, end)| {
+                vec![(format_time(date, start), format_time(date, (end - duration).max(start)))]
+            });
+            let mut skills = visit.required_capabilities.clone();
+            if let Some(visitor_id) = &visit.pinned_visitor
+                && matches!(visit.pin_type, VisitPinType::Visitor | VisitPinType::VisitorAndDate)
+            {
+                skills.push(format!("{}{}", PIN_SKILL_PREFIX, visitor_id));
+            }
+            PragmaticJob {
+                id: visit.id.clone(),
+                pickups: Vec::new(),
+                deliveries: vec![PragmaticJobTask {
+                    places: vec![PragmaticJobPlace {
+                        location: visit.location.into(),
+                        duration: duration as f64,
+                        times,
+                    }],
+                    demand: Some(vec![1]),
+                }],
+                services: Vec::new(),
+                skills: (!skills.is_empty()).then_some(PragmaticJobSkills {
+                    all_of: Some(skills),
+                    one_of: None,
+                    none_of: None,
+                }),
+                group: visit.same_visitor_group.clone(),
+            }
+        })
+        .collect();
+
+    let mut vehicles = Vec::with_capacity(problem.visitors.len());
+    for visitor in &problem.visitors {
+        let Some(windows) = problem.availability.availability_for(&visitor.id, date) else {
+            continue;
+        };
+        let start = visitor.start_location.ok_or_else(|| PragmaticError::MissingStartLocation(visitor.id.clone()))?;
+        let (open, close) = (windows[0].0, windows[windows.len() - 1].1);
+        let breaks = windows
+            .windows(2)
+            .map(|pair| PragmaticBreak {
+                time: (format_time(date, pair[0].1), format_time(date, pair[0].1)),
+                places: vec![PragmaticBreakPlace { duration: (pair[1].0 - pair[0].1) as f64 }],
+            })
+            .collect();
+
+        let mut skills = visitor.capabilities.clone();
+        skills.push(format!("{}{}", PIN_SKILL_PREFIX, visitor.id));
+        let capacity = visitor.max_visits.or(problem.options.max_visits_per_route).unwrap_or(problem.visits.len());
+
+        vehicles.push(PragmaticVehicleType {
+            type_id: visitor.id.clone(),
+            vehicle_ids: vec![visitor.id.clone()],
+            profile: PragmaticVehicleProfile { matrix: PROFILE.to_string() },
+            costs: vehicle_costs(visitor, problem.options.objective),
+            shifts: vec![PragmaticShift {
+                start: PragmaticShiftStart { earliest: format_time(date, open), location: start.into() },
+                end: Some(PragmaticShiftEnd {
+                    latest: format_time(date, visitor.return_window.map_or(close, |(_, latest)| latest)),
+                    location: visitor.end_location.unwrap_or(start).into(),
+                }),
+                breaks,
+            }],
+            capacity: vec![capacity as i32],
+            skills: Some(skills),
+        });
+    }
+
+    Ok(PragmaticProblem {
+        plan: PragmaticPlan { jobs, relations: Vec::new() },
+        fleet: PragmaticFleet { vehicles, profiles: vec![PragmaticProfile { name: PROFILE.to_string() }] },
+    })
+}
+
+/// Rewrites a problem's locations as matrix indices and builds the matching
+/// routing matrix from `matrix_provider`, so `vrp` uses the same travel
+/// times as this solver. Distances are zero if the provider has none.
+pub fn with_routing_matrix<M: DistanceMatrixProvider>(
+    mut problem: PragmaticProblem,
+    matrix_provider: &M,
+) -> (PragmaticProblem, PragmaticMatrix) {
+    let mut locations = Vec::new();
+    let mut index: HashMap<LocationRef, usize> = HashMap::new();
+    let mut reference = |location: &mut PragmaticLocation| {
+        if let PragmaticLocation::Coordinate { lat, lng } = *location {
+            let idx = *index.entry(LocationRef::from_coordinates((lat, lng))).or_insert_with(|| {
+                locations.push((lat, lng));
+                locations.len() - 1
+            });
+            *location = PragmaticLocation::Reference { index: idx };
+        }
+    };
+
+    for job in &mut problem.plan.jobs {
+        for task in job.pickups.iter_mut().chain(&mut job.deliveries).chain(&mut job.services) {
+            for place in &mut task.places {
+                reference(&mut place.location);
+            }
+        }
+    }
+    for vehicle in &mut problem.fleet.vehicles {
+        for shift in &mut vehicle.shifts {
+            reference(&mut shift.start.location);
+            if let Some(end) = &mut shift.end {
+                reference(&mut end.location);
+            }
+        }
+    }
+
+    let matrices = matrix_provider.matrices_for(&locations);
+    let flatten = |rows: &[Vec<i32>]| rows.iter().flatten().map(|&value| value as i64).collect::<Vec<_>>();
+    let travel_times = flatten(&matrices.durations);
+    let distances = matrices.distances.as_deref().map_or_else(|| vec![0; travel_times.len()], flatten);
+
+    (problem, PragmaticMatrix { profile: PROFILE.to_string(), travel_times, distances })
+}
+
+/// Converts a pragmatic problem into a [`Problem`] with default options.
+///
+/// The service date is the day the first shift starts on. Jobs must have a
+/// single task with a single place, and demands of one if any; vehicle
+/// capacity then becomes each visitor's visit cap. Fixed-time breaks become
+/// gaps in availability; flexible breaks are dropped.
+pub fn problem_from_pragmatic(pragmatic: &PragmaticProblem) -> Result<Problem, PragmaticError> {
+    if !pragmatic.plan.relations.is_empty() {
+        return Err(PragmaticError::Unsupported("relations"));
+    }
+    let first_start = pragmatic
+        .fleet
+        .vehicles
+        .iter()
+        .flat_map(|vehicle| &vehicle.shifts)
+        .next()
+        .ok_or(PragmaticError::Unsupported("fleet without shifts"))?;
+    let service_date = parse_timestamp(&first_start.start.earliest)?.div_euclid(86_400) * 86_400;
+    let time_of_day = |value: &str| parse_timestamp(value).map(|timestamp| (timestamp - service_date) as i32);
+
+    let mut visits = Vec::with_capacity(pragmatic.plan.jobs.len());
+    let mut unit_demands = true;
+    for job in &pragmatic.plan.jobs {
+        let tasks: Vec<&PragmaticJobTask> = job.pickups.iter().chain(&job.deliveries).chain(&job.services).collect();
+        let [task] = tasks[..] else {
+            return Err(PragmaticError::Unsupported("jobs with several tasks"));
+        };
+        let [place] = &task.places[..] else {
+            return Err(PragmaticError::Unsupported("tasks with several places"));
+        };
+        match task.demand.as_deref() {
+            Some([1]) => {}
+            Some(_) => return Err(PragmaticError::Unsupported("demands other than one")),
+            None => unit_demands = false,
+        }
+
+        let duration_secs = place.duration.round() as i32;
+        let mut visit = VisitData::new(job.id.clone(), place.location.coordinates()?, (duration_secs + 59) / 60);
+        match place.times.as_deref() {
+            None | Some([]) => {}
+            Some([(start, end)]) => {
+                visit.committed_window = Some((time_of_day(start)?, time_of_day(end)? + duration_secs));
+            }
+            Some(_) => return Err(PragmaticError::Unsupported("jobs with several time windows")),
+        }
+        if let Some(skills) = &job.skills {
+            if skills.one_of.is_some() || skills.none_of.is_some() {
+                return Err(PragmaticError::Unsupported("oneOf and noneOf skills"));
+            }
+            for skill in skills.all_of.iter().flatten() {
+                visit = match skill.strip_prefix(PIN_SKILL_PREFIX) {
+                    Some(visitor_id) => visit.with_pinned_visitor(visitor_id),
+                    None => visit.with_required_capability(skill.clone()),
+                };
+            }
+        }
+        visits.push(visit);
+    }
+
+    let mut visitors = Vec::new();
+    let mut availability = AvailabilityData::default();
+    for vehicle in &pragmatic.fleet.vehicles {
+        let [shift] = &vehicle.shifts[..] else {
+            return Err(PragmaticError::Unsupported("vehicles with several shifts"));
+        };
+        let open = time_of_day(&shift.start.earliest)?;
+        let close = match &shift.end {
+            Some(end) => time_of_day(&end.latest)?,
+            None => 86_400,
+        };
+        let mut windows = vec![(open, close)];
+        for break_ in &shift.breaks {
+            let (start, end) = (time_of_day(&break_.time.0)?, time_of_day(&break_.time.1)?);
+            if start != end {
+                continue;
+            }
+            let duration: f64 = break_.places.iter().map(|place| place.duration).sum();
+            windows = split_windows(windows, (start, start + duration.round() as i32));
+        }
+
+        let capabilities: Vec<String> = vehicle
+            .skills
+            .iter()
+            .flatten()
+            .filter(|skill| !skill.starts_with(PIN_SKILL_PREFIX))
+            .cloned()
+            .collect();
+        for vehicle_id in &vehicle.vehicle_ids {
+            let (lat, lng) = shift.start.location.coordinates()?;
+            let mut visitor = VisitorData::new(vehicle_id.clone()).with_start_location(lat, lng);
+            if let Some(end) = &shift.end {
+                let (lat, lng) = end.location.coordinates()?;
+                visitor = visitor.with_end_location(lat, lng);
+            }
+            visitor.capabilities = capabilities.clone();
+            if unit_demands && let Some(&capacity) = vehicle.capacity.first() {
+                visitor.max_visits = Some(capacity.max(0) as usize);
+            }
+            availability.visitors.insert(vehicle_id.clone(), Some(windows.clone()));
+            visitors.push(visitor);
+        }
+    }
+
+    Ok(Problem { service_date, visits, visitors, availability, options: Default::default(), time_zone: None })
+}
+
+/// Converts a plan for `problem` into a pragmatic solution.
+///
+/// Tours end at the last visit unless the route has a return leg, which is
+/// written as an arrival stop at the visitor's end location. Costs use the
+/// same rates as [`problem_to_pragmatic`], so they compare directly with
+/// `vrp`'s. A routed visit the problem doesn't have is an error.
+pub fn plan_to_pragmatic(
+    plan: &PlannerResult<String, String>,
+    problem: &Problem,
+) -> Result<PragmaticSolution, PragmaticError> {
+    let visits: HashMap<&str, &VisitData> = problem.visits.iter().map(|visit| (visit.id.as_str(), visit)).collect();
+    let visitors: HashMap<&str, &VisitorData> =
+        problem.visitors.iter().map(|visitor| (visitor.id.as_str(), visitor)).collect();
+    let date = problem.planning_day().midnight();
+
+    let mut tours = Vec::new();
+    for route in plan.routes.iter().filter(|route| !route.visit_ids.is_empty()) {
+        let visitor = visitors.get(route.visitor_id.as_str());
+        let stops_visits = route
+            .visit_ids
+            .iter()
+            .map(|id| visits.get(id.as_str()).copied().ok_or_else(|| PragmaticError::UnknownJob(id.clone())))
+            .collect::<Result<Vec<&VisitData>, _>>()?;
+        let leg = |idx: usize| route.legs.get(idx);
+        let first_start = route.estimated_windows[0].0;
+        let departure = first_start - leg(0).map_or(0, |leg| leg.travel_secs + leg.wait_secs);
+        let origin = visitor.and_then(|visitor| visitor.start_location).unwrap_or(stops_visits[0].location);
+
+        let mut times = PragmaticTimes::default();
+        let mut distance = 0i64;
+        let mut stops = vec![PragmaticStop {
+            location: origin.into(),
+            time: PragmaticStopTime {
+                arrival: format_time(date, departure),
+                departure: format_time(date, departure),
+            },
+            distance: 0,
+            load: vec![route.visit_ids.len() as i32],
+            activities: vec![PragmaticActivity {
+                job_id: "departure".to_string(),
+                activity_type: "departure".to_string(),
+                location: None,
+                time: None,
+            }],
+        }];
+        for (idx, (visit, &(start, end))) in stops_visits.iter().zip(&route.estimated_windows).enumerate() {
+            let wait = leg(idx).map_or(0, |leg| leg.wait_secs);
+            times.driving += leg(idx).map_or(0, |leg| leg.travel_secs) as i64;
+            times.waiting += wait as i64;
+            times.serving += (end - start) as i64;
+            distance += leg(idx).and_then(|leg| leg.distance_m).unwrap_or(0) as i64;
+            stops.push(PragmaticStop {
+                location: visit.location.into(),
+                time: PragmaticStopTime {
+                    arrival: format_time(date, start - wait),
+                    departure: format_time(date, end),
+                },
+                distance,
+                load: vec![(route.visit_ids.len() - idx - 1) as i32],
+                activities: vec![PragmaticActivity {
+                    job_id: visit.id.clone(),
+                    activity_type: "delivery".to_string(),
+                    location: None,
+                    time: None,
+                }],
+            });
+        }
+
+        let mut finish = route.estimated_windows[route.estimated_windows.len() - 1].1;
+        if let (Some(leg), Some(end)) = (route.return_leg, visitor.and_then(|visitor| visitor.end_location)) {
+            times.driving += leg.travel_secs as i64;
+            distance += leg.distance_m.unwrap_or(0) as i64;
+            finish = leg.arrival;
+            stops.push(PragmaticStop {
+                location: end.into(),
+                time: PragmaticStopTime { arrival: format_time(date, leg.arrival), departure: format_time(date, leg.arrival) },
+                distance,
+                load: vec![0],
+                activities: vec![PragmaticActivity {
+                    job_id: "arrival".to_string(),
+                    activity_type: "arrival".to_string(),
+                    location: None,
+                    time: None,
+                }],
+            });
+        }
+        let duration = (finish - departure) as i64;
+        let costs = visitor.map_or(PragmaticCosts { fixed: 0.0, distance: 0.0, time: 1.0 }, |visitor| {
+            vehicle_costs(visitor, problem.options.objective)
+        });
+        tours.push(PragmaticTour {
+            vehicle_id: route.visitor_id.clone(),
+            type_id: route.visitor_id.clone(),
+            shift_index: 0,
+            stops,
+            statistic: PragmaticStatistic {
+                cost: costs.fixed + costs.distance * distance as f64 + costs.time * duration as f64,
+                distance,
+                duration,
+                times,
+            },
+        });
+    }
+
+    let statistic = tours.iter().fold(PragmaticStatistic::default(), |mut total, tour| {
+        let tour = &tour.statistic;
+        total.cost += tour.cost;
+        total.distance += tour.distance;
+        total.duration += tour.duration;
+        total.times.driving += tour.times.driving;
+        total.times.serving += tour.times.serving;
+        total.times.waiting += tour.times.waiting;
+        total
+    });
+
+    let unassigned = plan
+        .unassigned
+        .iter()
+        .map(|visit| PragmaticUnassignedJob {
+            job_id: visit.visit_id.clone(),
+            reasons: vec![PragmaticUnassignedReason {
+                code: reason_code(visit.reason).to_string(),
+                description: format!("{:?}", visit.reason),
+            }],
+        })
+        .collect();
+
+    Ok(PragmaticSolution { statistic, tours, unassigned })
+}
+
+/// Converts a pragmatic solution for `problem` into a plan.
+///
+/// Stop times are read relative to the problem's service date. Legs are
+/// rebuilt from stop times and cumulative distances, so travel absorbs any
+/// break taken between visits. Unassigned reasons map from the first
+/// reason code, falling back to [`UnassignedReason::RouteFull`].
+pub fn plan_from_pragmatic(
+    solution: &PragmaticSolution,
+    problem: &Problem,
+) -> Result<PlannerResult<String, String>, PragmaticError> {
+    let visits: HashMap<&str, &VisitData> = problem.visits.iter().map(|visit| (visit.id.as_str(), visit)).collect();
+    let visit =
+        |job_id: &str| visits.get(job_id).copied().ok_or_else(|| PragmaticError::UnknownJob(job_id.to_string()));
+    let time_of_day = |value: &str| parse_timestamp(value).map(|timestamp| problem.planning_day().secs_at(timestamp));
+
+    let mut routes = Vec::with_capacity(solution.tours.len());
+    for tour in &solution.tours {
+        let mut route = RouteResult {
+            visitor_id: tour.vehicle_id.clone(),
+            visit_ids: Vec::new(),
+            estimated_windows: Vec::new(),
+            total_travel_time: 0,
+            total_distance_km: None,
+            legs: Vec::new(),
+            window_miss_risk: 0.0,
+            robustness_slack_secs: None,
+            return_leg: None,
+        };
+        let mut prev_departure: Option<i32> = None;
+        let mut prev_distance = 0;
+
+        for stop in &tour.stops {
+            let arrival = time_of_day(&stop.time.arrival)?;
+            let departure = time_of_day(&stop.time.departure)?;
+            let mut travel_secs = prev_departure.map_or(0, |prev| (arrival - prev).max(0));
+            let mut distance_m = (stop.distance - prev_distance) as i32;
+
+            for activity in &stop.activities {
+                if activity.activity_type == "arrival" && !route.visit_ids.is_empty() {
+                    route.return_leg = Some(ReturnLeg { travel_secs, arrival, distance_m: Some(distance_m) });
+                    route.total_travel_time += travel_secs;
+                }
+                if NON_JOB_ACTIVITIES.contains(&activity.activity_type.as_str()) {
+                    continue;
+                }
+                let window = match &activity.time {
+                    Some(time) => (time_of_day(&time.start)?, time_of_day(&time.end)?),
+                    None => (departure - visit(&activity.job_id)?.duration_minutes * 60, departure),
+                };
+                route.legs.push(LegDetail {
+                    from: route.visit_ids.last().cloned(),
+                    to: activity.job_id.clone(),
+                    travel_secs,
+                    wait_secs: (window.0 - arrival).max(0),
+                    distance_m: Some(distance_m),
+                });
+                route.total_travel_time += travel_secs;
+                route.visit_ids.push(activity.job_id.clone());
+                route.estimated_windows.push(window);
+                (travel_secs, distance_m) = (0, 0);
+            }
+
+            prev_departure = Some(departure);
+            prev_distance = stop.distance;
+        }
+
+        let meters: i32 = route.legs.iter().filter_map(|leg| leg.distance_m).chain(route.return_leg.and_then(|leg| leg.distance_m)).sum();
+        route.total_distance_km = Some(meters as f64 / 1000.0);
+        routes.push(route);
+    }
+
+    let unassigned = solution
+        .unassigned
+        .iter()
+        .map(|job| {
+            Ok(UnassignedVisit {
+                visit_id: job.job_id.clone(),
+                reason: job
+                    .reasons
+                    .first()
+                    .map_or(UnassignedReason::RouteFull, |reason| reason_from_code(&reason.code)),
+                unavailable_visitors: Vec::new(),
+                relaxation: None,
+                mandatory: visit(&job.job_id)?.must_assign,
+            })
+        })
+        .collect::<Result<_, PragmaticError>>()?;
+
+    Ok(PlannerResult { routes, unassigned })
+}
+
+/// Vehicle rates matching what the solver minimizes under `objective`.
+fn vehicle_costs(visitor: &VisitorData, objective: Objective) -> PragmaticCosts {
+    match objective {
+        Objective::TravelTime => PragmaticCosts { fixed: 0.0, distance: 0.0, time: 1.0 },
+        Objective::Cost => PragmaticCosts {
+            fixed: 0.0,
+            distance: visitor.per_km_cost / 1000.0,
+            time: visitor.hourly_cost / 3600.0,
+        },
+    }
+}
+
+fn reason_code(reason: UnassignedReason) -> &'static str {
+    match reason {
+        UnassignedReason::NoCapableVisitor | UnassignedReason::MissingPinnedVisitor => "SKILL_CONSTRAINT",
+        UnassignedReason::CommittedWindowOutsideAvailability | UnassignedReason::DurationExceedsWindow => {
+            "TIME_WINDOW_CONSTRAINT"
+        }
+        UnassignedReason::VisitorUnavailable | UnassignedReason::RouteFull => "SHIFT_TIME_CONSTRAINT",
+        UnassignedReason::OffRoadNetwork | UnassignedReason::InvalidLocation | UnassignedReason::Unreachable => {
+            "REACHABLE_CONSTRAINT"
+        }
+        UnassignedReason::WrongDate
+        | UnassignedReason::ReassignmentLimit
+        | UnassignedReason::SameVisitorGroup
+        | UnassignedReason::DifferentVisitorGroup
+        | UnassignedReason::RoutePositionTaken => "NO_REASON_FOUND",
+    }
+}
+
+fn reason_from_code(code: &str) -> UnassignedReason {
+    match code {
+        "SKILL_CONSTRAINT" => UnassignedReason::NoCapableVisitor,
+        "TIME_WINDOW_CONSTRAINT" => UnassignedReason::CommittedWindowOutsideAvailability,
+        "REACHABLE_CONSTRAINT" => UnassignedReason::OffRoadNetwork,
+        _ => UnassignedReason::RouteFull,
+    }
+}
+
+/// Removes `gap` from a sorted list of windows.
+fn split_windows(windows: Vec<TimeWindow>, gap: TimeWindow) -> Vec<TimeWindow> {
+    windows
+        .into_iter()
+        .flat_map(|(start, end)| [(start, end.min(gap.0)), (start.max(gap.1), end)])
+        .filter(|(start, end)| start < end)
+        .collect()
+}
+
+/// Formats seconds after the unix instant `date` as an RFC 3339 UTC timestamp.
+fn format_time(date: i64, secs: i32) -> String {
+    let timestamp = date + secs as i64;
+    let (days, secs) = (timestamp.div_euclid(86_400), timestamp.rem_euclid(86_400));
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Parses an RFC 3339 timestamp into unix seconds, ignoring fractions.
+fn parse_timestamp(value: &str) -> Result<i64, PragmaticError> {
+    let invalid = || PragmaticError::InvalidTimestamp(value.to_string());
+    let number = |text: Option<&str>| text.and_then(|text| text.parse::<i64>().ok()).ok_or_else(invalid);
+
+    let (date, time) = value.split_once(['T', 't', ' ']).ok_or_else(invalid)?;
+    let (time, offset) = match time.strip_suffix(['Z', 'z']) {
+        Some(time) => (time, 0),
+        None => {
+            let split = time.rfind(['+', '-']).ok_or_else(invalid)?;
+            let (time, offset) = time.split_at(split);
+            let sign = if offset.starts_with('-') { -1 } else { 1 };
+            let mut parts = offset[1..].split(':');
+            (time, sign * (number(parts.next())? * 3600 + number(parts.next())? * 60))
+        }
+    };
+
+    let mut date_parts = date.split('-');
+    let (year, month, day) = (number(date_parts.next())?, number(date_parts.next())?, number(date_parts.next())?);
+    let mut time_parts = time.split(':');
+    let (hours, minutes) = (number(time_parts.next())?, number(time_parts.next())?);
+    let seconds = number(time_parts.next().map(|seconds| seconds.split('.').next().unwrap_or(seconds)))?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hours > 23 || minutes > 59 || seconds > 60 {
+        return Err(invalid());
+    }
+
+    Ok(days_from_civil(year, month, day) * 86_400 + hours * 3600 + minutes * 60 + seconds - offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::haversine::HaversineMatrix;
+    use crate::solver::SolveOptions;
+    use crate::timezone::TimeZone;
+
+    const MONDAY: i64 = 1_705_276_800; // 2024-01-15
+
+    fn sample_problem() -> Problem {
+        Problem {
+            service_date: MONDAY,
+            visits: vec![
+                VisitData::new("v1", (34.05, -118.24), 30).with_committed_window(10 * 3600, 11 * 3600),
+                VisitData::new("v2", (34.06, -118.25), 45).with_pinned_visitor("alice"),
+                VisitData::new("v3", (34.04, -118.23), 30).with_required_capability("ladder"),
+            ],
+            visitors: vec![
+                VisitorData::new("alice").with_start_location(34.05, -118.25).with_end_location(34.05, -118.25),
+                VisitorData::new("bob")
+                    .with_start_location(34.04, -118.24)
+                    .with_end_location(34.04, -118.24)
+                    .with_capability("ladder")
+                    .with_max_visits(5),
+            ],
+            availability: AvailabilityData::default()
+                .with_visitor_windows("alice", vec![(8 * 3600, 12 * 3600), (13 * 3600, 17 * 3600)])
+                .with_visitor_windows("bob", vec![(8 * 3600, 17 * 3600)]),
+            options: SolveOptions::default(),
+            time_zone: None,
+        }
+    }
+
+    #[test]
+    fn test_timestamps_round_trip() {
+        assert_eq!(format_time(MONDAY, 10 * 3600), "2024-01-15T10:00:00Z");
+        assert_eq!(parse_timestamp("2024-01-15T10:00:00Z"), Ok(MONDAY + 10 * 3600));
+        assert_eq!(parse_timestamp("2024-01-15T12:00:00.500+02:00"), Ok(MONDAY + 10 * 3600));
+        assert_eq!(format_time(0, -1), "1969-12-31T23:59:59Z");
+        assert!(parse_timestamp("2024-13-15T10:00:00Z").is_err());
+    }
+
+    #[test]
+    fn test_times_are_written_in_the_problems_time_zone() {
+        let mut problem = sample_problem();
+        problem.service_date = 1_710_028_800; // 2024-03-10, US clocks go forward
+        problem.time_zone = Some(TimeZone::us(-8 * 3600));
+        let pragmatic = problem_to_pragmatic(&problem).unwrap();
+
+        // 10 hours after midnight PST is 11am PDT, 18:00 UTC
+        let v1 = &pragmatic.plan.jobs[0].deliveries[0].places[0];
+        assert_eq!(v1.times.as_ref().unwrap()[0].0, "2024-03-10T18:00:00Z");
+    }
+
+    #[test]
+    fn test_problem_round_trips() {
+        let problem = sample_problem();
+        let pragmatic = problem_to_pragmatic(&problem).unwrap();
+
+        let v1 = &pragmatic.plan.jobs[0].deliveries[0].places[0];
+        assert_eq!(v1.times, Some(vec![("2024-01-15T10:00:00Z".to_string(), "2024-01-15T10:30:00Z".to_string())]));
+        let alice = &pragmatic.fleet.vehicles[0].shifts[0];
+        assert_eq!(alice.breaks[0].time.0, "2024-01-15T12:00:00Z");
+        assert_eq!(alice.breaks[0].places[0].duration, 3600.0);
+
+        let json = serde_json::to_string(&pragmatic).unwrap();
+        let imported = problem_from_pragmatic(&serde_json::from_str(&json).unwrap()).unwrap();
+        assert_eq!(imported.service_date, MONDAY);
+        assert_eq!(imported.visits, problem.visits);
+        assert_eq!(imported.visitors[0], problem.visitors[0].clone().with_max_visits(3));
+        assert_eq!(imported.visitors[1], problem.visitors[1]);
+        assert_eq!(imported.availability.visitors, problem.availability.visitors);
+    }
+
+    #[test]
+    fn test_imports_vrp_cli_problem() {
+        let pragmatic: PragmaticProblem =
+            serde_json::from_str(include_str!("../specs/test-problems/service-basic.json")).unwrap();
+        let problem = problem_from_pragmatic(&pragmatic).unwrap();
+
+        assert_eq!(problem.service_date, MONDAY);
+        assert_eq!(problem.visits.len(), 10);
+        assert_eq!(problem.visits[1].required_capabilities, vec!["driver-alice"]);
+        assert_eq!(problem.visits[2].committed_window, Some((10 * 3600, 11 * 3600 + 1800)));
+        assert_eq!(problem.visitors[0].max_visits, Some(10));
+        // Flexible breaks don't carve out availability.
+        assert_eq!(problem.availability.visitors["bob-truck"], Some(vec![(8 * 3600, 17 * 3600)]));
+
+        let result = problem.solve(&HaversineMatrix::default()).unwrap();
+        assert!(result.unassigned.is_empty());
+    }
+
+    #[test]
+    fn test_imports_vrp_cli_solution() {
+        let pragmatic: PragmaticProblem =
+            serde_json::from_str(include_str!("../specs/test-problems/service-basic.json")).unwrap();
+        let problem = problem_from_pragmatic(&pragmatic).unwrap();
+        let solution: PragmaticSolution =
+            serde_json::from_str(include_str!("../specs/test-problems/service-basic-solution.json")).unwrap();
+
+        let plan = plan_from_pragmatic(&solution, &problem).unwrap();
+        let bob = &plan.routes[0];
+        assert_eq!(bob.visitor_id, "bob-truck");
+        assert_eq!(bob.visit_ids[..2], ["visit-3-pinned-time-10am", "visit-6-pinned-to-bob"]);
+        assert_eq!(bob.estimated_windows[0], (11 * 3600, 11 * 3600 + 1800));
+        assert_eq!(bob.legs[1].travel_secs, 145);
+        assert_eq!(plan.routes.iter().map(|route| route.visit_ids.len()).sum::<usize>(), 10);
+    }
+
+    #[test]
+    fn test_plan_round_trips_through_solution() {
+        let problem = sample_problem();
+        let plan = problem.solve(&HaversineMatrix::default()).unwrap();
+        let solution = plan_to_pragmatic(&plan, &problem).unwrap();
+
+        let json = serde_json::to_string(&solution).unwrap();
+        let restored = plan_from_pragmatic(&serde_json::from_str(&json).unwrap(), &problem).unwrap();
+        let routed = plan.routes.iter().filter(|route| !route.visit_ids.is_empty());
+        for (original, restored) in routed.zip(&restored.routes) {
+            assert_eq!(restored.visit_ids, original.visit_ids);
+            assert_eq!(restored.estimated_windows, original.estimated_windows);
+            assert_eq!(restored.total_travel_time, original.total_travel_time);
+        }
+        let driving: i64 = plan.routes.iter().map(|route| route.total_travel_time as i64).sum();
+        assert_eq!(solution.statistic.times.driving, driving);
+    }
+
+    #[test]
+    fn test_plan_with_unknown_visit_is_rejected() {
+        let problem = sample_problem();
+        let mut plan = problem.solve(&HaversineMatrix::default()).unwrap();
+        let route = plan.routes.iter_mut().find(|route| !route.visit_ids.is_empty()).unwrap();
+        route.visit_ids[0] = "ghost".to_string();
+
+        assert_eq!(plan_to_pragmatic(&plan, &problem).unwrap_err(), PragmaticError::UnknownJob("ghost".to_string()));
+    }
+
+    #[test]
+    fn test_reason_codes_round_trip() {
+        for reason in [
+            UnassignedReason::NoCapableVisitor,
+            UnassignedReason::CommittedWindowOutsideAvailability,
+            UnassignedReason::RouteFull,
+            UnassignedReason::OffRoadNetwork,
+        ] {
+            assert_eq!(reason_from_code(reason_code(reason)), reason);
+        }
+    }
+
+    #[test]
+    fn test_routing_matrix_indexes_shared_locations() {
+        let problem = problem_to_pragmatic(&sample_problem()).unwrap();
+        let (indexed, matrix) = with_routing_matrix(problem, &HaversineMatrix::default());
+
+        // Three visits plus two depots, each used as start and end.
+        assert_eq!(matrix.travel_times.len(), 25);
+        assert_eq!(matrix.distances.len(), 25);
+        let alice = &indexed.fleet.vehicles[0].shifts[0];
+        assert_eq!(alice.start.location, PragmaticLocation::Reference { index: 3 });
+        assert_eq!(alice.end.as_ref().unwrap().location, PragmaticLocation::Reference { index: 3 });
+        assert_eq!(
+            problem_from_pragmatic(&indexed).unwrap_err(),
+            PragmaticError::Unsupported("indexed locations")
+        );
+    }
+}
@@ -0,0 +1,115 @@
+//! Recurring visit series.
+//!
+//! A visit that belongs to a series (e.g., weekly service every Tuesday, give
+//! or take a day) reports its [`Recurrence`] through
+//! [`Visit::recurrence`](crate::traits::Visit::recurrence). The solver only
+//! routes it on dates within the series' tolerance of a nominal occurrence,
+//! so solving a week day by day can't drift a weekly customer off cadence.
+//!
+//! Within the tolerance, each day of [`drift_days`](Recurrence::drift_days)
+//! costs [`recurrence_drift_weight`](crate::solver::SolveOptions::recurrence_drift_weight),
+//! so on a day without room for everything, visits due that day are served
+//! before those that could wait for (or were due before) it.
+
+use serde::{Deserialize, Serialize};
+
+/// Seconds per day; dates are unix timestamps at midnight.
+const SECS_PER_DAY: i64 = 86_400;
+
+/// A series of nominal service dates every `interval_days` from `anchor_date`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recurrence {
+    /// First nominal date of the series (unix timestamp, date only).
+    pub anchor_date: i64,
+    /// Days between nominal dates.
+    pub interval_days: u32,
+    /// How many days early or late an occurrence may be served.
+    #[serde(default)]
+    pub tolerance_days: u32,
+}
+
+impl Recurrence {
+    /// Same weekday every week, with no tolerance.
+    pub fn weekly(anchor_date: i64) -> Self {
+        Self::every_weeks(anchor_date, 1)
+    }
+
+    /// Same weekday every `weeks` weeks, with no tolerance.
+    pub fn every_weeks(anchor_date: i64, weeks: u32) -> Self {
+        Self {
+            anchor_date,
+            interval_days: weeks * 7,
+            tolerance_days: 0,
+        }
+    }
+
+    pub fn with_tolerance_days(mut self, days: u32) -> Self {
+        self.tolerance_days = days;
+        self
+    }
+
+    /// Nominal date closest to `date`, never before the anchor.
+    pub fn nearest_occurrence(&self, date: i64) -> i64 {
+        let interval = i64::from(self.interval_days.max(1));
+        let offset = date.div_euclid(SECS_PER_DAY) - self.anchor_date.div_euclid(SECS_PER_DAY);
+        let index = ((offset as f64) / interval as f64).round().max(0.0) as i64;
+        self.anchor_date + index * interval * SECS_PER_DAY
+    }
+
+    /// Whole days between `date` and its nearest nominal date.
+    pub fn drift_days(&self, date: i64) -> i64 {
+        (date.div_euclid(SECS_PER_DAY) - self.nearest_occurrence(date).div_euclid(SECS_PER_DAY)).abs()
+    }
+
+    /// Whether an occurrence may be served on `date`.
+    pub fn allows(&self, date: i64) -> bool {
+        self.drift_days(date) <= i64::from(self.tolerance_days)
+    }
+
+    /// Nominal dates from `from` through `to`, inclusive.
+    pub fn occurrences(&self, from: i64, to: i64) -> Vec<i64> {
+        let step = i64::from(self.interval_days.max(1)) * SECS_PER_DAY;
+        let mut date = self.nearest_occurrence(from);
+        if date < from {
+            date += step;
+        }
+        let mut dates = Vec::new();
+        while date <= to {
+            dates.push(date);
+            date += step;
+        }
+        dates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-01-02, a Tuesday
+    const TUESDAY: i64 = 1_704_153_600;
+    const DAY: i64 = SECS_PER_DAY;
+
+    #[test]
+    fn test_weekly_with_one_day_tolerance() {
+        let series = Recurrence::weekly(TUESDAY).with_tolerance_days(1);
+
+        assert!(series.allows(TUESDAY + 7 * DAY));
+        assert!(series.allows(TUESDAY + 6 * DAY), "Monday is within a day");
+        assert!(series.allows(TUESDAY + 8 * DAY), "Wednesday is within a day");
+        assert!(!series.allows(TUESDAY + 10 * DAY), "Friday drifts 3 days");
+        assert_eq!(series.drift_days(TUESDAY + 10 * DAY), 3);
+        assert!(!series.allows(TUESDAY - 3 * DAY), "series hasn't started");
+    }
+
+    #[test]
+    fn test_occurrences_in_range() {
+        let series = Recurrence::every_weeks(TUESDAY, 2);
+
+        assert_eq!(
+            series.occurrences(TUESDAY + DAY, TUESDAY + 42 * DAY),
+            vec![TUESDAY + 14 * DAY, TUESDAY + 28 * DAY, TUESDAY + 42 * DAY]
+        );
+        assert_eq!(series.occurrences(TUESDAY - 30 * DAY, TUESDAY), vec![TUESDAY]);
+    }
+}
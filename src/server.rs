@@ -0,0 +1,208 @@
+//! HTTP service.
+//!
+//! Serves the planner over JSON using the plain-data model, so non-Rust
+//! backends can call it without a wrapper of their own:
+//!
+//! - `POST /solve` takes a [`Problem`] and returns a [`PlannerResult`].
+//! - `POST /validate` takes a [`Problem`] and returns its [`ProblemIssue`]s.
+//! - `POST /evaluate-insertion` takes an [`InsertionRequest`] and returns
+//!   [`InsertionOption`]s, cheapest first.
+//!
+//! A solve that fails answers with an [`ErrorBody`] naming the
+//! [`SolveError`].
+//!
+//! Solves run on tokio's blocking pool, so blocking matrix providers such as
+//! `OsrmClient` are fine.
+
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use tokio::net::{TcpListener, ToSocketAddrs};
+
+use crate::model::{Problem, ProblemIssue};
+use crate::solver::{evaluate_insertion, InsertionOption, PlannerResult, SolveError};
+use crate::traits::DistanceMatrixProvider;
+
+/// Body of `POST /evaluate-insertion`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InsertionRequest {
+    /// The day, including the visit being evaluated.
+    pub problem: Problem,
+    /// Current routes the visit would be added to.
+    pub plan: PlannerResult<String, String>,
+    pub visit_id: String,
+}
+
+/// Body of an error response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorBody {
+    /// The [`SolveError`] variant, such as `"InvalidOptions"`, or `"Internal"`
+    /// if the solve panicked.
+    pub error: String,
+    pub message: String,
+}
+
+type ErrorResponse = (StatusCode, Json<ErrorBody>);
+
+/// Routes for the planner endpoints, using `matrix` for travel times.
+pub fn router<M>(matrix: M) -> Router
+where
+    M: DistanceMatrixProvider + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/solve", post(solve::<M>))
+        .route("/validate", post(validate))
+        .route("/evaluate-insertion", post(insertion::<M>))
+        .with_state(Arc::new(matrix))
+}
+
+/// Serves [`router`] on `addr` until the process exits.
+pub async fn serve<M>(addr: impl ToSocketAddrs, matrix: M) -> std::io::Result<()>
+where
+    M: DistanceMatrixProvider + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind(addr).await?;
+    axum::serve(listener, router(matrix)).await
+}
+
+async fn solve<M>(
+    State(matrix): State<Arc<M>>,
+    Json(problem): Json<Problem>,
+) -> Result<Json<PlannerResult<String, String>>, ErrorResponse>
+where
+    M: DistanceMatrixProvider + Send + Sync + 'static,
+{
+    tokio::task::spawn_blocking(move || problem.solve(matrix.as_ref()))
+        .await
+        .map_err(internal_error)?
+        .map(Json)
+        .map_err(solve_error_response)
+}
+
+async fn validate(Json(problem): Json<Problem>) -> Json<Vec<ProblemIssue>> {
+    Json(problem.validate())
+}
+
+async fn insertion<M>(
+    State(matrix): State<Arc<M>>,
+    Json(request): Json<InsertionRequest>,
+) -> Result<Json<Vec<InsertionOption<String>>>, ErrorResponse>
+where
+    M: DistanceMatrixProvider + Send + Sync + 'static,
+{
+    tokio::task::spawn_blocking(move || {
+        let InsertionRequest { problem, plan, visit_id } = request;
+        problem.options.validate().map_err(SolveError::InvalidOptions)?;
+        evaluate_insertion(
+            problem.service_date,
+            &problem.visits,
+            &problem.visitors,
+            &problem.availability,
+            matrix.as_ref(),
+            problem.options,
+            &plan,
+            &visit_id,
+        )
+    })
+    .await
+    .map_err(internal_error)?
+    .map(Json)
+    .map_err(solve_error_response)
+}
+
+/// A matrix the routing backend got wrong is its failure, not the caller's.
+fn solve_error_response(err: SolveError<String>) -> ErrorResponse {
+    let (status, error) = match err {
+        SolveError::EmptyMatrix { .. } => (StatusCode::BAD_GATEWAY, "EmptyMatrix"),
+        SolveError::MatrixSizeMismatch { .. } => (StatusCode::BAD_GATEWAY, "MatrixSizeMismatch"),
+        SolveError::MissingCoordinates(_) => (StatusCode::UNPROCESSABLE_ENTITY, "MissingCoordinates"),
+        SolveError::DuplicateVisitId(_) => (StatusCode::UNPROCESSABLE_ENTITY, "DuplicateVisitId"),
+        SolveError::InvalidOptions(_) => (StatusCode::UNPROCESSABLE_ENTITY, "InvalidOptions"),
+    };
+    (status, Json(ErrorBody { error: error.to_string(), message: err.to_string() }))
+}
+
+fn internal_error(err: tokio::task::JoinError) -> ErrorResponse {
+    let body = ErrorBody { error: "Internal".to_string(), message: err.to_string() };
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::haversine::HaversineMatrix;
+
+    fn post_json(app: Router, path: &str, body: Value) -> (StatusCode, Value) {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let request = Request::post(path)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            let response = app.oneshot(request).await.unwrap();
+            let status = response.status();
+            let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+        })
+    }
+
+    fn problem() -> Value {
+        json!({
+            "service_date": 86_400,
+            "visits": [
+                { "id": "v1", "location": [36.11, -115.17], "duration_minutes": 30 },
+                { "id": "v2", "location": [36.12, -115.16], "duration_minutes": 30 },
+            ],
+            "visitors": [{ "id": "alice", "start_location": [36.10, -115.15] }],
+            "availability": { "default_windows": [[8 * 3600, 17 * 3600]] },
+        })
+    }
+
+    #[test]
+    fn test_solve_and_validate_endpoints() {
+        let app = router(HaversineMatrix::default());
+
+        let (status, plan) = post_json(app.clone(), "/solve", problem());
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(plan["routes"][0]["visit_ids"], json!(["v1", "v2"]));
+
+        let (status, issues) = post_json(app.clone(), "/validate", problem());
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(issues, json!([]));
+
+        let (status, _) = post_json(app.clone(), "/solve", json!({ "visits": [] }));
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let mut bad_options = problem();
+        bad_options["options"] = json!({ "target_time_weight": -1 });
+        let (status, error) = post_json(app, "/solve", bad_options);
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error["error"], "InvalidOptions");
+        assert_eq!(error["message"], "Invalid options: target_time_weight must not be negative");
+    }
+
+    #[test]
+    fn test_evaluate_insertion_endpoint() {
+        let app = router(HaversineMatrix::default());
+        let mut day = problem();
+        let (_, plan) = post_json(app.clone(), "/solve", day.clone());
+        day["visits"].as_array_mut().unwrap().push(json!({
+            "id": "new", "location": [36.13, -115.16], "duration_minutes": 45,
+        }));
+
+        let (status, slots) =
+            post_json(app, "/evaluate-insertion", json!({ "problem": day, "plan": plan, "visit_id": "new" }));
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(slots[0]["visitor_id"], "alice");
+        assert_eq!(slots[0]["position"], 2);
+    }
+}
@@ -0,0 +1,233 @@
+//! Shift patterns compiled down to availability.
+//!
+//! Rather than computing each visitor's windows for every service date, an
+//! integrator can describe how they work — weekdays, a 4-on-4-off rotation,
+//! alternating weeks, one-off on-call days — as a [`ShiftPattern`] and serve
+//! it through a [`ShiftProvider`]. [`ShiftAvailability`] turns any provider
+//! into an [`AvailabilityProvider`] for the solver.
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+
+use serde::{Deserialize, Serialize};
+
+use crate::timezone::{PlanningDay, TimeZone};
+use crate::traits::{AsyncAvailabilityProvider, AvailabilityProvider, Id, TimeWindow};
+
+/// Seconds per day; dates are unix timestamps at midnight.
+const SECS_PER_DAY: i64 = 86_400;
+
+/// 1970-01-05, the first Monday after the epoch.
+const FIRST_MONDAY: i64 = 4 * SECS_PER_DAY;
+
+/// Working hours that repeat in a cycle of days, with dated exceptions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShiftPattern {
+    /// First day of the cycle (unix timestamp, date only).
+    pub anchor_date: i64,
+    /// Windows for each day of the cycle; empty for a day off.
+    pub days: Vec<Vec<TimeWindow>>,
+    /// Dates whose windows replace the cycle's, e.g. on-call days or leave;
+    /// empty for a day off.
+    #[serde(default)]
+    pub overrides: BTreeMap<i64, Vec<TimeWindow>>,
+}
+
+impl ShiftPattern {
+    /// A cycle of `days` starting on `anchor_date`.
+    pub fn new(anchor_date: i64, days: Vec<Vec<TimeWindow>>) -> Self {
+        Self { anchor_date, days, overrides: BTreeMap::new() }
+    }
+
+    /// The same windows every day.
+    pub fn every_day(windows: Vec<TimeWindow>) -> Self {
+        Self::new(0, vec![windows])
+    }
+
+    /// The same windows Monday to Friday, weekends off.
+    pub fn weekdays(windows: Vec<TimeWindow>) -> Self {
+        let mut week = vec![windows; 5];
+        week.extend([Vec::new(), Vec::new()]);
+        Self::new(FIRST_MONDAY, week)
+    }
+
+    /// Windows for each day of the week, Monday first.
+    pub fn weekly(week: [Vec<TimeWindow>; 7]) -> Self {
+        Self::new(FIRST_MONDAY, week.into())
+    }
+
+    /// `on_days` working `windows`, then `off_days` off, from `anchor_date`
+    /// (e.g., 4-on-4-off).
+    pub fn rotation(anchor_date: i64, on_days: usize, off_days: usize, windows: Vec<TimeWindow>) -> Self {
+        let mut days = vec![windows; on_days];
+        days.extend(std::iter::repeat_n(Vec::new(), off_days));
+        Self::new(anchor_date, days)
+    }
+
+    /// Two alternating weeks, Monday first; `anchor_date` is the Monday of a
+    /// `first` week.
+    pub fn alternating_weeks(anchor_date: i64, first: [Vec<TimeWindow>; 7], second: [Vec<TimeWindow>; 7]) -> Self {
+        Self::new(anchor_date, first.into_iter().chain(second).collect())
+    }
+
+    /// Works `windows` on `date` whatever the cycle says, e.g. an on-call day.
+    pub fn with_shift_on(mut self, date: i64, windows: Vec<TimeWindow>) -> Self {
+        self.overrides.insert(date, windows);
+        self
+    }
+
+    /// Takes `date` off whatever the cycle says.
+    pub fn with_day_off(mut self, date: i64) -> Self {
+        self.overrides.insert(date, Vec::new());
+        self
+    }
+
+    /// Windows worked on `date`; `None` on a day off.
+    pub fn windows_on(&self, date: i64) -> Option<Vec<TimeWindow>> {
+        let windows = match self.overrides.get(&date) {
+            Some(windows) => windows,
+            None if self.days.is_empty() => return None,
+            None => {
+                let offset = date.div_euclid(SECS_PER_DAY) - self.anchor_date.div_euclid(SECS_PER_DAY);
+                &self.days[offset.rem_euclid(self.days.len() as i64) as usize]
+            }
+        };
+        Some(windows.clone()).filter(|windows| !windows.is_empty())
+    }
+}
+
+/// Each visitor's shift pattern.
+pub trait ShiftProvider {
+    type VisitorId: Id;
+
+    /// The visitor's pattern, `None` if they have no shifts at all.
+    fn shift_pattern(&self, visitor_id: &Self::VisitorId) -> Option<&ShiftPattern>;
+}
+
+impl<VisitorId: Id> ShiftProvider for HashMap<VisitorId, ShiftPattern> {
+    type VisitorId = VisitorId;
+
+    fn shift_pattern(&self, visitor_id: &VisitorId) -> Option<&ShiftPattern> {
+        self.get(visitor_id)
+    }
+}
+
+/// Availability from a [`ShiftProvider`].
+///
+/// Shift windows are wall-clock times. With a time zone they're converted to
+/// seconds since local midnight, so a 9am start stays 9am on DST days.
+#[derive(Debug, Clone)]
+pub struct ShiftAvailability<P> {
+    pub shifts: P,
+    pub time_zone: Option<TimeZone>,
+}
+
+impl<P> ShiftAvailability<P> {
+    pub fn new(shifts: P) -> Self {
+        Self { shifts, time_zone: None }
+    }
+
+    pub fn with_time_zone(mut self, time_zone: TimeZone) -> Self {
+        self.time_zone = Some(time_zone);
+        self
+    }
+}
+
+impl<P: ShiftProvider> AvailabilityProvider for ShiftAvailability<P> {
+    type VisitorId = P::VisitorId;
+
+    fn availability_for(&self, visitor_id: &Self::VisitorId, date: i64) -> Option<Vec<TimeWindow>> {
+        let windows = self.shifts.shift_pattern(visitor_id)?.windows_on(date)?;
+        match self.time_zone {
+            Some(time_zone) => {
+                let day = PlanningDay::new(date, time_zone);
+                Some(windows.into_iter().map(|window| day.window_from_wall_clock(window)).collect())
+            }
+            None => Some(windows),
+        }
+    }
+}
+
+impl<P: ShiftProvider + Sync> AsyncAvailabilityProvider for ShiftAvailability<P> {
+    type VisitorId = P::VisitorId;
+
+    fn fetch_availability(
+        &self,
+        visitor_id: &Self::VisitorId,
+        date: i64,
+    ) -> impl Future<Output = Option<Vec<TimeWindow>>> + Send {
+        std::future::ready(self.availability_for(visitor_id, date))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = SECS_PER_DAY;
+    /// 2024-01-01, a Monday.
+    const MONDAY: i64 = 19_723 * DAY;
+
+    fn hours(start: i32, end: i32) -> Vec<TimeWindow> {
+        vec![(start * 3600, end * 3600)]
+    }
+
+    #[test]
+    fn test_weekdays_skip_weekends() {
+        let pattern = ShiftPattern::weekdays(hours(8, 17));
+        let worked: Vec<bool> = (0..7).map(|day| pattern.windows_on(MONDAY + day * DAY).is_some()).collect();
+        assert_eq!(worked, vec![true, true, true, true, true, false, false]);
+    }
+
+    #[test]
+    fn test_rotation_cycles_from_anchor() {
+        let pattern = ShiftPattern::rotation(MONDAY, 4, 4, hours(7, 19));
+        let worked: Vec<bool> = (-2..10).map(|day| pattern.windows_on(MONDAY + day * DAY).is_some()).collect();
+        assert_eq!(worked, [false, false, true, true, true, true, false, false, false, false, true, true]);
+    }
+
+    #[test]
+    fn test_alternating_weeks_and_overrides() {
+        let mut first: [Vec<TimeWindow>; 7] = Default::default();
+        first[0] = hours(8, 12);
+        let mut second: [Vec<TimeWindow>; 7] = Default::default();
+        second[0] = hours(13, 17);
+        let pattern = ShiftPattern::alternating_weeks(MONDAY, first, second)
+            .with_shift_on(MONDAY + 5 * DAY, hours(0, 24))
+            .with_day_off(MONDAY + 14 * DAY);
+
+        assert_eq!(pattern.windows_on(MONDAY), Some(hours(8, 12)));
+        assert_eq!(pattern.windows_on(MONDAY + 7 * DAY), Some(hours(13, 17)));
+        assert_eq!(pattern.windows_on(MONDAY + 14 * DAY), None);
+        assert_eq!(pattern.windows_on(MONDAY + 21 * DAY), Some(hours(13, 17)));
+        assert_eq!(pattern.windows_on(MONDAY + 5 * DAY), Some(hours(0, 24)));
+        assert_eq!(pattern.windows_on(MONDAY + DAY), None);
+    }
+
+    #[test]
+    fn test_shift_availability_reads_each_visitors_pattern() {
+        let shifts = HashMap::from([
+            ("alice".to_string(), ShiftPattern::weekdays(hours(8, 17))),
+            ("bob".to_string(), ShiftPattern::every_day(hours(9, 13))),
+        ]);
+        let availability = ShiftAvailability::new(shifts);
+
+        let saturday = MONDAY + 5 * DAY;
+        assert_eq!(availability.availability_for(&"alice".to_string(), saturday), None);
+        assert_eq!(availability.availability_for(&"bob".to_string(), saturday), Some(hours(9, 13)));
+        assert_eq!(availability.availability_for(&"carol".to_string(), saturday), None);
+    }
+
+    #[test]
+    fn test_shift_availability_keeps_wall_clock_hours_across_dst() {
+        let shifts = HashMap::from([("alice".to_string(), ShiftPattern::every_day(hours(9, 17)))]);
+        let availability = ShiftAvailability::new(shifts).with_time_zone(TimeZone::us(-8 * 3600));
+        let alice = "alice".to_string();
+
+        // 2024-03-10: midnight is PST, 9am is PDT
+        assert_eq!(availability.availability_for(&alice, 19_792 * DAY), Some(hours(8, 16)));
+        // 2024-11-03: midnight is PDT, 9am is PST
+        assert_eq!(availability.availability_for(&alice, 20_030 * DAY), Some(hours(10, 18)));
+        assert_eq!(availability.availability_for(&alice, MONDAY), Some(hours(9, 17)));
+    }
+}
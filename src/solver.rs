@@ -1,21 +1,103 @@
 //! Routing planner solver (baseline implementation).
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
 use std::time::Instant;
 
 use rayon::prelude::*;
-use tracing::{debug, info};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tracing::field::Empty;
+use tracing::{debug, debug_span, info, info_span, warn, Instrument, Span};
 
-use crate::traits::{AvailabilityProvider, DistanceMatrixProvider, UnassignedReason, Visit, VisitPinType, Visitor};
+use crate::cluster::{self, Clustering};
+use crate::constraint::{Capabilities, Pinning, RouteCandidate, RouteConstraint};
+use crate::telemetry;
+use crate::traits::{
+    AsyncAvailabilityProvider, AsyncDistanceMatrixProvider, AvailabilityProvider, DistanceMatrixProvider, Id,
+    LocationRef, RoutePosition, TimeWindow, TravelMatrices, UNREACHABLE_SECS, UnassignedReason, Visit,
+    VisitHistoryProvider, VisitPinType, Visitor, WindowSemantics, service_bounds,
+};
 
-#[derive(Debug, Clone)]
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+use wasm_clock::Instant;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SolveOptions {
     /// Weight for target time deviation penalty (per second).
     pub target_time_weight: i32,
     /// Weight for reassigning a visit to a different visitor (stability penalty).
     pub reassignment_penalty: i32,
+    /// Minimize the number of visits moved off their current visitor first,
+    /// then cost among plans that move as many: each move is charged
+    /// [`STABILITY_FIRST_PENALTY`] in place of `reassignment_penalty`. A
+    /// visit's own [`reassignment_penalty`](Visit::reassignment_penalty)
+    /// still overrides it.
+    pub stability_first: bool,
+    /// Most visits one solve may move off their
+    /// [`current_visitor_id`](Visit::current_visitor_id). Visits pinned to
+    /// another visitor count toward it but are always kept; others that could
+    /// only move past the cap are unassigned with
+    /// [`UnassignedReason::ReassignmentLimit`]. `None` allows any number.
+    pub max_reassignments: Option<usize>,
     /// Maximum iterations for local search improvement.
     pub local_search_iterations: usize,
+    /// Relocate only tries positions next to one of a visit's this many
+    /// nearest visits (by travel time). `None` tries every position.
+    pub local_search_neighbors: Option<usize>,
+    /// Whether local search applies the first improving move it finds or
+    /// the best one per pass.
+    pub improvement_strategy: ImprovementStrategy,
+    /// What route cost measures before penalties are added.
+    pub objective: Objective,
+    /// Flag visits whose location is far from the road network, using the
+    /// snap distances reported by the matrix provider (e.g., OSRM).
+    pub snap_check: Option<SnapCheck>,
+    /// Most visits the solver will put on one route. A visitor's own
+    /// [`max_visits`](Visitor::max_visits) takes precedence. Pinned visits are
+    /// always kept, even past the cap.
+    pub max_visits_per_route: Option<usize>,
+    /// Cost of assigning a visit to someone other than the visitor who has
+    /// served its location most. Only applies with [`solve_with_history`].
+    pub continuity_weight: i32,
+    /// Cost per day a recurring visit is served away from its series'
+    /// nominal date, within the [`Recurrence`](crate::recurrence::Recurrence)
+    /// tolerance.
+    pub recurrence_drift_weight: i32,
+    /// Whether committed windows bound the whole visit or only its start.
+    /// A visit's own [`committed_window_semantics`](Visit::committed_window_semantics)
+    /// takes precedence.
+    pub committed_window_semantics: WindowSemantics,
+    /// Seconds added to every leg between distinct locations, for parking,
+    /// elevators, and gate codes.
+    pub travel_buffer_secs: i32,
+    /// Scales matrix travel times before the buffer is added.
+    pub travel_time_multiplier: f64,
+    /// Fractional travel-time increase each route's
+    /// [`robustness_slack_secs`](RouteResult::robustness_slack_secs) is
+    /// measured under, e.g. `0.2` for +20%.
+    pub robustness_travel_increase: f64,
+    /// Split unpinned visits into one cluster per visitor before insertion,
+    /// for days too large to route by insertion alone.
+    pub clustering: Option<Clustering>,
+    /// Leave the leg from the start location and the leg back to the end
+    /// location out of route cost, for visitors whose commute is unpaid.
+    /// They still count toward the schedule.
+    pub unpaid_commute: bool,
+    /// What to do when two visits share an ID.
+    pub duplicate_visits: DuplicateVisitPolicy,
+    /// What to do with visits whose coordinates are NaN, off the globe, or
+    /// (0, 0) under `null_island_invalid`.
+    pub invalid_locations: InvalidLocationPolicy,
+    /// Also treat visits at exactly (0, 0) as having no coordinates, as a
+    /// failed geocode leaves them. Off by default, since planar coordinates
+    /// use the origin.
+    pub null_island_invalid: bool,
+    /// Capacity each route leaves unworked for same-day emergency calls.
+    /// `None` packs routes as full as their windows allow.
+    pub reserve: Option<CapacityReserve>,
 }
 
 impl Default for SolveOptions {
@@ -23,361 +105,3875 @@ impl Default for SolveOptions {
         Self {
             target_time_weight: 1,
             reassignment_penalty: 300, // ~5 minutes equivalent
+            stability_first: false,
+            max_reassignments: None,
             local_search_iterations: 100,
+            local_search_neighbors: Some(20),
+            improvement_strategy: ImprovementStrategy::FirstImprovement,
+            objective: Objective::TravelTime,
+            snap_check: None,
+            max_visits_per_route: None,
+            continuity_weight: 600, // ~10 minutes equivalent
+            recurrence_drift_weight: 1800, // ~30 minutes equivalent per day
+            committed_window_semantics: WindowSemantics::Completion,
+            travel_buffer_secs: 0,
+            travel_time_multiplier: 1.0,
+            robustness_travel_increase: 0.2,
+            clustering: None,
+            unpaid_commute: false,
+            duplicate_visits: DuplicateVisitPolicy::KeepFirst,
+            invalid_locations: InvalidLocationPolicy::Unassign,
+            null_island_invalid: false,
+            reserve: None,
+        }
+    }
+}
+
+/// Reassignment penalty under [`SolveOptions::stability_first`]: more than
+/// any day's travel (about 11.5 days in seconds) or labor cost ($10,000 in
+/// cents) one move could save. Plan costs are summed as `i64`, and a route's
+/// cost saturates at `i32::MAX`, so many moves don't overflow.
+pub const STABILITY_FIRST_PENALTY: i32 = 1_000_000;
+
+/// Base cost the solver minimizes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Objective {
+    /// Travel time in seconds.
+    #[default]
+    TravelTime,
+    /// Money in cents: each visitor's [`hourly_cost`](Visitor::hourly_cost) over
+    /// travel and service time, plus [`per_km_cost`](Visitor::per_km_cost) over
+    /// distance driven. Penalty weights are then read as cents.
+    Cost,
+}
+
+/// How a solve treats visits whose [`id`](Visit::id) repeats an earlier one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DuplicateVisitPolicy {
+    /// Plan the first visit with each ID, log a warning, and leave the rest
+    /// out of the plan.
+    #[default]
+    KeepFirst,
+    /// Fail the solve with [`SolveError::DuplicateVisitId`].
+    Reject,
+}
+
+/// How a solve treats visits without usable coordinates.
+///
+/// Either way their coordinates never reach the matrix provider, so they
+/// can't skew travel times through a row at (0, 0) (see
+/// [`SolveOptions::null_island_invalid`]).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InvalidLocationPolicy {
+    /// Leave the visit unassigned with [`UnassignedReason::InvalidLocation`].
+    #[default]
+    Unassign,
+    /// Plan the visit at the centroid of the visits that have coordinates,
+    /// guessing its travel as about average for the day. If no visit has
+    /// coordinates, the visit is unassigned as under `Unassign`.
+    Centroid,
+}
+
+/// How local search picks among improving moves.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImprovementStrategy {
+    /// Apply the first improving move found. Fast, but favors moves out of
+    /// earlier routes.
+    #[default]
+    FirstImprovement,
+    /// Scan every move and apply the one that saves the most. Ties go to the
+    /// first move scanned.
+    BestImprovement,
+}
+
+/// Threshold for visits whose location snapped far onto the road network.
+///
+/// Bad geocodes snap hundreds of meters or more and produce absurd travel
+/// times. Ignored when the matrix provider reports no snap distances.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SnapCheck {
+    /// Largest acceptable distance from the visit location to a road, in meters.
+    pub max_distance_meters: i32,
+    pub action: SnapAction,
+}
+
+/// Time held back on every route, free of travel and service, so emergency
+/// work can be slotted in later without breaking the plan.
+///
+/// The reserve is one unbroken block of `minutes`; idle fragments between
+/// visits don't add up to it. Waiting counts as free. Pinned and locked visits count toward the route's
+/// work like any other, so a route they fill past the reserve leaves them
+/// unassigned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapacityReserve {
+    pub minutes: i32,
+    /// Hours the reserve must fall within (seconds from midnight), e.g. the
+    /// afternoon; the whole day if `None`.
+    #[serde(default)]
+    pub band: Option<TimeWindow>,
+}
+
+/// What to do with a visit that fails the [`SnapCheck`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SnapAction {
+    /// Log a warning and plan the visit anyway.
+    Warn,
+    /// Leave the visit unassigned with [`UnassignedReason::OffRoadNetwork`].
+    Unassign,
+}
+
+impl SolveOptions {
+    /// Starts from the defaults; [`SolveOptionsBuilder::build`] validates.
+    pub fn builder() -> SolveOptionsBuilder {
+        SolveOptionsBuilder { options: SolveOptions::default() }
+    }
+
+    /// Checks every option is in range and consistent with the others.
+    pub fn validate(&self) -> Result<(), InvalidOptions> {
+        let weights = [
+            ("target_time_weight", self.target_time_weight),
+            ("reassignment_penalty", self.reassignment_penalty),
+            ("continuity_weight", self.continuity_weight),
+            ("recurrence_drift_weight", self.recurrence_drift_weight),
+            ("travel_buffer_secs", self.travel_buffer_secs),
+        ];
+        if let Some(&(field, _)) = weights.iter().find(|(_, weight)| *weight < 0) {
+            return Err(InvalidOptions::Negative(field));
+        }
+        if !(self.travel_time_multiplier.is_finite() && self.travel_time_multiplier > 0.0) {
+            return Err(InvalidOptions::NotPositive("travel_time_multiplier"));
+        }
+        if !self.robustness_travel_increase.is_finite() || self.robustness_travel_increase < 0.0 {
+            return Err(InvalidOptions::Negative("robustness_travel_increase"));
+        }
+        if let Some(reserve) = self.reserve {
+            if reserve.minutes < 0 {
+                return Err(InvalidOptions::Negative("reserve.minutes"));
+            }
+            if reserve.band.is_some_and(|(start, end)| start >= end) {
+                return Err(InvalidOptions::Conflict("reserve.band must end after it starts"));
+            }
+        }
+        if self.snap_check.is_some_and(|check| check.max_distance_meters < 0) {
+            return Err(InvalidOptions::Negative("snap_check.max_distance_meters"));
         }
+        if self.local_search_neighbors == Some(0) {
+            return Err(InvalidOptions::Zero("local_search_neighbors"));
+        }
+        if self.max_visits_per_route == Some(0) {
+            return Err(InvalidOptions::Zero("max_visits_per_route"));
+        }
+        if self.local_search_iterations == 0 && self.improvement_strategy == ImprovementStrategy::BestImprovement {
+            return Err(InvalidOptions::Conflict("improvement_strategy is set but local search is disabled"));
+        }
+        Ok(())
+    }
+
+    /// Loads options from JSON config, filling omitted fields with defaults.
+    pub fn from_json(json: &str) -> Result<Self, OptionsError> {
+        let options: SolveOptions = serde_json::from_str(json).map_err(OptionsError::Json)?;
+        options.validate()?;
+        Ok(options)
+    }
+
+    /// Loads options from TOML config, filling omitted fields with defaults.
+    #[cfg(feature = "toml")]
+    pub fn from_toml(toml: &str) -> Result<Self, OptionsError> {
+        let options: SolveOptions = ::toml::from_str(toml).map_err(OptionsError::Toml)?;
+        options.validate()?;
+        Ok(options)
     }
 }
 
+/// Builds [`SolveOptions`], rejecting out-of-range values.
 #[derive(Debug, Clone)]
+pub struct SolveOptionsBuilder {
+    options: SolveOptions,
+}
+
+impl SolveOptionsBuilder {
+    pub fn with_target_time_weight(mut self, weight: i32) -> Self {
+        self.options.target_time_weight = weight;
+        self
+    }
+
+    pub fn with_reassignment_penalty(mut self, penalty: i32) -> Self {
+        self.options.reassignment_penalty = penalty;
+        self
+    }
+
+    pub fn with_stability_first(mut self, stability_first: bool) -> Self {
+        self.options.stability_first = stability_first;
+        self
+    }
+
+    pub fn with_max_reassignments(mut self, max: usize) -> Self {
+        self.options.max_reassignments = Some(max);
+        self
+    }
+
+    pub fn with_local_search_iterations(mut self, iterations: usize) -> Self {
+        self.options.local_search_iterations = iterations;
+        self
+    }
+
+    /// `None` lets relocate try every position.
+    pub fn with_local_search_neighbors(mut self, neighbors: Option<usize>) -> Self {
+        self.options.local_search_neighbors = neighbors;
+        self
+    }
+
+    pub fn with_improvement_strategy(mut self, strategy: ImprovementStrategy) -> Self {
+        self.options.improvement_strategy = strategy;
+        self
+    }
+
+    pub fn with_objective(mut self, objective: Objective) -> Self {
+        self.options.objective = objective;
+        self
+    }
+
+    pub fn with_snap_check(mut self, check: SnapCheck) -> Self {
+        self.options.snap_check = Some(check);
+        self
+    }
+
+    pub fn with_max_visits_per_route(mut self, max_visits: usize) -> Self {
+        self.options.max_visits_per_route = Some(max_visits);
+        self
+    }
+
+    pub fn with_continuity_weight(mut self, weight: i32) -> Self {
+        self.options.continuity_weight = weight;
+        self
+    }
+
+    pub fn with_recurrence_drift_weight(mut self, weight: i32) -> Self {
+        self.options.recurrence_drift_weight = weight;
+        self
+    }
+
+    pub fn with_committed_window_semantics(mut self, semantics: WindowSemantics) -> Self {
+        self.options.committed_window_semantics = semantics;
+        self
+    }
+
+    pub fn with_travel_buffer_secs(mut self, secs: i32) -> Self {
+        self.options.travel_buffer_secs = secs;
+        self
+    }
+
+    /// E.g. `1.15` when raw drive times run 15% optimistic.
+    pub fn with_travel_time_multiplier(mut self, multiplier: f64) -> Self {
+        self.options.travel_time_multiplier = multiplier;
+        self
+    }
+
+    pub fn with_robustness_travel_increase(mut self, increase: f64) -> Self {
+        self.options.robustness_travel_increase = increase;
+        self
+    }
+
+    pub fn with_clustering(mut self, clustering: Clustering) -> Self {
+        self.options.clustering = Some(clustering);
+        self
+    }
+
+    pub fn with_unpaid_commute(mut self, unpaid: bool) -> Self {
+        self.options.unpaid_commute = unpaid;
+        self
+    }
+
+    pub fn with_duplicate_visits(mut self, policy: DuplicateVisitPolicy) -> Self {
+        self.options.duplicate_visits = policy;
+        self
+    }
+
+    pub fn with_invalid_locations(mut self, policy: InvalidLocationPolicy) -> Self {
+        self.options.invalid_locations = policy;
+        self
+    }
+
+    pub fn with_null_island_invalid(mut self, invalid: bool) -> Self {
+        self.options.null_island_invalid = invalid;
+        self
+    }
+
+    pub fn with_reserve(mut self, reserve: CapacityReserve) -> Self {
+        self.options.reserve = Some(reserve);
+        self
+    }
+
+    pub fn build(self) -> Result<SolveOptions, InvalidOptions> {
+        self.options.validate()?;
+        Ok(self.options)
+    }
+}
+
+/// An option value [`SolveOptions::validate`] rejects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidOptions {
+    /// The named weight or threshold is below zero.
+    Negative(&'static str),
+    /// The named limit is zero, which would leave nothing to search or route.
+    Zero(&'static str),
+    /// The named factor is zero, negative, or not finite.
+    NotPositive(&'static str),
+    /// Two options contradict each other.
+    Conflict(&'static str),
+}
+
+impl std::fmt::Display for InvalidOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvalidOptions::Negative(field) => write!(f, "{} must not be negative", field),
+            InvalidOptions::Zero(field) => write!(f, "{} must be at least 1", field),
+            InvalidOptions::NotPositive(field) => write!(f, "{} must be a positive number", field),
+            InvalidOptions::Conflict(reason) => write!(f, "Conflicting options: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for InvalidOptions {}
+
+/// Error loading options from config.
+#[derive(Debug)]
+pub enum OptionsError {
+    Json(serde_json::Error),
+    #[cfg(feature = "toml")]
+    Toml(::toml::de::Error),
+    Invalid(InvalidOptions),
+}
+
+impl std::fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OptionsError::Json(err) => write!(f, "Invalid options JSON: {}", err),
+            #[cfg(feature = "toml")]
+            OptionsError::Toml(err) => write!(f, "Invalid options TOML: {}", err),
+            OptionsError::Invalid(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for OptionsError {}
+
+impl From<InvalidOptions> for OptionsError {
+    fn from(err: InvalidOptions) -> Self {
+        OptionsError::Invalid(err)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RouteResult<VisitorId, VisitId> {
     pub visitor_id: VisitorId,
     pub visit_ids: Vec<VisitId>,
     pub estimated_windows: Vec<(i32, i32)>,
     pub total_travel_time: i32,
+    /// Kilometers driven between visits, if the matrix provider returned distances.
+    #[serde(default)]
+    pub total_distance_km: Option<f64>,
+    /// Travel into each visit, parallel to `visit_ids`.
+    #[serde(default = "Vec::new")]
+    pub legs: Vec<LegDetail<VisitId>>,
+    /// Expected number of visits pushed past their committed window by
+    /// upstream duration overruns; see [`Visit::duration_std_dev_minutes`].
+    #[serde(default)]
+    pub window_miss_risk: f64,
+    /// Smallest margin, in seconds, between a committed visit's start and the
+    /// latest start its window allows once travel grows by
+    /// [`SolveOptions::robustness_travel_increase`]. Negative if the route then
+    /// misses a window; `None` without committed windows.
+    #[serde(default)]
+    pub robustness_slack_secs: Option<i32>,
+    /// Travel from the last visit to the visitor's end location; `None` for
+    /// an empty route or a visitor without one.
+    #[serde(default)]
+    pub return_leg: Option<ReturnLeg>,
 }
 
-#[derive(Debug, Clone)]
-pub struct UnassignedVisit<VisitId> {
+/// Travel back to the end location after a route's last visit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReturnLeg {
+    pub travel_secs: i32,
+    /// Estimated arrival, held to the earliest time of the visitor's
+    /// [`return_window`](Visitor::return_window).
+    pub arrival: i32,
+    /// Meters driven, if the matrix provider returned distances.
+    pub distance_m: Option<i32>,
+}
+
+/// Travel and waiting before a visit on a route.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LegDetail<VisitId> {
+    /// Previous visit, or `None` for the first visit of the route.
+    pub from: Option<VisitId>,
+    pub to: VisitId,
+    pub travel_secs: i32,
+    /// Idle time between arriving and the visit's estimated start.
+    pub wait_secs: i32,
+    /// Meters driven, if the matrix provider returned distances.
+    pub distance_m: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnassignedVisit<VisitorId, VisitId> {
     pub visit_id: VisitId,
     pub reason: UnassignedReason,
+    /// Visitors with the required capabilities who have no availability on the service date.
+    pub unavailable_visitors: Vec<VisitorId>,
+    /// Smallest relaxation found that would make the visit feasible, if any.
+    pub relaxation: Option<Relaxation<VisitorId>>,
+    /// The visit was marked [`must_assign`](Visit::must_assign), so the plan is infeasible.
+    #[serde(default)]
+    pub mandatory: bool,
 }
 
-#[derive(Debug, Clone)]
+/// Constraint that blocked the closest-to-feasible insertion of a visit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlockingConstraint {
+    /// The visit would end after its committed window closes.
+    CommittedWindow,
+    /// The visit would end after the visitor's availability window closes.
+    Availability,
+    /// The visitor would get back to their end location after their
+    /// [`return_window`](Visitor::return_window) closes.
+    ReturnWindow,
+}
+
+/// A relaxation that would allow an unassigned visit onto a visitor's route.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Relaxation<VisitorId> {
+    /// Visitor whose route the visit would join.
+    pub visitor_id: VisitorId,
+    /// The tightest constraint blocking the insertion.
+    pub constraint: BlockingConstraint,
+    /// How far the blocking window would need to be extended, in seconds.
+    pub extend_by_secs: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlannerResult<VisitorId, VisitId> {
     pub routes: Vec<RouteResult<VisitorId, VisitId>>,
-    pub unassigned: Vec<UnassignedVisit<VisitId>>,
+    pub unassigned: Vec<UnassignedVisit<VisitorId, VisitId>>,
 }
 
-#[derive(Debug, Clone)]
-struct RouteState<'a, V: Visit, R: Visitor<Id = V::VisitorId>> {
-    visitor: &'a R,
-    visits: Vec<&'a V>,
-    estimated_windows: Vec<(i32, i32)>,
-    total_travel_time: i32,
+impl<VisitorId, VisitId> PlannerResult<VisitorId, VisitId> {
+    /// Returns false if any mandatory visit was left unassigned.
+    ///
+    /// Optional visits may be dropped from a feasible plan.
+    pub fn is_feasible(&self) -> bool {
+        !self.unassigned.iter().any(|visit| visit.mandatory)
+    }
+
+    /// Mandatory visits the plan failed to serve.
+    pub fn unserved_mandatory(&self) -> impl Iterator<Item = &UnassignedVisit<VisitorId, VisitId>> {
+        self.unassigned.iter().filter(|visit| visit.mandatory)
+    }
+
+    /// Visitor each assigned visit was routed to.
+    ///
+    /// Feed a stored plan back in as each visit's
+    /// [`current_visitor_id`](crate::traits::Visit::current_visitor_id) so
+    /// the next solve keeps assignments stable.
+    pub fn current_visitors(&self) -> HashMap<VisitId, VisitorId>
+    where
+        VisitorId: Clone,
+        VisitId: Id,
+    {
+        self.routes
+            .iter()
+            .flat_map(|route| route.visit_ids.iter().map(|visit_id| (visit_id.clone(), route.visitor_id.clone())))
+            .collect()
+    }
 }
 
-pub fn solve<'a, V, R, A, M>(
-    service_date: i64,
-    visits: &'a [V],
-    visitors: &'a [R],
-    availability: &A,
-    matrix_provider: &M,
-    options: SolveOptions,
-) -> PlannerResult<V::VisitorId, V::Id>
-where
-    V: Visit + Sync,
-    R: Visitor<Id = V::VisitorId> + Sync,
-    A: AvailabilityProvider<VisitorId = V::VisitorId> + Sync,
-    M: DistanceMatrixProvider,
-{
-    let solve_start = Instant::now();
-    info!(visits = visits.len(), visitors = visitors.len(), "Starting VRP solve");
+/// Version of the JSON written by [`PlannerResult::to_json`]. Bumped when a
+/// change would stop older readers from loading a stored plan.
+pub const PLAN_SCHEMA_VERSION: u32 = 1;
 
-    let mut to_assign: Vec<&V> = Vec::new();
-    let mut unassigned_with_reason: Vec<(&V, UnassignedReason)> = Vec::new();
-    let mut pinned_assignments: HashMap<&V::VisitorId, Vec<&V>> = HashMap::new();
+/// A plan as stored, tagged with the schema version it was written with.
+#[derive(Serialize, Deserialize)]
+struct StoredPlan<P> {
+    /// Plans stored before versioning carry no tag and read as version 1.
+    #[serde(default = "first_schema_version")]
+    schema_version: u32,
+    #[serde(flatten)]
+    plan: P,
+}
 
-    for visit in visits {
-        if let Some(date) = visit.pinned_date() {
-            if date != service_date {
-                unassigned_with_reason.push((visit, UnassignedReason::WrongDate));
-                continue;
-            }
+fn first_schema_version() -> u32 {
+    1
+}
+
+/// Error loading a stored plan.
+#[derive(Debug)]
+pub enum PlanJsonError {
+    Json(serde_json::Error),
+    /// Written by a newer version of the planner than this one understands.
+    UnsupportedVersion(u32),
+}
+
+impl std::fmt::Display for PlanJsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlanJsonError::Json(err) => write!(f, "Invalid plan JSON: {}", err),
+            PlanJsonError::UnsupportedVersion(version) => write!(
+                f,
+                "Plan schema version {} is newer than supported version {}",
+                version, PLAN_SCHEMA_VERSION
+            ),
         }
+    }
+}
 
-        match visit.pin_type() {
-            VisitPinType::Visitor | VisitPinType::VisitorAndDate => {
-                if let Some(visitor_id) = visit.pinned_visitor() {
-                    pinned_assignments.entry(visitor_id).or_default().push(visit);
-                } else {
-                    unassigned_with_reason.push((visit, UnassignedReason::MissingPinnedVisitor));
-                }
+impl std::error::Error for PlanJsonError {}
+
+impl From<serde_json::Error> for PlanJsonError {
+    fn from(err: serde_json::Error) -> Self {
+        PlanJsonError::Json(err)
+    }
+}
+
+/// A mistake in solver input found by [`validate_input`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputError<VisitId, VisitorId> {
+    DuplicateVisitId(VisitId),
+    DuplicateVisitorId(VisitorId),
+    /// The visit's coordinates aren't finite or are off the globe.
+    InvalidVisitLocation(VisitId),
+    /// The visitor's start or end coordinates aren't finite or are off the
+    /// globe.
+    InvalidVisitorLocation(VisitorId),
+    NegativeDuration(VisitId),
+    /// The visit's committed window ends before it starts.
+    InvertedCommittedWindow(VisitId),
+    /// The visit is pinned to a visitor who isn't in the input.
+    UnknownPinnedVisitor { visit_id: VisitId, visitor_id: VisitorId },
+}
+
+impl<VisitId: std::fmt::Debug, VisitorId: std::fmt::Debug> std::fmt::Display for InputError<VisitId, VisitorId> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InputError::DuplicateVisitId(id) => write!(f, "Duplicate visit ID {:?}", id),
+            InputError::DuplicateVisitorId(id) => write!(f, "Duplicate visitor ID {:?}", id),
+            InputError::InvalidVisitLocation(id) => write!(f, "Visit {:?} has invalid coordinates", id),
+            InputError::InvalidVisitorLocation(id) => write!(f, "Visitor {:?} has invalid coordinates", id),
+            InputError::NegativeDuration(id) => write!(f, "Visit {:?} has a negative duration", id),
+            InputError::InvertedCommittedWindow(id) => {
+                write!(f, "Visit {:?} has a committed window that ends before it starts", id)
             }
-            VisitPinType::Date | VisitPinType::None => {
-                to_assign.push(visit);
+            InputError::UnknownPinnedVisitor { visit_id, visitor_id } => {
+                write!(f, "Visit {:?} is pinned to unknown visitor {:?}", visit_id, visitor_id)
             }
         }
     }
+}
 
-    let locations = collect_locations(visits, visitors);
+impl<VisitId: std::fmt::Debug, VisitorId: std::fmt::Debug> std::error::Error for InputError<VisitId, VisitorId> {}
 
-    let matrix_start = Instant::now();
-    let matrix = matrix_provider.matrix_for(&locations);
-    let matrix_duration = matrix_start.elapsed();
-    info!(locations = locations.len(), duration_ms = matrix_duration.as_millis(), "Distance matrix computed");
+/// Why a solve couldn't plan at all.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolveError<VisitId> {
+    /// The matrix provider returned nothing for a non-empty set of
+    /// locations, typically because its request failed.
+    EmptyMatrix { locations: usize },
+    /// The matrix provider returned a matrix with the wrong number of rows
+    /// or columns.
+    MatrixSizeMismatch { expected: usize, rows: usize, columns: usize },
+    /// A visitor's start or end location reference has no coordinates to
+    /// send to the matrix provider.
+    MissingCoordinates(LocationRef),
+    /// Two visits share this ID under [`DuplicateVisitPolicy::Reject`].
+    DuplicateVisitId(VisitId),
+    /// The solve options failed [`SolveOptions::validate`].
+    InvalidOptions(InvalidOptions),
+}
 
-    // Build efficient coordinate-to-index mapping (avoids string allocation per lookup)
-    let coord_index = build_coord_index(&locations);
+impl<VisitId: std::fmt::Debug> std::fmt::Display for SolveError<VisitId> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolveError::EmptyMatrix { locations } => {
+                write!(f, "Matrix provider returned no travel times for {} locations", locations)
+            }
+            SolveError::MatrixSizeMismatch { expected, rows, columns } => write!(
+                f,
+                "Matrix provider returned a {}x{} matrix for {} locations",
+                rows, columns, expected
+            ),
+            SolveError::MissingCoordinates(location) => write!(f, "No coordinates for location {:?}", location),
+            SolveError::DuplicateVisitId(id) => write!(f, "Duplicate visit ID {:?}", id),
+            SolveError::InvalidOptions(err) => write!(f, "Invalid options: {}", err),
+        }
+    }
+}
 
-    // Assignment phase - initial route building
-    let assignment_start = Instant::now();
+impl<VisitId: std::fmt::Debug> std::error::Error for SolveError<VisitId> {}
 
-    let mut routes: Vec<RouteState<'a, V, R>> = Vec::new();
-    for visitor in visitors {
-        let pinned = pinned_assignments
-            .get(visitor.id())
-            .cloned()
-            .unwrap_or_default();
+/// A plan, or why none could be made.
+pub type SolveResult<VisitorId, VisitId> = Result<PlannerResult<VisitorId, VisitId>, SolveError<VisitId>>;
 
-        let mut route = RouteState {
-            visitor,
-            visits: pinned,
-            estimated_windows: Vec::new(),
-            total_travel_time: 0,
-        };
+/// A single route scheduled by [`schedule_route`], as it would appear in a plan.
+pub type RouteSchedule<VisitorId, VisitId> = RouteResult<VisitorId, VisitId>;
 
-        if !route.visits.is_empty() {
-            if let Some(schedule) = compute_schedule(service_date, &route, availability, &matrix, &coord_index, &options) {
-                route.estimated_windows = schedule.0;
-                route.total_travel_time = schedule.1;
-            } else {
-                for visit in route.visits.drain(..) {
-                    unassigned_with_reason.push((visit, UnassignedReason::NoFeasibleWindow));
+/// Why [`schedule_route`] couldn't schedule a route.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError<VisitId> {
+    /// The travel matrix couldn't be used.
+    Matrix(SolveError<VisitId>),
+    /// The visitor has no availability on the service date.
+    VisitorUnavailable,
+    /// The visitor lacks a capability this visit requires.
+    MissingCapability(VisitId),
+    /// The route has more visits than the visitor's cap of `max`.
+    TooManyVisits { max: usize },
+    /// The visits can't be served in this order. `violations` lists each
+    /// broken window in route order, timing later stops as if the earlier
+    /// ones ran late; it's empty when no window is to blame (e.g., locked
+    /// visits out of sequence, a first or last stop out of place, or a start
+    /// gap).
+    Infeasible { violations: Vec<StopViolation<VisitId>> },
+}
+
+/// A window broken by a fixed route.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StopViolation<VisitId> {
+    /// The visit that breaks its window, or `None` for the visitor's return
+    /// to their end location.
+    pub visit_id: Option<VisitId>,
+    pub constraint: BlockingConstraint,
+    /// How long after the window closes the visit would end, or the visitor
+    /// get back, in seconds.
+    pub overrun_secs: i32,
+}
+
+impl<VisitId> From<SolveError<VisitId>> for ScheduleError<VisitId> {
+    fn from(err: SolveError<VisitId>) -> Self {
+        ScheduleError::Matrix(err)
+    }
+}
+
+impl<VisitId: std::fmt::Debug> std::fmt::Display for ScheduleError<VisitId> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScheduleError::Matrix(err) => write!(f, "{}", err),
+            ScheduleError::VisitorUnavailable => write!(f, "Visitor has no availability on the service date"),
+            ScheduleError::MissingCapability(id) => write!(f, "Visitor can't do visit {:?}", id),
+            ScheduleError::TooManyVisits { max } => write!(f, "Route has more than {} visits", max),
+            ScheduleError::Infeasible { violations } => match violations.first() {
+                Some(StopViolation { visit_id: Some(id), constraint, overrun_secs }) => write!(
+                    f,
+                    "Route breaks {} window(s), first visit {:?} misses its {:?} constraint by {}s",
+                    violations.len(),
+                    id,
+                    constraint,
+                    overrun_secs
+                ),
+                Some(StopViolation { visit_id: None, overrun_secs, .. }) => {
+                    write!(f, "Route gets back {}s after the return window closes", overrun_secs)
                 }
-            }
+                None => write!(f, "Route can't be served in this order"),
+            },
         }
+    }
+}
+
+impl<VisitId: std::fmt::Debug> std::error::Error for ScheduleError<VisitId> {}
 
-        routes.push(route);
+/// A scheduled route, or why it can't run.
+pub type ScheduleResult<VisitorId, VisitId> = Result<RouteSchedule<VisitorId, VisitId>, ScheduleError<VisitId>>;
+
+impl<VisitorId: Serialize, VisitId: Serialize> PlannerResult<VisitorId, VisitId> {
+    /// Serializes the plan for storage, tagged with [`PLAN_SCHEMA_VERSION`].
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(&StoredPlan { schema_version: PLAN_SCHEMA_VERSION, plan: self })
     }
+}
 
-    for visit in to_assign {
-        if !visit_is_compatible(visit, visitors) {
-            unassigned_with_reason.push((visit, UnassignedReason::NoCapableVisitor));
-            continue;
+impl<VisitorId: DeserializeOwned, VisitId: DeserializeOwned> PlannerResult<VisitorId, VisitId> {
+    /// Loads a plan written by [`to_json`](Self::to_json), or an untagged
+    /// plan serialized directly.
+    pub fn from_json(json: &str) -> Result<Self, PlanJsonError> {
+        let stored: StoredPlan<Self> = serde_json::from_str(json)?;
+        if stored.schema_version > PLAN_SCHEMA_VERSION {
+            return Err(PlanJsonError::UnsupportedVersion(stored.schema_version));
         }
+        Ok(stored.plan)
+    }
+}
 
-        // Evaluate all routes in parallel using rayon
-        let route_evaluations: Vec<(usize, Option<usize>, i32, Option<(Vec<(i32, i32)>, i32)>, bool)> =
-            routes.par_iter().enumerate()
-            .filter_map(|(route_index, route)| {
-                // Skip visitors who don't have required capabilities
-                if !visitor_can_do(visit, route.visitor) {
-                    return None;
-                }
-
-                // Check if this capable visitor is available
-                let is_available = availability.availability_for(route.visitor.id(), service_date).is_some();
+/// Trade-off a candidate plan was optimized for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlanTradeoff {
+    /// The caller's options as given.
+    Balanced,
+    /// Reassignment penalty scaled up to keep visits with their current visitor.
+    MostStable,
+    /// Target-time and reassignment penalties dropped to minimize driving.
+    LeastDriving,
+    /// Target-time weight scaled up to hit preferred times.
+    OnTime,
+    /// Reassignment penalty and target-time weight set to these values, tried
+    /// once the named trade-offs give fewer than `k` distinct plans.
+    Reweighted { reassignment_penalty: i32, target_time_weight: i32 },
+}
 
-                // Find best position for this route
-                let mut best_pos: Option<usize> = None;
-                let mut best_cost = i32::MAX;
-                let mut best_schedule: Option<(Vec<(i32, i32)>, i32)> = None;
-
-                for position in 0..=route.visits.len() {
-                    let mut candidate = route.visits.clone();
-                    candidate.insert(position, visit);
-
-                    let candidate_route = RouteState {
-                        visitor: route.visitor,
-                        visits: candidate,
-                        estimated_windows: Vec::new(),
-                        total_travel_time: 0,
-                    };
+/// Multiples of the caller's weights tried for [`PlanTradeoff::Reweighted`].
+const ALTERNATIVE_WEIGHT_FACTORS: [i32; 6] = [0, 1, 3, 10, 30, 100];
 
-                    if let Some(schedule) = compute_schedule(
-                        service_date,
-                        &candidate_route,
-                        availability,
-                        &matrix,
-                        &coord_index,
-                        &options,
-                    ) {
-                        if schedule.1 < best_cost {
-                            best_cost = schedule.1;
-                            best_pos = Some(position);
-                            best_schedule = Some(schedule);
-                        }
-                    }
-                }
+/// One of several distinct plans returned by [`solve_alternatives`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlternativePlan<VisitorId, VisitId> {
+    pub tradeoff: PlanTradeoff,
+    /// Objective cost re-evaluated under the caller's options, so plans are comparable.
+    pub cost: i64,
+    pub plan: PlannerResult<VisitorId, VisitId>,
+}
 
-                Some((route_index, best_pos, best_cost, best_schedule, is_available))
-            })
-            .collect();
+/// Per-visit schedule windows and total cost for a route.
+type Schedule = (Vec<(i32, i32)>, i32);
 
-        // Check if any capable visitor is available
-        let found_capable_available_visitor = route_evaluations.iter().any(|(_ri, _bp, _c, _s, is_available)| *is_available);
+/// Best insertion found on one route: (route index, position, cost, schedule, visitor available).
+type RouteEvaluation = (usize, Option<usize>, i32, Option<Schedule>, bool);
 
-        // Find overall best from parallel results
-        let best = route_evaluations.into_iter()
-            .filter(|(_ri, best_pos, _c, _s, _a)| best_pos.is_some())
-            .min_by_key(|(_ri, _bp, cost, _s, _a)| *cost);
+#[derive(Debug, Clone)]
+struct RouteState<'a, V: Visit, R: Visitor<Id = V::VisitorId>> {
+    visitor: &'a R,
+    /// Matrix row of the visitor's start location, if it has one.
+    start: Option<usize>,
+    /// Matrix row of the visitor's end location, if it has one.
+    end: Option<usize>,
+    visits: Vec<Stop<'a, V>>,
+    estimated_windows: Vec<(i32, i32)>,
+    total_travel_time: i32,
+}
 
-        if let Some((route_index, Some(best_position), _, best_schedule, _)) = best {
-            let route = &mut routes[route_index];
-            route.visits.insert(best_position, visit);
-            if let Some((windows, cost)) = best_schedule {
-                route.estimated_windows = windows;
-                route.total_travel_time = cost;
-            }
-        } else {
-            // Determine the reason: no capable available visitor, or no feasible window
-            let reason = if found_capable_available_visitor {
-                UnassignedReason::NoFeasibleWindow
-            } else {
-                UnassignedReason::NoCapableVisitor
-            };
-            unassigned_with_reason.push((visit, reason));
+impl<'a, V: Visit, R: Visitor<Id = V::VisitorId>> RouteState<'a, V, R> {
+    /// An unscheduled route for `visitor` with the given stops.
+    fn with_visits(visitor: &'a R, start: Option<usize>, end: Option<usize>, visits: Vec<Stop<'a, V>>) -> Self {
+        Self {
+            visitor,
+            start,
+            end,
+            visits,
+            estimated_windows: Vec::new(),
+            total_travel_time: 0,
         }
     }
 
-    let assignment_duration = assignment_start.elapsed();
-    let assigned_so_far = routes.iter().map(|r| r.visits.len()).sum::<usize>();
-    info!(
+    /// Matrix row the route departs from: the visitor's start location, or
+    /// the first visit if it has none.
+    fn origin(&self) -> Option<usize> {
+        self.start.or_else(|| self.visits.first().map(|stop| stop.location))
+    }
+}
+
+/// A visit with its matrix row resolved once, so schedule evaluation never
+/// looks locations up by [`LocationRef`].
+#[derive(Debug)]
+struct Stop<'a, V> {
+    visit: &'a V,
+    location: usize,
+    /// Span the whole visit must run within, from its committed window.
+    committed: Option<TimeWindow>,
+    /// Seconds held free after the visit, from its duration buffer.
+    buffer: i32,
+    /// Place in the visitor's locked route prefix, for visits pinned to one.
+    locked: Option<u32>,
+    /// First or last stop on the route, for visits that aren't locked.
+    position: Option<RoutePosition>,
+}
+
+impl<'a, V: Visit> Stop<'a, V> {
+    fn new(visit: &'a V, location: usize, options: &SolveOptions) -> Self {
+        let committed = service_bounds(visit, options.committed_window_semantics);
+        let locked = visit.locked_sequence().filter(|_| is_pinned_to_visitor(visit));
+        let position = visit.route_position().filter(|_| locked.is_none());
+        Self { visit, location, committed, buffer: visit.duration_buffer_minutes() * 60, locked, position }
+    }
+}
+
+impl<V> Clone for Stop<'_, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<V> Copy for Stop<'_, V> {}
+
+impl<V> std::ops::Deref for Stop<'_, V> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        self.visit
+    }
+}
+
+/// Checks visits and visitors for input the solver can't plan sensibly.
+///
+/// The solver doesn't run this itself: bad coordinates turn into garbage
+/// travel times and duplicate IDs into ambiguous plans rather than errors.
+/// Run it before [`solve`] on untrusted input; empty if the input looks
+/// well-formed.
+pub fn validate_input<V, R>(visits: &[V], visitors: &[R]) -> Vec<InputError<V::Id, V::VisitorId>>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+{
+    let mut errors = Vec::new();
+
+    let mut visitor_ids = HashSet::new();
+    for visitor in visitors {
+        if !visitor_ids.insert(visitor.id()) {
+            errors.push(InputError::DuplicateVisitorId(visitor.id().clone()));
+        }
+        if !visitor.start_location().into_iter().chain(visitor.end_location()).all(is_valid_location) {
+            errors.push(InputError::InvalidVisitorLocation(visitor.id().clone()));
+        }
+    }
+
+    let mut visit_ids = HashSet::new();
+    for visit in visits {
+        if !visit_ids.insert(visit.id()) {
+            errors.push(InputError::DuplicateVisitId(visit.id().clone()));
+        }
+        if !is_valid_location(visit.location()) {
+            errors.push(InputError::InvalidVisitLocation(visit.id().clone()));
+        }
+        if visit.estimated_duration_minutes() < 0 {
+            errors.push(InputError::NegativeDuration(visit.id().clone()));
+        }
+        if visit.committed_window().is_some_and(|(start, end)| start > end) {
+            errors.push(InputError::InvertedCommittedWindow(visit.id().clone()));
+        }
+        if let Some(visitor_id) = visit.pinned_visitor()
+            && !visitor_ids.contains(visitor_id)
+        {
+            errors.push(InputError::UnknownPinnedVisitor {
+                visit_id: visit.id().clone(),
+                visitor_id: visitor_id.clone(),
+            });
+        }
+    }
+
+    errors
+}
+
+/// Applies [`SolveOptions::duplicate_visits`]: warns about each visit whose
+/// ID repeats an earlier one, or rejects the first such visit.
+fn check_duplicate_visits<V: Visit>(visits: &[V], options: &SolveOptions) -> Result<(), SolveError<V::Id>> {
+    let mut seen = HashSet::new();
+    for (index, visit) in visits.iter().enumerate() {
+        if seen.insert(visit.id()) {
+            continue;
+        }
+        match options.duplicate_visits {
+            DuplicateVisitPolicy::KeepFirst => {
+                warn!(index, visit_id = ?visit.id(), "Duplicate visit ID, keeping the first visit with it")
+            }
+            DuplicateVisitPolicy::Reject => return Err(SolveError::DuplicateVisitId(visit.id().clone())),
+        }
+    }
+    Ok(())
+}
+
+/// The first visit with each ID, in order; later repeats are never planned.
+fn distinct_visits<V: Visit>(visits: &[V]) -> impl Iterator<Item = &V> {
+    let mut seen = HashSet::new();
+    visits.iter().filter(move |visit| seen.insert(visit.id()))
+}
+
+/// Finite (lat, lng) on the globe.
+pub(crate) fn is_valid_location((lat, lng): (f64, f64)) -> bool {
+    (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lng)
+}
+
+/// Whether `visit` is kept off every route for its coordinates: always under
+/// [`InvalidLocationPolicy::Unassign`], and under `Centroid` when none of
+/// `visits` has coordinates to take a centroid of.
+fn location_unassigned<V: Visit>(visit: &V, visits: &[V], options: &SolveOptions) -> bool {
+    !is_routable_location(visit.location(), options)
+        && (options.invalid_locations == InvalidLocationPolicy::Unassign
+            || !visits.iter().any(|other| is_routable_location(other.location(), options)))
+}
+
+/// Coordinates worth asking a matrix provider about: valid, and not the
+/// (0, 0) a failed geocode leaves behind if
+/// [`null_island_invalid`](SolveOptions::null_island_invalid) is set.
+fn is_routable_location(location: (f64, f64), options: &SolveOptions) -> bool {
+    is_valid_location(location) && !(options.null_island_invalid && location == (0.0, 0.0))
+}
+
+/// Plans routes for `service_date`.
+///
+/// Visits that can't be placed come back in
+/// [`unassigned`](PlannerResult::unassigned); the solve itself fails only when
+/// the matrix provider's travel times can't be used.
+pub fn solve<'a, V, R, A, M>(
+    service_date: i64,
+    visits: &'a [V],
+    visitors: &'a [R],
+    availability: &A,
+    matrix_provider: &M,
+    options: SolveOptions,
+) -> SolveResult<V::VisitorId, V::Id>
+where
+    V: Visit<Id: Sync> + Sync,
+    R: Visitor<Id = V::VisitorId> + Sync,
+    A: AvailabilityProvider<VisitorId = V::VisitorId> + Sync,
+    M: DistanceMatrixProvider,
+{
+    solve_with_constraints(service_date, visits, visitors, availability, matrix_provider, options, &[])
+}
+
+/// [`solve`] with additional hard constraints on every route.
+///
+/// See [`RouteConstraint`] for how constraints are applied.
+pub fn solve_with_constraints<'a, V, R, A, M>(
+    service_date: i64,
+    visits: &'a [V],
+    visitors: &'a [R],
+    availability: &A,
+    matrix_provider: &M,
+    options: SolveOptions,
+    constraints: &[Box<dyn RouteConstraint<V, R>>],
+) -> SolveResult<V::VisitorId, V::Id>
+where
+    V: Visit<Id: Sync> + Sync,
+    R: Visitor<Id = V::VisitorId> + Sync,
+    A: AvailabilityProvider<VisitorId = V::VisitorId> + Sync,
+    M: DistanceMatrixProvider,
+{
+    let rules = RouteRules::new(service_date, constraints).with_gaps(visits);
+    solve_with_rules(service_date, visits, visitors, availability, matrix_provider, options, &rules)
+}
+
+/// [`solve_with_constraints`] that also favors each location's regular visitor.
+///
+/// For every visit location, the visitor in `visitors` the history shows
+/// serving it most often (ties go to the earlier visitor) is preferred;
+/// assigning the visit to anyone else costs
+/// [`continuity_weight`](SolveOptions::continuity_weight). Unlike the
+/// reassignment penalty, which keeps yesterday's draft stable, this rewards
+/// long-term customer relationships.
+#[allow(clippy::too_many_arguments)]
+pub fn solve_with_history<'a, V, R, A, M, H>(
+    service_date: i64,
+    visits: &'a [V],
+    visitors: &'a [R],
+    availability: &A,
+    matrix_provider: &M,
+    history: &H,
+    options: SolveOptions,
+    constraints: &[Box<dyn RouteConstraint<V, R>>],
+) -> SolveResult<V::VisitorId, V::Id>
+where
+    V: Visit<Id: Sync> + Sync,
+    R: Visitor<Id = V::VisitorId> + Sync,
+    A: AvailabilityProvider<VisitorId = V::VisitorId> + Sync,
+    M: DistanceMatrixProvider,
+    H: VisitHistoryProvider<VisitorId = V::VisitorId>,
+{
+    let rules = RouteRules {
+        service_date,
+        constraints,
+        continuity: regular_visitors(visits, visitors, history),
+        gaps: VisitGaps::new(visits),
+    };
+    solve_with_rules(service_date, visits, visitors, availability, matrix_provider, options, &rules)
+}
+
+/// Fetch the matrix, then plan under the given rules.
+fn solve_with_rules<'a, V, R, A, M>(
+    service_date: i64,
+    visits: &'a [V],
+    visitors: &'a [R],
+    availability: &A,
+    matrix_provider: &M,
+    options: SolveOptions,
+    rules: &RouteRules<'_, V, R>,
+) -> SolveResult<V::VisitorId, V::Id>
+where
+    V: Visit<Id: Sync> + Sync,
+    R: Visitor<Id = V::VisitorId> + Sync,
+    A: AvailabilityProvider<VisitorId = V::VisitorId> + Sync,
+    M: DistanceMatrixProvider,
+{
+    let _solve = solve_span(visits.len(), visitors.len()).entered();
+    let solve_start = Instant::now();
+    info!(visits = visits.len(), visitors = visitors.len(), "Starting VRP solve");
+
+    check_duplicate_visits(visits, &options).inspect_err(failed)?;
+    let (locations, location_index) = collect_locations(visits, visitors, &options).inspect_err(failed)?;
+
+    let matrix_start = Instant::now();
+    let matrix = matrix_span(locations.len())
+        .in_scope(|| fleet_matrices(matrix_provider, visitors, &locations, &location_index, &options))
+        .inspect_err(failed)?;
+    let matrix_duration = matrix_start.elapsed();
+    info!(locations = locations.len(), duration_ms = matrix_duration.as_millis(), "Distance matrix computed");
+
+    Ok(solve_with_matrix(service_date, visits, visitors, availability, &location_index, &matrix, options, rules, solve_start, matrix_duration))
+}
+
+/// Caller-supplied rules every candidate route is checked and scored against.
+struct RouteRules<'c, V: Visit, R: Visitor<Id = V::VisitorId>> {
+    /// Date the routes run on, which recurring visits may drift from.
+    service_date: i64,
+    constraints: &'c [Box<dyn RouteConstraint<V, R>>],
+    /// Each location's regular visitor, from visit history.
+    continuity: HashMap<LocationRef, &'c R>,
+    gaps: VisitGaps<V::Id>,
+}
+
+impl<'c, V: Visit, R: Visitor<Id = V::VisitorId>> RouteRules<'c, V, R> {
+    fn new(service_date: i64, constraints: &'c [Box<dyn RouteConstraint<V, R>>]) -> Self {
+        Self { service_date, constraints, continuity: HashMap::new(), gaps: VisitGaps::default() }
+    }
+
+    /// Enforces the [`start_gap`](Visit::start_gap)s among `visits`.
+    fn with_gaps(mut self, visits: &[V]) -> Self {
+        self.gaps = VisitGaps::new(visits);
+        self
+    }
+}
+
+/// Start gaps between visits, keyed by visit ID.
+///
+/// A route is scheduled against [`LinkedWindows`]: where linked visits on
+/// other routes currently sit, taken from the plan by whoever schedules it.
+struct VisitGaps<I> {
+    /// Each visit's (predecessor, min secs, max secs).
+    after: HashMap<I, (I, i32, i32)>,
+    /// Each predecessor's (follower, min secs, max secs).
+    before: HashMap<I, Vec<(I, i32, i32)>>,
+}
+
+/// The scheduled window of each visit linked by a start gap, by visit ID.
+type LinkedWindows<I> = HashMap<I, TimeWindow>;
+
+impl<I> Default for VisitGaps<I> {
+    fn default() -> Self {
+        Self { after: HashMap::new(), before: HashMap::new() }
+    }
+}
+
+impl<I: Id> VisitGaps<I> {
+    fn new<V: Visit<Id = I>>(visits: &[V]) -> Self {
+        let ids: HashSet<&I> = visits.iter().map(Visit::id).collect();
+        let mut gaps = Self::default();
+        for visit in distinct_visits(visits) {
+            let Some(gap) = visit.start_gap().filter(|gap| gap.after != *visit.id() && ids.contains(&gap.after)) else {
+                continue;
+            };
+            gaps.after.insert(visit.id().clone(), (gap.after.clone(), gap.min_secs, gap.max_secs));
+            gaps.before.entry(gap.after.clone()).or_default().push((visit.id().clone(), gap.min_secs, gap.max_secs));
+        }
+        gaps
+    }
+
+    fn is_empty(&self) -> bool {
+        self.after.is_empty()
+    }
+
+    /// Whether a start gap ties the visit `id` to another visit.
+    fn links(&self, id: &I) -> bool {
+        self.after.contains_key(id) || self.before.contains_key(id)
+    }
+
+    /// Number of predecessors before `visit` in its chain of gaps.
+    fn depth<V: Visit<Id = I>>(&self, visit: &V) -> u32 {
+        let mut id = visit.id();
+        let mut depth = 0;
+        while let Some((first, _, _)) = self.after.get(id)
+            && (depth as usize) < self.after.len()
+        {
+            id = first;
+            depth += 1;
+        }
+        depth
+    }
+
+    /// Route indexes in scheduling order: where links allow, a route comes
+    /// after the routes holding its visits' predecessors.
+    fn route_order<V, R>(&self, routes: &[RouteState<'_, V, R>]) -> Vec<usize>
+    where
+        V: Visit<Id = I>,
+        R: Visitor<Id = V::VisitorId>,
+    {
+        if self.is_empty() {
+            return (0..routes.len()).collect();
+        }
+        let route_of: HashMap<&I, usize> = routes
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, route)| route.visits.iter().map(move |visit| (visit.id(), idx)))
+            .collect();
+        let mut done = vec![false; routes.len()];
+        let mut order = Vec::with_capacity(routes.len());
+        while order.len() < routes.len() {
+            let ready = |idx: &usize| {
+                !done[*idx]
+                    && routes[*idx].visits.iter().all(|visit| {
+                        let first = self.after.get(visit.id()).and_then(|(first, _, _)| route_of.get(first));
+                        first.is_none_or(|&first| first == *idx || done[first])
+                    })
+            };
+            let Some(next) = (0..routes.len()).find(ready).or_else(|| (0..routes.len()).find(|idx| !done[*idx])) else {
+                break;
+            };
+            done[next] = true;
+            order.push(next);
+        }
+        order
+    }
+
+    /// Where every linked visit on `routes` is scheduled.
+    fn linked_windows<V, R>(&self, routes: &[RouteState<'_, V, R>]) -> LinkedWindows<I>
+    where
+        V: Visit<Id = I>,
+        R: Visitor<Id = V::VisitorId>,
+    {
+        if self.is_empty() {
+            return LinkedWindows::new();
+        }
+        routes
+            .iter()
+            .flat_map(|route| route.visits.iter().zip(&route.estimated_windows))
+            .filter(|(visit, _)| self.links(visit.id()))
+            .map(|(visit, window)| (visit.id().clone(), *window))
+            .collect()
+    }
+
+    /// Where every linked visit in `plan` other than `except` is scheduled.
+    fn planned_windows<R>(&self, plan: &PlannerResult<R, I>, except: &I) -> LinkedWindows<I> {
+        plan.routes
+            .iter()
+            .flat_map(|route| route.visit_ids.iter().zip(&route.estimated_windows))
+            .filter(|(id, _)| *id != except && self.links(id))
+            .map(|(id, window)| (id.clone(), *window))
+            .collect()
+    }
+
+    /// Position of each visit on `visits`, empty without gaps.
+    fn positions<'v, V: Visit<Id = I>>(&self, visits: &[Stop<'v, V>]) -> HashMap<&'v I, usize> {
+        if self.is_empty() {
+            return HashMap::new();
+        }
+        visits.iter().enumerate().map(|(idx, visit)| (visit.visit.id(), idx)).collect()
+    }
+
+    /// Earliest and latest start for the visit at `idx` of a route being
+    /// scheduled, given the windows of the visits before it. `None` if it
+    /// comes before its predecessor on the route.
+    ///
+    /// Linked visits on the route bound each other as the later one is
+    /// scheduled; linked visits elsewhere bound it from their `linked` windows.
+    fn start_bounds<V: Visit<Id = I>>(
+        &self,
+        visit: &V,
+        idx: usize,
+        positions: &HashMap<&I, usize>,
+        windows: &[TimeWindow],
+        linked: &LinkedWindows<I>,
+    ) -> Option<TimeWindow> {
+        let mut bounds = (i32::MIN, i32::MAX);
+        if self.is_empty() {
+            return Some(bounds);
+        }
+        if let Some((first, min_secs, max_secs)) = self.after.get(visit.id()) {
+            let first_end = match positions.get(first) {
+                Some(&position) if position < idx => Some(windows[position].1),
+                Some(_) => return None,
+                None => linked.get(first).map(|(_, end)| *end),
+            };
+            if let Some(end) = first_end {
+                bounds = (end + min_secs, end + max_secs);
+            }
+        }
+        let duration_secs = visit.estimated_duration_minutes() * 60;
+        for (then, min_secs, max_secs) in self.before.get(visit.id()).into_iter().flatten() {
+            if positions.contains_key(then) {
+                continue;
+            }
+            if let Some(&(start, _)) = linked.get(then) {
+                bounds.0 = bounds.0.max(start - max_secs - duration_secs);
+                bounds.1 = bounds.1.min(start - min_secs - duration_secs);
+            }
+        }
+        Some(bounds)
+    }
+
+    /// Whether every gap between two visits on `routes` (stops with their
+    /// windows) holds.
+    fn hold<V: Visit<Id = I>>(&self, routes: &[(&[Stop<'_, V>], &[TimeWindow])]) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let windows: HashMap<&I, TimeWindow> = routes
+            .iter()
+            .flat_map(|(visits, windows)| visits.iter().zip(windows.iter()))
+            .map(|(visit, window)| (visit.visit.id(), *window))
+            .collect();
+        windows.iter().all(|(id, (start, _))| {
+            self.after.get(*id).is_none_or(|(first, min_secs, max_secs)| {
+                windows.get(first).is_none_or(|(_, end)| (end + min_secs..=end + max_secs).contains(start))
+            })
+        })
+    }
+}
+
+/// The visitor who has served each visit location most, among `visitors`.
+fn regular_visitors<'r, V, R, H>(visits: &[V], visitors: &'r [R], history: &H) -> HashMap<LocationRef, &'r R>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+    H: VisitHistoryProvider<VisitorId = V::VisitorId>,
+{
+    let mut regulars = HashMap::new();
+    for visit in visits {
+        let location = visit.location_ref();
+        if regulars.contains_key(&location) {
+            continue;
+        }
+        let mut best: Option<(&R, u32)> = None;
+        for visitor in visitors {
+            let count = history.visit_count(visitor.id(), location);
+            if count > best.map_or(0, |(_, most)| most) {
+                best = Some((visitor, count));
+            }
+        }
+        if let Some((visitor, _)) = best {
+            regulars.insert(location, visitor);
+        }
+    }
+    regulars
+}
+
+/// Async variant of [`solve`] for use inside async runtimes.
+///
+/// The matrix and every visitor's availability are fetched up front through
+/// the async providers, so no I/O blocks the executor. The planning itself is
+/// CPU-bound; very large instances may still warrant `spawn_blocking`.
+pub async fn solve_async<'a, V, R, A, M>(
+    service_date: i64,
+    visits: &'a [V],
+    visitors: &'a [R],
+    availability: &A,
+    matrix_provider: &M,
+    options: SolveOptions,
+) -> SolveResult<V::VisitorId, V::Id>
+where
+    V: Visit<Id: Sync> + Sync,
+    V::VisitorId: Send + Sync,
+    R: Visitor<Id = V::VisitorId> + Sync,
+    A: AsyncAvailabilityProvider<VisitorId = V::VisitorId> + Sync,
+    M: AsyncDistanceMatrixProvider + Sync,
+{
+    solve_async_with_constraints(service_date, visits, visitors, availability, matrix_provider, options, &[]).await
+}
+
+/// [`solve_async`] with additional hard constraints on every route.
+pub async fn solve_async_with_constraints<'a, V, R, A, M>(
+    service_date: i64,
+    visits: &'a [V],
+    visitors: &'a [R],
+    availability: &A,
+    matrix_provider: &M,
+    options: SolveOptions,
+    constraints: &[Box<dyn RouteConstraint<V, R>>],
+) -> SolveResult<V::VisitorId, V::Id>
+where
+    V: Visit<Id: Sync> + Sync,
+    V::VisitorId: Send + Sync,
+    R: Visitor<Id = V::VisitorId> + Sync,
+    A: AsyncAvailabilityProvider<VisitorId = V::VisitorId> + Sync,
+    M: AsyncDistanceMatrixProvider + Sync,
+{
+    async move {
+        let solve_start = Instant::now();
+        info!(visits = visits.len(), visitors = visitors.len(), "Starting async VRP solve");
+
+        check_duplicate_visits(visits, &options).inspect_err(failed)?;
+        let (locations, location_index) = collect_locations(visits, visitors, &options).inspect_err(failed)?;
+
+        let matrix_start = Instant::now();
+        let prepare = |matrix| prepare_matrix(matrix, &locations, &location_index, &options).inspect_err(failed);
+        let (profiles, uses_default) = routing_profiles(visitors);
+        let matrix = async {
+            let default =
+                if uses_default { Some(prepare(matrix_provider.fetch_matrices(&locations).await)?) } else { None };
+            let mut fetched = Vec::with_capacity(profiles.len());
+            for profile in profiles {
+                fetched.push((profile, prepare(matrix_provider.fetch_matrices_for_profile(profile, &locations).await)?));
+            }
+            Ok::<_, SolveError<V::Id>>(FleetMatrices::new(default, fetched))
+        }
+        .instrument(matrix_span(locations.len()))
+        .await?;
+        let matrix_duration = matrix_start.elapsed();
+        info!(locations = locations.len(), duration_ms = matrix_duration.as_millis(), "Distance matrix computed");
+
+        let mut windows = HashMap::with_capacity(visitors.len());
+        for visitor in visitors {
+            let visitor_windows = availability.fetch_availability(visitor.id(), service_date).await;
+            windows.insert(visitor.id().clone(), visitor_windows);
+        }
+        let availability = PrefetchedAvailability { windows };
+
+        let rules = RouteRules::new(service_date, constraints).with_gaps(visits);
+        Ok(solve_with_matrix(service_date, visits, visitors, &availability, &location_index, &matrix, options, &rules, solve_start, matrix_duration))
+    }
+    .instrument(solve_span(visits.len(), visitors.len()))
+    .await
+}
+
+/// Span covering a whole solve; [`solve_with_matrix`] records the outcome.
+fn solve_span(visits: usize, visitors: usize) -> Span {
+    let span = info_span!(
+        "vrp.solve",
+        visits,
+        visitors,
+        routes = Empty,
+        assigned = Empty,
+        unassigned = Empty,
+        cost = Empty,
+        otel.kind = Empty,
+        otel.status_code = Empty,
+    );
+    telemetry::internal(&span);
+    span
+}
+
+/// Logs a solve that couldn't start and marks its span as failed.
+fn failed<VisitId>(err: &SolveError<VisitId>) {
+    match err {
+        SolveError::EmptyMatrix { locations } => warn!(locations, "VRP solve failed: empty matrix"),
+        SolveError::MatrixSizeMismatch { expected, rows, columns } => {
+            warn!(expected, rows, columns, "VRP solve failed: matrix size mismatch")
+        }
+        SolveError::MissingCoordinates(location) => warn!(?location, "VRP solve failed: location without coordinates"),
+        SolveError::DuplicateVisitId(_) => warn!("VRP solve failed: duplicate visit ID"),
+        SolveError::InvalidOptions(err) => warn!(%err, "VRP solve failed: invalid options"),
+    }
+    telemetry::status(&Span::current(), false);
+}
+
+fn matrix_span(locations: usize) -> Span {
+    let span = info_span!("vrp.matrix", locations, otel.kind = Empty);
+    telemetry::internal(&span);
+    span
+}
+
+/// Availability fetched ahead of a solve, keyed by visitor.
+struct PrefetchedAvailability<VisitorId> {
+    windows: HashMap<VisitorId, Option<Vec<TimeWindow>>>,
+}
+
+impl<VisitorId: Id> AvailabilityProvider for PrefetchedAvailability<VisitorId> {
+    type VisitorId = VisitorId;
+
+    fn availability_for(&self, visitor_id: &Self::VisitorId, _date: i64) -> Option<Vec<TimeWindow>> {
+        self.windows.get(visitor_id).cloned().flatten()
+    }
+}
+
+/// Plan routes against an already-fetched matrix and log the solve summary.
+#[allow(clippy::too_many_arguments)]
+fn solve_with_matrix<'a, V, R, A>(
+    service_date: i64,
+    visits: &'a [V],
+    visitors: &'a [R],
+    availability: &A,
+    location_index: &LocationIndex,
+    matrix: &FleetMatrices,
+    options: SolveOptions,
+    rules: &RouteRules<'_, V, R>,
+    solve_start: Instant,
+    matrix_duration: Duration,
+) -> PlannerResult<V::VisitorId, V::Id>
+where
+    V: Visit<Id: Sync> + Sync,
+    R: Visitor<Id = V::VisitorId> + Sync,
+    A: AvailabilityProvider<VisitorId = V::VisitorId> + Sync,
+{
+    let plan = plan_routes(service_date, visits, visitors, availability, matrix, location_index, &options, rules);
+    let cost = plan_cost(&plan.routes);
+    let assignment_duration = plan.assignment_duration;
+    let local_search_duration = plan.local_search_duration;
+    let result = into_planner_result(plan, service_date, availability, matrix, &options, rules);
+
+    let total_duration = solve_start.elapsed();
+    let assigned_count = result.routes.iter().map(|r| r.visit_ids.len()).sum::<usize>();
+    info!(
+        total_ms = total_duration.as_millis(),
+        matrix_ms = matrix_duration.as_millis(),
+        assignment_ms = assignment_duration.as_millis(),
+        local_search_ms = local_search_duration.as_millis(),
+        routes = result.routes.len(),
+        assigned = assigned_count,
+        unassigned = result.unassigned.len(),
+        "VRP solve complete"
+    );
+
+    let span = Span::current();
+    span.record("routes", result.routes.len());
+    span.record("assigned", assigned_count);
+    span.record("unassigned", result.unassigned.len());
+    span.record("cost", cost);
+    telemetry::status(&span, result.is_feasible());
+
+    result
+}
+
+/// Solve for up to `k` distinct plans representing different trade-offs.
+///
+/// The matrix is fetched once and the planner is re-run with the options
+/// reweighted toward stability, driving, and target times. While that gives
+/// fewer than `k` distinct plans, it goes on through a grid of reassignment
+/// and target-time weights ([`PlanTradeoff::Reweighted`]), so fewer come back
+/// only when no weighting tells more plans apart. Plans with identical routes
+/// are collapsed, and the rest are ranked by unassigned count and then by
+/// cost under the caller's `options`.
+#[allow(clippy::type_complexity)]
+pub fn solve_alternatives<'a, V, R, A, M>(
+    service_date: i64,
+    visits: &'a [V],
+    visitors: &'a [R],
+    availability: &A,
+    matrix_provider: &M,
+    options: SolveOptions,
+    k: usize,
+) -> Result<Vec<AlternativePlan<V::VisitorId, V::Id>>, SolveError<V::Id>>
+where
+    V: Visit<Id: Sync> + Sync,
+    R: Visitor<Id = V::VisitorId> + Sync,
+    A: AvailabilityProvider<VisitorId = V::VisitorId> + Sync,
+    M: DistanceMatrixProvider,
+{
+    solve_alternatives_with_constraints(service_date, visits, visitors, availability, matrix_provider, options, &[], k)
+}
+
+/// [`solve_alternatives`] with additional hard constraints on every route.
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+pub fn solve_alternatives_with_constraints<'a, V, R, A, M>(
+    service_date: i64,
+    visits: &'a [V],
+    visitors: &'a [R],
+    availability: &A,
+    matrix_provider: &M,
+    options: SolveOptions,
+    constraints: &[Box<dyn RouteConstraint<V, R>>],
+    k: usize,
+) -> Result<Vec<AlternativePlan<V::VisitorId, V::Id>>, SolveError<V::Id>>
+where
+    V: Visit<Id: Sync> + Sync,
+    R: Visitor<Id = V::VisitorId> + Sync,
+    A: AvailabilityProvider<VisitorId = V::VisitorId> + Sync,
+    M: DistanceMatrixProvider,
+{
+    check_duplicate_visits(visits, &options)?;
+    let (locations, location_index) = collect_locations(visits, visitors, &options)?;
+    let matrix = fleet_matrices(matrix_provider, visitors, &locations, &location_index, &options)?;
+
+    let named = [
+        (PlanTradeoff::Balanced, options.clone()),
+        (
+            PlanTradeoff::MostStable,
+            SolveOptions { reassignment_penalty: options.reassignment_penalty.max(1).saturating_mul(10), ..options.clone() },
+        ),
+        (
+            PlanTradeoff::LeastDriving,
+            SolveOptions { target_time_weight: 0, reassignment_penalty: 0, ..options.clone() },
+        ),
+        (
+            PlanTradeoff::OnTime,
+            SolveOptions { target_time_weight: options.target_time_weight.max(1).saturating_mul(10), ..options.clone() },
+        ),
+    ];
+    let named_count = named.len();
+    let reweighted = ALTERNATIVE_WEIGHT_FACTORS.into_iter().flat_map(|stability| {
+        let options = &options;
+        ALTERNATIVE_WEIGHT_FACTORS.into_iter().map(move |punctuality| {
+            let reassignment_penalty = options.reassignment_penalty.max(1).saturating_mul(stability);
+            let target_time_weight = options.target_time_weight.max(1).saturating_mul(punctuality);
+            (
+                PlanTradeoff::Reweighted { reassignment_penalty, target_time_weight },
+                SolveOptions { reassignment_penalty, target_time_weight, ..options.clone() },
+            )
+        })
+    });
+
+    let mut seen: Vec<Vec<Vec<V::Id>>> = Vec::new();
+    let mut alternatives = Vec::new();
+    let rules = RouteRules::new(service_date, constraints).with_gaps(visits);
+    for (idx, (tradeoff, variant_options)) in named.into_iter().chain(reweighted).enumerate() {
+        if idx >= named_count && alternatives.len() >= k {
+            break;
+        }
+        let plan = plan_routes(service_date, visits, visitors, availability, &matrix, &location_index, &variant_options, &rules);
+
+        let key: Vec<Vec<V::Id>> = plan
+            .routes
+            .iter()
+            .map(|route| route.visits.iter().map(|visit| visit.id().clone()).collect())
+            .collect();
+        if seen.contains(&key) {
+            continue;
+        }
+        seen.push(key);
+
+        let linked = rules.gaps.linked_windows(&plan.routes);
+        let cost = plan
+            .routes
+            .iter()
+            .filter(|route| !route.visits.is_empty())
+            .map(|route| {
+                let cost = compute_schedule(service_date, route, availability, &matrix, &options, &rules, &linked)
+                    .map_or(route.total_travel_time, |(_, cost)| cost);
+                i64::from(cost)
+            })
+            .sum();
+        let plan = into_planner_result(plan, service_date, availability, &matrix, &variant_options, &rules);
+        alternatives.push(AlternativePlan { tradeoff, cost, plan });
+    }
+
+    alternatives.sort_by_key(|alternative| (alternative.plan.unassigned.len(), alternative.cost));
+    alternatives.truncate(k);
+    Ok(alternatives)
+}
+
+/// Where a visit could join one visitor's route in an existing plan.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InsertionOption<VisitorId> {
+    pub visitor_id: VisitorId,
+    /// Index the visit would take in the route's visit order.
+    pub position: usize,
+    /// Increase in the route's cost.
+    pub added_cost: i32,
+    /// Estimated (start, end) of the inserted visit.
+    pub estimated_window: (i32, i32),
+}
+
+/// Cheapest feasible slot for one visit on each visitor's route in `plan`,
+/// cheapest first.
+///
+/// The plan's routes are kept as they are apart from the insertion, so this
+/// answers "who can take this job, and what does it cost?" without
+/// re-solving. `visits` must include the visit being evaluated and every
+/// visit on the plan's routes. Visitors who can't take the visit
+/// (capabilities, pinning, its visitor groups, a full route, or no feasible
+/// position) are left out, as is every visitor if the visit's
+/// date bounds exclude `service_date` or `visit_id` isn't in `visits`.
+#[allow(clippy::too_many_arguments)]
+pub fn evaluate_insertion<V, R, A, M>(
+    service_date: i64,
+    visits: &[V],
+    visitors: &[R],
+    availability: &A,
+    matrix_provider: &M,
+    options: SolveOptions,
+    plan: &PlannerResult<V::VisitorId, V::Id>,
+    visit_id: &V::Id,
+) -> Result<Vec<InsertionOption<V::VisitorId>>, SolveError<V::Id>>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+    A: AvailabilityProvider<VisitorId = V::VisitorId>,
+    M: DistanceMatrixProvider,
+{
+    evaluate_insertion_with_constraints(
+        service_date,
+        visits,
+        visitors,
+        availability,
+        matrix_provider,
+        options,
+        &[],
+        plan,
+        visit_id,
+    )
+}
+
+/// [`evaluate_insertion`] that also leaves out slots a constraint rejects.
+#[allow(clippy::too_many_arguments)]
+pub fn evaluate_insertion_with_constraints<V, R, A, M>(
+    service_date: i64,
+    visits: &[V],
+    visitors: &[R],
+    availability: &A,
+    matrix_provider: &M,
+    options: SolveOptions,
+    constraints: &[Box<dyn RouteConstraint<V, R>>],
+    plan: &PlannerResult<V::VisitorId, V::Id>,
+    visit_id: &V::Id,
+) -> Result<Vec<InsertionOption<V::VisitorId>>, SolveError<V::Id>>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+    A: AvailabilityProvider<VisitorId = V::VisitorId>,
+    M: DistanceMatrixProvider,
+{
+    let Some(visit) = visits.iter().find(|visit| visit.id() == visit_id) else {
+        return Ok(Vec::new());
+    };
+    if !date_allowed(visit, service_date) || location_unassigned(visit, visits, &options) {
+        return Ok(Vec::new());
+    }
+
+    check_duplicate_visits(visits, &options)?;
+    let (locations, location_index) = collect_locations(visits, visitors, &options)?;
+    let matrix = fleet_matrices(matrix_provider, visitors, &locations, &location_index, &options)?;
+    let by_id: HashMap<&V::Id, &V> = distinct_visits(visits).map(|visit| (visit.id(), visit)).collect();
+    let rules = RouteRules::new(service_date, constraints).with_gaps(visits);
+    let linked = rules.gaps.planned_windows(plan, visit_id);
+
+    let mut slots: Vec<InsertionOption<V::VisitorId>> = visitors
+        .iter()
+        .filter_map(|visitor| {
+            let route = route_without(visit, visitor, &by_id, &location_index, &options, plan)?;
+            let (_, base_cost) =
+                compute_schedule(service_date, &route, availability, &matrix, &options, &rules, &linked)?;
+            let stop = Stop::new(visit, location_index[&visit.location_ref()], &options);
+            let (position, (windows, cost)) =
+                best_insertion(service_date, &route, stop, availability, &matrix, &options, &rules, &linked)?;
+
+            Some(InsertionOption {
+                visitor_id: visitor.id().clone(),
+                position,
+                added_cost: cost - base_cost,
+                estimated_window: windows[position],
+            })
+        })
+        .collect();
+
+    slots.sort_by_key(|slot| slot.added_cost);
+    Ok(slots)
+}
+
+/// An arrival window a new booking could be offered.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppointmentSlot<VisitorId> {
+    pub visitor_id: VisitorId,
+    /// Window the visitor would arrive in (seconds from midnight).
+    pub window: (i32, i32),
+    /// Increase in the route's cost for the cheapest fit in the window.
+    pub added_cost: i32,
+}
+
+/// Arrival windows of `granularity_secs` on a grid from midnight in which
+/// each visitor could take the visit, earliest first, then cheapest.
+///
+/// Like [`evaluate_insertion`], nothing in `plan` is changed; each window is
+/// tried as the visit's committed window with [`WindowSemantics::Arrival`],
+/// replacing any window the visit has; its release time and deadline still
+/// hold. Returns
+/// nothing if `granularity_secs` isn't positive.
+#[allow(clippy::too_many_arguments)]
+pub fn available_slots<V, R, A, M>(
+    service_date: i64,
+    visits: &[V],
+    visitors: &[R],
+    availability: &A,
+    matrix_provider: &M,
+    options: SolveOptions,
+    plan: &PlannerResult<V::VisitorId, V::Id>,
+    visit_id: &V::Id,
+    granularity_secs: i32,
+) -> Result<Vec<AppointmentSlot<V::VisitorId>>, SolveError<V::Id>>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+    A: AvailabilityProvider<VisitorId = V::VisitorId>,
+    M: DistanceMatrixProvider,
+{
+    available_slots_with_constraints(
+        service_date,
+        visits,
+        visitors,
+        availability,
+        matrix_provider,
+        options,
+        &[],
+        plan,
+        visit_id,
+        granularity_secs,
+    )
+}
+
+/// [`available_slots`] that also leaves out windows a constraint rejects.
+#[allow(clippy::too_many_arguments)]
+pub fn available_slots_with_constraints<V, R, A, M>(
+    service_date: i64,
+    visits: &[V],
+    visitors: &[R],
+    availability: &A,
+    matrix_provider: &M,
+    options: SolveOptions,
+    constraints: &[Box<dyn RouteConstraint<V, R>>],
+    plan: &PlannerResult<V::VisitorId, V::Id>,
+    visit_id: &V::Id,
+    granularity_secs: i32,
+) -> Result<Vec<AppointmentSlot<V::VisitorId>>, SolveError<V::Id>>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+    A: AvailabilityProvider<VisitorId = V::VisitorId>,
+    M: DistanceMatrixProvider,
+{
+    let Some(visit) = visits.iter().find(|visit| visit.id() == visit_id) else {
+        return Ok(Vec::new());
+    };
+    if granularity_secs <= 0 || !date_allowed(visit, service_date) || location_unassigned(visit, visits, &options) {
+        return Ok(Vec::new());
+    }
+
+    check_duplicate_visits(visits, &options)?;
+    let (locations, location_index) = collect_locations(visits, visitors, &options)?;
+    let matrix = fleet_matrices(matrix_provider, visitors, &locations, &location_index, &options)?;
+    let by_id: HashMap<&V::Id, &V> = distinct_visits(visits).map(|visit| (visit.id(), visit)).collect();
+    let rules = RouteRules::new(service_date, constraints).with_gaps(visits);
+    let linked = rules.gaps.planned_windows(plan, visit_id);
+    let duration_secs = visit.estimated_duration_minutes() * 60;
+
+    let mut slots = Vec::new();
+    for visitor in visitors {
+        let Some(route) = route_without(visit, visitor, &by_id, &location_index, &options, plan) else {
+            continue;
+        };
+        let Some(windows) = availability_windows(availability, visitor.id(), service_date) else {
+            continue;
+        };
+        let Some((_, base_cost)) =
+            compute_schedule(service_date, &route, availability, &matrix, &options, &rules, &linked)
+        else {
+            continue;
+        };
+
+        let (open, close) = (windows[0].0, windows[windows.len() - 1].1);
+        let mut start = open.div_euclid(granularity_secs) * granularity_secs;
+        while start < close {
+            let window = (start, start + granularity_secs);
+            let mut stop = Stop::new(visit, location_index[&visit.location_ref()], &options);
+            let (earliest, latest) = WindowSemantics::Arrival.visit_bounds(window, duration_secs);
+            stop.committed = Some((
+                visit.not_before().map_or(earliest, |release| earliest.max(release)),
+                visit.latest_completion().map_or(latest, |deadline| latest.min(deadline)),
+            ));
+            if let Some((_, (_, cost))) =
+                best_insertion(service_date, &route, stop, availability, &matrix, &options, &rules, &linked)
+            {
+                slots.push(AppointmentSlot { visitor_id: visitor.id().clone(), window, added_cost: cost - base_cost });
+            }
+            start += granularity_secs;
+        }
+    }
+
+    slots.sort_by_key(|slot| (slot.window.0, slot.added_cost));
+    Ok(slots)
+}
+
+/// Schedules `visits` for `visitor` in the order given, as a solve would.
+///
+/// Nothing is reordered or dropped, apart from repeated visit IDs under
+/// [`DuplicateVisitPolicy::KeepFirst`]: the route is timed and costed exactly
+/// as the solver would time and cost it, so a dispatcher's hand-built route
+/// can be priced or checked. Capabilities and the visit cap are checked;
+/// pinning and visitor groups, which involve other routes, are not.
+pub fn schedule_route<V, R, A, M>(
+    service_date: i64,
+    visitor: &R,
+    visits: &[V],
+    availability: &A,
+    matrix_provider: &M,
+    options: SolveOptions,
+) -> ScheduleResult<V::VisitorId, V::Id>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+    A: AvailabilityProvider<VisitorId = V::VisitorId>,
+    M: DistanceMatrixProvider,
+{
+    schedule_route_with_constraints(service_date, visitor, visits, availability, matrix_provider, options, &[])
+}
+
+/// [`schedule_route`] that also checks `constraints`; a route only a
+/// constraint rejects is `Infeasible` with no violations.
+pub fn schedule_route_with_constraints<V, R, A, M>(
+    service_date: i64,
+    visitor: &R,
+    visits: &[V],
+    availability: &A,
+    matrix_provider: &M,
+    options: SolveOptions,
+    constraints: &[Box<dyn RouteConstraint<V, R>>],
+) -> ScheduleResult<V::VisitorId, V::Id>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+    A: AvailabilityProvider<VisitorId = V::VisitorId>,
+    M: DistanceMatrixProvider,
+{
+    check_duplicate_visits(visits, &options)?;
+    let route_visits: Vec<&V> = distinct_visits(visits).collect();
+    if let Some(visit) = route_visits.iter().find(|visit| !Capabilities::allows(**visit, visitor)) {
+        return Err(ScheduleError::MissingCapability(visit.id().clone()));
+    }
+    if let Some(max) = visitor.max_visits().or(options.max_visits_per_route)
+        && route_visits.len() > max
+    {
+        return Err(ScheduleError::TooManyVisits { max });
+    }
+    if availability_windows(availability, visitor.id(), service_date).is_none() {
+        return Err(ScheduleError::VisitorUnavailable);
+    }
+
+    let visitors = std::slice::from_ref(visitor);
+    let (locations, location_index) = collect_locations(visits, visitors, &options)?;
+    let matrix = fleet_matrices(matrix_provider, visitors, &locations, &location_index, &options)?;
+    let stops = route_visits
+        .into_iter()
+        .map(|visit| Stop::new(visit, location_index[&visit.location_ref()], &options))
+        .collect();
+    let start = visitor.start_location_ref().map(|location| location_index[&location]);
+    let end = visitor.end_location_ref().map(|location| location_index[&location]);
+    let mut route = RouteState::with_visits(visitor, start, end, stops);
+
+    let rules = RouteRules::new(service_date, constraints).with_gaps(visits);
+    let Some(((windows, cost), departure)) =
+        scheduled_departure(service_date, &route, availability, &matrix, &options, &rules, &LinkedWindows::new())
+    else {
+        let violations = schedule_violations(service_date, &route, availability, matrix.of(visitor))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(stop, constraint, overrun_secs)| StopViolation {
+                visit_id: stop.map(|idx| route.visits[idx].id().clone()),
+                constraint,
+                overrun_secs,
+            })
+            .collect();
+        return Err(ScheduleError::Infeasible { violations });
+    };
+    (route.estimated_windows, route.total_travel_time) = (windows, cost);
+    Ok(route_result(route, Some(departure), &matrix, &options))
+}
+
+/// A remaining stop's estimate from a visitor's live position.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StopEta<VisitId> {
+    pub visit_id: VisitId,
+    /// Estimated arrival at the stop, before any wait (seconds from midnight).
+    pub arrival: i32,
+    /// Estimated (start, end) of the visit.
+    pub estimated_window: (i32, i32),
+    /// Seconds to spare before the visit would end past its committed
+    /// window or deadline; negative once it will. `None` if the visit has no
+    /// committed window, release time, or deadline.
+    pub slack_secs: Option<i32>,
+}
+
+impl<VisitId> StopEta<VisitId> {
+    /// Whether the visit's committed window will be missed on the current
+    /// estimate.
+    pub fn at_risk(&self) -> bool {
+        self.slack_secs.is_some_and(|slack| slack < 0)
+    }
+}
+
+/// Arrival estimates for `remaining` visits, in order, for a visitor at
+/// `position` at `now` (seconds from midnight).
+///
+/// Each visit starts on arrival, or when its committed window opens, and the
+/// visitor leaves after its duration and buffer. Unlike a solve, a visit
+/// that can no longer make its window is still scheduled, with negative
+/// slack, so the rest of the day keeps its estimates. Availability, start
+/// gaps, and the visitor's return are not considered. Travel is from the
+/// visitor's routing profile, padded as in a solve. Repeated visit IDs are
+/// handled per [`SolveOptions::duplicate_visits`].
+pub fn recompute_etas<V, R, M>(
+    now: i32,
+    position: (f64, f64),
+    visitor: &R,
+    remaining: &[V],
+    matrix_provider: &M,
+    options: SolveOptions,
+) -> Result<Vec<StopEta<V::Id>>, SolveError<V::Id>>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+    M: DistanceMatrixProvider,
+{
+    check_duplicate_visits(remaining, &options)?;
+    let (mut locations, mut location_index) = collect_locations(remaining, &[] as &[R], &options)?;
+    // The position takes the next row, moving the zero-travel row for visits
+    // without usable coordinates past it
+    let origin = locations.len();
+    for row in location_index.values_mut().filter(|row| **row == origin) {
+        *row += 1;
+    }
+    locations.push(position);
+    let matrix = fleet_matrices(matrix_provider, std::slice::from_ref(visitor), &locations, &location_index, &options)?;
+    let matrix = matrix.of(visitor);
+
+    let mut time = now;
+    let mut prev_location = origin;
+    let etas = distinct_visits(remaining)
+        .map(|visit| {
+            let stop = Stop::new(visit, location_index[&visit.location_ref()], &options);
+            time += travel_time(prev_location, stop.location, matrix);
+            let arrival = time;
+            let start = stop.committed.map_or(arrival, |(earliest, _)| arrival.max(earliest));
+            let end = start + visit.estimated_duration_minutes() * 60;
+            time = end + stop.buffer;
+            prev_location = stop.location;
+            StopEta {
+                visit_id: visit.id().clone(),
+                arrival,
+                estimated_window: (start, end),
+                slack_secs: stop.committed.map(|(_, latest)| latest.saturating_sub(end)),
+            }
+        })
+        .collect();
+    Ok(etas)
+}
+
+/// `visitor`'s planned route without `visit`, if the visitor could take it
+/// (capabilities, pinning, its visitor groups, and room on the route).
+fn route_without<'a, V, R>(
+    visit: &V,
+    visitor: &'a R,
+    by_id: &HashMap<&V::Id, &'a V>,
+    location_index: &LocationIndex,
+    options: &SolveOptions,
+    plan: &PlannerResult<V::VisitorId, V::Id>,
+) -> Option<RouteState<'a, V, R>>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+{
+    if !Capabilities::allows(visit, visitor) || !Pinning::allows(visit, visitor) {
+        return None;
+    }
+    if let Some(group) = visit.same_visitor_group() {
+        let grouped_elsewhere = plan.routes.iter().filter(|route| &route.visitor_id != visitor.id()).any(|route| {
+            route.visit_ids.iter().any(|id| {
+                id != visit.id() && by_id.get(id).is_some_and(|other| other.same_visitor_group() == Some(group))
+            })
+        });
+        if grouped_elsewhere {
+            return None;
+        }
+    }
+
+    let planned: Vec<Stop<'a, V>> = plan
+        .routes
+        .iter()
+        .find(|route| &route.visitor_id == visitor.id())
+        .map(|route| {
+            route
+                .visit_ids
+                .iter()
+                .filter(|id| *id != visit.id())
+                .filter_map(|id| by_id.get(id).copied())
+                .map(|planned| Stop::new(planned, location_index[&planned.location_ref()], options))
+                .collect()
+        })
+        .unwrap_or_default();
+    if route_is_full(visitor, planned.len(), options) {
+        return None;
+    }
+
+    let start = visitor.start_location_ref().map(|location| location_index[&location]);
+    let end = visitor.end_location_ref().map(|location| location_index[&location]);
+    let route = RouteState::with_visits(visitor, start, end, planned);
+    (!keeps_apart(&route, visit)).then_some(route)
+}
+
+/// One day's booked demand against visitor capacity.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DayForecast {
+    pub date: i64,
+    /// Visits dated to the day.
+    pub visits: usize,
+    /// Service time, including duration buffers, in seconds.
+    pub service_secs: i32,
+    /// Estimated travel in seconds: each visit's leg from the nearest other
+    /// stop or visitor start that day.
+    pub travel_secs: i32,
+    /// Total visitor availability in seconds.
+    pub capacity_secs: i32,
+}
+
+impl DayForecast {
+    /// Service plus estimated travel.
+    pub fn demand_secs(&self) -> i32 {
+        self.service_secs + self.travel_secs
+    }
+
+    /// Whether demand exceeds capacity. A day that isn't overbooked can
+    /// still fail to fit once windows and capabilities come into play.
+    pub fn is_overbooked(&self) -> bool {
+        self.demand_secs() > self.capacity_secs
+    }
+}
+
+/// Demand against capacity for each of `dates`, without solving.
+///
+/// A visit counts toward its pinned date, or else its scheduled date; visits
+/// with neither are left out. Travel is a rough lower bound from one matrix
+/// over all visits, padded like a solve, so a day can be flagged oversold
+/// before it is planned.
+pub fn forecast_capacity<V, R, A, M>(
+    dates: &[i64],
+    visits: &[V],
+    visitors: &[R],
+    availability: &A,
+    matrix_provider: &M,
+    options: SolveOptions,
+) -> Result<Vec<DayForecast>, SolveError<V::Id>>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+    A: AvailabilityProvider<VisitorId = V::VisitorId>,
+    M: DistanceMatrixProvider,
+{
+    check_duplicate_visits(visits, &options)?;
+    let (locations, location_index) = collect_locations(visits, visitors, &options)?;
+    let matrix = prepare_matrix(matrix_provider.matrices_for(&locations), &locations, &location_index, &options)?;
+
+    Ok(dates
+        .iter()
+        .map(|&date| {
+            let day: Vec<&V> = distinct_visits(visits)
+                .filter(|visit| visit.pinned_date().or(visit.scheduled_date()) == Some(date))
+                .collect();
+            let mut capacity_secs = 0;
+            let mut starts = Vec::new();
+            for visitor in visitors {
+                let Some(windows) = availability_windows(availability, visitor.id(), date) else {
+                    continue;
+                };
+                capacity_secs += windows.iter().map(|(start, end)| end - start).sum::<i32>();
+                starts.extend(visitor.start_location_ref().map(|location| location_index[&location]));
+            }
+
+            let rows: Vec<usize> = day.iter().map(|visit| location_index[&visit.location_ref()]).collect();
+            let travel_secs = rows
+                .iter()
+                .enumerate()
+                .map(|(i, &to)| {
+                    let others = rows.iter().enumerate().filter(|&(j, _)| j != i).map(|(_, &from)| from);
+                    others
+                        .chain(starts.iter().copied())
+                        .map(|from| travel_time(from, to, &matrix))
+                        .min()
+                        .unwrap_or(0)
+                })
+                .sum();
+            let service_secs = day
+                .iter()
+                .map(|visit| (visit.estimated_duration_minutes() + visit.duration_buffer_minutes()) * 60)
+                .sum();
+
+            DayForecast { date, visits: day.len(), service_secs, travel_secs, capacity_secs }
+        })
+        .collect())
+}
+
+/// Routes and drops produced by one construction + local search run.
+struct PlanState<'a, V: Visit, R: Visitor<Id = V::VisitorId>> {
+    routes: Vec<RouteState<'a, V, R>>,
+    unassigned: Vec<(Stop<'a, V>, UnassignedReason)>,
+    assignment_duration: Duration,
+    local_search_duration: Duration,
+}
+
+/// Whether the visit's date pin, recurrence, and date bounds all allow `service_date`.
+fn date_allowed<V: Visit>(visit: &V, service_date: i64) -> bool {
+    visit.pinned_date().is_none_or(|date| date == service_date)
+        && visit.recurrence().is_none_or(|series| series.allows(service_date))
+        && visit
+            .date_window()
+            .is_none_or(|(earliest, latest)| (earliest..=latest).contains(&service_date))
+        && visit.allowed_dates().is_none_or(|dates| dates.contains(&service_date))
+}
+
+/// Cost of serving a recurring visit on `service_date`, days away from its
+/// nominal date; zero for visits that don't recur.
+fn drift_penalty<V: Visit>(visit: &V, service_date: i64, options: &SolveOptions) -> i32 {
+    let drift = visit.recurrence().map_or(0, |series| series.drift_days(service_date));
+    options.recurrence_drift_weight.saturating_mul(i32::try_from(drift).unwrap_or(i32::MAX))
+}
+
+/// Classify, construct, and improve routes against a precomputed matrix.
+#[allow(clippy::too_many_arguments)]
+fn plan_routes<'a, V, R, A>(
+    service_date: i64,
+    visits: &'a [V],
+    visitors: &'a [R],
+    availability: &A,
+    matrix: &FleetMatrices,
+    location_index: &LocationIndex,
+    options: &SolveOptions,
+    rules: &RouteRules<'_, V, R>,
+) -> PlanState<'a, V, R>
+where
+    V: Visit<Id: Sync> + Sync,
+    R: Visitor<Id = V::VisitorId> + Sync,
+    A: AvailabilityProvider<VisitorId = V::VisitorId> + Sync,
+{
+    let mut to_assign: Vec<Stop<'a, V>> = Vec::new();
+    let mut unassigned_with_reason: Vec<(Stop<'a, V>, UnassignedReason)> = Vec::new();
+    let mut pinned_assignments: HashMap<&V::VisitorId, Vec<Stop<'a, V>>> = HashMap::new();
+
+    for visit in distinct_visits(visits) {
+        let visit = Stop::new(visit, location_index[&visit.location_ref()], options);
+        if !date_allowed(visit.visit, service_date) {
+            unassigned_with_reason.push((visit, UnassignedReason::WrongDate));
+            continue;
+        }
+        if location_unassigned(visit.visit, visits, options) {
+            unassigned_with_reason.push((visit, UnassignedReason::InvalidLocation));
+            continue;
+        }
+
+        if let Some(check) = options.snap_check
+            && let Some(snap_distances) = &matrix.default.snap_distances
+            && let Some(&snap_distance) = snap_distances.get(visit.location)
+            && snap_distance > check.max_distance_meters
+        {
+            warn!(
+                snap_distance_m = snap_distance,
+                max_distance_m = check.max_distance_meters,
+                location = ?visit.location(),
+                "Visit location is far from the road network"
+            );
+            if check.action == SnapAction::Unassign {
+                unassigned_with_reason.push((visit, UnassignedReason::OffRoadNetwork));
+                continue;
+            }
+        }
+
+        match visit.pin_type() {
+            VisitPinType::Visitor | VisitPinType::VisitorAndDate => {
+                if let Some(visitor_id) = visit.visit.pinned_visitor() {
+                    pinned_assignments.entry(visitor_id).or_default().push(visit);
+                } else {
+                    unassigned_with_reason.push((visit, UnassignedReason::MissingPinnedVisitor));
+                }
+            }
+            VisitPinType::Date | VisitPinType::None => {
+                to_assign.push(visit);
+            }
+        }
+    }
+
+    // Assignment phase - initial route building
+    let assignment_start = Instant::now();
+    let construction = info_span!(
+        "vrp.construction",
+        to_assign = to_assign.len(),
+        assigned = Empty,
+        unassigned = Empty,
+        cost = Empty,
+        otel.kind = Empty,
+    );
+    telemetry::internal(&construction);
+    let construction_guard = construction.enter();
+
+    // Place mandatory visits while routes are still empty, visits before
+    // those that must start a gap after them, and recurring visits on their
+    // nominal date before those drifting from it
+    to_assign.sort_by_key(|visit| {
+        (!visit.must_assign(), rules.gaps.depth(visit.visit), drift_penalty(visit.visit, service_date, options))
+    });
+
+    let mut routes: Vec<RouteState<'a, V, R>> = Vec::new();
+    for visitor in visitors {
+        let mut pinned = pinned_assignments
+            .get(visitor.id())
+            .cloned()
+            .unwrap_or_default();
+        // Locked visits first, in sequence
+        pinned.sort_by_key(|visit| visit.locked.map_or((1, rules.gaps.depth(visit.visit)), |sequence| (0, sequence)));
+
+        let start = visitor.start_location_ref().map(|location| location_index[&location]);
+        let end = visitor.end_location_ref().map(|location| location_index[&location]);
+        routes.push(RouteState::with_visits(visitor, start, end, pinned));
+    }
+
+    let mut linked = LinkedWindows::new();
+    for route_idx in rules.gaps.route_order(&routes) {
+        let route = &mut routes[route_idx];
+        if !route.visits.is_empty() {
+            if let Some(schedule) = compute_schedule(service_date, route, availability, matrix, options, rules, &linked) {
+                route.estimated_windows = schedule.0;
+                route.total_travel_time = schedule.1;
+            } else {
+                let ends = (route.start, route.end, matrix.of(route.visitor));
+                for visit in route.visits.drain(..) {
+                    let reason = if cut_off(visit.location, [ends]) {
+                        UnassignedReason::Unreachable
+                    } else {
+                        window_failure_reason(visit, std::iter::once(route.visitor), service_date, availability)
+                    };
+                    unassigned_with_reason.push((visit, reason));
+                }
+            }
+        }
+        linked = rules.gaps.linked_windows(&routes);
+    }
+
+    let home_routes = home_routes(&to_assign, &routes, service_date, availability, options);
+    for (visit, home) in to_assign.into_iter().zip(home_routes) {
+        if !visit_is_compatible(visit.visit, visitors) {
+            unassigned_with_reason.push((visit, UnassignedReason::NoCapableVisitor));
+            continue;
+        }
+
+        // Routes that would move the visit off its current visitor, once the cap is reached
+        let moved = reassignments(&routes);
+        let capped = |route: &RouteState<'a, V, R>| !within_reassignment_cap(moved, reassigned(visit.visit, route.visitor).into(), options);
+
+        // Evaluate routes in parallel using rayon, only the visit's cluster if it has one
+        let evaluate = |only: Option<usize>| -> Vec<RouteEvaluation> {
+            routes.par_iter().enumerate()
+            .filter(|(route_index, _)| only.is_none_or(|only| only == *route_index))
+            .filter_map(|(route_index, route)| {
+                // Skip visitors who don't have required capabilities
+                if !Capabilities::allows(visit.visit, route.visitor) {
+                    return None;
+                }
+
+                // Check if this capable visitor is available
+                let is_available = availability_windows(availability, route.visitor.id(), service_date).is_some();
+
+                if route_is_full(route.visitor, route.visits.len(), options) || keeps_apart(route, visit.visit) || capped(route) {
+                    return Some((route_index, None, i32::MAX, None, is_available));
+                }
+
+                // Find best position for this route
+                let insertion = best_insertion(service_date, route, visit, availability, matrix, options, rules, &linked);
+                let (best_pos, best_cost, best_schedule) = match insertion {
+                    Some((position, schedule)) => (Some(position), schedule.1, Some(schedule)),
+                    None => (None, i32::MAX, None),
+                };
+
+                Some((route_index, best_pos, best_cost, best_schedule, is_available))
+            })
+            .collect()
+        };
+        // A group member already placed decides the route for the rest
+        let group = group_route(&routes, visit.visit);
+        let mut route_evaluations = evaluate(group.or(home));
+        if group.is_none() && home.is_some() && route_evaluations.iter().all(|(_ri, best_pos, _c, _s, _a)| best_pos.is_none()) {
+            route_evaluations = evaluate(None);
+        }
+
+        // Check if any capable visitor is available
+        let found_capable_available_visitor = route_evaluations.iter().any(|(_ri, _bp, _c, _s, is_available)| *is_available);
+
+        // Find overall best from parallel results
+        let best = route_evaluations.into_iter()
+            .filter(|(_ri, best_pos, _c, _s, _a)| best_pos.is_some())
+            .min_by_key(|(_ri, _bp, cost, _s, _a)| *cost);
+
+        if let Some((route_index, Some(best_position), _, best_schedule, _)) = best {
+            let route = &mut routes[route_index];
+            route.visits.insert(best_position, visit);
+            if let Some((windows, cost)) = best_schedule {
+                route.estimated_windows = windows;
+                route.total_travel_time = cost;
+            }
+            linked = rules.gaps.linked_windows(&routes);
+        } else {
+            // Determine the reason: the reassignment cap, the visit's groups, no
+            // capable available visitor, or why no window fits
+            let reason = if routes.iter().enumerate().any(|(route_index, route)| {
+                group.is_none_or(|group| group == route_index)
+                    && capped(route)
+                    && Capabilities::allows(visit.visit, route.visitor)
+                    && !route_is_full(route.visitor, route.visits.len(), options)
+                    && !keeps_apart(route, visit.visit)
+                    && best_insertion(service_date, route, visit, availability, matrix, options, rules, &linked).is_some()
+            }) {
+                UnassignedReason::ReassignmentLimit
+            } else if group.is_some() {
+                UnassignedReason::SameVisitorGroup
+            } else if routes.iter().any(|route| {
+                keeps_apart(route, visit.visit)
+                    && Capabilities::allows(visit.visit, route.visitor)
+                    && !route_is_full(route.visitor, route.visits.len(), options)
+                    && !capped(route)
+                    && best_insertion(service_date, route, visit, availability, matrix, options, rules, &linked).is_some()
+            }) {
+                UnassignedReason::DifferentVisitorGroup
+            } else if visit.position.is_some()
+                && routes.iter().any(|route| {
+                    route.visits.iter().any(|stop| stop.position == visit.position)
+                        && Capabilities::allows(visit.visit, route.visitor)
+                        && !route_is_full(route.visitor, route.visits.len(), options)
+                        && !capped(route)
+                        && !keeps_apart(route, visit.visit)
+                        && best_insertion(
+                            service_date,
+                            route,
+                            Stop { position: None, ..visit },
+                            availability,
+                            matrix,
+                            options,
+                            rules,
+                            &linked,
+                        )
+                        .is_some()
+                })
+            {
+                UnassignedReason::RoutePositionTaken
+            } else if found_capable_available_visitor {
+                let capable_routes = routes.iter().filter(|route| Capabilities::allows(visit.visit, route.visitor));
+                if cut_off(visit.location, capable_routes.map(|route| (route.start, route.end, matrix.of(route.visitor)))) {
+                    UnassignedReason::Unreachable
+                } else {
+                    let capable = visitors.iter().filter(|visitor| Capabilities::allows(visit.visit, *visitor));
+                    window_failure_reason(visit, capable, service_date, availability)
+                }
+            } else {
+                UnassignedReason::NoCapableVisitor
+            };
+            unassigned_with_reason.push((visit, reason));
+        }
+    }
+
+    repair_mandatory(
+        &mut routes,
+        &mut unassigned_with_reason,
+        service_date,
+        availability,
+        matrix,
+        options,
+        rules,
+    );
+
+    let assignment_duration = assignment_start.elapsed();
+    let assigned_so_far = routes.iter().map(|r| r.visits.len()).sum::<usize>();
+    construction.record("assigned", assigned_so_far);
+    construction.record("unassigned", unassigned_with_reason.len());
+    construction.record("cost", plan_cost(&routes));
+    info!(
         duration_ms = assignment_duration.as_millis(),
         assigned = assigned_so_far,
         unassigned = unassigned_with_reason.len(),
         "Assignment phase complete"
     );
+    drop(construction_guard);
+
+    // Local search improvement phase
+    let local_search_start = Instant::now();
+    local_search(
+        &mut routes,
+        &mut unassigned_with_reason,
+        service_date,
+        availability,
+        matrix,
+        options,
+        rules,
+    );
+    let local_search_duration = local_search_start.elapsed();
+    info!(duration_ms = local_search_duration.as_millis(), "Local search complete");
+
+    PlanState {
+        routes,
+        unassigned: unassigned_with_reason,
+        assignment_duration,
+        local_search_duration,
+    }
+}
+
+/// The route whose cluster each visit to assign falls in, when
+/// [`SolveOptions::clustering`] applies to this many visits.
+///
+/// Clusters are formed over the visitors available on `service_date`.
+fn home_routes<V, R, A>(
+    to_assign: &[Stop<'_, V>],
+    routes: &[RouteState<'_, V, R>],
+    service_date: i64,
+    availability: &A,
+    options: &SolveOptions,
+) -> Vec<Option<usize>>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+    A: AvailabilityProvider<VisitorId = V::VisitorId>,
+{
+    let Some(clustering) = options.clustering.filter(|clustering| to_assign.len() >= clustering.min_visits) else {
+        return vec![None; to_assign.len()];
+    };
+    let available: Vec<usize> = routes
+        .iter()
+        .enumerate()
+        .filter(|(_, route)| availability_windows(availability, route.visitor.id(), service_date).is_some())
+        .map(|(index, _)| index)
+        .collect();
+    if available.is_empty() {
+        return vec![None; to_assign.len()];
+    }
+
+    let points: Vec<(f64, f64)> = to_assign.iter().map(|visit| visit.visit.location()).collect();
+    let weights: Vec<f64> = to_assign.iter().map(|visit| f64::from(visit.estimated_duration_minutes())).collect();
+    let anchors: Vec<Option<(f64, f64)>> = available.iter().map(|&index| routes[index].visitor.start_location()).collect();
+    debug!(visits = to_assign.len(), clusters = available.len(), method = ?clustering.method, "Clustering visits");
+    cluster::partition(clustering.method, &points, &weights, &anchors)
+        .into_iter()
+        .map(|cluster| Some(available[cluster]))
+        .collect()
+}
+
+/// Cheapest feasible position to insert `visit` into a visitor's route, with
+/// the resulting schedule.
+#[allow(clippy::too_many_arguments)]
+fn best_insertion<'a, V, R, A>(
+    service_date: i64,
+    route: &RouteState<'a, V, R>,
+    visit: Stop<'a, V>,
+    availability: &A,
+    matrix: &FleetMatrices,
+    options: &SolveOptions,
+    rules: &RouteRules<'_, V, R>,
+    linked: &LinkedWindows<V::Id>,
+) -> Option<(usize, Schedule)>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+    A: AvailabilityProvider<VisitorId = V::VisitorId>,
+{
+    let visits = &route.visits;
+    let slack = RouteSlack::new(service_date, route, availability, matrix.of(route.visitor), options, rules);
+    let schedule_at = |position: usize| {
+        let mut candidate = visits.clone();
+        candidate.insert(position, visit);
+        let candidate_route = RouteState::with_visits(route.visitor, route.start, route.end, candidate);
+        compute_schedule(service_date, &candidate_route, availability, matrix, options, rules, linked)
+    };
+
+    // Exact slack ranks the positions by cost; only the cheapest is scheduled
+    if let Some(slack) = slack.as_ref().filter(|slack| slack.is_exact_for(visit, &rules.gaps)) {
+        let mut deltas: Vec<(i32, usize)> = (0..=visits.len())
+            .filter_map(|position| {
+                let delta = slack.insertion_delta(route, position, visit, matrix.of(route.visitor), options, rules)?;
+                Some((delta, position))
+            })
+            .collect();
+        deltas.sort_unstable();
+        return deltas.into_iter().find_map(|(_, position)| Some((position, schedule_at(position)?)));
+    }
+
+    let mut best: Option<(usize, Schedule)> = None;
+    for position in 0..=visits.len() {
+        if slack
+            .as_ref()
+            .is_some_and(|slack| !slack.allows_insertion(visits, position, visit, matrix.of(route.visitor)))
+        {
+            continue;
+        }
+
+        if let Some(schedule) = schedule_at(position)
+            && best.as_ref().is_none_or(|(_, (_, best_cost))| schedule.1 < *best_cost)
+        {
+            best = Some((position, schedule));
+        }
+    }
+    best
+}
+
+/// A repair move: an unassigned visit takes another visit's place on a route.
+struct Ejection<'a, V> {
+    route_index: usize,
+    visits: Vec<Stop<'a, V>>,
+    schedule: Schedule,
+    ejected: Stop<'a, V>,
+    /// Route, visits, and schedule after reinserting the ejected visit, if it fit anywhere.
+    reinsertion: Option<(usize, Vec<Stop<'a, V>>, Schedule)>,
+    cost_delta: i64,
+}
+
+impl<'a, V: Visit> Ejection<'a, V> {
+    fn apply<R>(self, routes: &mut [RouteState<'a, V, R>], unassigned: &mut Vec<(Stop<'a, V>, UnassignedReason)>)
+    where
+        R: Visitor<Id = V::VisitorId>,
+    {
+        debug!(route = self.route_index, kept_ejected = self.reinsertion.is_some(), "Placed visit by ejection");
+        let route = &mut routes[self.route_index];
+        route.visits = self.visits;
+        (route.estimated_windows, route.total_travel_time) = self.schedule;
+        match self.reinsertion {
+            Some((other_index, visits, schedule)) => {
+                let other = &mut routes[other_index];
+                other.visits = visits;
+                (other.estimated_windows, other.total_travel_time) = schedule;
+            }
+            None => unassigned.push((self.ejected, UnassignedReason::RouteFull)),
+        }
+    }
+}
+
+/// Whether a visit dropped for `reason` may fit once the routes change.
+fn may_fit_later(reason: UnassignedReason) -> bool {
+    matches!(
+        reason,
+        UnassignedReason::RouteFull
+            | UnassignedReason::SameVisitorGroup
+            | UnassignedReason::DifferentVisitorGroup
+            | UnassignedReason::RoutePositionTaken
+    )
+}
+
+/// Place unassigned mandatory visits by ejecting optional ones.
+///
+/// For each mandatory visit that found no room, applies the
+/// [`best_ejection`] of any visit on the routes.
+#[allow(clippy::too_many_arguments)]
+fn repair_mandatory<'a, V, R, A>(
+    routes: &mut [RouteState<'a, V, R>],
+    unassigned: &mut Vec<(Stop<'a, V>, UnassignedReason)>,
+    service_date: i64,
+    availability: &A,
+    matrix: &FleetMatrices,
+    options: &SolveOptions,
+    rules: &RouteRules<'_, V, R>,
+) where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+    A: AvailabilityProvider<VisitorId = V::VisitorId>,
+{
+    let mut idx = 0;
+    while idx < unassigned.len() {
+        let (visit, reason) = unassigned[idx];
+        if !visit.must_assign() || !may_fit_later(reason) {
+            idx += 1;
+            continue;
+        }
+
+        let Some(ejection) =
+            best_ejection(routes, visit, |_| true, service_date, availability, matrix, options, rules)
+        else {
+            idx += 1;
+            continue;
+        };
+        ejection.apply(routes, unassigned);
+        unassigned.remove(idx);
+    }
+}
+
+/// Cheapest move placing `visit` by ejecting a stop `may_eject` allows.
+///
+/// Tries removing each such unpinned stop from each capable route,
+/// inserting `visit`, and reinserting the ejected stop on any route. Moves
+/// that keep the ejected stop are preferred, then the cheapest. Only a
+/// mandatory `visit` may leave an optional stop unassigned; ejected
+/// mandatory stops, and any stop ejected for an optional visit, must be
+/// reinserted. Moves past [`SolveOptions::max_reassignments`] are skipped.
+#[allow(clippy::too_many_arguments)]
+fn best_ejection<'a, V, R, A>(
+    routes: &[RouteState<'a, V, R>],
+    visit: Stop<'a, V>,
+    may_eject: impl Fn(&Stop<'a, V>) -> bool,
+    service_date: i64,
+    availability: &A,
+    matrix: &FleetMatrices,
+    options: &SolveOptions,
+    rules: &RouteRules<'_, V, R>,
+) -> Option<Ejection<'a, V>>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+    A: AvailabilityProvider<VisitorId = V::VisitorId>,
+{
+    let mut best: Option<Ejection<'a, V>> = None;
+    let group = group_route(routes, visit.visit);
+    let moved = reassignments(routes);
+    let linked = rules.gaps.linked_windows(routes);
+    for (route_index, route) in routes.iter().enumerate() {
+        if !Capabilities::allows(visit.visit, route.visitor)
+            || !Pinning::allows(visit.visit, route.visitor)
+            || group.is_some_and(|group| group != route_index)
+            || keeps_apart(route, visit.visit)
+        {
+            continue;
+        }
+
+        for eject_idx in 0..route.visits.len() {
+            let ejected = route.visits[eject_idx];
+            if is_pinned_to_visitor(ejected.visit) || ejected.same_visitor_group().is_some() || !may_eject(&ejected) {
+                continue;
+            }
+
+            let mut remaining = RouteState::with_visits(route.visitor, route.start, route.end, route.visits.clone());
+            remaining.visits.remove(eject_idx);
+            let Some((position, schedule)) =
+                best_insertion(service_date, &remaining, visit, availability, matrix, options, rules, &linked)
+            else {
+                continue;
+            };
+            remaining.visits.insert(position, visit);
+            let mut cost_delta = i64::from(schedule.1) - i64::from(route.total_travel_time);
+            // Reassignments added by the move if the ejected visit is dropped
+            let dropped = isize::from(reassigned(visit.visit, route.visitor))
+                - isize::from(reassigned(ejected.visit, route.visitor));
+
+            // Cheapest place to put the ejected visit back
+            let mut reinsertion: Option<(usize, Vec<Stop<'a, V>>, Schedule, i64)> = None;
+            for (other_index, other) in routes.iter().enumerate() {
+                let (base, base_cost) = if other_index == route_index {
+                    (&remaining, schedule.1)
+                } else {
+                    (other, other.total_travel_time)
+                };
+                if !Capabilities::allows(ejected.visit, other.visitor)
+                    || route_is_full(other.visitor, base.visits.len(), options)
+                    || keeps_apart(base, ejected.visit)
+                    || !within_reassignment_cap(moved, dropped + isize::from(reassigned(ejected.visit, other.visitor)), options)
+                {
+                    continue;
+                }
+                if let Some((position, other_schedule)) =
+                    best_insertion(service_date, base, ejected, availability, matrix, options, rules, &linked)
+                {
+                    let mut visits = base.visits.clone();
+                    visits.insert(position, ejected);
+                    if other_index != route_index
+                        && !rules.gaps.hold(&[(&remaining.visits, &schedule.0), (&visits, &other_schedule.0)])
+                    {
+                        continue;
+                    }
+                    let delta = i64::from(other_schedule.1) - i64::from(base_cost);
+                    if reinsertion.as_ref().is_none_or(|(_, _, _, best_delta)| delta < *best_delta) {
+                        reinsertion = Some((other_index, visits, other_schedule, delta));
+                    }
+                }
+            }
+            match &reinsertion {
+                Some((_, _, _, delta)) => cost_delta += delta,
+                // Mandatory visits can be moved but not dropped, and an
+                // optional visit doesn't displace another
+                None if ejected.must_assign()
+                    || !visit.must_assign()
+                    || !within_reassignment_cap(moved, dropped, options) => continue,
+                None => {}
+            }
+
+            let candidate = Ejection {
+                route_index,
+                visits: remaining.visits,
+                schedule,
+                ejected,
+                reinsertion: reinsertion.map(|(other_index, visits, schedule, _)| (other_index, visits, schedule)),
+                cost_delta,
+            };
+            let rank = |ejection: &Ejection<'a, V>| (ejection.reinsertion.is_none(), ejection.cost_delta);
+            if best.as_ref().is_none_or(|best| rank(&candidate) < rank(best)) {
+                best = Some(candidate);
+            }
+        }
+    }
+    best
+}
+
+/// Place visits dropped for a reason that [`may_fit_later`], mandatory ones
+/// first, into a plan local search has improved. Returns whether any was
+/// placed.
+///
+/// Each visit goes to its cheapest feasible insertion, or failing that to the
+/// best [`best_ejection`] move that ejects one of the visits at its
+/// `neighbors` nearest locations (all of them if `None`). Every move places
+/// a mandatory visit or raises the number assigned, so this terminates.
+#[allow(clippy::too_many_arguments)]
+fn reinsert_unassigned<'a, V, R, A>(
+    routes: &mut [RouteState<'a, V, R>],
+    unassigned: &mut Vec<(Stop<'a, V>, UnassignedReason)>,
+    service_date: i64,
+    availability: &A,
+    matrix: &FleetMatrices,
+    options: &SolveOptions,
+    rules: &RouteRules<'_, V, R>,
+    neighbors: Option<usize>,
+) -> bool
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+    A: AvailabilityProvider<VisitorId = V::VisitorId>,
+{
+    let mut placed = false;
+    for mandatory in [true, false] {
+        let mut idx = 0;
+        while idx < unassigned.len() {
+            let (visit, reason) = unassigned[idx];
+            if visit.must_assign() != mandatory || !may_fit_later(reason) {
+                idx += 1;
+                continue;
+            }
+
+            let group = group_route(routes, visit.visit);
+            let moved = reassignments(routes);
+            let linked = rules.gaps.linked_windows(routes);
+            let insertion = routes
+                .iter()
+                .enumerate()
+                .filter(|(route_index, route)| {
+                    Capabilities::allows(visit.visit, route.visitor)
+                        && Pinning::allows(visit.visit, route.visitor)
+                        && group.is_none_or(|group| group == *route_index)
+                        && !route_is_full(route.visitor, route.visits.len(), options)
+                        && !keeps_apart(route, visit.visit)
+                        && within_reassignment_cap(moved, reassigned(visit.visit, route.visitor).into(), options)
+                })
+                .filter_map(|(route_index, route)| {
+                    let (position, schedule) =
+                        best_insertion(service_date, route, visit, availability, matrix, options, rules, &linked)?;
+                    Some((route_index, position, schedule))
+                })
+                .min_by_key(|(route_index, _, schedule)| schedule.1 - routes[*route_index].total_travel_time);
+            if let Some((route_index, position, schedule)) = insertion {
+                let route = &mut routes[route_index];
+                route.visits.insert(position, visit);
+                (route.estimated_windows, route.total_travel_time) = schedule;
+            } else {
+                let near = nearest_locations(routes, visit.location, &matrix.default, neighbors);
+                let may_eject = |stop: &Stop<'a, V>| near.as_ref().is_none_or(|near| near.contains(&stop.location));
+                let Some(ejection) =
+                    best_ejection(routes, visit, may_eject, service_date, availability, matrix, options, rules)
+                else {
+                    idx += 1;
+                    continue;
+                };
+                ejection.apply(routes, unassigned);
+            }
+            debug!(mandatory, "Reinserted unassigned visit");
+            unassigned.remove(idx);
+            placed = true;
+        }
+    }
+    placed
+}
+
+/// Convert internal route state into the public result, explaining drops.
+fn into_planner_result<'a, V, R, A>(
+    plan: PlanState<'a, V, R>,
+    service_date: i64,
+    availability: &A,
+    matrix: &FleetMatrices,
+    options: &SolveOptions,
+    rules: &RouteRules<'_, V, R>,
+) -> PlannerResult<V::VisitorId, V::Id>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+    A: AvailabilityProvider<VisitorId = V::VisitorId>,
+{
+    let PlanState { routes, unassigned: unassigned_with_reason, .. } = plan;
+
+    let unassigned: Vec<UnassignedVisit<V::VisitorId, V::Id>> = unassigned_with_reason
+        .into_iter()
+        .map(|(visit, reason)| {
+            explain_unassigned(visit, reason, &routes, service_date, availability, matrix)
+        })
+        .collect();
+
+    let linked = rules.gaps.linked_windows(&routes);
+    let routes: Vec<RouteResult<V::VisitorId, V::Id>> = routes
+        .into_iter()
+        .map(|route| {
+            let departure = scheduled_departure(service_date, &route, availability, matrix, options, rules, &linked)
+                .map(|(_, departure)| departure);
+            route_result(route, departure, matrix, options)
+        })
+        .collect();
+
+    PlannerResult { routes, unassigned }
+}
+
+/// The public result for one scheduled route, leaving at `departure`.
+fn route_result<V, R>(
+    route: RouteState<'_, V, R>,
+    departure: Option<i32>,
+    matrix: &FleetMatrices,
+    options: &SolveOptions,
+) -> RouteResult<V::VisitorId, V::Id>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+{
+    let matrix = matrix.of(route.visitor);
+    let legs = route_legs(&route, departure, matrix);
+    let return_leg = return_leg(&route, matrix);
+    let window_miss_risk = window_miss_risk(&route, &legs);
+    let robustness_slack_secs = robustness_slack(&route, &legs, options.robustness_travel_increase);
+    RouteResult {
+        visitor_id: route.visitor.id().clone(),
+        visit_ids: route.visits.iter().map(|visit| visit.id().clone()).collect(),
+        total_distance_km: matrix.distances.as_ref().map(|_| {
+            let meters = legs.iter().filter_map(|leg| leg.distance_m).chain(return_leg.and_then(|leg| leg.distance_m));
+            meters.sum::<i32>() as f64 / 1000.0
+        }),
+        legs,
+        estimated_windows: route.estimated_windows,
+        total_travel_time: route.total_travel_time,
+        window_miss_risk,
+        robustness_slack_secs,
+        return_leg,
+    }
+}
+
+/// Per-visit travel and waiting along a scheduled route.
+///
+/// Legs are counted the same way as in [`compute_schedule`]: from the
+/// visitor's start location (or the first visit) through each visit in
+/// order. The first leg leaves at `departure`, or just in time for the first
+/// visit if it isn't known.
+fn route_legs<V, R>(
+    route: &RouteState<'_, V, R>,
+    departure: Option<i32>,
+    matrix: &TravelMatrices,
+) -> Vec<LegDetail<V::Id>>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+{
+    let Some(mut prev_location) = route.origin() else {
+        return Vec::new();
+    };
+    let mut time = match (departure, route.visits.first(), route.estimated_windows.first()) {
+        (Some(departure), _, _) => departure,
+        (None, Some(first), Some(&(start, _))) => start - travel_time(prev_location, first.location, matrix),
+        _ => 0,
+    };
+    let mut from: Option<&V> = None;
+
+    let mut legs = Vec::with_capacity(route.visits.len());
+    for (visit, &(start, end)) in route.visits.iter().zip(&route.estimated_windows) {
+        let travel_secs = travel_time(prev_location, visit.location, matrix);
+        let distance_m = matrix
+            .distances
+            .as_ref()
+            .map(|_| travel_distance(prev_location, visit.location, matrix));
+        legs.push(LegDetail {
+            from: from.map(|visit| visit.id().clone()),
+            to: visit.id().clone(),
+            travel_secs,
+            wait_secs: (start - time - travel_secs).max(0),
+            distance_m,
+        });
+
+        time = end;
+        prev_location = visit.location;
+        from = Some(visit.visit);
+    }
+    legs
+}
+
+/// The drive from a scheduled route's last visit to the visitor's end location.
+fn return_leg<V, R>(route: &RouteState<'_, V, R>, matrix: &TravelMatrices) -> Option<ReturnLeg>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+{
+    let end = route.end?;
+    let (last, &(_, last_end)) = route.visits.last().zip(route.estimated_windows.last())?;
+    let travel_secs = travel_time(last.location, end, matrix);
+    let arrival = last_end + last.buffer + travel_secs;
+    Some(ReturnLeg {
+        travel_secs,
+        arrival: route.visitor.return_window().map_or(arrival, |(earliest, _)| arrival.max(earliest)),
+        distance_m: matrix.distances.as_ref().map(|_| travel_distance(last.location, end, matrix)),
+    })
+}
+
+/// Expected number of committed-window misses on a scheduled route.
+///
+/// A visit misses if overruns push its start past `committed end - duration`.
+/// The delay reaching visit `i` is the largest, over each earlier visit `k`,
+/// of the overruns of visits `k..i` less the waiting after `k`, and waiting
+/// includes reserved duration buffers. Each candidate is treated as normal
+/// with the summed variances and the visit's risk is the largest tail
+/// probability, which slightly understates the exact value.
+fn window_miss_risk<V, R>(route: &RouteState<'_, V, R>, legs: &[LegDetail<V::Id>]) -> f64
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+{
+    let wait = |idx: usize| legs.get(idx).map_or(0, |leg| leg.wait_secs) as f64;
+    let mut risk = 0.0;
+    for (idx, visit) in route.visits.iter().enumerate() {
+        let Some((_, committed_end)) = visit.committed else {
+            continue;
+        };
+        let mut cushion = (committed_end - route.estimated_windows[idx].1) as f64 + wait(idx);
+        let mut variance = 0.0;
+        let mut worst: f64 = 0.0;
+        for earlier in (0..idx).rev() {
+            let std_dev = route.visits[earlier].duration_std_dev_minutes() * 60.0;
+            variance += std_dev * std_dev;
+            if variance > 0.0 {
+                worst = worst.max(normal_tail(cushion / variance.sqrt()));
+            }
+            cushion += wait(earlier);
+        }
+        risk += worst;
+    }
+    risk
+}
+
+/// Smallest committed-window margin on a scheduled route with every leg's
+/// travel scaled by `1 + increase`.
+///
+/// Delay carries from stop to stop, less any waiting before each start, and
+/// visits keep their order.
+fn robustness_slack<V, R>(route: &RouteState<'_, V, R>, legs: &[LegDetail<V::Id>], increase: f64) -> Option<i32>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+{
+    let mut delay = 0;
+    let mut slack: Option<i32> = None;
+    for ((visit, leg), &(_, end)) in route.visits.iter().zip(legs).zip(&route.estimated_windows) {
+        delay = (delay + (leg.travel_secs as f64 * increase).round() as i32 - leg.wait_secs).max(0);
+        if let Some((_, committed_end)) = visit.committed.filter(|(_, end)| *end < i32::MAX) {
+            let margin = committed_end - end - delay;
+            slack = Some(slack.map_or(margin, |slack| slack.min(margin)));
+        }
+    }
+    slack
+}
+
+/// Probability a standard normal exceeds `z` (Abramowitz and Stegun 7.1.26).
+fn normal_tail(z: f64) -> f64 {
+    let x = z / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.327_591_1 * x.abs());
+    let poly = t * (0.254_829_592 + t * (-0.284_496_736 + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let erfc = poly * (-x * x).exp();
+    if x >= 0.0 { 0.5 * erfc } else { 1.0 - 0.5 * erfc }
+}
+
+/// The route already serving another visit of `visit`'s
+/// [`same_visitor_group`](Visit::same_visitor_group), if any.
+fn group_route<V, R>(routes: &[RouteState<'_, V, R>], visit: &V) -> Option<usize>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+{
+    let group = visit.same_visitor_group()?;
+    routes.iter().position(|route| {
+        route
+            .visits
+            .iter()
+            .any(|other| !std::ptr::eq(other.visit, visit) && other.same_visitor_group() == Some(group))
+    })
+}
 
-    // Local search improvement phase
-    let local_search_start = Instant::now();
-    local_search(
-        &mut routes,
-        service_date,
-        availability,
-        &matrix,
-        &coord_index,
-        &options,
-    );
-    let local_search_duration = local_search_start.elapsed();
-    info!(duration_ms = local_search_duration.as_millis(), "Local search complete");
+/// Whether `route` serves another visit of `visit`'s
+/// [`different_visitor_group`](Visit::different_visitor_group).
+fn keeps_apart<V, R>(route: &RouteState<'_, V, R>, visit: &V) -> bool
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+{
+    visit.different_visitor_group().is_some_and(|group| {
+        route
+            .visits
+            .iter()
+            .any(|other| !std::ptr::eq(other.visit, visit) && other.different_visitor_group() == Some(group))
+    })
+}
 
-    let routes: Vec<RouteResult<V::VisitorId, V::Id>> = routes
-        .into_iter()
-        .map(|route| RouteResult {
-            visitor_id: route.visitor.id().clone(),
-            visit_ids: route.visits.iter().map(|visit| visit.id().clone()).collect(),
-            estimated_windows: route.estimated_windows,
-            total_travel_time: route.total_travel_time,
-        })
+/// Whether a route with `len` visits is at the visitor's visit cap.
+fn route_is_full<R: Visitor>(visitor: &R, len: usize, options: &SolveOptions) -> bool {
+    visitor
+        .max_visits()
+        .or(options.max_visits_per_route)
+        .is_some_and(|max| len >= max)
+}
+
+/// Whether serving `visit` on `visitor`'s route moves it off its
+/// [`current_visitor_id`](Visit::current_visitor_id).
+fn reassigned<V, R>(visit: &V, visitor: &R) -> bool
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+{
+    visit.current_visitor_id().is_some_and(|current| current != visitor.id())
+}
+
+/// Visits in `routes` moved off their current visitor.
+fn reassignments<V, R>(routes: &[RouteState<'_, V, R>]) -> usize
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+{
+    routes
+        .iter()
+        .map(|route| route.visits.iter().filter(|stop| reassigned(stop.visit, route.visitor)).count())
+        .sum()
+}
+
+/// Whether a move that changes a plan with `moved` reassignments by `delta`
+/// keeps it within [`SolveOptions::max_reassignments`]. Moves that add none
+/// are always allowed, even on a plan already past the cap.
+fn within_reassignment_cap(moved: usize, delta: isize, options: &SolveOptions) -> bool {
+    delta <= 0 || options.max_reassignments.is_none_or(|max| moved.saturating_add_signed(delta) <= max)
+}
+
+/// Whether a visit may only be served by its pinned visitor.
+fn is_pinned_to_visitor<V: Visit>(visit: &V) -> bool {
+    matches!(visit.pin_type(), VisitPinType::Visitor | VisitPinType::VisitorAndDate)
+}
+
+/// Whether locked visits lead the route in ascending sequence.
+///
+/// Every move is scheduled through [`compute_schedule`], which rejects
+/// routes that break this, so optimization only reorders the unlocked suffix.
+fn locked_prefix_holds<V>(visits: &[Stop<'_, V>]) -> bool {
+    let prefix = visits.iter().take_while(|stop| stop.locked.is_some()).count();
+    visits[..prefix].windows(2).all(|pair| pair[0].locked < pair[1].locked)
+        && visits[prefix..].iter().all(|stop| stop.locked.is_none())
+}
+
+/// Whether visits with a [`RoutePosition`] hold their slot: a `First` visit
+/// right after the locked prefix, a `Last` visit at the end. A route has room
+/// for one of each, and is checked in [`compute_schedule`] like the prefix.
+fn route_positions_hold<V>(visits: &[Stop<'_, V>]) -> bool {
+    let prefix = visits.iter().take_while(|stop| stop.locked.is_some()).count();
+    visits.iter().enumerate().all(|(idx, stop)| match stop.position {
+        Some(RoutePosition::First) => idx == prefix,
+        Some(RoutePosition::Last) => idx + 1 == visits.len(),
+        None => true,
+    })
+}
+
+/// Whether inserting `visit` at `position` of a route whose visits hold
+/// their order keeps the locked prefix and route positions holding, as
+/// [`locked_prefix_holds`] and [`route_positions_hold`] would find.
+fn keeps_order<V>(visits: &[Stop<'_, V>], position: usize, visit: Stop<'_, V>) -> bool {
+    let prefix = visits.iter().take_while(|stop| stop.locked.is_some()).count();
+    let in_prefix = match visit.locked {
+        Some(_) => {
+            position <= prefix
+                && visits[..position].last().is_none_or(|prev| prev.locked < visit.locked)
+                && visits[position..prefix].first().is_none_or(|next| visit.locked < next.locked)
+        }
+        None => position >= prefix,
+    };
+    let displaces_first = visit.locked.is_none()
+        && position == prefix
+        && visits.get(prefix).is_some_and(|stop| stop.position == Some(RoutePosition::First));
+    let displaces_last =
+        position == visits.len() && visits.last().is_some_and(|stop| stop.position == Some(RoutePosition::Last));
+    let holds_own = match visit.position {
+        Some(RoutePosition::First) => position == prefix,
+        Some(RoutePosition::Last) => position == visits.len(),
+        None => true,
+    };
+    in_prefix && !displaces_first && !displaces_last && holds_own
+}
+
+/// Check if any visitor in the list can handle this visit.
+fn visit_is_compatible<V, R>(visit: &V, visitors: &[R]) -> bool
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+{
+    visitors.iter().any(|visitor| Capabilities::allows(visit, visitor))
+}
+
+/// Whether every route, given as its (start, end) rows and matrices, has no
+/// way from its start to `location` or from `location` to its end.
+fn cut_off<'m>(
+    location: usize,
+    routes: impl IntoIterator<Item = (Option<usize>, Option<usize>, &'m TravelMatrices)>,
+) -> bool {
+    routes.into_iter().all(|(start, end, matrix)| {
+        start.is_some_and(|start| travel_time(start, location, matrix) >= UNREACHABLE_SECS)
+            || end.is_some_and(|end| travel_time(location, end, matrix) >= UNREACHABLE_SECS)
+    })
+}
+
+/// Classify why a visit found no feasible slot among the given visitors.
+///
+/// Checks, in order: whether any visitor is available at all, whether the
+/// visit is longer than every window, and whether the committed window
+/// overlaps availability enough to fit it. Anything else is a full route.
+fn window_failure_reason<'r, V, R, A>(
+    visit: Stop<'_, V>,
+    visitors: impl Iterator<Item = &'r R>,
+    service_date: i64,
+    availability: &A,
+) -> UnassignedReason
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId> + 'r,
+    A: AvailabilityProvider<VisitorId = V::VisitorId>,
+{
+    let windows: Vec<(i32, i32)> = visitors
+        .filter_map(|visitor| availability_windows(availability, visitor.id(), service_date))
+        .flatten()
         .collect();
+    if windows.is_empty() {
+        return UnassignedReason::VisitorUnavailable;
+    }
 
-    let unassigned: Vec<UnassignedVisit<V::Id>> = unassigned_with_reason
-        .into_iter()
-        .map(|(visit, reason)| UnassignedVisit {
-            visit_id: visit.id().clone(),
-            reason,
-        })
+    let duration_secs = visit.estimated_duration_minutes() * 60;
+    let longest_window = windows.iter().map(|(start, end)| end - start).max().unwrap_or(0);
+    let committed_too_short = visit.committed.is_some_and(|(start, end)| end - start < duration_secs);
+    if duration_secs > longest_window || committed_too_short {
+        return UnassignedReason::DurationExceedsWindow;
+    }
+
+    if let Some((committed_start, committed_end)) = visit.committed {
+        let fits_somewhere = windows.iter().any(|&(window_start, window_end)| {
+            window_end.min(committed_end) - window_start.max(committed_start) >= duration_secs
+        });
+        if !fits_somewhere {
+            return UnassignedReason::CommittedWindowOutsideAvailability;
+        }
+    }
+
+    UnassignedReason::RouteFull
+}
+
+/// Build the structured explanation for an unassigned visit.
+///
+/// Lists capable visitors that are unavailable on the service date and, for
+/// window failures, searches every capable route and position for the
+/// insertion that would need the smallest window extension.
+fn explain_unassigned<'a, V, R, A>(
+    visit: Stop<'a, V>,
+    reason: UnassignedReason,
+    routes: &[RouteState<'a, V, R>],
+    service_date: i64,
+    availability: &A,
+    matrix: &FleetMatrices,
+) -> UnassignedVisit<V::VisitorId, V::Id>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+    A: AvailabilityProvider<VisitorId = V::VisitorId>,
+{
+    let pinned_visitor = match visit.pin_type() {
+        VisitPinType::Visitor | VisitPinType::VisitorAndDate => visit.pinned_visitor(),
+        VisitPinType::Date | VisitPinType::None => None,
+    };
+
+    let candidates: Vec<&RouteState<'a, V, R>> = routes
+        .iter()
+        .filter(|route| Capabilities::allows(visit.visit, route.visitor))
+        .filter(|route| pinned_visitor.is_none_or(|id| id == route.visitor.id()))
         .collect();
 
-    let total_duration = solve_start.elapsed();
-    let assigned_count = routes.iter().map(|r| r.visit_ids.len()).sum::<usize>();
-    let unassigned_count = unassigned.len();
-    info!(
-        total_ms = total_duration.as_millis(),
-        matrix_ms = matrix_duration.as_millis(),
-        assignment_ms = assignment_duration.as_millis(),
-        local_search_ms = local_search_duration.as_millis(),
-        routes = routes.len(),
-        assigned = assigned_count,
-        unassigned = unassigned_count,
-        "VRP solve complete"
-    );
+    let unavailable_visitors = candidates
+        .iter()
+        .filter(|route| availability_windows(availability, route.visitor.id(), service_date).is_none())
+        .map(|route| route.visitor.id().clone())
+        .collect();
 
-    PlannerResult { routes, unassigned }
+    let mut relaxation: Option<Relaxation<V::VisitorId>> = None;
+    if reason.is_window_failure() {
+        for route in &candidates {
+            for position in 0..=route.visits.len() {
+                let mut candidate = RouteState::with_visits(route.visitor, route.start, route.end, route.visits.clone());
+                candidate.visits.insert(position, visit);
+
+                let Some((constraint, extend_by_secs)) =
+                    schedule_overrun(service_date, &candidate, availability, matrix.of(route.visitor))
+                else {
+                    continue;
+                };
+
+                if relaxation.as_ref().is_none_or(|best| extend_by_secs < best.extend_by_secs) {
+                    relaxation = Some(Relaxation {
+                        visitor_id: route.visitor.id().clone(),
+                        constraint,
+                        extend_by_secs,
+                    });
+                }
+            }
+        }
+    }
+
+    UnassignedVisit {
+        visit_id: visit.id().clone(),
+        reason,
+        unavailable_visitors,
+        relaxation,
+        mandatory: visit.must_assign(),
+    }
 }
 
-/// Check if a visitor has all required capabilities for a visit.
-fn visitor_can_do<V, R>(visit: &V, visitor: &R) -> bool
+/// The largest overrun on a route and the constraint it violates, or `None`
+/// if the visitor is unavailable or nothing overruns.
+fn schedule_overrun<V, R, A>(
+    service_date: i64,
+    route: &RouteState<'_, V, R>,
+    availability: &A,
+    matrix: &TravelMatrices,
+) -> Option<(BlockingConstraint, i32)>
 where
     V: Visit,
     R: Visitor<Id = V::VisitorId>,
+    A: AvailabilityProvider<VisitorId = V::VisitorId>,
 {
-    let required = visit.required_capabilities();
-    if required.is_empty() {
-        return true;
+    schedule_violations(service_date, route, availability, matrix)?
+        .into_iter()
+        .map(|(_, constraint, secs)| (constraint, secs))
+        .reduce(|worst, overrun| if overrun.1 > worst.1 { overrun } else { worst })
+}
+
+/// A broken window: the stop's index in the route (`None` for the return to
+/// the end location), the constraint, and the overrun in seconds.
+type Violation = (Option<usize>, BlockingConstraint, i32);
+
+/// Simulate a route while tolerating window violations.
+///
+/// Visits that don't fit are placed in the availability window they arrive in
+/// (or the last one) and the overrun past the closing window is recorded.
+/// Returns every overrun in route order, or `None` if the visitor is
+/// unavailable or the route has no origin.
+fn schedule_violations<V, R, A>(
+    service_date: i64,
+    route: &RouteState<'_, V, R>,
+    availability: &A,
+    matrix: &TravelMatrices,
+) -> Option<Vec<Violation>>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+    A: AvailabilityProvider<VisitorId = V::VisitorId>,
+{
+    let availability_windows = availability_windows(availability, route.visitor.id(), service_date)?;
+    let (day_start, _) = availability_windows[0];
+
+    let mut time = day_start;
+    let mut current_window_idx = 0;
+    let mut violations = Vec::new();
+    let mut prev_location = route.origin()?;
+
+    for (idx, visit) in route.visits.iter().enumerate() {
+        time += travel_time(prev_location, visit.location, matrix);
+        let duration_secs = visit.estimated_duration_minutes() * 60;
+        let committed_window = visit.committed;
+        if let Some((committed_start, _)) = committed_window {
+            time = time.max(committed_start);
+        }
+
+        let start_time = match find_fitting_window(
+            time,
+            duration_secs,
+            current_window_idx,
+            &availability_windows,
+            committed_window,
+        ) {
+            Some((start_time, window_idx)) => {
+                current_window_idx = window_idx;
+                start_time
+            }
+            None => {
+                let window_idx = (current_window_idx..availability_windows.len())
+                    .find(|&idx| time < availability_windows[idx].1)
+                    .unwrap_or(availability_windows.len() - 1);
+                let (window_start, window_end) = availability_windows[window_idx];
+                current_window_idx = window_idx;
+
+                let start_time = time.max(window_start);
+                let end_time = start_time + duration_secs;
+                let availability_overrun = end_time - window_end;
+                let committed_overrun = committed_window.map_or(0, |(_, committed_end)| end_time - committed_end);
+
+                let (constraint, overrun) = if committed_overrun >= availability_overrun {
+                    (BlockingConstraint::CommittedWindow, committed_overrun)
+                } else {
+                    (BlockingConstraint::Availability, availability_overrun)
+                };
+                if overrun > 0 {
+                    violations.push((Some(idx), constraint, overrun));
+                }
+                start_time
+            }
+        };
+
+        time = start_time + duration_secs + visit.buffer;
+        prev_location = visit.location;
+    }
+
+    if let Some((_, latest)) = route.visitor.return_window() {
+        let back = time + route.end.map_or(0, |end| travel_time(prev_location, end, matrix));
+        let overrun = back - latest;
+        if overrun > 0 {
+            violations.push((None, BlockingConstraint::ReturnWindow, overrun));
+        }
     }
-    let available = visitor.capabilities();
-    required.iter().all(|cap| available.contains(cap))
+
+    Some(violations)
 }
 
-/// Check if any visitor in the list can handle this visit.
-fn visit_is_compatible<V, R>(visit: &V, visitors: &[R]) -> bool
+#[allow(clippy::too_many_arguments)]
+fn compute_schedule<V, R, A>(
+    service_date: i64,
+    route: &RouteState<'_, V, R>,
+    availability: &A,
+    matrix: &FleetMatrices,
+    options: &SolveOptions,
+    rules: &RouteRules<'_, V, R>,
+    linked: &LinkedWindows<V::Id>,
+) -> Option<Schedule>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+    A: AvailabilityProvider<VisitorId = V::VisitorId>,
+{
+    scheduled_departure(service_date, route, availability, matrix, options, rules, linked).map(|(schedule, _)| schedule)
+}
+
+/// [`compute_schedule`], along with when the route leaves its start location.
+#[allow(clippy::too_many_arguments)]
+fn scheduled_departure<V, R, A>(
+    service_date: i64,
+    route: &RouteState<'_, V, R>,
+    availability: &A,
+    matrix: &FleetMatrices,
+    options: &SolveOptions,
+    rules: &RouteRules<'_, V, R>,
+    linked: &LinkedWindows<V::Id>,
+) -> Option<(Schedule, i32)>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+    A: AvailabilityProvider<VisitorId = V::VisitorId>,
+{
+    if !locked_prefix_holds(&route.visits) || !route_positions_hold(&route.visits) {
+        return None;
+    }
+    let availability_windows = availability_windows(availability, route.visitor.id(), service_date)?;
+    let matrix = matrix.of(route.visitor);
+
+    // Use visitor's start location, or if not set, use the first visit's location.
+    let Some(start_location) = route.origin() else {
+        return Some(((Vec::new(), 0), 0));
+    };
+
+    // Start at the beginning of the first availability window, then try
+    // leaving later if that trims idle time without costing more.
+    let mut departure = availability_windows[0].0;
+    let ((mut result_windows, mut total_cost), delay) =
+        forward_schedule(departure, start_location, route, &availability_windows, matrix, options, rules, linked)?;
+    if delay > 0
+        && let Some(((delayed_windows, delayed_cost), _)) = forward_schedule(
+            departure + delay,
+            start_location,
+            route,
+            &availability_windows,
+            matrix,
+            options,
+            rules,
+            linked,
+        )
+        && delayed_cost <= total_cost
+    {
+        result_windows = delayed_windows;
+        total_cost = delayed_cost;
+        departure += delay;
+    }
+    if let Some(reserve) = options.reserve
+        && reserved_secs(route, &result_windows, departure, start_location, &availability_windows, matrix, reserve.band)
+            < reserve.minutes * 60
+    {
+        return None;
+    }
+
+    if !rules.constraints.is_empty() {
+        let visits: Vec<&V> = route.visits.iter().map(|stop| stop.visit).collect();
+        let candidate = RouteCandidate {
+            service_date,
+            visitor: route.visitor,
+            visits: &visits,
+            windows: &result_windows,
+        };
+        if !rules.constraints.iter().all(|constraint| constraint.check(&candidate).is_feasible()) {
+            return None;
+        }
+    }
+
+    Some(((result_windows, total_cost), departure))
+}
+
+/// Longest unbroken stretch of availability within `band` (the whole day if
+/// `None`), in seconds, that a scheduled route leaves free of travel,
+/// service, and duration buffers.
+///
+/// The route leaves its start location at `departure`, and the return leg
+/// to the end location counts as travel.
+#[allow(clippy::too_many_arguments)]
+fn reserved_secs<V, R>(
+    route: &RouteState<'_, V, R>,
+    windows: &[(i32, i32)],
+    departure: i32,
+    start_location: usize,
+    availability_windows: &[TimeWindow],
+    matrix: &TravelMatrices,
+    band: Option<TimeWindow>,
+) -> i32
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+{
+    let (band_start, band_end) = band.unwrap_or((i32::MIN, i32::MAX));
+
+    let mut busy = Vec::with_capacity(2 * route.visits.len() + 1);
+    let (mut prev_location, mut leave) = (start_location, departure);
+    for (stop, &(start, end)) in route.visits.iter().zip(windows) {
+        busy.push((leave, leave + travel_time(prev_location, stop.location, matrix)));
+        busy.push((start, end + stop.buffer));
+        (prev_location, leave) = (stop.location, end + stop.buffer);
+    }
+    if let Some(end) = route.end.filter(|_| !route.visits.is_empty()) {
+        busy.push((leave, leave + travel_time(prev_location, end, matrix)));
+    }
+
+    busy.sort_unstable();
+
+    let mut longest = 0;
+    for &(open, close) in availability_windows {
+        let (open, close) = (open.max(band_start), close.min(band_end));
+        let mut free_from = open;
+        for &(start, end) in busy.iter().filter(|(start, end)| *end > open && *start < close) {
+            longest = longest.max(start - free_from);
+            free_from = free_from.max(end);
+        }
+        longest = longest.max(close - free_from);
+    }
+    longest
+}
+
+/// Schedule a route's visits in order, leaving the start location at `departure`.
+///
+/// Returns the visit windows, their cost, and how much later the route could
+/// depart: the wait before the first stop that has to wait, capped by the
+/// slack of the stops before it.
+#[allow(clippy::too_many_arguments)]
+fn forward_schedule<V, R>(
+    departure: i32,
+    start_location: usize,
+    route: &RouteState<'_, V, R>,
+    availability_windows: &[TimeWindow],
+    matrix: &TravelMatrices,
+    options: &SolveOptions,
+    rules: &RouteRules<'_, V, R>,
+    linked: &LinkedWindows<V::Id>,
+) -> Option<(Schedule, i32)>
 where
     V: Visit,
     R: Visitor<Id = V::VisitorId>,
 {
-    visitors.iter().any(|visitor| visitor_can_do(visit, visitor))
+    let mut time = departure;
+    let mut current_window_idx = 0;
+    let mut total_cost: i64 = 0;
+    let mut result_windows = Vec::with_capacity(route.visits.len());
+    let mut prev_location = start_location;
+    // Departure delay, settled at the first stop that waits
+    let mut delay: Option<i32> = None;
+    let mut prefix_slack = i32::MAX;
+    let positions = rules.gaps.positions(&route.visits);
+
+    for (idx, visit) in route.visits.iter().enumerate() {
+        let travel = travel_time(prev_location, visit.location, matrix);
+        if travel >= UNREACHABLE_SECS {
+            return None;
+        }
+        time += travel;
+        let arrival = time;
+
+        let duration_secs = visit.estimated_duration_minutes() * 60;
+        let commute = idx == 0 && options.unpaid_commute;
+        total_cost +=
+            i64::from(leg_cost(route.visitor, prev_location, visit.location, duration_secs, commute, matrix, options));
+
+        // Handle committed window constraints
+        if let Some((committed_start, committed_end)) = visit.committed {
+            if time < committed_start {
+                time = committed_start;
+            }
+            if time > committed_end {
+                return None;
+            }
+        }
+
+        // Start gaps to linked visits
+        let (gap_start, gap_end) = rules.gaps.start_bounds(visit.visit, idx, &positions, &result_windows, linked)?;
+        time = time.max(gap_start);
+
+        // Find a window where the visit fits entirely
+        let (start_time, window_idx) = find_fitting_window(
+            time,
+            duration_secs,
+            current_window_idx,
+            availability_windows,
+            visit.committed,
+        )?;
+        if start_time > gap_end {
+            return None;
+        }
+
+        if delay.is_none() {
+            if start_time > arrival {
+                delay = Some((start_time - arrival).min(prefix_slack));
+            } else {
+                let window_end = availability_windows[window_idx].1;
+                let latest_end = visit.committed.map_or(window_end, |(_, end)| end.min(window_end));
+                prefix_slack = prefix_slack.min(latest_end - duration_secs - start_time);
+            }
+        }
+
+        time = start_time + duration_secs + visit.buffer;
+        current_window_idx = window_idx;
+
+        // Target time penalty
+        if let Some(target) = visit.target_time() {
+            let deviation = ((start_time - target).abs() - visit.target_time_tolerance()).max(0);
+            total_cost += i64::from(deviation) * i64::from(visit.target_time_weight().unwrap_or(options.target_time_weight));
+        }
+
+        total_cost += i64::from(visit_penalty(*visit, route.visitor, options, rules));
+
+        result_windows.push((start_time, start_time + duration_secs));
+        prev_location = visit.location;
+    }
+
+    if !route.visits.is_empty() {
+        if let Some(end) = route.end {
+            let travel = travel_time(prev_location, end, matrix);
+            if travel >= UNREACHABLE_SECS {
+                return None;
+            }
+            time += travel;
+            total_cost += i64::from(leg_cost(route.visitor, prev_location, end, 0, options.unpaid_commute, matrix, options));
+        }
+        if route.visitor.return_window().is_some_and(|(_, latest)| time > latest) {
+            return None;
+        }
+    }
+
+    let total_cost = total_cost.clamp(i32::MIN.into(), i32::MAX.into()) as i32;
+    Some(((result_windows, total_cost), delay.unwrap_or(0)))
+}
+
+/// Cost of travelling from `from` to `to` and spending `service_secs` there,
+/// as [`forward_schedule`] charges it. An unpaid `commute` leg only pays for
+/// the service.
+#[allow(clippy::too_many_arguments)]
+fn leg_cost<R: Visitor>(
+    visitor: &R,
+    from: usize,
+    to: usize,
+    service_secs: i32,
+    commute: bool,
+    matrix: &TravelMatrices,
+    options: &SolveOptions,
+) -> i32 {
+    match options.objective {
+        Objective::TravelTime if commute => 0,
+        Objective::TravelTime => travel_time(from, to, matrix),
+        Objective::Cost if commute => money_cost(visitor, service_secs, 0),
+        Objective::Cost => {
+            let travel = travel_time(from, to, matrix);
+            money_cost(visitor, travel + service_secs, travel_distance(from, to, matrix))
+        }
+    }
 }
 
-fn compute_schedule<V, R, A>(
-    service_date: i64,
-    route: &RouteState<'_, V, R>,
-    availability: &A,
-    matrix: &[Vec<i32>],
-    coord_index: &HashMap<(i64, i64), usize>,
-    options: &SolveOptions,
-) -> Option<(Vec<(i32, i32)>, i32)>
+/// Reassignment, continuity, and recurrence drift penalties for serving
+/// `visit` on `visitor`'s route, whenever it starts.
+fn visit_penalty<V, R>(visit: Stop<'_, V>, visitor: &R, options: &SolveOptions, rules: &RouteRules<'_, V, R>) -> i32
 where
     V: Visit,
     R: Visitor<Id = V::VisitorId>,
-    A: AvailabilityProvider<VisitorId = V::VisitorId>,
 {
-    let availability_windows = availability.availability_for(route.visitor.id(), service_date)?;
-    if availability_windows.is_empty() {
-        return None;
+    let mut penalty = 0;
+
+    // Stability penalty: penalize reassigning to a different visitor
+    if let Some(current_visitor) = visit.current_visitor_id()
+        && current_visitor != visitor.id()
+    {
+        let reassignment = if options.stability_first { STABILITY_FIRST_PENALTY } else { options.reassignment_penalty };
+        penalty += visit.reassignment_penalty().unwrap_or(reassignment);
     }
 
-    // Start at the beginning of the first availability window
-    let mut time = availability_windows[0].0;
-    let mut current_window_idx = 0;
-    let mut total_cost = 0;
-    let mut result_windows = Vec::with_capacity(route.visits.len());
+    // Continuity penalty: penalize passing over the location's regular visitor
+    if let Some(regular) = rules.continuity.get(&visit.location_ref())
+        && regular.id() != visitor.id()
+    {
+        penalty += options.continuity_weight;
+    }
 
-    // Use visitor's start location, or if not set, use the first visit's location.
-    // This avoids a panic when (0.0, 0.0) isn't in the distance matrix index.
-    let mut prev_location = route
-        .visitor
-        .start_location()
-        .or_else(|| route.visits.first().map(|v| v.location()))
-        .unwrap_or((0.0, 0.0));
-
-    for visit in &route.visits {
-        let travel = travel_time_fast(prev_location, visit.location(), matrix, coord_index);
-        time += travel;
-        total_cost += travel;
+    // Drift penalty: penalize serving a recurring visit off its nominal date
+    penalty.saturating_add(drift_penalty(visit.visit, rules.service_date, options))
+}
 
-        let duration_secs = visit.estimated_duration_minutes() * 60;
+/// Per-stop timing of a route departing at availability open, for cheap
+/// insertion checks (Savelsbergh-style forward slack).
+///
+/// `forward_slack[i]` bounds how far stop `i`'s start can be pushed later
+/// before some stop from `i` on would start past its latest possible start,
+/// or the route would miss its return window (waiting at later stops absorbs
+/// part of a push). The latest start ignores gaps between availability
+/// windows, so an insertion that passes the check may still be infeasible,
+/// but one that fails it always is.
+///
+/// With a single availability window and nothing else that depends on when
+/// visits start, the slack is [`exact`](Self::exact): a move passes exactly
+/// when it's feasible, and its cost change is the change in legs and
+/// penalties. Moves are then priced from the slack alone and only the chosen
+/// one is scheduled.
+struct RouteSlack {
+    availability_windows: Vec<TimeWindow>,
+    start_location: usize,
+    end_location: Option<usize>,
+    latest_return: Option<i32>,
+    windows: Vec<(i32, i32)>,
+    forward_slack: Vec<i32>,
+    exact: bool,
+}
 
-        // Handle committed window constraints
-        if let Some((committed_start, committed_end)) = visit.committed_window() {
-            if time < committed_start {
-                time = committed_start;
+impl RouteSlack {
+    /// Returns `None` if the visitor is unavailable, has no start location,
+    /// a start gap links one of its visits, or the route can't be scheduled.
+    fn new<V, R, A>(
+        service_date: i64,
+        route: &RouteState<'_, V, R>,
+        availability: &A,
+        matrix: &TravelMatrices,
+        options: &SolveOptions,
+        rules: &RouteRules<'_, V, R>,
+    ) -> Option<Self>
+    where
+        V: Visit,
+        R: Visitor<Id = V::VisitorId>,
+        A: AvailabilityProvider<VisitorId = V::VisitorId>,
+    {
+        // Slack doesn't know about waits for start gaps
+        if route.visits.iter().any(|visit| rules.gaps.links(visit.id())) {
+            return None;
+        }
+        let visits = &route.visits;
+        let availability_windows = availability_windows(availability, route.visitor.id(), service_date)?;
+        // Without a start location the route starts at its first visit, which insertion can change
+        let start_location = route.start?;
+        let last_close = availability_windows.last()?.1;
+
+        let mut arrivals = Vec::with_capacity(visits.len());
+        let mut windows = Vec::with_capacity(visits.len());
+        let mut latest_starts = Vec::with_capacity(visits.len());
+        let mut time = availability_windows[0].0;
+        let mut prev_location = start_location;
+        for visit in visits {
+            time += travel_time(prev_location, visit.location, matrix);
+            let (start, end) = fit_visit(*visit, time, &availability_windows)?;
+            let close = visit.committed.map_or(last_close, |(_, end)| end.min(last_close));
+            arrivals.push(time);
+            windows.push((start, end));
+            latest_starts.push(close - (end - start));
+            time = end + visit.buffer;
+            prev_location = visit.location;
+        }
+
+        let latest_return = route.visitor.return_window().map(|(_, latest)| latest);
+        let back = time + route.end.map_or(0, |end| travel_time(prev_location, end, matrix));
+        let mut forward_slack = vec![0; visits.len()];
+        let mut downstream = latest_return.map_or(i32::MAX, |latest| latest - back);
+        for idx in (0..visits.len()).rev() {
+            let own = latest_starts[idx] - windows[idx].0;
+            forward_slack[idx] = own.min(downstream);
+            downstream = forward_slack[idx].saturating_add(windows[idx].0 - arrivals[idx]);
+        }
+
+        let exact = availability_windows.len() == 1
+            && rules.constraints.is_empty()
+            && options.reserve.is_none()
+            && visits.iter().all(|visit| visit.target_time().is_none());
+
+        Some(Self { availability_windows, start_location, end_location: route.end, latest_return, windows, forward_slack, exact })
+    }
+
+    /// Whether moves involving `visit` can be priced from the slack alone.
+    fn is_exact_for<V: Visit>(&self, visit: Stop<'_, V>, gaps: &VisitGaps<V::Id>) -> bool {
+        self.exact && visit.target_time().is_none() && !gaps.links(visit.id())
+    }
+
+    /// Whether inserting `visit` at `position` could be feasible.
+    fn allows_insertion<V: Visit>(
+        &self,
+        visits: &[Stop<'_, V>],
+        position: usize,
+        visit: Stop<'_, V>,
+        matrix: &TravelMatrices,
+    ) -> bool {
+        let (prev_location, prev_end) = match position.checked_sub(1) {
+            Some(prev) => (visits[prev].location, self.windows[prev].1 + visits[prev].buffer),
+            None => (self.start_location, self.availability_windows[0].0),
+        };
+        let travel = travel_time(prev_location, visit.location, matrix);
+        if travel >= UNREACHABLE_SECS {
+            return false;
+        }
+        fit_visit(visit, prev_end + travel, &self.availability_windows)
+            .is_some_and(|(_, end)| self.continues(visits, position, visit.location, end + visit.buffer, matrix))
+    }
+
+    /// Whether the route can carry on from `location`, free at `ready`, to
+    /// the stop at `position` (the route's end if past the last stop).
+    fn continues<V>(&self, visits: &[Stop<'_, V>], position: usize, location: usize, ready: i32, matrix: &TravelMatrices) -> bool {
+        match visits.get(position) {
+            Some(next) => {
+                let travel = travel_time(location, next.location, matrix);
+                travel < UNREACHABLE_SECS && ready + travel - self.windows[position].0 <= self.forward_slack[position]
             }
-            if time > committed_end {
-                return None;
+            None => {
+                let travel = self.end_location.map_or(0, |end| travel_time(location, end, matrix));
+                travel < UNREACHABLE_SECS && self.latest_return.is_none_or(|latest| ready + travel <= latest)
             }
         }
+    }
 
-        // Find a window where the visit fits entirely
-        let (start_time, window_idx) = find_fitting_window(
-            time,
-            duration_secs,
-            current_window_idx,
-            &availability_windows,
-            visit.committed_window(),
-        )?;
-
-        time = start_time + duration_secs;
-        current_window_idx = window_idx;
+    /// Change in route cost from inserting `visit` at `position`, or `None`
+    /// if that's infeasible. Only valid if the slack
+    /// [`is_exact_for`](Self::is_exact_for) the visit.
+    fn insertion_delta<V, R>(
+        &self,
+        route: &RouteState<'_, V, R>,
+        position: usize,
+        visit: Stop<'_, V>,
+        matrix: &TravelMatrices,
+        options: &SolveOptions,
+        rules: &RouteRules<'_, V, R>,
+    ) -> Option<i32>
+    where
+        V: Visit,
+        R: Visitor<Id = V::VisitorId>,
+    {
+        let visits = &route.visits;
+        if !keeps_order(visits, position, visit) || !self.allows_insertion(visits, position, visit, matrix) {
+            return None;
+        }
+        let prev = position.checked_sub(1).map_or(self.start_location, |prev| visits[prev].location);
+        let next = visits.get(position);
+        let first = position == 0;
+        // An empty route pays no leg to its end
+        let removed = if visits.is_empty() { 0 } else { next_leg_cost(route, prev, next, first, matrix, options) };
+        let added = next_leg_cost(route, prev, Some(&visit), first, matrix, options)
+            + next_leg_cost(route, visit.location, next, false, matrix, options);
+        Some(added - removed + visit_penalty(visit, route.visitor, options, rules))
+    }
 
-        // Target time penalty
-        if let Some(target) = visit.target_time() {
-            total_cost += (start_time - target).abs() * options.target_time_weight;
+    /// Change in route cost from reversing the stops `after + 1..=last`, or
+    /// `None` if that's infeasible. Only valid if the slack is exact.
+    fn reversal_delta<V, R>(
+        &self,
+        route: &RouteState<'_, V, R>,
+        after: usize,
+        last: usize,
+        matrix: &TravelMatrices,
+        options: &SolveOptions,
+    ) -> Option<i32>
+    where
+        V: Visit,
+        R: Visitor<Id = V::VisitorId>,
+    {
+        let visits = &route.visits;
+        let segment = &visits[after + 1..=last];
+        // Reversing moves every stop, so none may hold a fixed slot
+        if segment.iter().any(|stop| stop.locked.is_some() || stop.position.is_some()) {
+            return None;
         }
+        let next = visits.get(last + 1);
 
-        // Stability penalty: penalize reassigning to a different visitor
-        if let Some(current_visitor) = visit.current_visitor_id() {
-            if current_visitor != route.visitor.id() {
-                total_cost += options.reassignment_penalty;
+        let mut time = self.windows[after].1 + visits[after].buffer;
+        let mut prev = visits[after].location;
+        let mut delta = 0;
+        for stop in segment.iter().rev() {
+            let travel = travel_time(prev, stop.location, matrix);
+            if travel >= UNREACHABLE_SECS {
+                return None;
             }
+            let (_, end) = fit_visit(*stop, time + travel, &self.availability_windows)?;
+            delta += next_leg_cost(route, prev, Some(stop), false, matrix, options);
+            time = end + stop.buffer;
+            prev = stop.location;
         }
+        if !self.continues(visits, last + 1, prev, time, matrix) {
+            return None;
+        }
+        delta += next_leg_cost(route, prev, next, false, matrix, options);
 
-        result_windows.push((start_time, start_time + duration_secs));
-        prev_location = visit.location();
+        for pair in visits[after..=last].windows(2) {
+            delta -= next_leg_cost(route, pair[0].location, Some(&pair[1]), false, matrix, options);
+        }
+        Some(delta - next_leg_cost(route, visits[last].location, next, false, matrix, options))
+    }
+}
+
+/// Cost of the leg from `from` to `next` and serving it, or of the leg to
+/// the route's end if `next` is `None`, as [`forward_schedule`] charges it.
+fn next_leg_cost<V, R>(
+    route: &RouteState<'_, V, R>,
+    from: usize,
+    next: Option<&Stop<'_, V>>,
+    first: bool,
+    matrix: &TravelMatrices,
+    options: &SolveOptions,
+) -> i32
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+{
+    match next {
+        Some(stop) => {
+            let service_secs = stop.estimated_duration_minutes() * 60;
+            leg_cost(route.visitor, from, stop.location, service_secs, first && options.unpaid_commute, matrix, options)
+        }
+        None => route.end.map_or(0, |end| leg_cost(route.visitor, from, end, 0, options.unpaid_commute, matrix, options)),
+    }
+}
+
+/// Earliest (start, end) for a visit reached at `arrival`, as in [`forward_schedule`].
+fn fit_visit<V: Visit>(visit: Stop<'_, V>, arrival: i32, availability_windows: &[TimeWindow]) -> Option<(i32, i32)> {
+    let duration_secs = visit.estimated_duration_minutes() * 60;
+    let mut time = arrival;
+    if let Some((committed_start, committed_end)) = visit.committed {
+        if time > committed_end {
+            return None;
+        }
+        time = time.max(committed_start);
+    }
+    let (start, _) = find_fitting_window(time, duration_secs, 0, availability_windows, visit.committed)?;
+    Some((start, start + duration_secs))
+}
+
+/// Fetch a visitor's availability, sorted with overlapping windows merged.
+///
+/// Returns `None` if the visitor is unavailable or has no non-empty windows.
+fn availability_windows<A: AvailabilityProvider>(
+    availability: &A,
+    visitor_id: &A::VisitorId,
+    service_date: i64,
+) -> Option<Vec<TimeWindow>> {
+    let mut windows = availability.availability_for(visitor_id, service_date)?;
+    windows.retain(|(start, end)| start < end);
+    windows.sort_unstable();
+
+    let mut merged: Vec<TimeWindow> = Vec::with_capacity(windows.len());
+    for (start, end) in windows {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
     }
 
-    Some((result_windows, total_cost))
+    if merged.is_empty() { None } else { Some(merged) }
 }
 
 /// Find the earliest window where a visit can fit entirely.
@@ -424,72 +4020,211 @@ fn find_fitting_window(
     None
 }
 
-fn collect_locations<V, R>(visits: &[V], visitors: &[R]) -> Vec<(f64, f64)>
+/// Matrix row for each distinct location referenced by visits and visitors.
+type LocationIndex = HashMap<LocationRef, usize>;
+
+/// Distinct coordinates to request, and each reference's row among them.
+type Locations = (Vec<(f64, f64)>, LocationIndex);
+
+/// Collect the distinct locations to request from the matrix provider.
+///
+/// Locations are deduplicated by [`LocationRef`]; the first coordinates seen
+/// for a reference are the ones sent to the provider.
+fn collect_locations<V, R>(
+    visits: &[V],
+    visitors: &[R],
+    options: &SolveOptions,
+) -> Result<Locations, SolveError<V::Id>>
 where
     V: Visit,
     R: Visitor<Id = V::VisitorId>,
 {
     let mut locations = Vec::new();
+    let mut index = LocationIndex::new();
+    let mut add = |location_ref: LocationRef, coord: (f64, f64)| {
+        index.entry(location_ref).or_insert_with(|| {
+            locations.push(coord);
+            locations.len() - 1
+        });
+    };
+
     for visitor in visitors {
-        if let Some(start) = visitor.start_location() {
-            locations.push(start);
+        if let (Some(location_ref), Some(start)) = (visitor.start_location_ref(), visitor.start_location()) {
+            add(location_ref, start);
         }
-        if let Some(end) = visitor.end_location() {
-            locations.push(end);
+        if let (Some(location_ref), Some(end)) = (visitor.end_location_ref(), visitor.end_location()) {
+            add(location_ref, end);
         }
     }
-    for visit in visits {
-        locations.push(visit.location());
+    let (routable, unroutable): (Vec<&V>, Vec<&V>) =
+        visits.iter().partition(|visit| is_routable_location(visit.location(), options));
+    for visit in &routable {
+        add(visit.location_ref(), visit.location());
+    }
+
+    // Visits without usable coordinates share one extra row: the centroid, or
+    // past the provider's rows, where travel costs nothing (such visits are
+    // unassigned by `location_unassigned`)
+    let mut extra_row = locations.len();
+    if options.invalid_locations == InvalidLocationPolicy::Centroid && !unroutable.is_empty() && !routable.is_empty() {
+        let (lat, lng) = routable.iter().fold((0.0, 0.0), |(lat, lng), visit| {
+            (lat + visit.location().0, lng + visit.location().1)
+        });
+        let count = routable.len() as f64;
+        locations.push((lat / count, lng / count));
+        extra_row = locations.len() - 1;
+    }
+    for visit in unroutable {
+        index.entry(visit.location_ref()).or_insert(extra_row);
+    }
+
+    let mut refs = visitors.iter().flat_map(|visitor| visitor.start_location_ref().into_iter().chain(visitor.end_location_ref()));
+    if let Some(missing) = refs.find(|location_ref| !index.contains_key(location_ref)) {
+        return Err(SolveError::MissingCoordinates(missing));
     }
 
-    dedupe_locations(locations)
+    Ok((locations, index))
 }
 
-fn dedupe_locations(locations: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
-    let mut seen: HashMap<(i64, i64), usize> = HashMap::new();
-    let mut unique = Vec::new();
-    for location in locations {
-        let key = coord_to_int_key(location);
-        if seen.contains_key(&key) {
-            continue;
+/// Travel matrices for a solve: the provider's default plus one per visitor
+/// [`routing_profile`](Visitor::routing_profile).
+struct FleetMatrices {
+    default: TravelMatrices,
+    profiles: HashMap<String, TravelMatrices>,
+}
+
+impl FleetMatrices {
+    /// Without a `default` (no visitor uses it), the first profile's
+    /// matrices stand in for it.
+    fn new(default: Option<TravelMatrices>, mut profiles: Vec<(&str, TravelMatrices)>) -> Self {
+        let default = match default {
+            Some(default) => default,
+            None if !profiles.is_empty() => profiles.remove(0).1,
+            None => TravelMatrices::from_durations(Vec::new()),
+        };
+        let profiles = profiles.into_iter().map(|(profile, matrices)| (profile.to_string(), matrices)).collect();
+        Self { default, profiles }
+    }
+
+    /// The matrices `visitor` travels by.
+    fn of<R: Visitor>(&self, visitor: &R) -> &TravelMatrices {
+        visitor.routing_profile().and_then(|profile| self.profiles.get(profile)).unwrap_or(&self.default)
+    }
+}
+
+/// Distinct routing profiles among `visitors`, in order, and whether any
+/// visitor travels by the provider's default.
+fn routing_profiles<R: Visitor>(visitors: &[R]) -> (Vec<&str>, bool) {
+    let mut profiles: Vec<&str> = Vec::new();
+    let mut uses_default = visitors.is_empty();
+    for visitor in visitors {
+        match visitor.routing_profile() {
+            Some(profile) if !profiles.contains(&profile) => profiles.push(profile),
+            Some(_) => {}
+            None => uses_default = true,
         }
-        seen.insert(key, unique.len());
-        unique.push(location);
     }
-    unique
+    (profiles, uses_default)
 }
 
-/// Convert floating-point coordinates to integer-scaled coordinates for efficient hashing.
-/// Scales by 1,000,000 to preserve 6 decimal places of precision.
-/// Uses round() to match the formatting behavior of location_key which uses {:.6}.
-#[inline]
-fn coord_to_int_key(coord: (f64, f64)) -> (i64, i64) {
-    ((coord.0 * 1_000_000.0).round() as i64, (coord.1 * 1_000_000.0).round() as i64)
+/// Fetches and prepares the matrices for every routing profile in the fleet.
+fn fleet_matrices<VisitId, R, M>(
+    matrix_provider: &M,
+    visitors: &[R],
+    locations: &[(f64, f64)],
+    location_index: &LocationIndex,
+    options: &SolveOptions,
+) -> Result<FleetMatrices, SolveError<VisitId>>
+where
+    R: Visitor,
+    M: DistanceMatrixProvider,
+{
+    let prepare = |matrix| prepare_matrix(matrix, locations, location_index, options);
+    let (profiles, uses_default) = routing_profiles(visitors);
+    let default = if uses_default { Some(prepare(matrix_provider.matrices_for(locations))?) } else { None };
+    let profiles = profiles
+        .into_iter()
+        .map(|profile| Ok((profile, prepare(matrix_provider.matrices_for_profile(profile, locations))?)))
+        .collect::<Result<_, _>>()?;
+    Ok(FleetMatrices::new(default, profiles))
 }
 
-/// Build an efficient coordinate-to-index mapping using integer-scaled coordinates.
-/// This avoids string allocation on every lookup.
-/// Takes the original locations to ensure consistent float->int conversion.
-fn build_coord_index(locations: &[(f64, f64)]) -> HashMap<(i64, i64), usize> {
-    locations.iter()
-        .enumerate()
-        .map(|(idx, &coord)| (coord_to_int_key(coord), idx))
-        .collect()
+/// Rejects a matrix that doesn't cover every location exactly once, so a
+/// failed provider can't send row lookups out of bounds.
+fn check_matrix<VisitId>(matrix: TravelMatrices, locations: usize) -> Result<TravelMatrices, SolveError<VisitId>> {
+    if matrix.durations.is_empty() && locations > 0 {
+        return Err(SolveError::EmptyMatrix { locations });
+    }
+    let tables = std::iter::once(&matrix.durations).chain(matrix.distances.as_ref());
+    for table in tables {
+        let bad_row = table.iter().find(|row| row.len() != locations);
+        if table.len() != locations || bad_row.is_some() {
+            let columns = bad_row.or(table.first()).map_or(0, Vec::len);
+            return Err(SolveError::MatrixSizeMismatch { expected: locations, rows: table.len(), columns });
+        }
+    }
+    Ok(matrix)
+}
+
+/// Checks the provider's matrix, applies the travel options, and adds the
+/// zero-travel row [`collect_locations`] may give visits without usable
+/// coordinates.
+fn prepare_matrix<VisitId>(
+    matrix: TravelMatrices,
+    locations: &[(f64, f64)],
+    location_index: &LocationIndex,
+    options: &SolveOptions,
+) -> Result<TravelMatrices, SolveError<VisitId>> {
+    let mut matrix = pad_travel(check_matrix(matrix, locations.len())?, options);
+    if location_index.values().any(|&row| row == locations.len()) {
+        for table in std::iter::once(&mut matrix.durations).chain(matrix.distances.as_mut()) {
+            for row in table.iter_mut() {
+                row.push(0);
+            }
+            table.push(vec![0; table.len() + 1]);
+        }
+        if let Some(snap_distances) = &mut matrix.snap_distances {
+            snap_distances.push(0);
+        }
+    }
+    Ok(matrix)
+}
+
+/// Applies [`SolveOptions::travel_time_multiplier`] and
+/// [`SolveOptions::travel_buffer_secs`] to every routable leg between
+/// distinct rows.
+fn pad_travel(mut matrix: TravelMatrices, options: &SolveOptions) -> TravelMatrices {
+    let (multiplier, buffer) = (options.travel_time_multiplier, options.travel_buffer_secs);
+    if multiplier == 1.0 && buffer == 0 {
+        return matrix;
+    }
+    for (from, row) in matrix.durations.iter_mut().enumerate() {
+        for (to, secs) in row.iter_mut().enumerate() {
+            if from != to && *secs < UNREACHABLE_SECS {
+                *secs = (*secs as f64 * multiplier).round() as i32 + buffer;
+            }
+        }
+    }
+    matrix
 }
 
-/// Fast travel time lookup using integer-scaled coordinates (no string allocation).
+/// Travel time between two matrix rows.
 #[inline]
-fn travel_time_fast(
-    from: (f64, f64),
-    to: (f64, f64),
-    matrix: &[Vec<i32>],
-    coord_index: &HashMap<(i64, i64), usize>,
-) -> i32 {
-    let from_key = coord_to_int_key(from);
-    let to_key = coord_to_int_key(to);
-    let from_idx = coord_index[&from_key];
-    let to_idx = coord_index[&to_key];
-    matrix[from_idx][to_idx]
+fn travel_time(from: usize, to: usize, matrix: &TravelMatrices) -> i32 {
+    matrix.durations[from][to]
+}
+
+/// Travel distance in meters between two matrix rows, or 0 if the provider
+/// returned no distances.
+#[inline]
+fn travel_distance(from: usize, to: usize, matrix: &TravelMatrices) -> i32 {
+    matrix.distances.as_ref().map_or(0, |distances| distances[from][to])
+}
+
+/// Money cost of a visitor spending `secs` working and driving `meters`, in cents.
+fn money_cost<R: Visitor>(visitor: &R, secs: i32, meters: i32) -> i32 {
+    let cost = secs as f64 / 3600.0 * visitor.hourly_cost() + meters as f64 / 1000.0 * visitor.per_km_cost();
+    (cost * 100.0).round() as i32
 }
 
 // ============================================================================
@@ -498,13 +4233,15 @@ fn travel_time_fast(
 
 /// 2-opt: Reverse a segment within a route to reduce travel time.
 /// Returns true if an improvement was made.
+#[allow(clippy::too_many_arguments)]
 fn two_opt_improve<'a, V, R, A>(
     route: &mut RouteState<'a, V, R>,
     service_date: i64,
     availability: &A,
-    matrix: &[Vec<i32>],
-    coord_index: &HashMap<(i64, i64), usize>,
+    matrix: &FleetMatrices,
     options: &SolveOptions,
+    rules: &RouteRules<'_, V, R>,
+    linked: &LinkedWindows<V::Id>,
 ) -> bool
 where
     V: Visit,
@@ -515,59 +4252,207 @@ where
         return false;
     }
 
-    let current_cost = route.total_travel_time;
+    let first_improvement = options.improvement_strategy == ImprovementStrategy::FirstImprovement;
     let n = route.visits.len();
 
+    // Exact slack prices every reversal; only the chosen one is scheduled
+    if let Some(slack) = RouteSlack::new(service_date, route, availability, matrix.of(route.visitor), options, rules)
+        .filter(|slack| slack.exact)
+    {
+        let mut best: Option<(usize, usize, i32)> = None;
+        'search: for i in 0..n - 1 {
+            for j in i + 2..n {
+                if let Some(delta) = slack.reversal_delta(route, i, j, matrix.of(route.visitor), options)
+                    && delta < best.map_or(0, |(_, _, best_delta)| best_delta)
+                {
+                    best = Some((i, j, delta));
+                    if first_improvement {
+                        break 'search;
+                    }
+                }
+            }
+        }
+
+        let Some((i, j, _)) = best else {
+            return false;
+        };
+        let mut candidate = route.visits.clone();
+        candidate[i + 1..=j].reverse();
+        let candidate_route = RouteState::with_visits(route.visitor, route.start, route.end, candidate);
+        let Some((windows, cost)) =
+            compute_schedule(service_date, &candidate_route, availability, matrix, options, rules, linked)
+        else {
+            return false;
+        };
+        route.visits = candidate_route.visits;
+        route.estimated_windows = windows;
+        route.total_travel_time = cost;
+        return true;
+    }
+
+    let mut best: Option<(usize, usize, Schedule)> = None;
+
     for i in 0..n - 1 {
         for j in i + 2..n {
             // Reverse segment [i+1..=j]
             let mut candidate = route.visits.clone();
             candidate[i + 1..=j].reverse();
 
-            let candidate_route = RouteState {
-                visitor: route.visitor,
-                visits: candidate,
-                estimated_windows: Vec::new(),
-                total_travel_time: 0,
-            };
+            let candidate_route = RouteState::with_visits(route.visitor, route.start, route.end, candidate);
 
             if let Some((windows, cost)) = compute_schedule(
                 service_date,
                 &candidate_route,
                 availability,
                 matrix,
-                coord_index,
                 options,
-            ) {
-                if cost < current_cost {
-                    route.visits[i + 1..=j].reverse();
-                    route.estimated_windows = windows;
-                    route.total_travel_time = cost;
-                    return true;
+                rules,
+                linked,
+            ) && cost < best.as_ref().map_or(route.total_travel_time, |(_, _, (_, best_cost))| *best_cost)
+            {
+                best = Some((i, j, (windows, cost)));
+                if first_improvement {
+                    break;
                 }
             }
         }
+        if first_improvement && best.is_some() {
+            break;
+        }
+    }
+
+    let Some((i, j, (windows, cost))) = best else {
+        return false;
+    };
+    route.visits[i + 1..=j].reverse();
+    route.estimated_windows = windows;
+    route.total_travel_time = cost;
+    true
+}
+
+/// The `k` visit locations on the routes nearest `location`, by travel time
+/// from it. `None` if `k` is `None` or covers every location.
+fn nearest_locations<V, R>(
+    routes: &[RouteState<'_, V, R>],
+    location: usize,
+    matrix: &TravelMatrices,
+    k: Option<usize>,
+) -> Option<Vec<usize>>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+{
+    let k = k?;
+    let mut locations: Vec<usize> =
+        routes.iter().flat_map(|route| route.visits.iter().map(|stop| stop.location)).collect();
+    locations.sort_unstable();
+    locations.dedup();
+    if locations.len() <= k {
+        return None;
+    }
+    locations.select_nth_unstable_by_key(k, |&other| travel_time(location, other, matrix));
+    locations.truncate(k);
+    Some(locations)
+}
+
+/// Nearest other visit locations for each assigned visit's location.
+type NeighborLists = HashMap<usize, Vec<usize>>;
+
+/// The `k` closest visit locations to each location on the routes, by travel
+/// time. Returns `None` if every list would hold all the other locations.
+fn neighbor_lists<V, R>(routes: &[RouteState<'_, V, R>], matrix: &TravelMatrices, k: usize) -> Option<NeighborLists>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+{
+    let mut locations: Vec<usize> =
+        routes.iter().flat_map(|route| route.visits.iter().map(|stop| stop.location)).collect();
+    locations.sort_unstable();
+    locations.dedup();
+    if locations.len() <= k + 1 {
+        return None;
+    }
+
+    let lists = locations
+        .iter()
+        .map(|&from| {
+            let mut nearest: Vec<usize> = locations.iter().copied().filter(|&to| to != from).collect();
+            nearest.sort_by_key(|&to| travel_time(from, to, matrix));
+            nearest.truncate(k);
+            (from, nearest)
+        })
+        .collect();
+    Some(lists)
+}
+
+/// Stops on the routes at each location, as (route index, position).
+type LocationStops = HashMap<usize, Vec<(usize, usize)>>;
+
+fn location_stops<V, R>(routes: &[RouteState<'_, V, R>]) -> LocationStops
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+{
+    let mut stops: LocationStops = HashMap::new();
+    for (route_idx, route) in routes.iter().enumerate() {
+        for (position, stop) in route.visits.iter().enumerate() {
+            stops.entry(stop.location).or_default().push((route_idx, position));
+        }
     }
+    stops
+}
 
-    false
+/// Insertion positions on each route that put `visit` right before or after
+/// a stop at one of its neighbors or at its own location. An empty route's
+/// only position always qualifies.
+fn neighbor_positions<V, R>(
+    routes: &[RouteState<'_, V, R>],
+    stops: &LocationStops,
+    neighbors: &NeighborLists,
+    visit: Stop<'_, V>,
+) -> Vec<Vec<usize>>
+where
+    V: Visit,
+    R: Visitor<Id = V::VisitorId>,
+{
+    let mut positions: Vec<Vec<usize>> =
+        routes.iter().map(|route| if route.visits.is_empty() { vec![0] } else { Vec::new() }).collect();
+    let nearest = neighbors.get(&visit.location).map_or(&[][..], Vec::as_slice);
+    for location in std::iter::once(&visit.location).chain(nearest) {
+        for &(route_idx, position) in stops.get(location).into_iter().flatten() {
+            positions[route_idx].extend([position, position + 1]);
+        }
+    }
+    for route_positions in &mut positions {
+        route_positions.sort_unstable();
+        route_positions.dedup();
+    }
+    positions
 }
 
 /// Relocate: Move a visit from one route to another (or within the same route).
 /// Returns true if an improvement was made.
+#[allow(clippy::too_many_arguments)]
 fn relocate_improve<'a, V, R, A>(
     routes: &mut [RouteState<'a, V, R>],
     service_date: i64,
     availability: &A,
-    matrix: &[Vec<i32>],
-    coord_index: &HashMap<(i64, i64), usize>,
+    matrix: &FleetMatrices,
     options: &SolveOptions,
+    rules: &RouteRules<'_, V, R>,
+    neighbors: Option<&NeighborLists>,
 ) -> bool
 where
     V: Visit,
     R: Visitor<Id = V::VisitorId>,
     A: AvailabilityProvider<VisitorId = V::VisitorId>,
 {
-    let total_cost: i32 = routes.iter().map(|r| r.total_travel_time).sum();
+    let total_cost = plan_cost(routes);
+    let first_improvement = options.improvement_strategy == ImprovementStrategy::FirstImprovement;
+    let mut best: Option<Relocation> = None;
+    let moved = reassignments(routes);
+    let linked = rules.gaps.linked_windows(routes);
+    let stops = neighbors.map(|_| location_stops(routes));
 
     // Try moving each visit from each route to every other position
     for from_route_idx in 0..routes.len() {
@@ -578,17 +4463,35 @@ where
 
         for visit_idx in 0..from_route_len {
             let visit = routes[from_route_idx].visits[visit_idx];
+            let group = group_route(routes, visit.visit);
 
-            // Check if visit is pinned to current visitor
-            let is_pinned_to_visitor = matches!(
-                visit.pin_type(),
-                VisitPinType::Visitor | VisitPinType::VisitorAndDate
-            );
+            // Schedule of the source route without this visit, shared by every
+            // move to another route
+            let mut removal_schedule: Option<Option<Schedule>> = None;
+
+            // Only positions beside a nearby visit are tried
+            let nearby = neighbors.zip(stops.as_ref()).map(|(neighbors, stops)| neighbor_positions(routes, stops, neighbors, visit));
 
             // Try inserting into every route (including same route, different position)
             for to_route_idx in 0..routes.len() {
-                // Skip moving pinned visits to different routes
-                if is_pinned_to_visitor && to_route_idx != from_route_idx {
+                // Skip routes the visit's pin or capabilities rule out, and
+                // grouped visits away from their group; route constraints are
+                // checked when the candidates are scheduled
+                let to_visitor = routes[to_route_idx].visitor;
+                if (to_route_idx != from_route_idx
+                    && !(Pinning::allows(visit.visit, to_visitor) && Capabilities::allows(visit.visit, to_visitor)))
+                    || group.is_some_and(|group| group != to_route_idx)
+                {
+                    continue;
+                }
+
+                let delta = isize::from(reassigned(visit.visit, routes[to_route_idx].visitor))
+                    - isize::from(reassigned(visit.visit, routes[from_route_idx].visitor));
+                if to_route_idx != from_route_idx
+                    && (route_is_full(routes[to_route_idx].visitor, routes[to_route_idx].visits.len(), options)
+                        || keeps_apart(&routes[to_route_idx], visit.visit)
+                        || !within_reassignment_cap(moved, delta, options))
+                {
                     continue;
                 }
 
@@ -598,22 +4501,33 @@ where
                 } else {
                     to_route_len + 1 // different route: can insert at 0..=len
                 };
+                let positions: Vec<usize> = match &nearby {
+                    Some(nearby) => nearby[to_route_idx].iter().copied().filter(|&pos| pos < insert_positions).collect(),
+                    None => (0..insert_positions).collect(),
+                };
+                if positions.is_empty() {
+                    continue;
+                }
+
+                let to_slack = if from_route_idx == to_route_idx {
+                    None
+                } else {
+                    let to_route = &routes[to_route_idx];
+                    RouteSlack::new(service_date, to_route, availability, matrix.of(to_route.visitor), options, rules)
+                };
 
-                for insert_pos in 0..insert_positions {
+                for insert_pos in positions {
                     // Skip if same route and same or adjacent position (no change)
-                    if from_route_idx == to_route_idx {
-                        if insert_pos == visit_idx || insert_pos == visit_idx + 1 {
-                            continue;
-                        }
+                    if from_route_idx == to_route_idx
+                        && (insert_pos == visit_idx || insert_pos == visit_idx + 1)
+                    {
+                        continue;
                     }
 
-                    // Check capability match for target route
-                    let required = visit.required_capabilities();
-                    if !required.is_empty() {
-                        let available = routes[to_route_idx].visitor.capabilities();
-                        if !required.iter().all(|cap| available.contains(cap)) {
-                            continue;
-                        }
+                    if let Some(slack) = &to_slack
+                        && !slack.allows_insertion(&routes[to_route_idx].visits, insert_pos, visit, matrix.of(routes[to_route_idx].visitor))
+                    {
+                        continue;
                     }
 
                     // Build candidate routes
@@ -634,25 +4548,29 @@ where
                     to_candidate.insert(actual_insert_pos, visit);
 
                     // Compute new schedules
-                    let from_route_state = RouteState {
-                        visitor: routes[from_route_idx].visitor,
-                        visits: if from_route_idx == to_route_idx {
-                            to_candidate.clone()
-                        } else {
-                            from_candidate
-                        },
-                        estimated_windows: Vec::new(),
-                        total_travel_time: 0,
+                    let schedule_from_route = || {
+                        let from_route = &routes[from_route_idx];
+                        let from_route_state = RouteState::with_visits(
+                            from_route.visitor,
+                            from_route.start,
+                            from_route.end,
+                            if from_route_idx == to_route_idx { to_candidate.clone() } else { from_candidate },
+                        );
+                        compute_schedule(
+                            service_date,
+                            &from_route_state,
+                            availability,
+                            matrix,
+                            options,
+                            rules,
+                            &linked,
+                        )
+                    };
+                    let from_schedule = if from_route_idx == to_route_idx {
+                        schedule_from_route()
+                    } else {
+                        removal_schedule.get_or_insert_with(schedule_from_route).clone()
                     };
-
-                    let from_schedule = compute_schedule(
-                        service_date,
-                        &from_route_state,
-                        availability,
-                        matrix,
-                        coord_index,
-                        options,
-                    );
 
                     if from_schedule.is_none() {
                         continue;
@@ -661,36 +4579,65 @@ where
                     if from_route_idx == to_route_idx {
                         // Same route: just the new cost
                         let (windows, cost) = from_schedule.unwrap();
-                        let other_cost: i32 = routes
+                        let other_cost: i64 = routes
                             .iter()
                             .enumerate()
                             .filter(|(i, _)| *i != from_route_idx)
-                            .map(|(_, r)| r.total_travel_time)
+                            .map(|(_, r)| i64::from(r.total_travel_time))
                             .sum();
 
-                        if cost + other_cost < total_cost {
-                            routes[from_route_idx].visits = to_candidate;
-                            routes[from_route_idx].estimated_windows = windows;
-                            routes[from_route_idx].total_travel_time = cost;
-                            return true;
+                        let new_cost = i64::from(cost) + other_cost;
+                        if new_cost < best.as_ref().map_or(total_cost, |best| best.total_cost) {
+                            let relocation = Relocation {
+                                from_route_idx,
+                                visit_idx,
+                                to_route_idx,
+                                insert_pos: actual_insert_pos,
+                                from_schedule: (windows, cost),
+                                to_schedule: None,
+                                total_cost: new_cost,
+                            };
+                            if first_improvement {
+                                relocation.apply(routes);
+                                return true;
+                            }
+                            best = Some(relocation);
                         }
                         continue;
                     } else {
+                        let other_cost: i64 = routes
+                            .iter()
+                            .enumerate()
+                            .filter(|(i, _)| *i != from_route_idx && *i != to_route_idx)
+                            .map(|(_, r)| i64::from(r.total_travel_time))
+                            .sum();
+
+                        // Exact slack prices the insertion; only an improving one is scheduled
+                        if let Some(slack) = to_slack.as_ref().filter(|slack| slack.is_exact_for(visit, &rules.gaps)) {
+                            let to_route = &routes[to_route_idx];
+                            let Some(delta) = slack.insertion_delta(to_route, insert_pos, visit, matrix.of(to_route.visitor), options, rules) else {
+                                continue;
+                            };
+                            let from_cost = from_schedule.as_ref().map_or(0, |(_, cost)| *cost);
+                            if i64::from(from_cost) + i64::from(to_route.total_travel_time) + i64::from(delta) + other_cost
+                                >= best.as_ref().map_or(total_cost, |best| best.total_cost)
+                            {
+                                continue;
+                            }
+                        }
+
                         // Different routes: compute both
-                        let to_route_state = RouteState {
-                            visitor: routes[to_route_idx].visitor,
-                            visits: to_candidate.clone(),
-                            estimated_windows: Vec::new(),
-                            total_travel_time: 0,
-                        };
+                        let to_route = &routes[to_route_idx];
+                        let to_route_state = RouteState::with_visits(to_route.visitor, to_route.start, to_route.end, to_candidate.clone());
 
                         let to_schedule = compute_schedule(
                             service_date,
                             &to_route_state,
                             availability,
                             matrix,
-                            coord_index,
                             options,
+                            rules,
+                            &linked,
                         );
 
                         if to_schedule.is_none() {
@@ -700,23 +4647,31 @@ where
                         let (from_windows, from_cost) = from_schedule.unwrap();
                         let (to_windows, to_cost) = to_schedule.unwrap();
 
-                        let other_cost: i32 = routes
-                            .iter()
-                            .enumerate()
-                            .filter(|(i, _)| *i != from_route_idx && *i != to_route_idx)
-                            .map(|(_, r)| r.total_travel_time)
-                            .sum();
-
-                        if from_cost + to_cost + other_cost < total_cost {
-                            // Apply the move
-                            routes[from_route_idx].visits.remove(visit_idx);
-                            routes[from_route_idx].estimated_windows = from_windows;
-                            routes[from_route_idx].total_travel_time = from_cost;
+                        // Each route was scheduled against the other's old windows
+                        if !rules.gaps.is_empty() {
+                            let mut from_visits = routes[from_route_idx].visits.clone();
+                            from_visits.remove(visit_idx);
+                            if !rules.gaps.hold(&[(&from_visits, &from_windows), (&to_candidate, &to_windows)]) {
+                                continue;
+                            }
+                        }
 
-                            routes[to_route_idx].visits.insert(insert_pos, visit);
-                            routes[to_route_idx].estimated_windows = to_windows;
-                            routes[to_route_idx].total_travel_time = to_cost;
-                            return true;
+                        let new_cost = i64::from(from_cost) + i64::from(to_cost) + other_cost;
+                        if new_cost < best.as_ref().map_or(total_cost, |best| best.total_cost) {
+                            let relocation = Relocation {
+                                from_route_idx,
+                                visit_idx,
+                                to_route_idx,
+                                insert_pos,
+                                from_schedule: (from_windows, from_cost),
+                                to_schedule: Some((to_windows, to_cost)),
+                                total_cost: new_cost,
+                            };
+                            if first_improvement {
+                                relocation.apply(routes);
+                                return true;
+                            }
+                            best = Some(relocation);
                         }
                     }
                 }
@@ -724,61 +4679,175 @@ where
         }
     }
 
-    false
+    match best {
+        Some(relocation) => {
+            relocation.apply(routes);
+            true
+        }
+        None => false,
+    }
+}
+
+/// An improving relocate move and the new schedules of the routes it touches.
+struct Relocation {
+    from_route_idx: usize,
+    visit_idx: usize,
+    to_route_idx: usize,
+    /// Position in the destination route after the visit has been removed.
+    insert_pos: usize,
+    from_schedule: Schedule,
+    /// Destination schedule; `None` when moving within one route.
+    to_schedule: Option<Schedule>,
+    total_cost: i64,
+}
+
+impl Relocation {
+    fn apply<V, R>(self, routes: &mut [RouteState<'_, V, R>])
+    where
+        V: Visit,
+        R: Visitor<Id = V::VisitorId>,
+    {
+        let visit = routes[self.from_route_idx].visits.remove(self.visit_idx);
+        routes[self.to_route_idx].visits.insert(self.insert_pos, visit);
+        let from = &mut routes[self.from_route_idx];
+        (from.estimated_windows, from.total_travel_time) = self.from_schedule;
+        if let Some(schedule) = self.to_schedule {
+            let to = &mut routes[self.to_route_idx];
+            (to.estimated_windows, to.total_travel_time) = schedule;
+        }
+    }
 }
 
 /// Run local search improvement until no more improvements or max iterations reached.
+///
+/// At each local optimum, visits construction dropped get another chance
+/// through [`reinsert_unassigned`]; the search goes on if any is placed.
 fn local_search<'a, V, R, A>(
     routes: &mut [RouteState<'a, V, R>],
+    unassigned: &mut Vec<(Stop<'a, V>, UnassignedReason)>,
     service_date: i64,
     availability: &A,
-    matrix: &[Vec<i32>],
-    coord_index: &HashMap<(i64, i64), usize>,
+    matrix: &FleetMatrices,
     options: &SolveOptions,
+    rules: &RouteRules<'_, V, R>,
 )
 where
     V: Visit,
     R: Visitor<Id = V::VisitorId>,
     A: AvailabilityProvider<VisitorId = V::VisitorId>,
 {
+    let initial_cost = plan_cost(routes);
+    let span = info_span!(
+        "vrp.local_search",
+        max_iterations = options.local_search_iterations,
+        iterations = Empty,
+        initial_cost,
+        cost_delta = Empty,
+        otel.kind = Empty,
+    );
+    telemetry::internal(&span);
+    let _guard = span.enter();
+
+    let mut neighbors = options.local_search_neighbors.and_then(|k| neighbor_lists(routes, &matrix.default, k));
     let mut iterations_completed = 0;
     for iteration in 0..options.local_search_iterations {
-        let mut improved = false;
+        let iteration_span = debug_span!(
+            "vrp.local_search.iteration",
+            iteration,
+            two_opt_routes = Empty,
+            relocated = Empty,
+            reinserted = Empty,
+            cost_delta = Empty,
+        );
+        let _iteration_guard = iteration_span.enter();
+        let cost_before = plan_cost(routes);
 
         // Try 2-opt on each route
-        for route in routes.iter_mut() {
+        let mut two_opt_routes = 0;
+        let mut linked = rules.gaps.linked_windows(routes);
+        for route_idx in 0..routes.len() {
             if two_opt_improve(
-                route,
+                &mut routes[route_idx],
                 service_date,
                 availability,
                 matrix,
-                coord_index,
                 options,
+                rules,
+                &linked,
             ) {
-                improved = true;
+                two_opt_routes += 1;
+                linked = rules.gaps.linked_windows(routes);
             }
         }
 
         // Try relocate moves between routes
-        if relocate_improve(
+        let relocated = relocate_improve(
             routes,
             service_date,
             availability,
             matrix,
-            coord_index,
             options,
-        ) {
-            improved = true;
+            rules,
+            neighbors.as_ref(),
+        );
+
+        // At a local optimum, retry the visits construction dropped
+        let reinserted = two_opt_routes == 0
+            && !relocated
+            && reinsert_unassigned(
+                routes,
+                unassigned,
+                service_date,
+                availability,
+                matrix,
+                options,
+                rules,
+                options.local_search_neighbors,
+            );
+        if reinserted {
+            neighbors = options.local_search_neighbors.and_then(|k| neighbor_lists(routes, &matrix.default, k));
         }
 
+        iteration_span.record("two_opt_routes", two_opt_routes);
+        iteration_span.record("relocated", relocated);
+        iteration_span.record("reinserted", reinserted);
+        iteration_span.record("cost_delta", plan_cost(routes) - cost_before);
+
         iterations_completed = iteration + 1;
-        if !improved {
+        if two_opt_routes == 0 && !relocated && !reinserted {
             break;
         }
     }
+    span.record("iterations", iterations_completed);
+    span.record("cost_delta", plan_cost(routes) - initial_cost);
     debug!(
         iterations = iterations_completed,
         max_iterations = options.local_search_iterations,
         "Local search iterations"
     );
 }
+
+/// Sum of route costs (travel plus penalties) under the solve's objective.
+fn plan_cost<V: Visit, R: Visitor<Id = V::VisitorId>>(routes: &[RouteState<'_, V, R>]) -> i64 {
+    routes.iter().map(|route| i64::from(route.total_travel_time)).sum()
+}
+
+/// `std::time::Instant::now` panics on `wasm32-unknown-unknown`, which has no
+/// clock without a JS binding. Logged phase timings read as zero there instead.
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub(crate) mod wasm_clock {
+    use std::time::Duration;
+
+    #[derive(Debug, Clone, Copy)]
+    pub struct Instant;
+
+    impl Instant {
+        pub fn now() -> Self {
+            Instant
+        }
+
+        pub fn elapsed(&self) -> Duration {
+            Duration::ZERO
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Splitting long visits across days.
+//!
+//! The solver plans one day at a time and a visit must fit inside a single
+//! availability window, so a job longer than any window (e.g., a 14-hour
+//! install) comes back as
+//! [`DurationExceedsWindow`](crate::traits::UnassignedReason::DurationExceedsWindow).
+//! Splitting is opt-in: [`split_across_days`] breaks such a visit into
+//! segments for one visitor on consecutive available dates. Each segment is
+//! itself a [`Visit`] pinned to that visitor and date, so it can be passed to
+//! the daily solve alongside the day's other visits.
+
+use crate::traits::{AvailabilityProvider, LocationRef, Visit, VisitPinType};
+
+/// One day's share of a split visit.
+///
+/// Reports the original visit's id, location, and capabilities, with the
+/// segment's own duration. Committed windows and target times don't carry
+/// over, since they describe the job as a whole.
+#[derive(Debug, Clone)]
+pub struct VisitSegment<'a, V: Visit> {
+    visit: &'a V,
+    visitor_id: V::VisitorId,
+    date: i64,
+    duration_minutes: i32,
+    index: usize,
+}
+
+impl<'a, V: Visit> VisitSegment<'a, V> {
+    /// The visit this segment belongs to.
+    pub fn visit(&self) -> &'a V {
+        self.visit
+    }
+
+    pub fn date(&self) -> i64 {
+        self.date
+    }
+
+    /// Position of this segment within the split, starting at 0.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl<V: Visit> Visit for VisitSegment<'_, V> {
+    type Id = V::Id;
+    type VisitorId = V::VisitorId;
+
+    fn id(&self) -> &Self::Id {
+        self.visit.id()
+    }
+
+    fn scheduled_date(&self) -> Option<i64> {
+        Some(self.date)
+    }
+
+    fn estimated_duration_minutes(&self) -> i32 {
+        self.duration_minutes
+    }
+
+    fn committed_window(&self) -> Option<(i32, i32)> {
+        None
+    }
+
+    fn target_time(&self) -> Option<i32> {
+        None
+    }
+
+    fn pin_type(&self) -> VisitPinType {
+        VisitPinType::VisitorAndDate
+    }
+
+    fn pinned_visitor(&self) -> Option<&Self::VisitorId> {
+        Some(&self.visitor_id)
+    }
+
+    fn pinned_date(&self) -> Option<i64> {
+        Some(self.date)
+    }
+
+    fn required_capabilities(&self) -> &[String] {
+        self.visit.required_capabilities()
+    }
+
+    fn location(&self) -> (f64, f64) {
+        self.visit.location()
+    }
+
+    fn location_ref(&self) -> LocationRef {
+        self.visit.location_ref()
+    }
+
+    fn must_assign(&self) -> bool {
+        self.visit.must_assign()
+    }
+}
+
+/// Splits a visit into same-visitor segments over `dates`, in order.
+///
+/// Each available date gets a segment as long as the visitor's longest window
+/// that day, less `travel_allowance_minutes` to reach the site and return.
+/// Dates where the visitor is unavailable or the allowance leaves no time are
+/// skipped, and the split stops once the visit's duration is covered.
+/// Returns `None` if `dates` can't cover the whole visit.
+pub fn split_across_days<'a, V, A>(
+    visit: &'a V,
+    visitor_id: &V::VisitorId,
+    dates: &[i64],
+    availability: &A,
+    travel_allowance_minutes: i32,
+) -> Option<Vec<VisitSegment<'a, V>>>
+where
+    V: Visit,
+    A: AvailabilityProvider<VisitorId = V::VisitorId>,
+{
+    let mut remaining = visit.estimated_duration_minutes();
+    let mut segments = Vec::new();
+
+    for &date in dates {
+        if remaining <= 0 {
+            break;
+        }
+        let Some(longest_secs) = availability
+            .availability_for(visitor_id, date)
+            .and_then(|windows| windows.iter().map(|(start, end)| end - start).max())
+        else {
+            continue;
+        };
+        let capacity = longest_secs / 60 - travel_allowance_minutes;
+        if capacity <= 0 {
+            continue;
+        }
+
+        let duration_minutes = remaining.min(capacity);
+        segments.push(VisitSegment {
+            visit,
+            visitor_id: visitor_id.clone(),
+            date,
+            duration_minutes,
+            index: segments.len(),
+        });
+        remaining -= duration_minutes;
+    }
+
+    (remaining <= 0).then_some(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::haversine::HaversineMatrix;
+    use crate::model::{AvailabilityData, VisitData, VisitorData};
+    use crate::solver::{solve, SolveOptions};
+    use crate::traits::UnassignedReason;
+
+    const DAY: i64 = 86_400;
+
+    #[test]
+    fn test_split_fills_each_day_and_skips_unavailable_dates() {
+        // 14 h install, 9 h days with a 1 h travel allowance -> 8 h + 6 h
+        let install = VisitData::new("install", (36.11, -115.17), 14 * 60);
+        let availability = AvailabilityData::new(vec![(8 * 3600, 17 * 3600)]).with_visitor_unavailable("bob");
+        let alice = "alice".to_string();
+
+        let segments = split_across_days(&install, &alice, &[DAY, 2 * DAY, 3 * DAY], &availability, 60).unwrap();
+
+        let parts: Vec<(i64, i32)> =
+            segments.iter().map(|seg| (seg.date(), seg.estimated_duration_minutes())).collect();
+        assert_eq!(parts, vec![(DAY, 8 * 60), (2 * DAY, 6 * 60)]);
+        assert!(segments.iter().all(|seg| seg.pinned_visitor() == Some(&alice) && seg.id() == "install"));
+
+        assert!(split_across_days(&install, &"bob".to_string(), &[DAY, 2 * DAY], &availability, 60).is_none());
+        assert!(split_across_days(&install, &alice, &[DAY], &availability, 60).is_none());
+    }
+
+    #[test]
+    fn test_segments_solve_on_their_own_days() {
+        let install = VisitData::new("install", (36.11, -115.17), 14 * 60);
+        let visitors = vec![
+            VisitorData::new("alice").with_start_location(36.10, -115.15),
+            VisitorData::new("bob").with_start_location(36.10, -115.15),
+        ];
+        let availability = AvailabilityData::new(vec![(8 * 3600, 17 * 3600)]);
+
+        let matrix = HaversineMatrix::default();
+
+        let whole = solve(DAY, std::slice::from_ref(&install), &visitors, &availability, &matrix, SolveOptions::default()).unwrap();
+        assert_eq!(whole.unassigned[0].reason, UnassignedReason::DurationExceedsWindow);
+
+        let segments = split_across_days(&install, &"bob".to_string(), &[DAY, 2 * DAY], &availability, 60).unwrap();
+        for segment in &segments {
+            let result = solve(
+                segment.date(),
+                std::slice::from_ref(segment),
+                &visitors,
+                &availability,
+                &matrix,
+                SolveOptions::default(),
+            ).unwrap();
+            assert!(result.unassigned.is_empty(), "segment {} unassigned", segment.index());
+            assert_eq!(result.routes.iter().find(|route| !route.visit_ids.is_empty()).unwrap().visitor_id, "bob");
+        }
+    }
+}
@@ -0,0 +1,47 @@
+//! Span attributes for OpenTelemetry export.
+//!
+//! Solver phases and routing requests run in `tracing` spans whose plain
+//! fields (counts, costs, cost deltas) work with any subscriber. Spans also
+//! declare the special fields `tracing-opentelemetry` maps onto OpenTelemetry
+//! spans, left empty unless the `otel` feature is enabled. Routing requests
+//! then follow the HTTP client semantic conventions.
+
+use tracing::Span;
+
+/// Marks a solver phase span as an internal operation.
+#[cfg_attr(not(feature = "otel"), allow(unused_variables))]
+pub(crate) fn internal(span: &Span) {
+    #[cfg(feature = "otel")]
+    span.record("otel.kind", "internal");
+}
+
+/// Marks a span as an outgoing HTTP GET to `path`.
+///
+/// Only the path is recorded: full request URLs carry customer coordinates.
+#[cfg(feature = "http")]
+#[cfg_attr(not(feature = "otel"), allow(unused_variables))]
+pub(crate) fn http_client(span: &Span, path: &str) {
+    #[cfg(feature = "otel")]
+    {
+        span.record("otel.kind", "client");
+        span.record("http.request.method", "GET");
+        span.record("url.path", path);
+    }
+}
+
+/// Records the HTTP status of a response, if one was received.
+#[cfg(feature = "http")]
+#[cfg_attr(not(feature = "otel"), allow(unused_variables))]
+pub(crate) fn http_status(span: &Span, status: Option<u16>) {
+    #[cfg(feature = "otel")]
+    if let Some(status) = status {
+        span.record("http.response.status_code", status);
+    }
+}
+
+/// Records whether the operation succeeded.
+#[cfg_attr(not(feature = "otel"), allow(unused_variables))]
+pub(crate) fn status(span: &Span, ok: bool) {
+    #[cfg(feature = "otel")]
+    span.record("otel.status_code", if ok { "OK" } else { "ERROR" });
+}
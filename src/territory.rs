@@ -0,0 +1,257 @@
+//! Designing balanced service territories.
+//!
+//! [`design_territories`] splits historical visit locations into one
+//! territory per visitor, balanced by workload. Territories are a planning
+//! aid rather than a hard rule: [`TerritoryPreference`] turns them into a
+//! [`VisitHistoryProvider`], so
+//! [`solve_with_history`](crate::solver::solve_with_history) prefers each
+//! territory's owner at the cost of
+//! [`continuity_weight`](crate::solver::SolveOptions::continuity_weight).
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cluster::{self, centroid, distance_sq, ClusterMethod};
+use crate::traits::{Id, LocationRef, Visit, VisitHistoryProvider};
+
+/// Rounds of reassigning locations to the nearest territory with room.
+const BALANCE_ITERATIONS: usize = 10;
+
+/// How far over an even share of the workload a territory may go.
+const BALANCE_TOLERANCE: f64 = 0.1;
+
+/// A proposed territory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Territory {
+    /// Mean of the territory's locations.
+    pub center: (f64, f64),
+    /// Convex hull of the territory's locations, counter-clockwise.
+    pub boundary: Vec<(f64, f64)>,
+    /// Historical locations in the territory.
+    pub locations: usize,
+    /// Those locations, as balancing assigned them.
+    #[serde(default)]
+    pub members: Vec<(f64, f64)>,
+    /// Sum of those locations' weights.
+    pub workload: f64,
+}
+
+/// Splits `locations` into `count` territories of similar workload.
+///
+/// `weights` give each location's workload (e.g., service hours per year);
+/// missing or non-positive weights count as 1. Starts from k-means and then
+/// moves locations to their nearest territory with room, so no territory
+/// takes much over an even share. Returns nothing if `count` is 0 or there are
+/// no locations.
+pub fn design_territories(locations: &[(f64, f64)], weights: &[f64], count: usize) -> Vec<Territory> {
+    if count == 0 || locations.is_empty() {
+        return Vec::new();
+    }
+    let weight = |i: usize| weights.get(i).copied().filter(|w| *w > 0.0).unwrap_or(1.0);
+    let total: f64 = (0..locations.len()).map(weight).sum();
+    let capacity = total / count as f64 * (1.0 + BALANCE_TOLERANCE);
+
+    let mut assignment = cluster::partition(ClusterMethod::KMeans, locations, weights, &vec![None; count]);
+    let mut centers = territory_centers(locations, &assignment, count, &[]);
+    for _ in 0..BALANCE_ITERATIONS {
+        // Locations with the most to lose from a second choice pick first
+        let ranked = |i: usize| {
+            let mut territories: Vec<usize> = (0..count).collect();
+            territories.sort_by(|&a, &b| {
+                distance_sq(locations[i], centers[a]).total_cmp(&distance_sq(locations[i], centers[b]))
+            });
+            territories
+        };
+        let choices: Vec<Vec<usize>> = (0..locations.len()).map(ranked).collect();
+        let regret = |i: usize| match choices[i][..] {
+            [first, second, ..] => distance_sq(locations[i], centers[second]) - distance_sq(locations[i], centers[first]),
+            _ => 0.0,
+        };
+        let mut order: Vec<usize> = (0..locations.len()).collect();
+        order.sort_by(|&a, &b| regret(b).total_cmp(&regret(a)));
+
+        let mut loads = vec![0.0; count];
+        let mut balanced = vec![0; locations.len()];
+        for i in order {
+            let territory = choices[i]
+                .iter()
+                .copied()
+                .find(|&t| loads[t] + weight(i) <= capacity)
+                .unwrap_or_else(|| (0..count).min_by(|&a, &b| loads[a].total_cmp(&loads[b])).unwrap_or(0));
+            loads[territory] += weight(i);
+            balanced[i] = territory;
+        }
+
+        let settled = balanced == assignment;
+        assignment = balanced;
+        centers = territory_centers(locations, &assignment, count, &centers);
+        if settled {
+            break;
+        }
+    }
+
+    (0..count)
+        .map(|t| {
+            let members: Vec<usize> = (0..locations.len()).filter(|&i| assignment[i] == t).collect();
+            let points: Vec<(f64, f64)> = members.iter().map(|&i| locations[i]).collect();
+            Territory {
+                center: centers[t],
+                boundary: convex_hull(&points),
+                locations: members.len(),
+                workload: members.iter().map(|&i| weight(i)).sum(),
+                members: points,
+            }
+        })
+        .collect()
+}
+
+/// The territory `location` was assigned to, or for a location that wasn't
+/// among those designed from, the territory whose center is nearest.
+pub fn territory_of(territories: &[Territory], location: (f64, f64)) -> Option<usize> {
+    territories.iter().position(|territory| territory.members.contains(&location)).or_else(|| {
+        (0..territories.len()).min_by(|&a, &b| {
+            distance_sq(location, territories[a].center).total_cmp(&distance_sq(location, territories[b].center))
+        })
+    })
+}
+
+/// Each territory's owner as the preferred visitor for visits inside it.
+///
+/// Implements [`VisitHistoryProvider`] by reporting one visit from the owner
+/// of each location's territory (see [`territory_of`]), and none from
+/// anyone else.
+#[derive(Debug, Clone)]
+pub struct TerritoryPreference<VisitorId> {
+    owners: HashMap<LocationRef, VisitorId>,
+}
+
+impl<VisitorId: Id> TerritoryPreference<VisitorId> {
+    /// `owners[i]` owns `territories[i]`; territories without an owner are
+    /// left to the solver.
+    pub fn new<V>(territories: &[Territory], owners: &[VisitorId], visits: &[V]) -> Self
+    where
+        V: Visit<VisitorId = VisitorId>,
+    {
+        let owners = visits
+            .iter()
+            .filter_map(|visit| {
+                let owner = owners.get(territory_of(territories, visit.location())?)?;
+                Some((visit.location_ref(), owner.clone()))
+            })
+            .collect();
+        Self { owners }
+    }
+
+    /// The visitor who owns `location`, if it was among the visits given.
+    pub fn owner(&self, location: LocationRef) -> Option<&VisitorId> {
+        self.owners.get(&location)
+    }
+}
+
+impl<VisitorId: Id> VisitHistoryProvider for TerritoryPreference<VisitorId> {
+    type VisitorId = VisitorId;
+
+    fn visit_count(&self, visitor_id: &VisitorId, location: LocationRef) -> u32 {
+        u32::from(self.owner(location) == Some(visitor_id))
+    }
+}
+
+/// Centroid of each territory's locations, keeping `previous` (or the
+/// origin) for a territory left empty.
+fn territory_centers(
+    locations: &[(f64, f64)],
+    assignment: &[usize],
+    count: usize,
+    previous: &[(f64, f64)],
+) -> Vec<(f64, f64)> {
+    (0..count)
+        .map(|t| {
+            let members: Vec<(f64, f64)> =
+                locations.iter().zip(assignment).filter(|(_, a)| **a == t).map(|(p, _)| *p).collect();
+            centroid(&members).or_else(|| previous.get(t).copied()).unwrap_or((0.0, 0.0))
+        })
+        .collect()
+}
+
+/// Convex hull by Andrew's monotone chain, treating (lat, lng) as (y, x).
+fn convex_hull(points: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.total_cmp(&b.0)));
+    sorted.dedup();
+    if sorted.len() < 3 {
+        return sorted;
+    }
+
+    let cross = |o: (f64, f64), a: (f64, f64), b: (f64, f64)| (a.1 - o.1) * (b.0 - o.0) - (a.0 - o.0) * (b.1 - o.1);
+    let mut hull: Vec<(f64, f64)> = Vec::with_capacity(sorted.len() * 2);
+    for pass in [sorted.clone(), sorted.into_iter().rev().collect()] {
+        let floor = hull.len();
+        for point in pass {
+            while hull.len() >= floor + 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], point) <= 0.0 {
+                hull.pop();
+            }
+            hull.push(point);
+        }
+        hull.pop();
+    }
+    hull
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::VisitData;
+
+    #[test]
+    fn test_territories_follow_geographic_groups() {
+        let locations = [(0.0, 0.0), (0.1, 0.1), (0.0, 0.2), (5.0, 5.0), (5.1, 5.1), (5.0, 5.2)];
+
+        let territories = design_territories(&locations, &[], 2);
+
+        assert_eq!(territories.len(), 2);
+        assert!(territories.iter().all(|territory| territory.locations == 3));
+        assert!(territories.iter().all(|territory| territory.boundary.len() == 3));
+        let west = territory_of(&territories, (0.05, 0.05)).unwrap();
+        assert_ne!(west, territory_of(&territories, (5.05, 5.05)).unwrap());
+    }
+
+    #[test]
+    fn test_territories_balance_workload() {
+        // A dense town and a few outlying sites; plain k-means gives the town
+        // one territory and the outliers the other
+        let mut locations: Vec<(f64, f64)> = (0..10).map(|i| (0.0, f64::from(i) * 0.01)).collect();
+        locations.extend([(10.0, 0.0), (10.0, 0.1)]);
+
+        let territories = design_territories(&locations, &[], 2);
+
+        assert!(territories.iter().all(|territory| territory.workload <= 6.6), "{territories:?}");
+        // Town locations balanced into the outliers' territory stay there
+        for (t, territory) in territories.iter().enumerate() {
+            assert!(territory.members.iter().all(|&location| territory_of(&territories, location) == Some(t)));
+        }
+    }
+
+    #[test]
+    fn test_preference_reports_owner_only() {
+        let territories = design_territories(&[(0.0, 0.0), (5.0, 5.0)], &[], 2);
+        let owners: Vec<String> = (0..2).map(|t| format!("tech-{t}")).collect();
+        let visits = vec![VisitData::new("near-origin", (0.1, 0.0), 30)];
+        let owner = &owners[territory_of(&territories, (0.1, 0.0)).unwrap()];
+
+        let preference = TerritoryPreference::new(&territories, &owners, &visits);
+
+        let location = visits[0].location_ref();
+        assert_eq!(preference.owner(location), Some(owner));
+        for visitor in &owners {
+            assert_eq!(preference.visit_count(visitor, location), u32::from(visitor == owner));
+        }
+    }
+
+    #[test]
+    fn test_convex_hull_drops_interior_points() {
+        let hull = convex_hull(&[(0.0, 0.0), (0.0, 2.0), (2.0, 2.0), (2.0, 0.0), (1.0, 1.0)]);
+        assert_eq!(hull.len(), 4);
+        assert!(!hull.contains(&(1.0, 1.0)));
+    }
+}
@@ -0,0 +1,256 @@
+//! Time zones and daylight saving for planning days.
+//!
+//! The solver measures every time of day as seconds elapsed since the
+//! service date's local midnight, so travel and service add up even on the
+//! 23- and 25-hour days when clocks change. [`PlanningDay`] converts between
+//! those seconds, wall-clock times ("9am"), and unix instants for a date in a
+//! [`TimeZone`].
+
+use serde::{Deserialize, Serialize};
+
+use crate::traits::TimeWindow;
+
+/// Seconds per day; dates are unix timestamps at midnight.
+const SECS_PER_DAY: i64 = 86_400;
+
+/// A UTC offset with an optional yearly daylight-saving rule.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeZone {
+    /// Offset from UTC outside daylight saving, in seconds (east positive).
+    pub standard_offset_secs: i32,
+    #[serde(default)]
+    pub dst: Option<DstRule>,
+}
+
+/// When daylight saving starts and ends each year.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DstRule {
+    pub start: DstTransition,
+    pub end: DstTransition,
+    /// Seconds clocks move forward, usually 3600.
+    pub save_secs: i32,
+}
+
+/// A clock change on the `week`th `weekday` of `month`, at `time_secs` on
+/// the wall clock in effect just before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DstTransition {
+    /// 1 = January.
+    pub month: u8,
+    /// 1 to 4, or 5 for the last such weekday of the month.
+    pub week: u8,
+    /// 0 = Sunday.
+    pub weekday: u8,
+    pub time_secs: i32,
+}
+
+impl DstTransition {
+    /// Unix instant of the transition in `year`, with `offset_secs` the UTC
+    /// offset in effect before it.
+    fn instant(&self, year: i64, offset_secs: i32) -> i64 {
+        let month = i64::from(self.month.clamp(1, 12));
+        let weekday = i64::from(self.weekday % 7);
+        let day_of_week = |days: i64| (days + 4).rem_euclid(7);
+        let day = if self.week >= 5 {
+            let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+            let last = days_from_civil(next_year, next_month, 1) - 1;
+            last - (day_of_week(last) - weekday).rem_euclid(7)
+        } else {
+            let first = days_from_civil(year, month, 1);
+            first + (weekday - day_of_week(first)).rem_euclid(7) + 7 * i64::from(self.week.max(1) - 1)
+        };
+        day * SECS_PER_DAY + i64::from(self.time_secs) - i64::from(offset_secs)
+    }
+}
+
+impl TimeZone {
+    pub fn utc() -> Self {
+        Self::default()
+    }
+
+    /// A fixed offset with no daylight saving (e.g., Arizona at -7h).
+    pub fn fixed(offset_secs: i32) -> Self {
+        Self { standard_offset_secs: offset_secs, dst: None }
+    }
+
+    /// US daylight saving: an hour forward from 2am on the second Sunday in
+    /// March to 2am on the first Sunday in November.
+    pub fn us(standard_offset_secs: i32) -> Self {
+        Self::fixed(standard_offset_secs).with_dst(DstRule {
+            start: DstTransition { month: 3, week: 2, weekday: 0, time_secs: 2 * 3600 },
+            end: DstTransition { month: 11, week: 1, weekday: 0, time_secs: 2 * 3600 },
+            save_secs: 3600,
+        })
+    }
+
+    /// EU summer time: an hour forward from 01:00 UTC on the last Sunday in
+    /// March to 01:00 UTC on the last Sunday in October.
+    pub fn eu(standard_offset_secs: i32) -> Self {
+        Self::fixed(standard_offset_secs).with_dst(DstRule {
+            start: DstTransition { month: 3, week: 5, weekday: 0, time_secs: 3600 + standard_offset_secs },
+            end: DstTransition { month: 10, week: 5, weekday: 0, time_secs: 2 * 3600 + standard_offset_secs },
+            save_secs: 3600,
+        })
+    }
+
+    pub fn with_dst(mut self, dst: DstRule) -> Self {
+        self.dst = Some(dst);
+        self
+    }
+
+    /// UTC offset in effect at a unix instant.
+    pub fn offset_at(&self, instant: i64) -> i32 {
+        let Some(dst) = &self.dst else {
+            return self.standard_offset_secs;
+        };
+        let (year, _, _) = civil_from_days((instant + i64::from(self.standard_offset_secs)).div_euclid(SECS_PER_DAY));
+        let start = dst.start.instant(year, self.standard_offset_secs);
+        let end = dst.end.instant(year, self.standard_offset_secs + dst.save_secs);
+        // Southern-hemisphere rules start late in the year and end early
+        let in_dst = if start < end { (start..end).contains(&instant) } else { instant >= start || instant < end };
+        if in_dst { self.standard_offset_secs + dst.save_secs } else { self.standard_offset_secs }
+    }
+
+    /// Unix instant of a local wall-clock time (seconds since 1970-01-01
+    /// local). A time repeated when clocks go back is its first occurrence;
+    /// one skipped when they go forward is read on the old offset, landing
+    /// after the change.
+    pub fn instant_of_local(&self, local: i64) -> i64 {
+        let standard = local - i64::from(self.standard_offset_secs);
+        let daylight = standard - self.dst.map_or(0, |dst| i64::from(dst.save_secs));
+        [daylight, standard]
+            .into_iter()
+            .find(|instant| local - instant == i64::from(self.offset_at(*instant)))
+            .unwrap_or(standard)
+    }
+}
+
+/// A service date in a time zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanningDay {
+    /// Service date (unix timestamp, date only), as passed to the solver.
+    pub date: i64,
+    pub time_zone: TimeZone,
+}
+
+impl PlanningDay {
+    pub fn new(date: i64, time_zone: TimeZone) -> Self {
+        Self { date, time_zone }
+    }
+
+    /// Unix instant of the day's local midnight.
+    pub fn midnight(&self) -> i64 {
+        self.time_zone.instant_of_local(self.date)
+    }
+
+    /// Seconds from this midnight to the next: 82,800 or 90,000 on days the
+    /// clocks change.
+    pub fn length_secs(&self) -> i32 {
+        (Self::new(self.date + SECS_PER_DAY, self.time_zone).midnight() - self.midnight()) as i32
+    }
+
+    /// Unix instant `secs` after local midnight.
+    pub fn instant(&self, secs: i32) -> i64 {
+        self.midnight() + i64::from(secs)
+    }
+
+    /// Seconds after local midnight of a unix instant.
+    pub fn secs_at(&self, instant: i64) -> i32 {
+        (instant - self.midnight()) as i32
+    }
+
+    /// Seconds after local midnight at which the wall clock reads `wall_secs`.
+    pub fn from_wall_clock(&self, wall_secs: i32) -> i32 {
+        self.secs_at(self.time_zone.instant_of_local(self.date + i64::from(wall_secs)))
+    }
+
+    /// What the wall clock reads `secs` after local midnight.
+    pub fn to_wall_clock(&self, secs: i32) -> i32 {
+        let instant = self.instant(secs);
+        (instant + i64::from(self.time_zone.offset_at(instant)) - self.date) as i32
+    }
+
+    /// [`from_wall_clock`](Self::from_wall_clock) for both ends of a window.
+    pub fn window_from_wall_clock(&self, (start, end): TimeWindow) -> TimeWindow {
+        (self.from_wall_clock(start), self.from_wall_clock(end))
+    }
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+pub(crate) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Proleptic Gregorian (year, month, day) of days since 1970-01-01.
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: i32 = 3600;
+
+    fn date(year: i64, month: i64, day: i64) -> i64 {
+        days_from_civil(year, month, day) * SECS_PER_DAY
+    }
+
+    #[test]
+    fn test_us_spring_forward_day_has_23_hours() {
+        let day = PlanningDay::new(date(2024, 3, 10), TimeZone::us(-8 * HOUR));
+
+        assert_eq!(day.midnight(), date(2024, 3, 10) + 8 * 3600);
+        assert_eq!(day.length_secs(), 23 * HOUR);
+        assert_eq!(day.from_wall_clock(9 * HOUR), 8 * HOUR);
+        assert_eq!(day.to_wall_clock(8 * HOUR), 9 * HOUR);
+        // 2:30 doesn't exist; it reads as 3:30
+        assert_eq!(day.from_wall_clock(2 * HOUR + 1800), 2 * HOUR + 1800);
+        assert_eq!(day.to_wall_clock(2 * HOUR + 1800), 3 * HOUR + 1800);
+    }
+
+    #[test]
+    fn test_us_fall_back_day_has_25_hours() {
+        let day = PlanningDay::new(date(2024, 11, 3), TimeZone::us(-8 * HOUR));
+
+        assert_eq!(day.length_secs(), 25 * HOUR);
+        assert_eq!(day.from_wall_clock(9 * HOUR), 10 * HOUR);
+        // 1:30 happens twice; the first is meant
+        assert_eq!(day.from_wall_clock(HOUR + 1800), HOUR + 1800);
+        assert_eq!(day.to_wall_clock(2 * HOUR + 1800), HOUR + 1800);
+    }
+
+    #[test]
+    fn test_fixed_offset_days_are_24_hours() {
+        let arizona = TimeZone::fixed(-7 * HOUR);
+        for day in [date(2024, 3, 10), date(2024, 11, 3)] {
+            let day = PlanningDay::new(day, arizona);
+            assert_eq!(day.length_secs(), 24 * HOUR);
+            assert_eq!(day.from_wall_clock(9 * HOUR), 9 * HOUR);
+            assert_eq!(day.instant(9 * HOUR), day.date + 16 * 3600);
+        }
+    }
+
+    #[test]
+    fn test_eu_transitions_on_last_sundays() {
+        let berlin = TimeZone::eu(HOUR);
+        assert_eq!(PlanningDay::new(date(2024, 3, 31), berlin).length_secs(), 23 * HOUR);
+        assert_eq!(PlanningDay::new(date(2024, 10, 27), berlin).length_secs(), 25 * HOUR);
+        assert_eq!(berlin.offset_at(date(2024, 7, 1)), 2 * HOUR);
+        assert_eq!(berlin.offset_at(date(2024, 1, 1)), HOUR);
+    }
+}
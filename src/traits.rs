@@ -3,12 +3,19 @@
 //! These are intentionally minimal and domain-agnostic. Concrete apps should
 //! implement them for their own data models.
 
+use std::collections::HashMap;
+use std::future::Future;
+use std::fmt::Debug;
 use std::hash::Hash;
 
+use serde::{Deserialize, Serialize};
+
+use crate::recurrence::Recurrence;
+
 /// Unique identifier for planner entities.
-pub trait Id: Clone + Eq + Hash {}
+pub trait Id: Clone + Eq + Hash + Debug {}
 
-impl<T> Id for T where T: Clone + Eq + Hash {}
+impl<T> Id for T where T: Clone + Eq + Hash + Debug {}
 
 /// A visit is a single service occurrence to be routed.
 pub trait Visit {
@@ -23,12 +30,56 @@ pub trait Visit {
     /// Estimated service duration in minutes.
     fn estimated_duration_minutes(&self) -> i32;
 
+    /// Minutes held free after the visit before travelling on, for job types
+    /// that historically overrun. The visit's estimated window is unchanged.
+    fn duration_buffer_minutes(&self) -> i32 {
+        0
+    }
+
+    /// Standard deviation of the actual duration in minutes, used to report
+    /// each route's [`window_miss_risk`](crate::solver::RouteResult::window_miss_risk).
+    fn duration_std_dev_minutes(&self) -> f64 {
+        0.0
+    }
+
     /// Committed window start/end (seconds from midnight).
     fn committed_window(&self) -> Option<(i32, i32)>;
 
+    /// How the committed window is read, overriding
+    /// [`SolveOptions::committed_window_semantics`](crate::solver::SolveOptions::committed_window_semantics).
+    fn committed_window_semantics(&self) -> Option<WindowSemantics> {
+        None
+    }
+
+    /// Earliest the visit may start (seconds from midnight), e.g. once a part
+    /// arrives. Enforced with or without a committed window.
+    fn not_before(&self) -> Option<i32> {
+        None
+    }
+
+    /// Hard deadline for finishing the visit (seconds from midnight), e.g. a
+    /// 4-hour SLA. Enforced with or without a committed window.
+    fn latest_completion(&self) -> Option<i32> {
+        None
+    }
+
     /// Target time preference (seconds from midnight).
     fn target_time(&self) -> Option<i32>;
 
+    /// Penalty per second of deviation from the target time, overriding
+    /// [`SolveOptions::target_time_weight`](crate::solver::SolveOptions::target_time_weight).
+    ///
+    /// Lets "exactly 2pm" outweigh "around 2pm" in the same plan.
+    fn target_time_weight(&self) -> Option<i32> {
+        None
+    }
+
+    /// Seconds either side of the target time that carry no penalty; only
+    /// deviation beyond the band is weighted.
+    fn target_time_tolerance(&self) -> i32 {
+        0
+    }
+
     /// Pin type for routing constraints.
     fn pin_type(&self) -> VisitPinType;
 
@@ -45,12 +96,96 @@ pub trait Visit {
     /// Location coordinates (lat, lng).
     fn location(&self) -> (f64, f64);
 
+    /// Identity of this visit's location for matrix lookups.
+    ///
+    /// Defaults to the coordinates quantized to 1e-6 degrees. Return a
+    /// [`LocationRef::Id`] to share one matrix row across visits at the same
+    /// site regardless of geocoding noise.
+    fn location_ref(&self) -> LocationRef {
+        LocationRef::from_coordinates(self.location())
+    }
+
     /// Current visitor assignment (for stability penalty).
     /// If the visit is currently assigned to a visitor, reassigning it
     /// to a different visitor incurs a soft penalty.
     fn current_visitor_id(&self) -> Option<&Self::VisitorId> {
         None
     }
+
+    /// Penalty for moving this visit off its current visitor, overriding
+    /// [`SolveOptions::reassignment_penalty`](crate::solver::SolveOptions::reassignment_penalty).
+    ///
+    /// Lets an already-notified customer stay put while internal tasks move freely.
+    fn reassignment_penalty(&self) -> Option<i32> {
+        None
+    }
+
+    /// Place in its pinned visitor's locked route prefix (e.g., already
+    /// dispatched). Locked visits run first in ascending order and only the
+    /// rest of the route is optimized. Ignored unless pinned to a visitor.
+    fn locked_sequence(&self) -> Option<u32> {
+        None
+    }
+
+    /// Slot the visit must take on whichever route serves it, e.g.
+    /// [`RoutePosition::First`] for a keyholder who has to open the site.
+    /// Combine with [`latest_completion`](Visit::latest_completion) for
+    /// "last stop before 5pm".
+    fn route_position(&self) -> Option<RoutePosition> {
+        None
+    }
+
+    /// Group of visits one visitor must serve together, e.g. several units in
+    /// one building. The first member placed decides the visitor; members
+    /// that don't fit on that route are left unassigned. Travel cost keeps
+    /// members at one site back to back.
+    fn same_visitor_group(&self) -> Option<&str> {
+        None
+    }
+
+    /// Group of visits that must each go to a different visitor, e.g.
+    /// inspections that need independent parties.
+    fn different_visitor_group(&self) -> Option<&str> {
+        None
+    }
+
+    /// Another visit this one must start a bounded time after, e.g. a second
+    /// coat once the first has dried. Holds across routes; ignored while the
+    /// other visit is unassigned.
+    fn start_gap(&self) -> Option<&VisitGap<Self::Id>> {
+        None
+    }
+
+    /// Whether the plan is infeasible without this visit.
+    ///
+    /// Mandatory visits are placed first, and the solver ejects optional
+    /// visits to make room for them before giving up.
+    fn must_assign(&self) -> bool {
+        false
+    }
+
+    /// Series this visit is an occurrence of, if it recurs.
+    ///
+    /// The visit is only routed on dates the series allows; on other dates
+    /// it is reported as [`UnassignedReason::WrongDate`]. Within the
+    /// tolerance, drift from the nominal date is penalized per day.
+    fn recurrence(&self) -> Option<&Recurrence> {
+        None
+    }
+
+    /// Earliest and latest dates (inclusive) this visit may be routed on.
+    ///
+    /// Lets an unpinned visit float within bounds instead of being pinned to
+    /// one date. Outside them it is reported as [`UnassignedReason::WrongDate`].
+    fn date_window(&self) -> Option<(i64, i64)> {
+        None
+    }
+
+    /// The only dates this visit may be routed on (e.g., every Monday and
+    /// Thursday in the horizon). Other dates get [`UnassignedReason::WrongDate`].
+    fn allowed_dates(&self) -> Option<&[i64]> {
+        None
+    }
 }
 
 /// The worker/vehicle that performs visits.
@@ -65,8 +200,74 @@ pub trait Visitor {
     /// End location (lat, lng). If None, solver may assume end = start.
     fn end_location(&self) -> Option<(f64, f64)>;
 
+    /// Identity of the start location for matrix lookups (see [`Visit::location_ref`]).
+    fn start_location_ref(&self) -> Option<LocationRef> {
+        self.start_location().map(LocationRef::from_coordinates)
+    }
+
+    /// Identity of the end location for matrix lookups (see [`Visit::location_ref`]).
+    fn end_location_ref(&self) -> Option<LocationRef> {
+        self.end_location().map(LocationRef::from_coordinates)
+    }
+
     /// Capability identifiers for this visitor.
     fn capabilities(&self) -> &[String];
+
+    /// Cost per hour of working time (travel and service), in currency units.
+    ///
+    /// Only used when solving with [`Objective::Cost`](crate::solver::Objective::Cost).
+    fn hourly_cost(&self) -> f64 {
+        0.0
+    }
+
+    /// Cost per kilometer driven, in currency units.
+    ///
+    /// Only used with [`Objective::Cost`](crate::solver::Objective::Cost), and
+    /// only when the matrix provider returns distances.
+    fn per_km_cost(&self) -> f64 {
+        0.0
+    }
+
+    /// Most visits this visitor may take in a day, overriding
+    /// [`SolveOptions::max_visits_per_route`](crate::solver::SolveOptions::max_visits_per_route).
+    fn max_visits(&self) -> Option<usize> {
+        None
+    }
+
+    /// Earliest and latest arrival at the end location (seconds from
+    /// midnight), e.g. the van must be back by 17:30. Arriving early waits for
+    /// the earliest time. Without an end location, the latest time bounds the
+    /// end of the last visit.
+    fn return_window(&self) -> Option<TimeWindow> {
+        None
+    }
+
+    /// Routing profile the visitor travels with, e.g. `bicycle` in a fleet
+    /// of cars and bikes. Routes are scheduled against the provider's
+    /// [`matrices_for_profile`](DistanceMatrixProvider::matrices_for_profile);
+    /// `None` uses its default matrix.
+    fn routing_profile(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// Identity of a location, used to share distance matrix rows.
+///
+/// Two references that compare equal are treated as the same place and get a
+/// single matrix row, even if their coordinates differ slightly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LocationRef {
+    /// A caller-assigned location ID (e.g., a customer site or depot).
+    Id(u64),
+    /// Coordinates quantized to 1e-6 degrees (~11 cm).
+    Quantized(i64, i64),
+}
+
+impl LocationRef {
+    /// Quantize (lat, lng) coordinates to 1e-6 degrees.
+    pub fn from_coordinates(coord: (f64, f64)) -> Self {
+        LocationRef::Quantized((coord.0 * 1_000_000.0).round() as i64, (coord.1 * 1_000_000.0).round() as i64)
+    }
 }
 
 /// A route plan is a container for a visitor on a specific date.
@@ -84,28 +285,227 @@ pub type TimeWindow = (i32, i32);
 
 /// Provides availability windows for a visitor on a given date.
 ///
-/// Returns multiple windows to support breaks (e.g., lunch breaks).
-/// Windows should be non-overlapping and sorted by start time.
+/// Returns multiple windows to support split shifts, breaks (e.g., lunch),
+/// and appointments blocked out of the day. A visit must be served entirely
+/// inside one window; travel between visits may happen during a gap, and a
+/// visitor arriving in a gap waits for the next window to open.
 pub trait AvailabilityProvider {
     type VisitorId: Id;
 
     /// Returns availability windows for a visitor on a given date.
     ///
-    /// Returns `None` if the visitor is completely unavailable.
-    /// Returns `Some(vec![])` should not happen - use `None` instead.
+    /// Returns `None` if the visitor is completely unavailable. Windows
+    /// should be sorted and non-overlapping; the solver sorts and merges them
+    /// defensively and treats `Some(vec![])` the same as `None`.
     /// Example: `Some(vec![(8*3600, 12*3600), (13*3600, 17*3600)])` for 8am-12pm and 1pm-5pm.
     fn availability_for(&self, visitor_id: &Self::VisitorId, date: i64) -> Option<Vec<TimeWindow>>;
 }
 
+/// Past service counts, used to keep customers with their regular visitor.
+pub trait VisitHistoryProvider {
+    type VisitorId: Id;
+
+    /// How many times the visitor has served the location.
+    fn visit_count(&self, visitor_id: &Self::VisitorId, location: LocationRef) -> u32;
+}
+
+/// Travel time marking a pair with no route between them (one week).
+///
+/// Large enough to be infeasible against any day's availability, small
+/// enough not to overflow when summed along a route. The solver never
+/// schedules a leg this long.
+pub const UNREACHABLE_SECS: i32 = 7 * 24 * 3600;
+
+/// Distance marking a pair with no route between them, in meters.
+pub const UNREACHABLE_METERS: i32 = 10_000_000;
+
+/// Travel durations and, optionally, distances between a set of locations.
+///
+/// Providers fill pairs they can't route with [`UNREACHABLE_SECS`] and
+/// [`UNREACHABLE_METERS`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TravelMatrices {
+    /// Travel time in seconds.
+    pub durations: Vec<Vec<i32>>,
+    /// Travel distance in meters, if the provider knows it.
+    pub distances: Option<Vec<Vec<i32>>>,
+    /// Distance in meters from each location to the nearest road, if the
+    /// provider snaps locations onto a road network.
+    #[serde(default)]
+    pub snap_distances: Option<Vec<i32>>,
+}
+
+impl TravelMatrices {
+    /// Matrices with durations only.
+    pub fn from_durations(durations: Vec<Vec<i32>>) -> Self {
+        Self {
+            durations,
+            distances: None,
+            snap_distances: None,
+        }
+    }
+}
+
 /// Provides a distance/time matrix for a set of locations.
 ///
 /// The matrix is indexed by the provided location order.
 pub trait DistanceMatrixProvider {
     fn matrix_for(&self, locations: &[(f64, f64)]) -> Vec<Vec<i32>>;
+
+    /// Durations plus distances, for providers that can supply both.
+    ///
+    /// Defaults to [`matrix_for`](Self::matrix_for) with no distances.
+    fn matrices_for(&self, locations: &[(f64, f64)]) -> TravelMatrices {
+        TravelMatrices::from_durations(self.matrix_for(locations))
+    }
+
+    /// Durations, and distances if known, from each of `sources` to each of
+    /// `destinations`, both indices into `locations`; empty durations on
+    /// failure.
+    ///
+    /// Defaults to [`matrices_for`](Self::matrices_for) over just the
+    /// locations involved. Override for backends that can route a subset
+    /// directly.
+    fn table_for(&self, locations: &[(f64, f64)], sources: &[usize], destinations: &[usize]) -> TravelMatrices {
+        let subset = TableSubset::new(locations, sources, destinations);
+        subset.slice(self.matrices_for(&subset.locations))
+    }
+
+    /// Durations and distances for a named routing profile (see
+    /// [`Visitor::routing_profile`]).
+    ///
+    /// Defaults to [`matrices_for`](Self::matrices_for), ignoring the profile.
+    /// Override for backends that route more than one mode of travel.
+    fn matrices_for_profile(&self, _profile: &str, locations: &[(f64, f64)]) -> TravelMatrices {
+        self.matrices_for(locations)
+    }
+
+    /// Bytes sent and received by matrix requests so far, for network
+    /// providers that count them.
+    fn bytes_transferred(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// Async counterpart of [`AvailabilityProvider`] for availability backed by
+/// non-blocking I/O (e.g., a database or HTTP calendar service).
+pub trait AsyncAvailabilityProvider {
+    type VisitorId: Id;
+
+    /// Fetches availability windows for a visitor on a given date.
+    ///
+    /// Same semantics as [`AvailabilityProvider::availability_for`].
+    fn fetch_availability(
+        &self,
+        visitor_id: &Self::VisitorId,
+        date: i64,
+    ) -> impl Future<Output = Option<Vec<TimeWindow>>> + Send;
+}
+
+/// Async counterpart of [`DistanceMatrixProvider`] for providers backed by
+/// non-blocking I/O.
+pub trait AsyncDistanceMatrixProvider {
+    /// Fetches the matrix for the given locations, indexed in the provided order.
+    fn fetch_matrix(&self, locations: &[(f64, f64)]) -> impl Future<Output = Vec<Vec<i32>>> + Send;
+
+    /// Fetches durations plus distances; see [`DistanceMatrixProvider::matrices_for`].
+    fn fetch_matrices(&self, locations: &[(f64, f64)]) -> impl Future<Output = TravelMatrices> + Send {
+        let durations = self.fetch_matrix(locations);
+        async move { TravelMatrices::from_durations(durations.await) }
+    }
+
+    /// Fetches durations and distances from `sources` to `destinations`;
+    /// see [`DistanceMatrixProvider::table_for`].
+    fn fetch_table(
+        &self,
+        locations: &[(f64, f64)],
+        sources: &[usize],
+        destinations: &[usize],
+    ) -> impl Future<Output = TravelMatrices> + Send
+    where
+        Self: Sync,
+    {
+        let subset = TableSubset::new(locations, sources, destinations);
+        async move { subset.slice(self.fetch_matrices(&subset.locations).await) }
+    }
+
+    /// Fetches matrices for a routing profile; see
+    /// [`DistanceMatrixProvider::matrices_for_profile`].
+    fn fetch_matrices_for_profile(
+        &self,
+        _profile: &str,
+        locations: &[(f64, f64)],
+    ) -> impl Future<Output = TravelMatrices> + Send {
+        self.fetch_matrices(locations)
+    }
+
+    /// See [`DistanceMatrixProvider::bytes_transferred`].
+    fn bytes_transferred(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// The locations a source-to-destination table refers to, each once.
+pub(crate) struct TableSubset {
+    pub locations: Vec<(f64, f64)>,
+    /// Position of each source in `locations`.
+    pub sources: Vec<usize>,
+    /// Position of each destination in `locations`.
+    pub destinations: Vec<usize>,
+}
+
+impl TableSubset {
+    pub fn new(locations: &[(f64, f64)], sources: &[usize], destinations: &[usize]) -> Self {
+        let mut positions = HashMap::new();
+        let mut subset = Vec::new();
+        let mut position = |index: usize| {
+            *positions.entry(index).or_insert_with(|| {
+                subset.push(locations[index]);
+                subset.len() - 1
+            })
+        };
+        let sources = sources.iter().map(|&index| position(index)).collect();
+        let destinations = destinations.iter().map(|&index| position(index)).collect();
+        Self { locations: subset, sources, destinations }
+    }
+
+    /// The source rows and destination columns of matrices over
+    /// `locations`, without snap distances.
+    pub fn slice(&self, matrices: TravelMatrices) -> TravelMatrices {
+        TravelMatrices {
+            durations: self.slice_cells(&matrices.durations),
+            distances: matrices.distances.map(|distances| self.slice_cells(&distances)).filter(|cells| !cells.is_empty()),
+            snap_distances: None,
+        }
+    }
+
+    /// The source rows and destination columns of a matrix over
+    /// `locations`; empty if the matrix is incomplete.
+    fn slice_cells(&self, matrix: &[Vec<i32>]) -> Vec<Vec<i32>> {
+        let n = self.locations.len();
+        if matrix.len() != n || matrix.iter().any(|row| row.len() != n) {
+            return Vec::new();
+        }
+        self.sources
+            .iter()
+            .map(|&source| self.destinations.iter().map(|&destination| matrix[source][destination]).collect())
+            .collect()
+    }
+}
+
+/// How long after another visit ends a visit must start.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VisitGap<Id> {
+    /// The visit that goes first.
+    pub after: Id,
+    /// Fewest seconds from its end to this visit's start.
+    pub min_secs: i32,
+    /// Most seconds from its end to this visit's start.
+    pub max_secs: i32,
 }
 
 /// Pin type for routing constraints.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum VisitPinType {
     None,
     Visitor,
@@ -113,15 +513,109 @@ pub enum VisitPinType {
     VisitorAndDate,
 }
 
+/// Fixed slot for a visit on its route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RoutePosition {
+    /// The first stop, after only the visitor's locked visits.
+    First,
+    /// The last stop before the visitor heads to their end location.
+    Last,
+}
+
+/// What a committed window promises.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WindowSemantics {
+    /// The whole visit runs inside the window.
+    #[default]
+    Completion,
+    /// The visit starts inside the window and may run past its end
+    /// ("we arrive between 10 and 12").
+    Arrival,
+}
+
+impl WindowSemantics {
+    /// The span the whole visit must fit in under these semantics.
+    pub fn visit_bounds(self, (start, end): (i32, i32), duration_secs: i32) -> (i32, i32) {
+        match self {
+            WindowSemantics::Completion => (start, end),
+            WindowSemantics::Arrival => (start, end + duration_secs),
+        }
+    }
+}
+
+/// The span a visit must run within: its committed window read with its own
+/// semantics (or `default`), narrowed to its [`not_before`](Visit::not_before)
+/// and [`latest_completion`](Visit::latest_completion). Without a deadline or
+/// a committed window the span runs to `i32::MAX`. `None` if it has none of
+/// the three.
+pub fn service_bounds<V: Visit>(visit: &V, default: WindowSemantics) -> Option<TimeWindow> {
+    let semantics = visit.committed_window_semantics().unwrap_or(default);
+    let duration_secs = visit.estimated_duration_minutes() * 60;
+    let committed = visit.committed_window().map(|window| semantics.visit_bounds(window, duration_secs));
+    let (release, deadline) = (visit.not_before(), visit.latest_completion());
+    if committed.is_none() && release.is_none() && deadline.is_none() {
+        return None;
+    }
+    let (start, end) = committed.unwrap_or((0, i32::MAX));
+    Some((release.map_or(start, |release| start.max(release)), deadline.map_or(end, |deadline| end.min(deadline))))
+}
+
 /// Reason why a visit could not be assigned.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum UnassignedReason {
-    /// Visit is pinned to a date that doesn't match the service date.
+    /// Visit is pinned to a date that doesn't match the service date, or its
+    /// recurrence, date window, or allowed dates exclude the service date.
     WrongDate,
     /// Visit is pinned to a visitor but no pinned_visitor was provided.
     MissingPinnedVisitor,
     /// No visitor has the required capabilities.
     NoCapableVisitor,
-    /// No feasible time window could be found (availability or committed window conflict).
-    NoFeasibleWindow,
+    /// None of the visitors who could take the visit has availability on the service date.
+    VisitorUnavailable,
+    /// The committed window doesn't overlap any capable visitor's availability
+    /// long enough to fit the visit.
+    CommittedWindowOutsideAvailability,
+    /// The visit would fit in principle, but not on any route once travel and
+    /// already-scheduled visits are accounted for.
+    RouteFull,
+    /// Another visit of the visit's [`same_visitor_group`](Visit::same_visitor_group)
+    /// is on a route the visit doesn't fit on.
+    SameVisitorGroup,
+    /// The visit fits only on routes that already serve another visit of its
+    /// [`different_visitor_group`](Visit::different_visitor_group).
+    DifferentVisitorGroup,
+    /// The visit would fit, but every route it fits on already has a visit in
+    /// its [`route_position`](Visit::route_position).
+    RoutePositionTaken,
+    /// The visit is longer than any availability window (or its own committed window).
+    ///
+    /// Multi-day jobs can be split with [`split_across_days`](crate::split::split_across_days).
+    DurationExceedsWindow,
+    /// The visit's location is too far from the road network to trust its
+    /// travel times (see `SolveOptions::snap_check`).
+    OffRoadNetwork,
+    /// The visit's coordinates are NaN, off the globe, or (0, 0) under
+    /// `SolveOptions::null_island_invalid` (see
+    /// `SolveOptions::invalid_locations`).
+    InvalidLocation,
+    /// The matrix has no route between the visit and the start or end of
+    /// every capable visitor (see [`UNREACHABLE_SECS`]).
+    Unreachable,
+    /// The visit fits only on routes other than its current visitor's, and
+    /// the solve has already moved as many visits as `SolveOptions::max_reassignments`
+    /// allows.
+    ReassignmentLimit,
+}
+
+impl UnassignedReason {
+    /// Whether the visit was dropped because no time slot could be found for it.
+    pub fn is_window_failure(&self) -> bool {
+        matches!(
+            self,
+            UnassignedReason::VisitorUnavailable
+                | UnassignedReason::CommittedWindowOutsideAvailability
+                | UnassignedReason::RouteFull
+                | UnassignedReason::DurationExceedsWindow
+        )
+    }
 }
@@ -0,0 +1,322 @@
+//! Valhalla HTTP adapter for distance matrices and route geometry.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::matrix::metered::{decode_body, is_gzipped, ByteCounter};
+use crate::polyline::Polyline;
+use crate::traits::{DistanceMatrixProvider, TravelMatrices, UNREACHABLE_METERS, UNREACHABLE_SECS};
+
+#[derive(Debug, Clone)]
+pub struct ValhallaConfig {
+    pub base_url: String,
+    /// Costing model, e.g. "auto", "truck", or "bicycle".
+    pub costing: String,
+    pub timeout_secs: u64,
+}
+
+impl Default for ValhallaConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "http://localhost:8002".to_string(),
+            costing: "auto".to_string(),
+            timeout_secs: 10,
+        }
+    }
+}
+
+/// Route geometry response from Valhalla
+#[derive(Debug, Clone)]
+pub struct ValhallaRoute {
+    /// Decoded shape of the entire route
+    pub polyline: Polyline,
+    /// Total distance in meters
+    pub distance_meters: i32,
+    /// Total duration in seconds
+    pub duration_seconds: i32,
+    /// Per-leg breakdown (between consecutive waypoints)
+    pub legs: Vec<ValhallaLeg>,
+}
+
+/// Geometry for a single leg (segment between two consecutive waypoints)
+#[derive(Debug, Clone)]
+pub struct ValhallaLeg {
+    /// Decoded shape of this leg
+    pub polyline: Polyline,
+    /// Distance of this leg in meters
+    pub distance_meters: i32,
+    /// Duration of this leg in seconds
+    pub duration_seconds: i32,
+}
+
+/// Error type for Valhalla requests
+#[derive(Debug)]
+pub enum ValhallaError {
+    /// HTTP request failed
+    RequestFailed(String),
+    /// Failed to parse response
+    ParseError(String),
+    /// No route found between waypoints
+    NoRoute,
+}
+
+impl std::fmt::Display for ValhallaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValhallaError::RequestFailed(msg) => write!(f, "Valhalla request failed: {}", msg),
+            ValhallaError::ParseError(msg) => write!(f, "Failed to parse Valhalla response: {}", msg),
+            ValhallaError::NoRoute => write!(f, "No route found between waypoints"),
+        }
+    }
+}
+
+impl std::error::Error for ValhallaError {}
+
+#[derive(Debug, Clone)]
+pub struct ValhallaClient {
+    config: ValhallaConfig,
+    client: reqwest::blocking::Client,
+    /// Matrix request and response bodies.
+    wire: ByteCounter,
+}
+
+impl ValhallaClient {
+    pub fn new(config: ValhallaConfig) -> Result<Self, reqwest::Error> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()?;
+
+        Ok(Self { config, client, wire: ByteCounter::default() })
+    }
+
+    /// Fetch route geometry between ordered (lat, lng) waypoints.
+    pub fn get_route_geometry(&self, waypoints: &[(f64, f64)]) -> Result<ValhallaRoute, ValhallaError> {
+        if waypoints.len() < 2 {
+            return Err(ValhallaError::NoRoute);
+        }
+
+        let request = RouteRequest {
+            locations: waypoints.iter().map(|&location| location.into()).collect(),
+            costing: &self.config.costing,
+            units: "kilometers",
+        };
+        let body: RouteResponse = self.post("route", &request, None)?;
+
+        let legs: Vec<ValhallaLeg> = body
+            .trip
+            .legs
+            .into_iter()
+            .map(|leg| ValhallaLeg {
+                polyline: Polyline::decode6(&leg.shape),
+                distance_meters: (leg.summary.length * 1000.0).round() as i32,
+                duration_seconds: leg.summary.time.round() as i32,
+            })
+            .collect();
+        if legs.is_empty() {
+            return Err(ValhallaError::NoRoute);
+        }
+
+        // Legs share their boundary points; drop the duplicates when joining.
+        let mut points: Vec<(f64, f64)> = Vec::new();
+        for leg in &legs {
+            let skip = usize::from(!points.is_empty());
+            points.extend(leg.polyline.points().iter().skip(skip));
+        }
+
+        Ok(ValhallaRoute {
+            polyline: Polyline::new(points),
+            distance_meters: (body.trip.summary.length * 1000.0).round() as i32,
+            duration_seconds: body.trip.summary.time.round() as i32,
+            legs,
+        })
+    }
+
+    /// Request a `/sources_to_targets` matrix, or `None` on any failure.
+    fn fetch_matrix(&self, locations: &[(f64, f64)]) -> Option<MatrixResponse> {
+        if locations.is_empty() {
+            return None;
+        }
+
+        let points: Vec<ValhallaLocation> = locations.iter().map(|&location| location.into()).collect();
+        let request = MatrixRequest {
+            sources: &points,
+            targets: &points,
+            costing: &self.config.costing,
+            units: "kilometers",
+        };
+        self.post("sources_to_targets", &request, Some(&self.wire)).ok()
+    }
+
+    /// POST a JSON request, adding the body sizes to `wire` if given.
+    fn post<Req: Serialize, Resp: for<'de> Deserialize<'de>>(
+        &self,
+        endpoint: &str,
+        request: &Req,
+        wire: Option<&ByteCounter>,
+    ) -> Result<Resp, ValhallaError> {
+        let request = serde_json::to_vec(request).map_err(|e| ValhallaError::RequestFailed(e.to_string()))?;
+        let request_len = request.len();
+        let response = self
+            .client
+            .post(format!("{}/{}", self.config.base_url, endpoint))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(reqwest::header::ACCEPT_ENCODING, "gzip")
+            .body(request)
+            .send()
+            .map_err(|e: reqwest::Error| ValhallaError::RequestFailed(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(ValhallaError::RequestFailed(format!("HTTP {}", response.status())));
+        }
+
+        let gzipped = is_gzipped(response.headers());
+        let body = response
+            .bytes()
+            .map_err(|e: reqwest::Error| ValhallaError::ParseError(e.to_string()))?;
+        if let Some(wire) = wire {
+            wire.add(request_len + body.len());
+        }
+        let body = decode_body(&body, gzipped).map_err(|e| ValhallaError::ParseError(e.to_string()))?;
+        serde_json::from_slice(&body).map_err(|e| ValhallaError::ParseError(e.to_string()))
+    }
+}
+
+impl DistanceMatrixProvider for ValhallaClient {
+    fn matrix_for(&self, locations: &[(f64, f64)]) -> Vec<Vec<i32>> {
+        self.matrices_for(locations).durations
+    }
+
+    /// Durations and distances from a single `/sources_to_targets` request.
+    fn matrices_for(&self, locations: &[(f64, f64)]) -> TravelMatrices {
+        match self.fetch_matrix(locations) {
+            Some(body) => matrix_response_to_matrices(body),
+            None => TravelMatrices::from_durations(Vec::new()),
+        }
+    }
+
+    fn bytes_transferred(&self) -> Option<u64> {
+        Some(self.wire.total())
+    }
+}
+
+/// Convert a `/sources_to_targets` response into travel matrices.
+fn matrix_response_to_matrices(body: MatrixResponse) -> TravelMatrices {
+    let durations = body
+        .sources_to_targets
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|cell| cell.time.map_or(UNREACHABLE_SECS, |time| time.round() as i32))
+                .collect()
+        })
+        .collect();
+    let distances = body
+        .sources_to_targets
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|cell| cell.distance.map_or(UNREACHABLE_METERS, |km| (km * 1000.0).round() as i32))
+                .collect()
+        })
+        .collect();
+
+    TravelMatrices {
+        durations,
+        distances: Some(distances),
+        snap_distances: None,
+    }
+}
+
+// Valhalla API request/response types
+
+#[derive(Debug, Serialize)]
+struct ValhallaLocation {
+    lat: f64,
+    lon: f64,
+}
+
+impl From<(f64, f64)> for ValhallaLocation {
+    fn from((lat, lon): (f64, f64)) -> Self {
+        Self { lat, lon }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct MatrixRequest<'a> {
+    sources: &'a [ValhallaLocation],
+    targets: &'a [ValhallaLocation],
+    costing: &'a str,
+    units: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct MatrixResponse {
+    sources_to_targets: Vec<Vec<MatrixCell>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MatrixCell {
+    /// Distance in kilometers, or null if unreachable
+    distance: Option<f64>,
+    /// Time in seconds, or null if unreachable
+    time: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+struct RouteRequest<'a> {
+    locations: Vec<ValhallaLocation>,
+    costing: &'a str,
+    units: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct RouteResponse {
+    trip: Trip,
+}
+
+#[derive(Debug, Deserialize)]
+struct Trip {
+    legs: Vec<TripLeg>,
+    summary: TripSummary,
+}
+
+#[derive(Debug, Deserialize)]
+struct TripLeg {
+    /// Precision-6 encoded polyline
+    shape: String,
+    summary: TripSummary,
+}
+
+#[derive(Debug, Deserialize)]
+struct TripSummary {
+    /// Length in kilometers
+    length: f64,
+    /// Time in seconds
+    time: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matrix_response_conversion() {
+        let body: MatrixResponse = serde_json::from_str(
+            r#"{
+                "sources_to_targets": [
+                    [{"distance": 0.0, "time": 0, "from_index": 0, "to_index": 0},
+                     {"distance": 1.234, "time": 95.6, "from_index": 0, "to_index": 1}],
+                    [{"distance": 1.3, "time": 101, "from_index": 1, "to_index": 0},
+                     {"distance": null, "time": null, "from_index": 1, "to_index": 1}]
+                ],
+                "units": "kilometers"
+            }"#,
+        )
+        .expect("parse matrix response");
+
+        let matrices = matrix_response_to_matrices(body);
+        assert_eq!(matrices.durations, vec![vec![0, 96], vec![101, UNREACHABLE_SECS]]);
+        assert_eq!(matrices.distances.unwrap()[0][1], 1234);
+    }
+}
@@ -4,6 +4,9 @@
 //! - Real Las Vegas / Henderson locations (from OpenStreetMap)
 //! - Test builders for visits, visitors, and availability
 
+#![allow(dead_code)]
+
 pub mod las_vegas_locations;
 
+#[allow(unused_imports)]
 pub use las_vegas_locations::*;
@@ -1,62 +1,20 @@
 use std::env;
 
-use testcontainers::core::{IntoContainerPort, Mount};
-use testcontainers::ReuseDirective;
-use testcontainers::runners::SyncRunner;
-use testcontainers::{Container, GenericImage, ImageExt, TestcontainersError};
-
-use vrp_planner::osrm_data::{GeofabrikRegion, OsrmDataset, OsrmDatasetConfig};
-use vrp_planner::osrm::{OsrmClient, OsrmConfig};
+use vrp_planner::osrm_data::{GeofabrikRegion, OsrmDataset, OsrmDatasetConfig, OsrmServer};
 use vrp_planner::traits::DistanceMatrixProvider;
 
-fn osrm_container() -> Result<(Container<GenericImage>, String), TestcontainersError> {
+fn osrm_server() -> OsrmServer {
     let data_root = env::var("OSRM_DATA_DIR").unwrap_or_else(|_| "osrm-data".to_string());
     let region = GeofabrikRegion::new("north-america/us/nevada");
     let config = OsrmDatasetConfig::new(region, data_root);
-    let dataset = OsrmDataset::ensure(&config)
-        .map_err(|err| TestcontainersError::other(format!("OSRM prep failed: {:?}", err)))?;
-    let mtime = std::fs::metadata(dataset.osrm_base.with_extension("osrm.partition"))
-        .ok()
-        .and_then(|meta| meta.modified().ok())
-        .and_then(|time| time.duration_since(std::time::SystemTime::UNIX_EPOCH).ok())
-        .map(|duration| duration.as_secs())
-        .unwrap_or(0);
-    let container_name = format!("osrm-nevada-mld-{}", mtime);
-
-    let image = GenericImage::new("osrm/osrm-backend", "latest")
-        .with_exposed_port(5000.tcp())
-        .with_mount(Mount::bind_mount(
-            dataset.data_dir.to_string_lossy().to_string(),
-            "/data",
-        ))
-        .with_cmd(vec![
-            "osrm-routed",
-            "--algorithm",
-            "mld",
-            "/data/nevada-latest.osrm",
-        ])
-        .with_container_name(container_name)
-        .with_startup_timeout(std::time::Duration::from_secs(30))
-        .with_reuse(ReuseDirective::Always);
-
-    let container = image.start()?;
-    let port = container.get_host_port_ipv4(5000.tcp())?;
-    let base_url = format!("http://127.0.0.1:{}", port);
-
-    Ok((container, base_url))
+    let dataset = OsrmDataset::ensure(&config).unwrap_or_else(|err| panic!("prepare OSRM dataset: {err}"));
+    OsrmServer::start(&dataset).unwrap_or_else(|err| panic!("start OSRM server: {err}"))
 }
 
 #[test]
 fn osrm_table_returns_matrix() {
-    let (container, base_url) = osrm_container().expect("start OSRM container");
-
-    let profile = "car".to_string();
-    let config = OsrmConfig {
-        base_url: base_url.clone(),
-        profile: profile.clone(),
-        timeout_secs: 10,
-    };
-    let client = OsrmClient::new(config).expect("build OSRM client");
+    let server = osrm_server();
+    let client = server.client();
 
     let locations = vec![
         (36.1147, -115.1728),
@@ -64,18 +22,7 @@ fn osrm_table_returns_matrix() {
         (36.1215, -115.1739),
     ];
 
-    let matrix = {
-        let start = std::time::Instant::now();
-        let mut last = Vec::new();
-        while start.elapsed() < std::time::Duration::from_secs(15) {
-            last = client.matrix_for(&locations);
-            if last.len() == locations.len() && !last.is_empty() {
-                break;
-            }
-            std::thread::sleep(std::time::Duration::from_millis(500));
-        }
-        last
-    };
+    let matrix = client.matrix_for(&locations);
     if matrix.is_empty() {
         let coords = locations
             .iter()
@@ -83,8 +30,8 @@ fn osrm_table_returns_matrix() {
             .collect::<Vec<_>>()
             .join(";");
         let url = format!(
-            "{}/table/v1/{}/{}?annotations=duration",
-            base_url, profile, coords
+            "{}/table/v1/car/{}?annotations=duration",
+            server.base_url(), coords
         );
         match reqwest::blocking::get(&url) {
             Ok(resp) => {
@@ -97,19 +44,44 @@ fn osrm_table_returns_matrix() {
                 eprintln!("OSRM request error: {}", err);
             }
         }
-        if let Ok(stdout) = container.stdout_to_vec() {
-            if !stdout.is_empty() {
-                eprintln!("OSRM stdout:\n{}", String::from_utf8_lossy(&stdout));
-            }
-        }
-        if let Ok(stderr) = container.stderr_to_vec() {
-            if !stderr.is_empty() {
-                eprintln!("OSRM stderr:\n{}", String::from_utf8_lossy(&stderr));
-            }
-        }
     }
     assert_eq!(matrix.len(), locations.len());
     assert_eq!(matrix[0].len(), locations.len());
+}
+
+#[test]
+fn osrm_table_returns_distances() {
+    let server = osrm_server();
+    let client = server.client();
+
+    let locations = vec![(36.1147, -115.1728), (36.1727, -115.1580)];
+
+    let matrices = client.matrices_for(&locations);
+
+    assert_eq!(matrices.durations.len(), locations.len());
+    let distances = matrices.distances.expect("OSRM should return distances");
+    assert_eq!(distances.len(), locations.len());
+    // The Strip to downtown is several kilometers by road
+    assert!(distances[0][1] > 5_000, "unexpected distance {}", distances[0][1]);
+}
+
+#[test]
+fn osrm_trip_visits_every_waypoint_from_the_first() {
+    let server = osrm_server();
+    let client = server.client();
+
+    let waypoints = vec![
+        (36.1147, -115.1728),
+        (36.1727, -115.1580),
+        (36.1215, -115.1739),
+        (36.1699, -115.1398),
+    ];
+
+    let trip = client.trip(&waypoints).expect("OSRM trip");
 
-    drop(container);
+    assert_eq!(trip.order[0], 0);
+    let mut visited = trip.order.clone();
+    visited.sort_unstable();
+    assert_eq!(visited, vec![0, 1, 2, 3]);
+    assert!(trip.duration_seconds > 0);
 }
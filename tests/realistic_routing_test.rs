@@ -7,12 +7,7 @@ mod fixtures;
 
 use std::env;
 
-use testcontainers::core::{IntoContainerPort, Mount};
-use testcontainers::runners::SyncRunner;
-use testcontainers::{Container, GenericImage, ImageExt, ReuseDirective, TestcontainersError};
-
-use vrp_planner::osrm::{OsrmClient, OsrmConfig};
-use vrp_planner::osrm_data::{GeofabrikRegion, OsrmDataset, OsrmDatasetConfig};
+use vrp_planner::osrm_data::{GeofabrikRegion, OsrmDataset, OsrmDatasetConfig, OsrmServer};
 use vrp_planner::solver::{solve, SolveOptions};
 use vrp_planner::traits::{AvailabilityProvider, Visit, VisitPinType, Visitor};
 
@@ -39,6 +34,7 @@ struct RealVisit {
     required_capabilities: Vec<String>,
 }
 
+#[allow(dead_code)]
 impl RealVisit {
     fn new(id: &str, location: Location) -> Self {
         Self {
@@ -172,51 +168,15 @@ fn hours(h: i32) -> i32 {
 }
 
 // ============================================================================
-// OSRM Setup (reused from osrm_integration test)
+// OSRM Setup
 // ============================================================================
 
-fn osrm_container() -> Result<(Container<GenericImage>, OsrmClient), TestcontainersError> {
+fn osrm_server() -> OsrmServer {
     let data_root = env::var("OSRM_DATA_DIR").unwrap_or_else(|_| "osrm-data".to_string());
     let region = GeofabrikRegion::new("north-america/us/nevada");
     let config = OsrmDatasetConfig::new(region, data_root);
-    let dataset = OsrmDataset::ensure(&config)
-        .map_err(|err| TestcontainersError::other(format!("OSRM prep failed: {:?}", err)))?;
-
-    let mtime = std::fs::metadata(dataset.osrm_base.with_extension("osrm.partition"))
-        .ok()
-        .and_then(|meta| meta.modified().ok())
-        .and_then(|time| time.duration_since(std::time::SystemTime::UNIX_EPOCH).ok())
-        .map(|duration| duration.as_secs())
-        .unwrap_or(0);
-    let container_name = format!("osrm-nevada-mld-{}", mtime);
-
-    let image = GenericImage::new("osrm/osrm-backend", "latest")
-        .with_exposed_port(5000.tcp())
-        .with_mount(Mount::bind_mount(
-            dataset.data_dir.to_string_lossy().to_string(),
-            "/data",
-        ))
-        .with_cmd(vec![
-            "osrm-routed",
-            "--algorithm",
-            "mld",
-            "/data/nevada-latest.osrm",
-        ])
-        .with_container_name(container_name)
-        .with_startup_timeout(std::time::Duration::from_secs(30))
-        .with_reuse(ReuseDirective::Always);
-
-    let container = image.start()?;
-    let port = container.get_host_port_ipv4(5000.tcp())?;
-    let base_url = format!("http://127.0.0.1:{}", port);
-
-    let osrm = OsrmClient::new(OsrmConfig {
-        base_url,
-        profile: "car".to_string(),
-        timeout_secs: 30,
-    }).map_err(|err| TestcontainersError::other(format!("OSRM client failed: {:?}", err)))?;
-
-    Ok((container, osrm))
+    let dataset = OsrmDataset::ensure(&config).unwrap_or_else(|err| panic!("prepare OSRM dataset: {err}"));
+    OsrmServer::start(&dataset).unwrap_or_else(|err| panic!("start OSRM server: {err}"))
 }
 
 // ============================================================================
@@ -226,7 +186,8 @@ fn osrm_container() -> Result<(Container<GenericImage>, OsrmClient), Testcontain
 /// Test with a small number of real locations to verify OSRM integration.
 #[test]
 fn test_small_route_with_osrm() {
-    let (_container, osrm) = osrm_container().expect("start OSRM container");
+    let server = osrm_server();
+    let osrm = server.client();
 
     // Use geographically diverse locations
     let locations = las_vegas_locations::geographically_diverse_locations();
@@ -250,9 +211,9 @@ fn test_small_route_with_osrm() {
         &visits,
         &visitors,
         &StandardAvailability,
-        &osrm,
+        osrm,
         SolveOptions::default(),
-    );
+    ).unwrap();
 
     // All visits should be assigned
     assert!(
@@ -304,7 +265,8 @@ fn test_small_route_with_osrm() {
 /// Test with 20 visits across 3 technicians - a realistic day.
 #[test]
 fn test_medium_route_with_osrm() {
-    let (_container, osrm) = osrm_container().expect("start OSRM container");
+    let server = osrm_server();
+    let osrm = server.client();
 
     // Get 20 locations from different areas
     let all_locs = las_vegas_locations::all_locations();
@@ -330,9 +292,9 @@ fn test_medium_route_with_osrm() {
         &visits,
         &visitors,
         &StandardAvailability,
-        &osrm,
+        osrm,
         SolveOptions::default(),
-    );
+    ).unwrap();
 
     // Count assigned vs unassigned
     let total_assigned: usize = result.routes.iter().map(|r| r.visit_ids.len()).sum();
@@ -363,7 +325,8 @@ fn test_medium_route_with_osrm() {
 /// Test with committed time windows.
 #[test]
 fn test_time_windows_with_osrm() {
-    let (_container, osrm) = osrm_container().expect("start OSRM container");
+    let server = osrm_server();
+    let osrm = server.client();
 
     let locations = las_vegas_locations::STRIP_RESTAURANTS;
 
@@ -391,9 +354,9 @@ fn test_time_windows_with_osrm() {
         &visits,
         &visitors,
         &StandardAvailability,
-        &osrm,
+        osrm,
         SolveOptions::default(),
-    );
+    ).unwrap();
 
     // All should be assigned
     assert!(
@@ -403,7 +366,7 @@ fn test_time_windows_with_osrm() {
 
     // Verify estimated windows respect committed windows
     let route = &result.routes[0];
-    for (i, visit) in visits.iter().enumerate() {
+    for visit in visits.iter() {
         if let Some((commit_start, commit_end)) = visit.committed_window {
             let (est_start, _est_end) = route.estimated_windows[route
                 .visit_ids
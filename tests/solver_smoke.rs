@@ -1,7 +1,8 @@
 use std::collections::HashMap;
 
+use vrp_planner::matrix::synthetic::GridMatrix;
 use vrp_planner::solver::{solve, SolveOptions};
-use vrp_planner::traits::{AvailabilityProvider, DistanceMatrixProvider, Visit, VisitPinType, Visitor};
+use vrp_planner::traits::{AvailabilityProvider, Visit, VisitPinType, Visitor};
 
 #[derive(Clone, Debug, Hash, Eq, PartialEq)]
 struct Id(&'static str);
@@ -95,21 +96,6 @@ impl AvailabilityProvider for MockAvailability {
     }
 }
 
-struct MockMatrix;
-
-impl DistanceMatrixProvider for MockMatrix {
-    fn matrix_for(&self, locations: &[(f64, f64)]) -> Vec<Vec<i32>> {
-        let mut matrix = vec![vec![0; locations.len()]; locations.len()];
-        for (i, from) in locations.iter().enumerate() {
-            for (j, to) in locations.iter().enumerate() {
-                let dist = (from.0 - to.0).abs() + (from.1 - to.1).abs();
-                matrix[i][j] = (dist * 60.0) as i32;
-            }
-        }
-        matrix
-    }
-}
-
 #[test]
 fn honors_pinned_visitor() {
     let visits = vec![
@@ -131,7 +117,7 @@ fn honors_pinned_visitor() {
 
     let visitors = vec![MockVisitor { id: Id("a") }, MockVisitor { id: Id("b") }];
 
-    let result = solve(1, &visits, &visitors, &MockAvailability, &MockMatrix, SolveOptions::default());
+    let result = solve(1, &visits, &visitors, &MockAvailability, &GridMatrix::default(), SolveOptions::default()).unwrap();
 
     let mut assigned: HashMap<&str, Vec<&str>> = HashMap::new();
     for route in result.routes {